@@ -0,0 +1,225 @@
+//! Derives `ServiceMessage` for the service messages of the `bacnet` crate, whose fields are
+//! written in order as the values of the message's body.
+//!
+//! The message's service choice is given with `#[bacnet(choice = n)]` on the struct. Each field
+//! is application tagged unless it has `#[bacnet(context = n)]`. An optional field is an `Option`
+//! with `#[bacnet(optional)]`, it is left out of the body when it is `None`. A field with
+//! `#[bacnet(context = n, constructed)]` is a `ValueSequence` of any values, in opening and
+//! closing tags. Every other field's type implements `ast::Primitive`.
+//!
+//! Unmarshalling fails with an `UnmarshallError` naming the field's context tag, or its index in
+//! the body for an application tagged field, when a required value is missing or has the wrong
+//! datatype, and when there are values after the last field.
+//!
+//! ```ignore
+//! #[derive(ServiceMessage)]
+//! #[bacnet(choice = 12)]
+//! pub struct Message {
+//!     #[bacnet(context = 0)]
+//!     pub object_id: ObjectId,
+//!     #[bacnet(context = 1)]
+//!     pub property: PropertyIdentifier,
+//!     #[bacnet(context = 2, optional)]
+//!     pub array_index: Option<u32>,
+//! }
+//! ```
+//!
+//! The generated code names the items it uses by their paths in the `bacnet` crate, so the derive
+//! is only for use within it.
+
+extern crate proc_macro;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, GenericArgument, Ident, LitInt, PathArguments, Type};
+
+#[proc_macro_derive(ServiceMessage, attributes(bacnet))]
+pub fn derive_service_message(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// How a field is tagged in the message's body
+enum Tagging {
+    Application,
+    Context(u8),
+    Constructed(u8),
+}
+
+struct Field<'a> {
+    name: &'a Ident,
+    tagging: Tagging,
+    optional: bool,
+    /// The field's type, or the type in the `Option` of an optional field
+    value_type: &'a Type,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream, Error> {
+    let name = &input.ident;
+    let choice = choice(input)?;
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => fields.named.iter().map(field).collect::<Result<Vec<_>, _>>()?,
+            Fields::Unit => vec!(),
+            Fields::Unnamed(_) => return Err(Error::new_spanned(input, "a service message's fields must be named")),
+        },
+        _ => return Err(Error::new_spanned(input, "a service message must be a struct")),
+    };
+
+    let schemas = fields.iter().map(schema);
+    let marshalled = fields.iter().map(marshall);
+    let names = fields.iter().map(|field| field.name);
+    let unmarshalled = fields.iter().map(unmarshall);
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics crate::service::ServiceMessage for #name #type_generics #where_clause {
+            type Message = Self;
+
+            const SCHEMA: crate::parse::Schema = crate::parse::Schema::Sequence(&[#(#schemas),*]);
+
+            fn choice() -> u8 { #choice }
+
+            fn marshall(&self) -> crate::ast::ValueSequence {
+                #[allow(unused_mut)]
+                let mut body = vec!();
+                #(#marshalled)*
+                body
+            }
+
+            fn unmarshall(body: &crate::ast::ValueSequence) -> Result<Self, crate::service::UnmarshallError> {
+                #[allow(unused_mut)]
+                let mut values = crate::service::Values::new(body);
+                #(#unmarshalled)*
+                values.end()?;
+                Ok(#name { #(#names),* })
+            }
+        }
+    })
+}
+
+/// The service choice from `#[bacnet(choice = n)]` on the struct
+fn choice(input: &DeriveInput) -> Result<u8, Error> {
+    let mut choice = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("bacnet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("choice") {
+                choice = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `choice = n` on a service message"))
+            }
+        })?;
+    }
+    choice.ok_or_else(|| Error::new_spanned(&input.ident, "a service message needs `#[bacnet(choice = n)]`"))
+}
+
+fn field(field: &syn::Field) -> Result<Field<'_>, Error> {
+    let mut context = None;
+    let mut constructed = false;
+    let mut optional = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("bacnet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("context") {
+                context = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("constructed") {
+                constructed = true;
+            } else if meta.path.is_ident("optional") {
+                optional = true;
+            } else {
+                return Err(meta.error("expected `context = n`, `constructed` or `optional`"))
+            }
+            Ok(())
+        })?;
+    }
+    let tagging = match (context, constructed) {
+        (Some(number), false) => Tagging::Context(number),
+        (Some(number), true) => Tagging::Constructed(number),
+        (None, false) => Tagging::Application,
+        (None, true) => return Err(Error::new_spanned(field, "a constructed field needs a context tag number")),
+    };
+    let value_type = if optional {
+        option_type(&field.ty).ok_or_else(|| Error::new_spanned(&field.ty, "an optional field must be an `Option`"))?
+    } else {
+        &field.ty
+    };
+    Ok(Field { name: field.ident.as_ref().expect("named fields have names"), tagging, optional, value_type })
+}
+
+/// The `T` of `Option<T>`
+fn option_type(ty: &Type) -> Option<&Type> {
+    let segment = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref arguments) if segment.ident == "Option" && arguments.args.len() == 1 =>
+            match arguments.args[0] {
+                GenericArgument::Type(ref ty) => Some(ty),
+                _ => None,
+            },
+        _ => None,
+    }
+}
+
+fn schema(field: &Field) -> TokenStream {
+    let value_type = field.value_type;
+    let schema = match field.tagging {
+        Tagging::Application => quote!(crate::parse::Schema::Application),
+        Tagging::Context(number) =>
+            quote!(crate::parse::Schema::Context(#number, <#value_type as crate::ast::Primitive>::TYPE)),
+        Tagging::Constructed(number) =>
+            quote!(crate::parse::Schema::Constructed(#number, &crate::parse::Schema::Any)),
+    };
+    if field.optional {
+        quote!(crate::parse::Schema::Optional(&#schema))
+    } else {
+        schema
+    }
+}
+
+fn marshall(field: &Field) -> TokenStream {
+    let value = match field.tagging {
+        Tagging::Application =>
+            quote!(crate::ast::SequenceableValue::ApplicationValue(crate::ast::Primitive::to_primitive(value))),
+        Tagging::Context(number) =>
+            quote!(crate::ast::SequenceableValue::ContextValue(#number, crate::ast::Primitive::to_primitive(value))),
+        Tagging::Constructed(number) =>
+            quote!(crate::ast::SequenceableValue::ContextValueSequence(#number, value.clone())),
+    };
+    let name = field.name;
+    if field.optional {
+        quote! {
+            if let Some(ref value) = self.#name {
+                body.push(#value);
+            }
+        }
+    } else {
+        quote! {
+            let value = &self.#name;
+            body.push(#value);
+        }
+    }
+}
+
+fn unmarshall(field: &Field) -> TokenStream {
+    let value = match field.tagging {
+        Tagging::Application => quote!(values.application()?),
+        Tagging::Context(number) => quote!(values.context(#number)?),
+        Tagging::Constructed(number) => quote!(values.constructed(#number)),
+    };
+    let missing = match field.tagging {
+        Tagging::Application => quote!(values.missing()),
+        Tagging::Context(number) | Tagging::Constructed(number) => quote!(
+            crate::service::UnmarshallError::MissingRequiredValue(crate::service::Position::Context(#number))),
+    };
+    let name = field.name;
+    if field.optional {
+        quote!(let #name = #value;)
+    } else {
+        quote!(let #name = match #value {
+            Some(value) => value,
+            None => return Err(#missing),
+        };)
+    }
+}
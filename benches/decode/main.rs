@@ -0,0 +1,64 @@
+//! Compares decoding the examples of Annex E from a slice with parsing them from a reader, as
+//! the reader parser in `reader` did before the decoder replaced it. Run with `cargo bench`.
+
+extern crate bacnet;
+
+mod reader;
+
+use bacnet::decode::{decode_value_sequence_to_end, SequenceableValue};
+use bacnet::parse::Schema;
+use reader::parse_value_sequence_to_end;
+use bacnet::service::{covnotification, readpropertymultiple, writeproperty, writepropertymultiple};
+use bacnet::service::ServiceMessage;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 200_000;
+
+/// The time for one of `ITERATIONS`, after as many to warm up
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn compare(name: &str, data: &[u8], schema: &Schema) {
+    let decoded = decode_value_sequence_to_end(data, schema).unwrap();
+    assert_eq!(parse_value_sequence_to_end(&mut &data[..], schema).unwrap(),
+               decoded.into_iter().map(SequenceableValue::into_owned).collect::<Vec<_>>());
+    let reader = time(|| {
+        let mut reader = black_box(data);
+        black_box(parse_value_sequence_to_end(&mut reader, schema).unwrap());
+    });
+    let slice = time(|| {
+        black_box(decode_value_sequence_to_end(black_box(data), schema).unwrap());
+    });
+    println!("{:<28} reader {:>8?}  slice {:>8?}  {:.1}x", name, reader, slice,
+             reader.as_secs_f64() / slice.as_secs_f64());
+}
+
+fn main() {
+    // Clause E.3.7
+    compare("ReadPropertyMultiple-ACK", &[
+        0x0C, 0x00, 0x00, 0x00, 0x10, 0x1E, 0x29, 0x55, 0x4E, 0x44, 0x42, 0x90, 0x99, 0x9A, 0x4F,
+        0x29, 0x67, 0x4E, 0x91, 0x00, 0x4F, 0x1F], &readpropertymultiple::Ack::SCHEMA);
+    // Clause E.3.10
+    compare("WritePropertyMultiple", &[
+        0x0C, 0x00, 0x80, 0x00, 0x05, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x86, 0x00, 0x00, 0x2F, 0x1F,
+        0x0C, 0x00, 0x80, 0x00, 0x06, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x86, 0x00, 0x00, 0x2F, 0x1F,
+        0x0C, 0x00, 0x80, 0x00, 0x07, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x90, 0x00, 0x00, 0x2F, 0x1F],
+        &writepropertymultiple::Message::SCHEMA);
+    // Clause E.3.9
+    compare("WriteProperty", &[0x0C, 0x00, 0x80, 0x00, 0x01, 0x19, 0x55, 0x3E, 0x44, 0x43, 0x34, 0x00, 0x00, 0x3F],
+            &writeproperty::Message::SCHEMA);
+    // Clause E.2.1
+    compare("COVNotification", &[
+        0x09, 0x12, 0x1C, 0x02, 0x00, 0x00, 0x04, 0x2C, 0x00, 0x00, 0x00, 0x0A, 0x39, 0x00,
+        0x4E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x82, 0x00, 0x00, 0x2F, 0x09, 0x6F, 0x2E, 0x82, 0x04, 0x00, 0x2F, 0x4F],
+        &covnotification::Unconfirmed::SCHEMA);
+}
@@ -0,0 +1,436 @@
+//! The reader parser which `parse::parse_value_sequence_to_end` used before it was built on the
+//! slice decoder, reading a tag and then its content octets a byte at a time. It's kept as the
+//! baseline the decoder is compared against.
+
+use bacnet::ast;
+use bacnet::ast::{ObjectId, SequenceableValue, Year};
+use bacnet::decode::Tag;
+use bacnet::enumerations::ObjectType;
+use bacnet::parse::{ParseError, Schema};
+use std::convert::TryFrom;
+use std::io;
+use std::io::Read;
+
+/// Whether the production can start with `tag`, which is `None` at the end of the input
+fn starts_with(schema: &Schema, tag: Option<&Tag>) -> bool {
+    match (schema, tag) {
+        (&Schema::Context(number, _), Some(&Tag::Context(tag_number, _))) => number == tag_number,
+        (&Schema::Application, Some(&Tag::Application(..))) => true,
+        (&Schema::Constructed(number, _), Some(&Tag::Open(tag_number))) => number == tag_number,
+        (&Schema::Sequence(schemas), _) => {
+            for schema in schemas {
+                if starts_with(schema, tag) {
+                    return true
+                }
+                if !is_optional(schema) {
+                    return false
+                }
+            }
+            false
+        },
+        (&Schema::Choice(schemas), _) => schemas.iter().any(|schema| starts_with(schema, tag)),
+        (&Schema::SequenceOf(schema), _) | (&Schema::Optional(schema), _) => starts_with(schema, tag),
+        (&Schema::Any, Some(&Tag::Close(_))) | (&Schema::Any, None) => false,
+        (&Schema::Any, _) => true,
+        _ => false,
+    }
+}
+
+/// Whether the production can be left out entirely
+fn is_optional(schema: &Schema) -> bool {
+    match *schema {
+        Schema::Optional(_) | Schema::SequenceOf(_) | Schema::Any => true,
+        Schema::Sequence(schemas) => schemas.iter().all(is_optional),
+        Schema::Choice(schemas) => schemas.iter().any(is_optional),
+        _ => false,
+    }
+}
+
+/// The error for a tag which isn't allowed where it is, a closing tag or the end of the input
+/// where a value is required means the value is missing
+fn unexpected(tag: Option<Tag>) -> ParseError {
+    match tag {
+        Some(Tag::Close(_)) | None => ParseError::MissingRequiredValue,
+        Some(_) => ParseError::UnexpectedTag,
+    }
+}
+
+/// Reads tags one ahead of the parser, so that the schema can decide what comes next. The
+/// content octets of a value are read straight from the reader once its tag has been taken.
+struct Tags<'a> {
+    reader: &'a mut dyn Read,
+    next: Option<Option<Tag>>,
+}
+
+impl<'a> Tags<'a> {
+    fn new(reader: &'a mut dyn Read) -> Tags<'a> {
+        Tags { reader, next: None }
+    }
+
+    /// The next tag, `None` at the end of the input
+    fn peek(&mut self) -> Result<Option<&Tag>, ParseError> {
+        if self.next.is_none() {
+            let tag = match read_optional_byte(self.reader)? {
+                Some(first_byte) => Some(parse_tag_after(first_byte, self.reader)?),
+                None => None,
+            };
+            self.next = Some(tag);
+        }
+        Ok(self.next.as_ref().and_then(Option::as_ref))
+    }
+
+    fn take(&mut self) -> Result<Option<Tag>, ParseError> {
+        self.peek()?;
+        Ok(self.next.take().and_then(|tag| tag))
+    }
+}
+
+/// Parses the whole of the input as the production described by `schema`
+///
+/// # Errors
+///
+/// - if a tag is found which the schema doesn't allow where it is, or a required value is
+///   missing
+/// - if the application tag is unsupported / reserved
+/// - if the length is too long
+/// - if the value can't be parsed
+/// - if the reader reaches the end of input in the middle of a value
+pub fn parse_value_sequence_to_end(reader: &mut dyn Read, schema: &Schema) -> Result<ast::ValueSequence, ParseError> {
+    let mut tags = Tags::new(reader);
+    let mut values = vec!();
+    parse_production(&mut tags, schema, &mut values)?;
+    match tags.peek()? {
+        None => Ok(values),
+        Some(_) => Err(ParseError::UnexpectedTag),
+    }
+}
+
+/// Parses the values of one production, adding them to `values`
+fn parse_production(tags: &mut Tags, schema: &Schema, values: &mut ast::ValueSequence) -> Result<(), ParseError> {
+    match *schema {
+        Schema::Context(number, primitive_type) => match tags.take()? {
+            Some(Tag::Context(tag_number, tag_value)) if tag_number == number => values.push(
+                SequenceableValue::ContextValue(number, context_tag_to_value(tags.reader, primitive_type as u8, tag_value)?)),
+            tag => return Err(unexpected(tag)),
+        },
+        Schema::Application => match tags.take()? {
+            Some(Tag::Application(tag_number, tag_value)) =>
+                values.push(SequenceableValue::ApplicationValue(tag_to_value(tags.reader, tag_number, tag_value)?)),
+            tag => return Err(unexpected(tag)),
+        },
+        Schema::Constructed(number, schema) => match tags.take()? {
+            Some(Tag::Open(tag_number)) if tag_number == number => {
+                let mut list = vec!();
+                parse_production(tags, schema, &mut list)?;
+                close(tags, number)?;
+                values.push(SequenceableValue::ContextValueSequence(number, list));
+            },
+            tag => return Err(unexpected(tag)),
+        },
+        Schema::Sequence(schemas) => {
+            for schema in schemas {
+                parse_production(tags, schema, values)?;
+            }
+        },
+        Schema::Choice(schemas) => {
+            let tag = tags.peek()?;
+            match schemas.iter().find(|alternative| starts_with(alternative, tag)) {
+                Some(alternative) => parse_production(tags, alternative, values)?,
+                None if is_optional(schema) => (),
+                None => return Err(unexpected(tags.take()?)),
+            }
+        },
+        Schema::SequenceOf(schema) => {
+            while starts_with(schema, tags.peek()?) {
+                parse_production(tags, schema, values)?;
+            }
+        },
+        Schema::Optional(schema) => {
+            if starts_with(schema, tags.peek()?) {
+                parse_production(tags, schema, values)?;
+            }
+        },
+        Schema::Any => {
+            while starts_with(&Schema::Any, tags.peek()?) {
+                match tags.take()? {
+                    Some(Tag::Application(tag_number, tag_value)) =>
+                        values.push(SequenceableValue::ApplicationValue(tag_to_value(tags.reader, tag_number, tag_value)?)),
+                    Some(Tag::Context(tag_number, tag_value)) => values.push(SequenceableValue::ContextValue(
+                        tag_number, ast::PrimitiveValue::OctetString(read_octets(tags.reader, tag_value as usize)?))),
+                    Some(Tag::Open(tag_number)) => {
+                        let mut list = vec!();
+                        parse_production(tags, &Schema::Any, &mut list)?;
+                        close(tags, tag_number)?;
+                        values.push(SequenceableValue::ContextValueSequence(tag_number, list));
+                    },
+                    Some(Tag::Close(_)) | None => unreachable!("any value doesn't start with a closing tag"),
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Takes the closing tag of a constructed value, a closing tag with another number doesn't match
+/// the opening tag
+fn close(tags: &mut Tags, number: u8) -> Result<(), ParseError> {
+    match tags.take()? {
+        Some(Tag::Close(tag_number)) if tag_number == number => Ok(()),
+        None => Err(ParseError::MissingRequiredValue),
+        Some(_) => Err(ParseError::UnexpectedTag),
+    }
+}
+
+/// Reads the content octets of an application tagged value, the tag number identifies the type
+/// and the tag value is usually the length of the content (Clause 20.2.2 - 20.2.14)
+fn tag_to_value(reader: &mut dyn Read, tag: u8, tag_value: u32) -> Result<ast::PrimitiveValue, ParseError> {
+    use bacnet::ast::PrimitiveValue;
+
+    let length = tag_value as usize;
+    match tag {
+        0 => Ok(PrimitiveValue::Null),
+        1 => Ok(PrimitiveValue::Boolean(tag_value != 0)),
+        2 => Ok(PrimitiveValue::Unsigned(read_unsigned(reader, length)?)),
+        3 => Ok(PrimitiveValue::Signed(read_signed(reader, length)?)),
+        4 => {
+            if length != 4 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            Ok(PrimitiveValue::Real(f32::from_bits(read_unsigned(reader, 4)?)))
+        },
+        5 => {
+            if length != 8 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            let high = read_unsigned(reader, 4)? as u64;
+            let low = read_unsigned(reader, 4)? as u64;
+            Ok(PrimitiveValue::Double(f64::from_bits(high << 32 | low)))
+        },
+        6 => Ok(PrimitiveValue::OctetString(read_octets(reader, length)?)),
+        7 => Ok(PrimitiveValue::CharacterString(read_character_string(reader, length)?)),
+        8 => Ok(PrimitiveValue::BitString(read_bit_string(reader, length)?)),
+        9 => Ok(PrimitiveValue::Enumerated(read_unsigned(reader, length)?)),
+        10 => {
+            if length != 4 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            Ok(PrimitiveValue::Date(read_date(reader)?))
+        },
+        11 => {
+            if length != 4 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            Ok(PrimitiveValue::Time(read_time(reader)?))
+        },
+        12 => {
+            if length != 4 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            let value = read_unsigned(reader, 4)?;
+            Ok(PrimitiveValue::ObjectId(ObjectId(ObjectType::try_from((value >> 22) as u16).unwrap(), value & 0x3FFFFF)))
+        },
+        _ => Err(ParseError::NotImplemented("Reserved application tag")),
+    }
+}
+
+/// Context tagged values are the same as application tagged values except for booleans, which
+/// can't be encoded in the tag as the tag value is needed for the length - so they have a single
+/// content octet (Clause 20.2.3)
+fn context_tag_to_value(reader: &mut dyn Read, tag: u8, tag_value: u32) -> Result<ast::PrimitiveValue, ParseError> {
+    match tag {
+        1 => {
+            if tag_value != 1 {
+                return Err(ParseError::ValueSizeNotSupported)
+            }
+            Ok(ast::PrimitiveValue::Boolean(read_one_byte(reader)? != 0))
+        },
+        _ => tag_to_value(reader, tag, tag_value),
+    }
+}
+
+/// Character strings start with an octet identifying the character set (Clause 20.2.9), UTF-8,
+/// ISO 10646 (UCS-2) and ISO 8859-1 are supported
+fn read_character_string(reader: &mut dyn Read, length: usize) -> Result<String, ParseError> {
+    if length == 0 {
+        return Err(ParseError::InvalidValue("Character string without a character set"))
+    }
+    let character_set = read_one_byte(reader)?;
+    let octets = read_octets(reader, length - 1)?;
+    match character_set {
+        0 => String::from_utf8(octets).map_err(|_| ParseError::InvalidValue("Invalid UTF-8")),
+        4 => {
+            if !octets.len().is_multiple_of(2) {
+                return Err(ParseError::InvalidValue("Invalid UCS-2"))
+            }
+            let units: Vec<u16> = octets.chunks(2).map(|pair| (pair[0] as u16) << 8 | pair[1] as u16).collect();
+            String::from_utf16(&units).map_err(|_| ParseError::InvalidValue("Invalid UCS-2"))
+        },
+        5 => Ok(octets.iter().map(|&octet| octet as char).collect()),
+        _ => Err(ParseError::NotImplemented("Character set")),
+    }
+}
+
+/// Bit strings start with an octet giving the number of unused bits in the last octet (Clause 20.2.10)
+fn read_bit_string(reader: &mut dyn Read, length: usize) -> Result<ast::BitString, ParseError> {
+    if length == 0 {
+        return Err(ParseError::InvalidValue("Bit string without unused bits octet"))
+    }
+    let unused_bits = read_one_byte(reader)?;
+    if unused_bits > 7 || (length == 1 && unused_bits != 0) {
+        return Err(ParseError::InvalidValue("Invalid bit string unused bits"))
+    }
+    Ok(ast::BitString::from_octets(read_octets(reader, length - 1)?, unused_bits))
+}
+
+/// Dates are encoded as year - 1900, month, day and day of week, with 0xFF for unspecified
+/// fields (Clause 20.2.12)
+fn read_date(reader: &mut dyn Read) -> Result<ast::Date, ParseError> {
+    use bacnet::ast::{Month, DayOfMonth};
+
+    let year = read_one_byte(reader)?;
+    let month = match read_one_byte(reader)? {
+        month @ 1..=12 => Month::Number(month),
+        13 => Month::Odd,
+        14 => Month::Even,
+        0xFF => Month::Unspecified,
+        _ => return Err(ParseError::InvalidValue("Invalid month")),
+    };
+    let day = match read_one_byte(reader)? {
+        day @ 1..=31 => DayOfMonth::Number(day),
+        32 => DayOfMonth::Last,
+        33 => DayOfMonth::Odd,
+        34 => DayOfMonth::Even,
+        0xFF => DayOfMonth::Unspecified,
+        _ => return Err(ParseError::InvalidValue("Invalid day of month")),
+    };
+    let weekday = match read_one_byte(reader)? {
+        weekday @ 1..=7 => Some(weekday),
+        0xFF => None,
+        _ => return Err(ParseError::InvalidValue("Invalid day of week")),
+    };
+    Ok(ast::Date {
+        year: if year == 0xFF { None } else { Year::new(Year::MIN + year as u16) },
+        month,
+        day,
+        weekday,
+    })
+}
+
+/// Times are encoded as hour, minute, second and hundredths, with 0xFF for unspecified fields
+/// (Clause 20.2.13)
+fn read_time(reader: &mut dyn Read) -> Result<ast::Time, ParseError> {
+    fn field(reader: &mut dyn Read, max: u8) -> Result<Option<u8>, ParseError> {
+        match read_one_byte(reader)? {
+            0xFF => Ok(None),
+            value if value <= max => Ok(Some(value)),
+            _ => Err(ParseError::InvalidValue("Invalid time")),
+        }
+    }
+    Ok(ast::Time {
+        hour: field(reader, 23)?,
+        minute: field(reader, 59)?,
+        second: field(reader, 59)?,
+        hundredths: field(reader, 99)?,
+    })
+}
+
+/// The first octet has already been read, so that the end of the data can be told apart from a
+/// truncated tag.
+fn parse_tag_after(first_byte: u8, reader: &mut dyn Read) -> Result<Tag, ParseError> {
+    let mut tag_num = (first_byte & 0xF0) >> 4;
+    let class = (first_byte & 0x08) == 0x08;
+    let mut value = (first_byte & 0x07) as u32;
+
+    // Extended tag numbers
+    if tag_num == 0xF {
+        tag_num = read_one_byte(reader)?;
+    }
+    // Open / Close tags
+    if value == 0x6 {
+        Ok(Tag::Open(tag_num))
+    } else if value == 0x7 {
+        Ok(Tag::Close(tag_num))
+    } // Extended values
+    else {
+        if value == 0x5 {
+            value = read_one_byte(reader)? as u32;
+            if value == 0xFE {
+                value = read_unsigned(reader, 2)?;
+            } else if value == 0xFF {
+                value = read_unsigned(reader, 4)?;
+            }
+        }
+        Ok(
+            if class {
+                Tag::Context(tag_num, value)
+            } else {
+                Tag::Application(tag_num, value)
+            }
+        )
+    }
+}
+
+// Read an unsigned integer of the specified number of bytes
+fn read_unsigned(reader: &mut dyn Read, size: usize) -> Result<u32, ParseError> {
+    if size > 4 {
+        return Err(ParseError::ValueSizeNotSupported)
+    }
+    let mut value: u32 = 0;
+    for i in (0..size).rev() {
+        let next_byte = read_one_byte(reader)? as u32;
+        value |= next_byte << (8 * i);
+    }
+    Ok(value)
+}
+
+// Read a two's complement signed integer of the specified number of bytes
+fn read_signed(reader: &mut dyn Read, size: usize) -> Result<i32, ParseError> {
+    if size == 0 || size > 4 {
+        return Err(ParseError::ValueSizeNotSupported)
+    }
+    let value = read_unsigned(reader, size)?;
+    let unused_bits = 32 - 8 * size as u32;
+    Ok(((value << unused_bits) as i32) >> unused_bits)
+}
+
+// Read the specified number of bytes
+fn read_octets(reader: &mut dyn Read, size: usize) -> Result<Vec<u8>, ParseError> {
+    let mut octets = vec![0; size];
+    match reader.read_exact(&mut octets) {
+        Ok(()) => Ok(octets),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(ParseError::InputEndedBeforeParsingCompleted),
+        Err(e) => Err(ParseError::ReadError(e)),
+    }
+}
+
+// Read one byte, or none if the reader is at the end
+fn read_optional_byte(reader: &mut dyn Read) -> Result<Option<u8>, ParseError> {
+    match read_one_byte(reader) {
+        Ok(byte) => Ok(Some(byte)),
+        Err(ParseError::InputEndedBeforeParsingCompleted) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+fn read_one_byte(reader: &mut dyn Read) -> Result<u8, ParseError> {
+    let mut buf = [0];
+    let ret;
+    loop {
+        match reader.read(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => {
+                ret = Err(ParseError::ReadError(e));
+                break;
+            },
+            Ok(0) => {
+                ret = Err(ParseError::InputEndedBeforeParsingCompleted);
+                break;
+            },
+            Ok(..) => {
+                ret = Ok(buf[0]);
+                break;
+            },
+        };
+    }
+    ret
+}
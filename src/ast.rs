@@ -1,17 +1,24 @@
-//! Defining an abstract syntax tree for bacnet APDUs
+//! Defining an abstract syntax tree for bacnet APDUs. The headers, dates, times and object
+//! identifiers are part of the encoding core, the values which own their content need `std`.
 
-use object;
+use core::fmt;
+use core::ops::Deref;
+#[cfg(feature = "std")]
+use decode::PrimitiveType;
+use enumerations::{AbortReason, ObjectType, RejectReason};
+#[cfg(feature = "std")]
+use std::net::{Ipv4Addr, SocketAddrV4};
 
 /// Defines the whole body of a BACnet APDU message
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ApduHeader {
     /// BACnet Confirmed Request - Clause 20.1.2
     /// Transfers a variable length request to a service, which may be segmented, and expects an acknowledgement
     ConfirmedReq { 
         segmented: Option<SegmentInfo>,
         segmented_response_accepted: bool,
-        max_segments: u8, 
-        max_apdu: u8, 
+        max_segments: MaxSegments,
+        max_apdu: MaxApdu,
         invoke_id: u8, 
         service: ServiceChoice,
     },
@@ -55,33 +62,344 @@ pub enum ApduHeader {
     /// requested service has not been called to any effect
     RejectPdu {
         invoke_id: u8,
-        reject_reason: u8,
+        reject_reason: RejectReason,
     },
     /// BACnet Abort PDU - Clause 20.1.9
     /// Aborts a confirmed request between 2 peers
     AbortPdu {
         server: bool,
         invoke_id: u8,
-        abort_reason: u8,
+        abort_reason: AbortReason,
     },
 }
 
 type ServiceChoice = u8;
 
-/// The fields which are present on message segments - they do not appear on unsegmented messages
-/// TODO move into a segmentation module which generates these and maybe make the fields private
-#[derive(Debug, PartialEq)]
+/// BACnet NPDU header - Clause 6.2
+/// Precedes every APDU or network layer message, it carries the addressing needed to pass the
+/// message through routers
+#[derive(Debug, PartialEq, Clone)]
+pub struct Npdu {
+    /// Network priority - Clause 6.2.2, 0 is normal and 3 is life safety
+    pub priority: u8,
+    pub expecting_reply: bool,
+    /// The ultimate destination, if it is on a remote network
+    pub destination: Option<NetworkAddress>,
+    /// The original source, added by a router when the message has come from a remote network
+    pub source: Option<NetworkAddress>,
+    /// Only encoded when there is a destination, it is 255 when there is none
+    pub hop_count: u8,
+    /// Present when this NPDU carries a network layer message instead of an APDU
+    pub network_message: Option<NetworkMessage>,
+}
+
+impl Npdu {
+    /// A header for an APDU to a device on the local network
+    pub fn local(expecting_reply: bool) -> Npdu {
+        Npdu {
+            priority: 0,
+            expecting_reply,
+            destination: None,
+            source: None,
+            hop_count: 255,
+            network_message: None,
+        }
+    }
+
+    /// A header for an APDU responding to the sender of `request`, which is routed back to the
+    /// original source if the request came through a router
+    pub fn reply_to(request: &Npdu, expecting_reply: bool) -> Npdu {
+        Npdu {
+            priority: request.priority,
+            destination: request.source.clone(),
+            ..Npdu::local(expecting_reply)
+        }
+    }
+}
+
+/// A device's address on a BACnet network - Clause 6.2.2
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NetworkAddress {
+    /// The network number, 0xFFFF is a broadcast to all networks
+    pub network: u16,
+    /// The MAC address on that network, empty for a broadcast on that network
+    pub address: MacAddress,
+}
+
+/// The most octets in a MAC address, which is more than the MAC address of any data link needs
+pub const MAX_MAC_LEN: usize = 18;
+
+/// A MAC address of up to `MAX_MAC_LEN` octets, kept inline so that an NPDU header doesn't need
+/// to allocate. It dereferences to its octets.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct MacAddress {
+    len: u8,
+    octets: [u8; MAX_MAC_LEN],
+}
+
+impl MacAddress {
+    /// `None` if there are more than `MAX_MAC_LEN` octets
+    pub fn new(address: &[u8]) -> Option<MacAddress> {
+        if address.len() > MAX_MAC_LEN {
+            return None
+        }
+        let mut octets = [0; MAX_MAC_LEN];
+        octets[..address.len()].copy_from_slice(address);
+        Some(MacAddress { len: address.len() as u8, octets })
+    }
+}
+
+impl Deref for MacAddress {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.octets[..self.len as usize]
+    }
+}
+
+/// Debugs as its octets
+impl fmt::Debug for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Identifies a network layer message - Clause 6.2.4, the message content follows the header
+#[derive(Debug, PartialEq, Clone)]
+pub struct NetworkMessage {
+    pub message_type: u8,
+    /// Present for the proprietary message types 0x80 to 0xFF
+    pub vendor_id: Option<u16>,
+}
+
+/// BACnet Virtual Link Control message - Annex J.2
+/// The header of every BACnet/IP datagram, the NPDU carrying messages have the NPDU as their
+/// content and the others are for managing broadcast distribution
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Bvlc {
+    /// Annex J.2.1 - the result of a BVLL request, see `bvlc_result` for the codes
+    Result(u16),
+    /// Annex J.2.2 - replaces a BBMD's broadcast distribution table
+    WriteBroadcastDistributionTable(Vec<BdtEntry>),
+    /// Annex J.2.3
+    ReadBroadcastDistributionTable,
+    /// Annex J.2.4
+    ReadBroadcastDistributionTableAck(Vec<BdtEntry>),
+    /// Annex J.2.5 - an NPDU forwarded by a BBMD, with the address of the device which originally
+    /// sent it
+    ForwardedNpdu {
+        originator: SocketAddrV4,
+        npdu: Vec<u8>,
+    },
+    /// Annex J.2.6 - asks a BBMD to forward broadcasts to this device for a time to live in seconds
+    RegisterForeignDevice(u16),
+    /// Annex J.2.7
+    ReadForeignDeviceTable,
+    /// Annex J.2.8
+    ReadForeignDeviceTableAck(Vec<FdtEntry>),
+    /// Annex J.2.9
+    DeleteForeignDeviceTableEntry(SocketAddrV4),
+    /// Annex J.2.10 - a foreign device's broadcast for a BBMD to distribute
+    DistributeBroadcastToNetwork(Vec<u8>),
+    /// Annex J.2.11
+    OriginalUnicastNpdu(Vec<u8>),
+    /// Annex J.2.12
+    OriginalBroadcastNpdu(Vec<u8>),
+}
+
+/// The result codes of the BVLC Result message - Annex J.2.1.1
+pub mod bvlc_result {
+    pub const SUCCESSFUL_COMPLETION: u16 = 0x0000;
+    pub const WRITE_BROADCAST_DISTRIBUTION_TABLE_NAK: u16 = 0x0010;
+    pub const READ_BROADCAST_DISTRIBUTION_TABLE_NAK: u16 = 0x0020;
+    pub const REGISTER_FOREIGN_DEVICE_NAK: u16 = 0x0030;
+    pub const READ_FOREIGN_DEVICE_TABLE_NAK: u16 = 0x0040;
+    pub const DELETE_FOREIGN_DEVICE_TABLE_ENTRY_NAK: u16 = 0x0050;
+    pub const DISTRIBUTE_BROADCAST_TO_NETWORK_NAK: u16 = 0x0060;
+}
+
+/// A broadcast distribution table entry - Annex J.4.3.1
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct BdtEntry {
+    pub address: SocketAddrV4,
+    pub broadcast_mask: Ipv4Addr,
+}
+
+/// A foreign device table entry - Annex J.5.2.1
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FdtEntry {
+    pub address: SocketAddrV4,
+    pub time_to_live: u16,
+    pub time_remaining: u16,
+}
+
+/// The fields which are present on message segments - they do not appear on unsegmented messages.
+/// The `segmentation` module produces and checks them.
+#[derive(Debug, PartialEq, Clone)]
 pub struct SegmentInfo {
     pub more_follows: bool,
     pub sequence_number: u8,
     pub proposed_window_size: u8,
 }
 
+/// The largest APDU a requester accepts, as encoded in a Confirmed-Request - Clause 20.1.2.5
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MaxApdu {
+    Up50,
+    Up128,
+    Up206,
+    Up480,
+    Up1024,
+    Up1476,
+    /// A value reserved for ASHRAE
+    Reserved(u8),
+}
+
+impl MaxApdu {
+    /// The length in octets. Reserved values are taken as the 50 octets every device accepts.
+    pub fn length(self) -> usize {
+        match self {
+            MaxApdu::Up50 | MaxApdu::Reserved(_) => 50,
+            MaxApdu::Up128 => 128,
+            MaxApdu::Up206 => 206,
+            MaxApdu::Up480 => 480,
+            MaxApdu::Up1024 => 1024,
+            MaxApdu::Up1476 => 1476,
+        }
+    }
+
+    /// The largest value which doesn't promise more than `length` octets
+    pub fn from_length(length: usize) -> MaxApdu {
+        match length {
+            1476.. => MaxApdu::Up1476,
+            1024.. => MaxApdu::Up1024,
+            480.. => MaxApdu::Up480,
+            206.. => MaxApdu::Up206,
+            128.. => MaxApdu::Up128,
+            _ => MaxApdu::Up50,
+        }
+    }
+}
+
+impl From<u8> for MaxApdu {
+    fn from(value: u8) -> MaxApdu {
+        match value {
+            0 => MaxApdu::Up50,
+            1 => MaxApdu::Up128,
+            2 => MaxApdu::Up206,
+            3 => MaxApdu::Up480,
+            4 => MaxApdu::Up1024,
+            5 => MaxApdu::Up1476,
+            value => MaxApdu::Reserved(value),
+        }
+    }
+}
+
+impl From<MaxApdu> for u8 {
+    fn from(value: MaxApdu) -> u8 {
+        match value {
+            MaxApdu::Up50 => 0,
+            MaxApdu::Up128 => 1,
+            MaxApdu::Up206 => 2,
+            MaxApdu::Up480 => 3,
+            MaxApdu::Up1024 => 4,
+            MaxApdu::Up1476 => 5,
+            MaxApdu::Reserved(value) => value,
+        }
+    }
+}
+
+/// The most segments a requester accepts in a Complex-ACK, as encoded in a Confirmed-Request -
+/// Clause 20.1.2.4
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MaxSegments {
+    Unspecified,
+    Up2,
+    Up4,
+    Up8,
+    Up16,
+    Up32,
+    Up64,
+    MoreThan64,
+}
+
+impl MaxSegments {
+    /// The number of segments, `None` when there's no known limit because it's unspecified or
+    /// more than 64
+    pub fn count(self) -> Option<usize> {
+        match self {
+            MaxSegments::Unspecified | MaxSegments::MoreThan64 => None,
+            MaxSegments::Up2 => Some(2),
+            MaxSegments::Up4 => Some(4),
+            MaxSegments::Up8 => Some(8),
+            MaxSegments::Up16 => Some(16),
+            MaxSegments::Up32 => Some(32),
+            MaxSegments::Up64 => Some(64),
+        }
+    }
+
+    /// Whether a message of `segments` segments is within the limit
+    pub fn accepts(self, segments: usize) -> bool {
+        self.count().is_none_or(|count| segments <= count)
+    }
+
+    /// The largest value which doesn't promise more than `count` segments, fewer than two
+    /// segments is unspecified
+    pub fn from_count(count: usize) -> MaxSegments {
+        match count {
+            65.. => MaxSegments::MoreThan64,
+            64.. => MaxSegments::Up64,
+            32.. => MaxSegments::Up32,
+            16.. => MaxSegments::Up16,
+            8.. => MaxSegments::Up8,
+            4.. => MaxSegments::Up4,
+            2.. => MaxSegments::Up2,
+            _ => MaxSegments::Unspecified,
+        }
+    }
+}
+
+impl From<u8> for MaxSegments {
+    /// Only the three bits of the field are used
+    fn from(value: u8) -> MaxSegments {
+        match value & 0b111 {
+            0 => MaxSegments::Unspecified,
+            1 => MaxSegments::Up2,
+            2 => MaxSegments::Up4,
+            3 => MaxSegments::Up8,
+            4 => MaxSegments::Up16,
+            5 => MaxSegments::Up32,
+            6 => MaxSegments::Up64,
+            _ => MaxSegments::MoreThan64,
+        }
+    }
+}
+
+impl From<MaxSegments> for u8 {
+    fn from(value: MaxSegments) -> u8 {
+        match value {
+            MaxSegments::Unspecified => 0,
+            MaxSegments::Up2 => 1,
+            MaxSegments::Up4 => 2,
+            MaxSegments::Up8 => 3,
+            MaxSegments::Up16 => 4,
+            MaxSegments::Up32 => 5,
+            MaxSegments::Up64 => 6,
+            MaxSegments::MoreThan64 => 7,
+        }
+    }
+}
+
 /// A sequence of BACnet values
+#[cfg(feature = "std")]
 pub type ValueSequence = Vec<SequenceableValue>;
 
 /// Extracts a single value from the sequence with the provided context number
-pub fn get_context_value<'a>(sequence: &'a ValueSequence, context_number: Context) -> Option<&'a PrimitiveValue> {
+#[cfg(feature = "std")]
+pub fn get_context_value(sequence: &ValueSequence, context_number: Context) -> Option<&PrimitiveValue> {
     for element in sequence.iter() {
         match element {
             &SequenceableValue::ContextValue(number, ref value) if number == context_number =>
@@ -92,7 +410,20 @@ pub fn get_context_value<'a>(sequence: &'a ValueSequence, context_number: Contex
     None
 }
 
-#[cfg(test)]
+/// Extracts the sequence enclosed in the opening and closing tags with the provided context number
+#[cfg(feature = "std")]
+pub fn get_context_sequence(sequence: &ValueSequence, context_number: Context) -> Option<&ValueSequence> {
+    for element in sequence.iter() {
+        match element {
+            &SequenceableValue::ContextValueSequence(number, ref value) if number == context_number =>
+                return Some(value),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(all(test, feature = "std"))]
 mod test_get_context_value {
     use super::get_context_value;
     use super::PrimitiveValue::Boolean;
@@ -115,10 +446,21 @@ mod test_get_context_value {
         assert_eq!(None,
             get_context_value(&vec!(ContextValue(2, Boolean(false)), ContextValue(3, Boolean(true))), 4));
     }
+
+    #[test]
+    fn get_context_sequence() {
+        use super::get_context_sequence;
+        use super::SequenceableValue::ContextValueSequence;
+        assert_eq!(Some(&vec!(ContextValue(0, Boolean(true)))),
+            get_context_sequence(&vec!(ContextValue(3, Boolean(false)), ContextValueSequence(3, vec!(ContextValue(0, Boolean(true))))), 3));
+        assert_eq!(None,
+            get_context_sequence(&vec!(ContextValue(3, Boolean(false))), 3));
+    }
 }
 
 /// The Bacnet types whih can be elements of a sequence
-#[derive(Debug, PartialEq)]
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
 pub enum SequenceableValue {
     ApplicationValue(PrimitiveValue),
     ContextValue(Context, PrimitiveValue),
@@ -128,12 +470,9 @@ pub enum SequenceableValue {
 /// Context values have an id which has a meaning specific to the message it is within
 pub type Context = u8;
 
-pub mod object_type {
-    pub const DEVICE: u16 = 8;
-}
-
 /// BACnet primitive application value types
-#[derive(Debug, PartialEq)]
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
 pub enum PrimitiveValue {
     Null,
     Boolean(bool),
@@ -141,14 +480,326 @@ pub enum PrimitiveValue {
     Signed(i32),
     Real(f32),
     Double(f64),
-    // OctetString([u8]), // not yey implemented as a working, non-ugly type signature will take
-    // some thought
+    OctetString(Vec<u8>),
     CharacterString(String),
-    // BitString([u8]), // not yet implemented as rust types dont have an efficeint representation of
-    // this
+    BitString(BitString),
     Enumerated(u32),
-    // Date, // not yet implemented as they dont have close enough equvalents in rust
-    // Time,
-    ObjectId(object::ObjectId),
+    Date(Date),
+    Time(Time),
+    ObjectId(ObjectId),
+}
+
+#[cfg(feature = "std")]
+impl PrimitiveValue {
+    /// The application type of the value
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match *self {
+            PrimitiveValue::Null => PrimitiveType::Null,
+            PrimitiveValue::Boolean(_) => PrimitiveType::Boolean,
+            PrimitiveValue::Unsigned(_) => PrimitiveType::Unsigned,
+            PrimitiveValue::Signed(_) => PrimitiveType::Signed,
+            PrimitiveValue::Real(_) => PrimitiveType::Real,
+            PrimitiveValue::Double(_) => PrimitiveType::Double,
+            PrimitiveValue::OctetString(_) => PrimitiveType::OctetString,
+            PrimitiveValue::CharacterString(_) => PrimitiveType::CharacterString,
+            PrimitiveValue::BitString(_) => PrimitiveType::BitString,
+            PrimitiveValue::Enumerated(_) => PrimitiveType::Enumerated,
+            PrimitiveValue::Date(_) => PrimitiveType::Date,
+            PrimitiveValue::Time(_) => PrimitiveType::Time,
+            PrimitiveValue::ObjectId(_) => PrimitiveType::ObjectId,
+        }
+    }
+}
+
+/// A Rust type which holds one kind of primitive value, so that it can be a field of a derived
+/// `ServiceMessage`
+#[cfg(feature = "std")]
+pub trait Primitive: Sized {
+    /// The application type of the value
+    const TYPE: PrimitiveType;
+    fn to_primitive(&self) -> PrimitiveValue;
+    /// `None` if the value is of another type, or out of the range of this one
+    fn from_primitive(value: &PrimitiveValue) -> Option<Self>;
+}
+
+macro_rules! primitive {
+    ($rust_type:ty, $variant:ident) => {
+        #[cfg(feature = "std")]
+        impl Primitive for $rust_type {
+            const TYPE: PrimitiveType = PrimitiveType::$variant;
+
+            fn to_primitive(&self) -> PrimitiveValue {
+                PrimitiveValue::$variant(self.clone())
+            }
+
+            fn from_primitive(value: &PrimitiveValue) -> Option<Self> {
+                match *value {
+                    PrimitiveValue::$variant(ref value) => Some(value.clone()),
+                    _ => None,
+                }
+            }
+        }
+    };
 }
 
+primitive!(bool, Boolean);
+primitive!(u32, Unsigned);
+primitive!(i32, Signed);
+primitive!(f32, Real);
+primitive!(f64, Double);
+primitive!(Vec<u8>, OctetString);
+primitive!(String, CharacterString);
+primitive!(BitString, BitString);
+primitive!(Date, Date);
+primitive!(Time, Time);
+primitive!(ObjectId, ObjectId);
+
+/// A BACnet bit string (Clause 20.2.10). Bit 0 is the most significant bit of the first octet,
+/// the last octet may have unused trailing bits which are always kept clear.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BitString {
+    octets: Vec<u8>,
+    unused_bits: u8,
+}
+
+#[cfg(feature = "std")]
+impl BitString {
+    /// A bit string of `len` bits, all of which are clear
+    pub fn new(len: usize) -> BitString {
+        BitString {
+            octets: vec![0; len.div_ceil(8)],
+            unused_bits: ((8 - len % 8) % 8) as u8,
+        }
+    }
+
+    pub fn from_bits(bits: &[bool]) -> BitString {
+        let mut bit_string = BitString::new(bits.len());
+        for (bit, &value) in bits.iter().enumerate() {
+            bit_string.set(bit, value);
+        }
+        bit_string
+    }
+
+    /// Builds a bit string from its encoded form, any unused bits which are set are cleared
+    ///
+    /// # Panics
+    ///
+    /// If there are more than 7 unused bits, or if there are unused bits but no octets
+    pub fn from_octets(mut octets: Vec<u8>, unused_bits: u8) -> BitString {
+        assert!(unused_bits < 8 && (unused_bits == 0 || !octets.is_empty()), "Invalid unused bits");
+        if let Some(last) = octets.last_mut() {
+            *last &= 0xFFu8 << unused_bits;
+        }
+        BitString {
+            octets,
+            unused_bits,
+        }
+    }
+
+    /// The number of bits in the string
+    pub fn len(&self) -> usize {
+        self.octets.len() * 8 - self.unused_bits as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The value of a bit, bits past the end of the string are clear
+    pub fn get(&self, bit: usize) -> bool {
+        bit < self.len() && self.octets[bit / 8] & (0x80 >> (bit % 8)) != 0
+    }
+
+    /// Sets the value of a bit, extending the string if the bit is past the end
+    pub fn set(&mut self, bit: usize, value: bool) {
+        if bit >= self.len() {
+            self.octets.resize(bit / 8 + 1, 0);
+            self.unused_bits = (7 - bit % 8) as u8;
+        }
+        if value {
+            self.octets[bit / 8] |= 0x80 >> (bit % 8);
+        } else {
+            self.octets[bit / 8] &= !(0x80 >> (bit % 8));
+        }
+    }
+
+    pub fn octets(&self) -> &[u8] {
+        &self.octets
+    }
+
+    /// The number of bits at the end of the last octet which aren't part of the string
+    pub fn unused_bits(&self) -> u8 {
+        self.unused_bits
+    }
+}
+
+#[cfg(test)]
+mod test_transport_limits {
+    use super::{MaxApdu, MaxSegments};
+
+    #[test]
+    fn max_apdu() {
+        assert_eq!(1476, MaxApdu::from(5).length());
+        assert_eq!(50, MaxApdu::from(0).length());
+        assert_eq!(MaxApdu::Reserved(0xF), MaxApdu::from(0xF));
+        assert_eq!(50, MaxApdu::Reserved(0xF).length());
+        assert_eq!(MaxApdu::Up480, MaxApdu::from_length(1000));
+        assert_eq!(MaxApdu::Up1476, MaxApdu::from_length(1476));
+        assert_eq!(MaxApdu::Up50, MaxApdu::from_length(0));
+        for value in 0..=0xF {
+            assert_eq!(value, u8::from(MaxApdu::from(value)));
+        }
+    }
+
+    #[test]
+    fn max_segments() {
+        assert_eq!(Some(64), MaxSegments::from(6).count());
+        assert_eq!(None, MaxSegments::from(0).count());
+        assert_eq!(None, MaxSegments::from(7).count());
+        assert!(MaxSegments::Up4.accepts(4));
+        assert!(!MaxSegments::Up4.accepts(5));
+        assert!(MaxSegments::Unspecified.accepts(1000));
+        assert_eq!(MaxSegments::Up8, MaxSegments::from_count(10));
+        assert_eq!(MaxSegments::MoreThan64, MaxSegments::from_count(65));
+        assert_eq!(MaxSegments::Unspecified, MaxSegments::from_count(1));
+        for value in 0..=7 {
+            assert_eq!(value, u8::from(MaxSegments::from(value)));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test_bit_string {
+    use super::BitString;
+
+    #[test]
+    fn new_is_clear() {
+        let bits = BitString::new(10);
+        assert_eq!(10, bits.len());
+        assert_eq!(&[0u8, 0], bits.octets());
+        assert_eq!(6, bits.unused_bits());
+        assert!(!(0..10).any(|bit| bits.get(bit)));
+        assert!(BitString::new(0).is_empty());
+        assert_eq!(0, BitString::new(16).unused_bits());
+    }
+
+    #[test]
+    fn from_bits() {
+        let bits = BitString::from_bits(&[true, false, true, true, false]);
+        assert_eq!(5, bits.len());
+        assert_eq!(&[0b10110000u8], bits.octets());
+        assert_eq!(3, bits.unused_bits());
+        assert!(bits.get(0) && !bits.get(1) && bits.get(3) && !bits.get(4) && !bits.get(5));
+    }
+
+    #[test]
+    fn set_extends() {
+        let mut bits = BitString::new(2);
+        bits.set(9, true);
+        assert_eq!(10, bits.len());
+        assert_eq!(&[0u8, 0b01000000], bits.octets());
+        bits.set(9, false);
+        assert!(!bits.get(9));
+        assert_eq!(10, bits.len());
+    }
+
+    #[test]
+    fn from_octets_clears_unused_bits() {
+        assert_eq!(BitString::from_bits(&[true, true, true, true, true]), BitString::from_octets(vec![0xFF], 3));
+    }
+}
+
+/// A BACnet date (Clause 20.2.12), any of the fields may be unspecified - which in a pattern
+/// matches any value. A date with a numbered field out of its range can't be encoded.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Date {
+    pub year: Option<Year>,
+    pub month: Month,
+    pub day: DayOfMonth,
+    /// The day of the week, 1 is Monday and 7 is Sunday
+    pub weekday: Option<u8>,
+}
+
+/// A year which can be encoded in a date, 1900 to 2154. It's encoded as the number of years
+/// since 1900 in an octet, and 2155 would be 0xFF which is the unspecified year.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Year(u8);
+
+impl Year {
+    pub const MIN: u16 = 1900;
+    pub const MAX: u16 = 2154;
+
+    /// `None` if the year can't be encoded
+    pub fn new(year: u16) -> Option<Year> {
+        if (Year::MIN..=Year::MAX).contains(&year) {
+            Some(Year((year - Year::MIN) as u8))
+        } else {
+            None
+        }
+    }
+
+    /// The year, e.g. 2016
+    pub fn get(self) -> u16 {
+        Year::MIN + self.0 as u16
+    }
+}
+
+#[cfg(test)]
+mod test_year {
+    use super::Year;
+
+    #[test]
+    fn only_encodable_years() {
+        assert_eq!(None, Year::new(1899));
+        assert_eq!(Some(1900), Year::new(1900).map(Year::get));
+        assert_eq!(Some(2154), Year::new(2154).map(Year::get));
+        // 2155 would encode as the unspecified year
+        assert_eq!(None, Year::new(2155));
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Month {
+    /// January is 1 and December is 12
+    Number(u8),
+    Odd,
+    Even,
+    Unspecified,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DayOfMonth {
+    /// 1 to 31
+    Number(u8),
+    Last,
+    Odd,
+    Even,
+    Unspecified,
+}
+
+/// A BACnet time of day (Clause 20.2.13), any of the fields may be unspecified - which in a
+/// pattern matches any value. A time with a field out of its range, such as an hour above 23,
+/// can't be encoded.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Time {
+    pub hour: Option<u8>,
+    pub minute: Option<u8>,
+    pub second: Option<u8>,
+    pub hundredths: Option<u8>,
+}
+
+/// The largest object instance number, instances are 22 bits
+pub const MAX_INSTANCE: u32 = 0x3FFFFF;
+
+/// An object's type and instance number, they are unique within a device. The instance must be
+/// at most `MAX_INSTANCE` to be encoded.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+pub struct ObjectId(pub ObjectType, pub u32);
+
+/// Displays as the type and the instance, e.g. `analog-input,3`
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.0, self.1)
+    }
+}
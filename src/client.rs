@@ -0,0 +1,810 @@
+//! The requesting side of confirmed services - Clause 5.4.4. A request is sent with an invoke id
+//! which is unique among the transactions in progress with the peer, and is sent again if it isn't
+//! answered within APDU_Timeout, up to Number_Of_APDU_Retries times. The answer, or the lack of
+//! one, is matched back to the request as a confirmation.
+//!
+//! Requests too long for the peer are segmented if the peer can receive segments, and segmented
+//! answers are accepted and put back together.
+
+use ast::ApduHeader;
+use ast::{MaxApdu, MaxSegments};
+use ast::NetworkAddress;
+use ast::SegmentInfo;
+use ast::ValueSequence;
+use clock::Clock;
+use decode::{decode_value_sequence_to_end, into_owned};
+use encode::EncodeError;
+use enumerations::{AbortReason, Segmentation};
+use object::DeviceObject;
+use parse::{ParseError, Schema};
+use segmentation::{SEGMENT_WAIT_TIMEOUTS, SegmentAcked, SegmentReceiver, SegmentSender, UNSEGMENTED_CONFIRMED_REQUEST_HEADER_LENGTH};
+use serialise::write_value_sequence;
+use service;
+use service::{ConfirmedAck, ServiceError, ServiceMessage};
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+/// The largest APDU the client accepts in an answer
+const MAX_APDU_ACCEPTED: MaxApdu = MaxApdu::Up1476;
+
+/// The most segments the client accepts in an answer
+const MAX_SEGMENTS_ACCEPTED: MaxSegments = MaxSegments::Up64;
+
+/// Carries APDUs to other devices
+pub trait Transport {
+    /// Sends an APDU made of the header and the encoded service data
+    fn send(&mut self, destination: &NetworkAddress, header: ApduHeader, data: &[u8]) -> io::Result<()>;
+}
+
+/// The ways a confirmed request can fail
+#[derive(Debug, PartialEq)]
+pub enum RequestError {
+    /// The peer answered with an Error, Reject or Abort PDU, or the client aborted the
+    /// transaction because the peer's answer made no sense
+    Service(ServiceError),
+    /// There was no answer to the request or any of its retries
+    Timeout,
+    /// Every invoke id is in use by a transaction with the peer
+    NoInvokeIdAvailable,
+    /// The request is too long for the peer's APDUs and can't be segmented for it
+    TooLong,
+    /// The request has a value which can't be encoded, such as an object instance above
+    /// `MAX_INSTANCE`
+    Unencodable(EncodeError),
+    /// The answer's service data couldn't be parsed
+    InvalidAnswer(ParseError),
+    /// The request couldn't be sent
+    Transport(io::ErrorKind),
+}
+
+/// The outcome of a confirmed request
+#[derive(Debug, PartialEq)]
+pub struct Confirmation {
+    pub peer: NetworkAddress,
+    pub invoke_id: u8,
+    pub service: u8,
+    pub result: Result<ConfirmedAck, RequestError>,
+}
+
+/// What the client knows of how much a peer can receive, from its I-Am or its device object. A
+/// Max_APDU_Length_Accepted which isn't one of the standard lengths is rounded down with
+/// `MaxApdu::from_length`, and Max_Segments_Accepted with `MaxSegments::from_count`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PeerLimits {
+    pub max_apdu: MaxApdu,
+    pub segmentation: Segmentation,
+    pub max_segments: MaxSegments,
+}
+
+impl Default for PeerLimits {
+    /// A peer which accepts 1476 octet APDUs, as every BACnet/IP device can, without segments
+    fn default() -> PeerLimits {
+        PeerLimits {
+            max_apdu: MaxApdu::Up1476,
+            segmentation: Segmentation::NoSegmentation,
+            max_segments: MaxSegments::Unspecified,
+        }
+    }
+}
+
+/// Where a transaction has got to
+enum State {
+    /// Sending the segments of the request
+    SegmentedRequest,
+    /// Waiting for the answer
+    AwaitConfirmation,
+    /// Receiving the segments of a Complex-ACK
+    SegmentedConfirmation(SegmentReceiver),
+}
+
+/// A request waiting for its answer
+struct Transaction {
+    peer: NetworkAddress,
+    invoke_id: u8,
+    service: u8,
+    /// Describes the complex ack's values
+    schema: &'static Schema,
+    /// The request's service data
+    data: Vec<u8>,
+    /// The request's segments, if it is too long to send in one APDU
+    segments: Option<SegmentSender>,
+    state: State,
+    retries: u32,
+    deadline: Duration,
+}
+
+/// Sends confirmed requests and matches the answers to them. Answers are passed in with
+/// `receive`, and `poll` should be called when `next_deadline` has passed to send requests again
+/// and to time them out.
+pub struct Client<T: Transport> {
+    transport: T,
+    clock: Box<dyn Clock>,
+    /// How long to wait for an answer before sending a request again, usually the device's
+    /// APDU_Timeout
+    pub apdu_timeout: Duration,
+    /// How long to wait for a segment or a SegmentACK, usually the device's APDU_Segment_Timeout
+    pub apdu_segment_timeout: Duration,
+    /// How many times a request or a window of segments is sent again, usually the device's
+    /// Number_Of_APDU_Retries
+    pub number_of_apdu_retries: u32,
+    /// The window size proposed when sending segments, and the largest accepted when receiving
+    /// them
+    pub window_size: u8,
+    peers: HashMap<NetworkAddress, PeerLimits>,
+    transactions: Vec<Transaction>,
+    next_invoke_id: HashMap<NetworkAddress, u8>,
+}
+
+impl<T: Transport> Client<T> {
+    /// A client with the default APDU_Timeout of 3 seconds, APDU_Segment_Timeout of 2 seconds, 3
+    /// retries and windows of 16 segments
+    pub fn new(transport: T, clock: Box<dyn Clock>) -> Client<T> {
+        Client {
+            transport,
+            clock,
+            apdu_timeout: Duration::from_millis(3000),
+            apdu_segment_timeout: Duration::from_millis(2000),
+            number_of_apdu_retries: 3,
+            window_size: 16,
+            peers: HashMap::new(),
+            transactions: vec!(),
+            next_invoke_id: HashMap::new(),
+        }
+    }
+
+    /// Takes the APDU timeouts and number of retries from the device's properties
+    pub fn use_device_timing(&mut self, device: &DeviceObject) {
+        self.apdu_timeout = Duration::from_millis(device.apdu_timeout as u64);
+        self.apdu_segment_timeout = Duration::from_millis(device.apdu_segment_timeout as u64);
+        self.number_of_apdu_retries = device.number_of_apdu_retries;
+    }
+
+    /// Sets how much a peer can receive, requests to peers without limits are sent as if to a peer
+    /// with the default limits
+    pub fn set_peer_limits(&mut self, peer: &NetworkAddress, limits: PeerLimits) {
+        self.peers.insert(peer.clone(), limits);
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Sends a service request, giving the invoke id its confirmation will have. A complex ack is
+    /// parsed with `schema`, usually the `SCHEMA` of the service's ack.
+    pub fn request<M: ServiceMessage>(&mut self, peer: &NetworkAddress, message: &M, schema: &'static Schema) -> Result<u8, RequestError> {
+        self.send_request(peer, M::choice(), message.marshall(), schema)
+    }
+
+    /// Sends a request for the service with the choice `service`, giving the invoke id its
+    /// confirmation will have
+    pub fn send_request(&mut self, peer: &NetworkAddress, service: u8, body: ValueSequence, schema: &'static Schema) -> Result<u8, RequestError> {
+        let mut data = vec![];
+        write_value_sequence(&mut data, body).map_err(RequestError::Unencodable)?;
+        let limits = self.peers.get(peer).cloned().unwrap_or_default();
+        let max_apdu_length = limits.max_apdu.length().min(MAX_APDU_ACCEPTED.length());
+        let invoke_id = self.allocate_invoke_id(peer).ok_or(RequestError::NoInvokeIdAvailable)?;
+        let segments = if UNSEGMENTED_CONFIRMED_REQUEST_HEADER_LENGTH + data.len() <= max_apdu_length {
+            None
+        } else {
+            let segments = SegmentSender::new(request_header(invoke_id, service), &data, max_apdu_length, self.window_size);
+            let receives_segments = limits.segmentation == Segmentation::SegmentedBoth || limits.segmentation == Segmentation::SegmentedReceive;
+            if !receives_segments || !limits.max_segments.accepts(segments.segment_count()) {
+                return Err(RequestError::TooLong)
+            }
+            Some(segments)
+        };
+        let timeout = if segments.is_some() { self.apdu_segment_timeout } else { self.apdu_timeout };
+        let transaction = Transaction {
+            peer: peer.clone(),
+            invoke_id,
+            service,
+            schema,
+            data,
+            state: if segments.is_some() { State::SegmentedRequest } else { State::AwaitConfirmation },
+            segments,
+            retries: 0,
+            deadline: self.clock.now() + timeout,
+        };
+        send(&mut self.transport, &transaction).map_err(|error| RequestError::Transport(error.kind()))?;
+        self.transactions.push(transaction);
+        Ok(invoke_id)
+    }
+
+    /// Handles an APDU from `source`, giving the confirmation of the request it answers once the
+    /// transaction is over. APDUs which aren't for a request in progress are ignored.
+    pub fn receive(&mut self, source: &NetworkAddress, header: ApduHeader, data: &[u8]) -> Option<Confirmation> {
+        let invoke_id = match header {
+            ApduHeader::SimpleAck { invoke_id, .. } |
+            ApduHeader::ComplexAck { invoke_id, .. } |
+            ApduHeader::SegmentAck { server: true, invoke_id, .. } |
+            ApduHeader::ErrorPdu { invoke_id, .. } |
+            ApduHeader::RejectPdu { invoke_id, .. } |
+            ApduHeader::AbortPdu { server: true, invoke_id, .. } => invoke_id,
+            _ => return None,
+        };
+        let index = self.transactions.iter()
+            .position(|transaction| transaction.peer == *source && transaction.invoke_id == invoke_id)?;
+        let service = self.transactions[index].service;
+        // The answer can come before the SegmentACK for the last segment of the request
+        let awaiting = !matches!(self.transactions[index].state, State::SegmentedConfirmation(_));
+        match header {
+            ApduHeader::RejectPdu { reject_reason, .. } =>
+                self.finish(index, Err(RequestError::Service(ServiceError::Reject(reject_reason)))),
+            ApduHeader::AbortPdu { abort_reason, .. } =>
+                self.finish(index, Err(RequestError::Service(ServiceError::Abort(abort_reason)))),
+            ApduHeader::ErrorPdu { error_choice, .. } if error_choice == service && awaiting => {
+                let error = match parse(data, &service::ERROR) {
+                    Ok(error) => RequestError::Service(ServiceError::Error(error)),
+                    Err(error) => error,
+                };
+                self.finish(index, Err(error))
+            },
+            ApduHeader::SimpleAck { service: acked, .. } if acked == service && awaiting =>
+                self.finish(index, Ok(ConfirmedAck::Simple)),
+            ApduHeader::ComplexAck { segmented: None, service: acked, .. } if acked == service && awaiting => {
+                let result = parse(data, self.transactions[index].schema).map(ConfirmedAck::Complex);
+                self.finish(index, result)
+            },
+            ApduHeader::ComplexAck { segmented: Some(info), service: acked, .. } if acked == service =>
+                self.segment_received(index, &info, data),
+            ApduHeader::SegmentAck { sequence_number, actual_window_size, .. } =>
+                self.segment_acked(index, sequence_number, actual_window_size),
+            _ => self.abort(index, AbortReason::InvalidApduInThisState),
+        }
+    }
+
+    /// Sends requests or windows of segments again whose answers are overdue, giving the
+    /// confirmations of those which have run out of retries
+    pub fn poll(&mut self) -> Vec<Confirmation> {
+        let now = self.clock.now();
+        let mut timed_out = vec!();
+        let mut index = 0;
+        while index < self.transactions.len() {
+            let transaction = &mut self.transactions[index];
+            // A Complex-ACK whose segments stop coming isn't asked for again
+            let can_retry = transaction.retries < self.number_of_apdu_retries
+                && !matches!(transaction.state, State::SegmentedConfirmation(_));
+            if transaction.deadline > now {
+                index += 1;
+            } else if can_retry {
+                transaction.retries += 1;
+                if let Some(ref mut segments) = transaction.segments {
+                    if let State::AwaitConfirmation = transaction.state {
+                        // The whole request is sent again, starting with its first segment
+                        segments.restart();
+                        transaction.state = State::SegmentedRequest;
+                    }
+                    transaction.deadline = now + self.apdu_segment_timeout;
+                } else {
+                    transaction.deadline = now + self.apdu_timeout;
+                }
+                // A request which can't be sent is as good as lost, it times out like one
+                let _ = send(&mut self.transport, transaction);
+                index += 1;
+            } else {
+                timed_out.push(confirmation(self.transactions.remove(index), Err(RequestError::Timeout)));
+            }
+        }
+        timed_out
+    }
+
+    /// When the next answer, segment or SegmentACK becomes overdue, if there are requests in
+    /// progress
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.transactions.iter().map(|transaction| transaction.deadline).min()
+    }
+
+    /// Whether a request to the peer with the invoke id is waiting for its answer
+    pub fn is_pending(&self, peer: &NetworkAddress, invoke_id: u8) -> bool {
+        self.transactions.iter().any(|transaction| transaction.peer == *peer && transaction.invoke_id == invoke_id)
+    }
+
+    /// Moves on to the next window of the request's segments, or to waiting for the answer once
+    /// they have all been received
+    fn segment_acked(&mut self, index: usize, sequence_number: u8, actual_window_size: u8) -> Option<Confirmation> {
+        let now = self.clock.now();
+        let transaction = &mut self.transactions[index];
+        let acked = match (&transaction.state, transaction.segments.as_mut()) {
+            (&State::SegmentedRequest, Some(segments)) => segments.ack(sequence_number, actual_window_size),
+            _ => Err(AbortReason::InvalidApduInThisState),
+        };
+        match acked {
+            Ok(SegmentAcked::Duplicate) =>
+                transaction.deadline = now + self.apdu_segment_timeout,
+            Ok(SegmentAcked::NextWindow) => {
+                transaction.retries = 0;
+                transaction.deadline = now + self.apdu_segment_timeout;
+                let _ = send(&mut self.transport, transaction);
+            },
+            Ok(SegmentAcked::Complete) => {
+                transaction.state = State::AwaitConfirmation;
+                transaction.retries = 0;
+                transaction.deadline = now + self.apdu_timeout;
+            },
+            Err(reason) => return self.abort(index, reason),
+        }
+        None
+    }
+
+    /// Adds a segment of a Complex-ACK, giving the confirmation once it is complete
+    fn segment_received(&mut self, index: usize, info: &SegmentInfo, data: &[u8]) -> Option<Confirmation> {
+        let now = self.clock.now();
+        let transaction = &mut self.transactions[index];
+        let received = match transaction.state {
+            State::SegmentedConfirmation(ref mut receiver) => receiver.receive(info, data),
+            State::AwaitConfirmation => {
+                let max_segments = MAX_SEGMENTS_ACCEPTED.count();
+                SegmentReceiver::new(transaction.invoke_id, false, info, data, self.window_size, max_segments)
+                    .map(|(receiver, ack)| {
+                        transaction.state = State::SegmentedConfirmation(receiver);
+                        Some(ack)
+                    })
+            },
+            State::SegmentedRequest => Err(AbortReason::InvalidApduInThisState),
+        };
+        let ack = match received {
+            Ok(ack) => ack,
+            Err(reason) => return self.abort(index, reason),
+        };
+        transaction.deadline = now + self.apdu_segment_timeout * SEGMENT_WAIT_TIMEOUTS;
+        if let Some(ack) = ack {
+            let _ = self.transport.send(&transaction.peer, ack, &[]);
+        }
+        let result = match transaction.state {
+            State::SegmentedConfirmation(ref receiver) if receiver.is_complete() => parse(receiver.data(), transaction.schema),
+            _ => return None,
+        };
+        self.finish(index, result.map(ConfirmedAck::Complex))
+    }
+
+    /// Ends a transaction because the peer's answer makes no sense, telling the peer
+    fn abort(&mut self, index: usize, abort_reason: AbortReason) -> Option<Confirmation> {
+        let transaction = &self.transactions[index];
+        let abort = ApduHeader::AbortPdu { server: false, invoke_id: transaction.invoke_id, abort_reason };
+        // The transaction is over whether or not the peer hears about it
+        let _ = self.transport.send(&transaction.peer, abort, &[]);
+        self.finish(index, Err(RequestError::Service(ServiceError::Abort(abort_reason))))
+    }
+
+    fn finish(&mut self, index: usize, result: Result<ConfirmedAck, RequestError>) -> Option<Confirmation> {
+        Some(confirmation(self.transactions.remove(index), result))
+    }
+
+    /// The next invoke id for the peer which isn't in use
+    fn allocate_invoke_id(&mut self, peer: &NetworkAddress) -> Option<u8> {
+        let next = self.next_invoke_id.entry(peer.clone()).or_insert(0);
+        let start = *next;
+        let transactions = &self.transactions;
+        let invoke_id = (0..=255u8).map(|offset| start.wrapping_add(offset))
+            .find(|&id| !transactions.iter().any(|transaction| transaction.peer == *peer && transaction.invoke_id == id))?;
+        *next = invoke_id.wrapping_add(1);
+        Some(invoke_id)
+    }
+}
+
+/// The header of a request, which accepts a segmented answer
+fn request_header(invoke_id: u8, service: u8) -> ApduHeader {
+    ApduHeader::ConfirmedReq {
+        segmented: None,
+        segmented_response_accepted: true,
+        max_segments: MAX_SEGMENTS_ACCEPTED,
+        max_apdu: MAX_APDU_ACCEPTED,
+        invoke_id,
+        service,
+    }
+}
+
+/// Sends the request of a transaction, or the current window of its segments
+fn send<T: Transport>(transport: &mut T, transaction: &Transaction) -> io::Result<()> {
+    match transaction.segments {
+        Some(ref segments) => {
+            for (header, segment) in segments.window() {
+                transport.send(&transaction.peer, header, segment)?;
+            }
+            Ok(())
+        },
+        None => transport.send(&transaction.peer, request_header(transaction.invoke_id, transaction.service), &transaction.data),
+    }
+}
+
+/// Parses service data from the peer
+fn parse(data: &[u8], schema: &Schema) -> Result<ValueSequence, RequestError> {
+    decode_value_sequence_to_end(data, schema).map(into_owned).map_err(|error| RequestError::InvalidAnswer(error.into()))
+}
+
+fn confirmation(transaction: Transaction, result: Result<ConfirmedAck, RequestError>) -> Confirmation {
+    Confirmation {
+        peer: transaction.peer,
+        invoke_id: transaction.invoke_id,
+        service: transaction.service,
+        result,
+    }
+}
+
+/// The transport used in tests
+#[cfg(test)]
+pub mod test_link {
+    use super::Transport;
+    use ast::ApduHeader;
+    use ast::NetworkAddress;
+    use std::io;
+
+    /// A link which keeps what is sent on it, and fails to send when told to
+    #[derive(Default)]
+    pub struct TestLink {
+        pub sent: Vec<(NetworkAddress, ApduHeader, Vec<u8>)>,
+        pub broken: bool,
+    }
+
+    impl Transport for TestLink {
+        fn send(&mut self, destination: &NetworkAddress, header: ApduHeader, data: &[u8]) -> io::Result<()> {
+            if self.broken {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "broken"))
+            }
+            self.sent.push((destination.clone(), header, data.to_vec()));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ast::MacAddress;
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue::ApplicationValue;
+    use clock::ManualClock;
+    use enumerations::{ErrorClass, ErrorCode, ObjectType, PropertyIdentifier, RejectReason};
+    use object::ObjectId;
+    use service::readproperty;
+    use service::test_peer;
+    use super::test_link::TestLink;
+
+    fn client() -> (Client<TestLink>, ManualClock) {
+        let clock = ManualClock::new();
+        (Client::new(TestLink::default(), Box::new(clock.clone())), clock)
+    }
+
+    fn other_peer() -> NetworkAddress {
+        NetworkAddress { network: 0, address: MacAddress::new(&[192, 168, 1, 11, 0xBA, 0xC0]).unwrap() }
+    }
+
+    fn read_name() -> readproperty::Message {
+        readproperty::Message::new(ObjectId(ObjectType::Device, 7), PropertyIdentifier::ObjectName, None)
+    }
+
+    fn read(client: &mut Client<TestLink>, peer: &NetworkAddress) -> u8 {
+        client.request(peer, &read_name(), &readproperty::Ack::SCHEMA).unwrap()
+    }
+
+    fn encode(body: ValueSequence) -> Vec<u8> {
+        let mut data = vec![];
+        write_value_sequence(&mut data, body).unwrap();
+        data
+    }
+
+    fn simple_ack(invoke_id: u8) -> ApduHeader {
+        ApduHeader::SimpleAck { invoke_id, service: 12 }
+    }
+
+    /// The ack of a read of a long object name
+    fn long_name_ack() -> (String, Vec<u8>) {
+        let name: String = (0..2000).map(|n| (b'a' + (n % 26) as u8) as char).collect();
+        let ack = readproperty::Ack {
+            object_id: ObjectId(ObjectType::Device, 7),
+            property: PropertyIdentifier::ObjectName,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::CharacterString(name.clone()))),
+        };
+        (name, encode(ack.marshall()))
+    }
+
+    fn segment(sequence_number: u8, more_follows: bool) -> ApduHeader {
+        ApduHeader::ComplexAck {
+            segmented: Some(SegmentInfo { more_follows, sequence_number, proposed_window_size: 2 }),
+            invoke_id: 0,
+            service: 12,
+        }
+    }
+
+    fn segment_ack(negative_ack: bool, sequence_number: u8, actual_window_size: u8) -> ApduHeader {
+        ApduHeader::SegmentAck { negative_ack, server: false, invoke_id: 0, sequence_number, actual_window_size }
+    }
+
+    #[test]
+    fn request_is_sent() {
+        let (mut client, _) = client();
+        assert_eq!(Ok(0), client.request(&test_peer(), &read_name(), &readproperty::Ack::SCHEMA));
+        assert_eq!(vec!((test_peer(), ApduHeader::ConfirmedReq {
+            segmented: None,
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::Up64,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 0,
+            service: 12,
+        }, encode(read_name().marshall()))), client.transport().sent);
+        assert!(client.is_pending(&test_peer(), 0));
+        assert_eq!(Some(Duration::from_secs(3)), client.next_deadline());
+    }
+
+    #[test]
+    fn complex_ack_confirms_request() {
+        let (mut client, _) = client();
+        let invoke_id = read(&mut client, &test_peer());
+        let value = readproperty::Ack {
+            object_id: ObjectId(ObjectType::Device, 7),
+            property: PropertyIdentifier::ObjectName,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::CharacterString("Seven".to_string()))),
+        }.marshall();
+        assert_eq!(Some(Confirmation {
+            peer: test_peer(),
+            invoke_id,
+            service: 12,
+            result: Ok(ConfirmedAck::Complex(value.clone())),
+        }), client.receive(&test_peer(), ApduHeader::ComplexAck { segmented: None, invoke_id, service: 12 }, &encode(value)));
+        assert!(!client.is_pending(&test_peer(), invoke_id));
+        assert_eq!(None, client.next_deadline());
+    }
+
+    #[test]
+    fn failures_confirm_request() {
+        let (mut client, _) = client();
+        let error = vec!(ApplicationValue(PrimitiveValue::Enumerated(ErrorClass::Object.into())),
+                         ApplicationValue(PrimitiveValue::Enumerated(ErrorCode::UnknownObject.into())));
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Error(error.clone())))),
+                   client.receive(&test_peer(), ApduHeader::ErrorPdu { invoke_id, error_choice: 12 }, &encode(error)).map(|c| c.result));
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Reject(RejectReason::MissingRequiredParameter)))),
+                   client.receive(&test_peer(), ApduHeader::RejectPdu { invoke_id, reject_reason: RejectReason::MissingRequiredParameter }, &[])
+                       .map(|c| c.result));
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Abort(AbortReason::OutOfResources)))),
+                   client.receive(&test_peer(), ApduHeader::AbortPdu { server: true, invoke_id, abort_reason: AbortReason::OutOfResources }, &[])
+                       .map(|c| c.result));
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::InvalidAnswer(ParseError::UnexpectedTag))),
+                   client.receive(&test_peer(), ApduHeader::ComplexAck { segmented: None, invoke_id, service: 12 }, &[0x21, 0x07])
+                       .map(|c| c.result));
+    }
+
+    #[test]
+    fn answers_to_other_transactions_are_ignored() {
+        let (mut client, _) = client();
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(None, client.receive(&other_peer(), simple_ack(invoke_id), &[]));
+        assert_eq!(None, client.receive(&test_peer(), simple_ack(invoke_id + 1), &[]));
+        // An abort from a client is about the peer's own request
+        assert_eq!(None, client.receive(&test_peer(), ApduHeader::AbortPdu { server: false, invoke_id, abort_reason: AbortReason::Other }, &[]));
+        assert!(client.is_pending(&test_peer(), invoke_id));
+    }
+
+    #[test]
+    fn unexpected_answer_is_aborted() {
+        let (mut client, _) = client();
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Abort(AbortReason::InvalidApduInThisState)))),
+                   client.receive(&test_peer(), ApduHeader::SimpleAck { invoke_id, service: 15 }, &[]).map(|c| c.result));
+        assert_eq!(Some(&(test_peer(), ApduHeader::AbortPdu { server: false, invoke_id, abort_reason: AbortReason::InvalidApduInThisState }, vec!())),
+                   client.transport().sent.last());
+        assert!(!client.is_pending(&test_peer(), invoke_id));
+        // The request wasn't segmented, so there's nothing to acknowledge
+        let invoke_id = read(&mut client, &test_peer());
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Abort(AbortReason::InvalidApduInThisState)))),
+                   client.receive(&test_peer(), ApduHeader::SegmentAck { negative_ack: false, server: true, invoke_id, sequence_number: 0, actual_window_size: 1 }, &[])
+                       .map(|c| c.result));
+    }
+
+    #[test]
+    fn invoke_ids_are_per_peer() {
+        let (mut client, _) = client();
+        assert_eq!(0, read(&mut client, &test_peer()));
+        assert_eq!(1, read(&mut client, &test_peer()));
+        assert_eq!(0, read(&mut client, &other_peer()));
+        client.receive(&test_peer(), simple_ack(0), &[]);
+        assert_eq!(2, read(&mut client, &test_peer()));
+    }
+
+    #[test]
+    fn invoke_ids_run_out() {
+        let (mut client, _) = client();
+        for invoke_id in 0..=255 {
+            assert_eq!(invoke_id, read(&mut client, &test_peer()));
+        }
+        assert_eq!(Err(RequestError::NoInvokeIdAvailable), client.request(&test_peer(), &read_name(), &readproperty::Ack::SCHEMA));
+        client.receive(&test_peer(), simple_ack(17), &[]);
+        assert_eq!(17, read(&mut client, &test_peer()));
+    }
+
+    #[test]
+    fn requests_are_retried_then_time_out() {
+        let (mut client, clock) = client();
+        client.number_of_apdu_retries = 2;
+        let invoke_id = read(&mut client, &test_peer());
+        clock.advance(Duration::from_millis(2999));
+        assert!(client.poll().is_empty());
+        assert_eq!(1, client.transport().sent.len());
+        clock.advance(Duration::from_millis(1));
+        assert!(client.poll().is_empty());
+        assert_eq!(2, client.transport().sent.len());
+        assert_eq!(client.transport().sent[0], client.transport().sent[1]);
+        assert_eq!(Some(Duration::from_secs(6)), client.next_deadline());
+        clock.advance(Duration::from_secs(3));
+        assert!(client.poll().is_empty());
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(vec!(Confirmation { peer: test_peer(), invoke_id, service: 12, result: Err(RequestError::Timeout) }), client.poll());
+        assert_eq!(3, client.transport().sent.len());
+        assert!(!client.is_pending(&test_peer(), invoke_id));
+    }
+
+    #[test]
+    fn answer_to_a_retry_confirms_request() {
+        let (mut client, clock) = client();
+        let invoke_id = read(&mut client, &test_peer());
+        clock.advance(Duration::from_secs(3));
+        client.poll();
+        assert!(client.receive(&test_peer(), simple_ack(invoke_id), &[]).unwrap().result.is_ok());
+        clock.advance(Duration::from_secs(30));
+        assert!(client.poll().is_empty());
+    }
+
+    #[test]
+    fn request_which_cant_be_sent_fails() {
+        let (mut client, _) = client();
+        client.transport_mut().broken = true;
+        assert_eq!(Err(RequestError::Transport(io::ErrorKind::NotConnected)), client.request(&test_peer(), &read_name(), &readproperty::Ack::SCHEMA));
+        assert_eq!(None, client.next_deadline());
+    }
+
+    #[test]
+    fn request_which_cant_be_encoded_fails() {
+        let (mut client, _) = client();
+        let message = readproperty::Message::new(ObjectId(ObjectType::Device, 0x400000), PropertyIdentifier::ObjectName, None);
+        assert_eq!(Err(RequestError::Unencodable(EncodeError::OutOfRange)), client.request(&test_peer(), &message, &readproperty::Ack::SCHEMA));
+        assert!(client.transport().sent.is_empty());
+        assert_eq!(0, read(&mut client, &test_peer()));
+    }
+
+    #[test]
+    fn timing_from_device() {
+        let (mut client, _) = client();
+        let mut device = DeviceObject::new(45, "Test device", 23);
+        device.apdu_timeout = 500;
+        device.apdu_segment_timeout = 200;
+        device.number_of_apdu_retries = 0;
+        client.use_device_timing(&device);
+        assert_eq!(Duration::from_millis(500), client.apdu_timeout);
+        assert_eq!(Duration::from_millis(200), client.apdu_segment_timeout);
+        assert_eq!(0, client.number_of_apdu_retries);
+    }
+
+    #[test]
+    fn segmented_answer_is_reassembled() {
+        let (mut client, _) = client();
+        client.window_size = 2;
+        let invoke_id = read(&mut client, &test_peer());
+        let (name, data) = long_name_ack();
+        let chunks: Vec<&[u8]> = data.chunks(480).collect();
+        assert_eq!(5, chunks.len());
+        assert_eq!(None, client.receive(&test_peer(), segment(0, true), chunks[0]));
+        assert_eq!(Some(&(test_peer(), segment_ack(false, 0, 2), vec!())), client.transport().sent.last());
+        assert_eq!(None, client.receive(&test_peer(), segment(1, true), chunks[1]));
+        assert_eq!(None, client.receive(&test_peer(), segment(2, true), chunks[2]));
+        assert_eq!(Some(&(test_peer(), segment_ack(false, 2, 2), vec!())), client.transport().sent.last());
+        // A segment goes missing, the window is asked for again
+        assert_eq!(None, client.receive(&test_peer(), segment(4, false), chunks[4]));
+        assert_eq!(Some(&(test_peer(), segment_ack(true, 2, 2), vec!())), client.transport().sent.last());
+        assert_eq!(None, client.receive(&test_peer(), segment(3, true), chunks[3]));
+        let confirmation = client.receive(&test_peer(), segment(4, false), chunks[4]).unwrap();
+        assert_eq!(Some(&(test_peer(), segment_ack(false, 4, 2), vec!())), client.transport().sent.last());
+        assert_eq!(invoke_id, confirmation.invoke_id);
+        let ack = match confirmation.result {
+            Ok(ConfirmedAck::Complex(body)) => readproperty::Ack::unmarshall(&body).unwrap(),
+            result => panic!("{:?}", result),
+        };
+        assert_eq!(vec!(ApplicationValue(PrimitiveValue::CharacterString(name))), ack.value);
+    }
+
+    #[test]
+    fn segmented_answer_times_out() {
+        let (mut client, clock) = client();
+        let invoke_id = read(&mut client, &test_peer());
+        client.receive(&test_peer(), segment(0, true), &[0x0C]);
+        // The next segment is waited for four segment timeouts, without asking again
+        clock.advance(Duration::from_millis(7999));
+        assert!(client.poll().is_empty());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(vec!(Confirmation { peer: test_peer(), invoke_id, service: 12, result: Err(RequestError::Timeout) }), client.poll());
+        assert_eq!(2, client.transport().sent.len());
+    }
+
+    #[test]
+    fn too_many_segments_are_aborted() {
+        let (mut client, _) = client();
+        client.window_size = 127;
+        read(&mut client, &test_peer());
+        client.receive(&test_peer(), segment(0, true), &[]);
+        for sequence_number in 1..64 {
+            assert_eq!(None, client.receive(&test_peer(), segment(sequence_number, true), &[]));
+        }
+        assert_eq!(Some(Err(RequestError::Service(ServiceError::Abort(AbortReason::BufferOverflow)))),
+                   client.receive(&test_peer(), segment(64, true), &[]).map(|c| c.result));
+        assert_eq!(Some(&(test_peer(), ApduHeader::AbortPdu { server: false, invoke_id: 0, abort_reason: AbortReason::BufferOverflow }, vec!())),
+                   client.transport().sent.last());
+    }
+
+    /// A write of a value too long for a peer which accepts 480 octet APDUs
+    fn long_write() -> ValueSequence {
+        vec!(ApplicationValue(PrimitiveValue::OctetString(vec![0x55; 1000])))
+    }
+
+    fn segmented_peer() -> PeerLimits {
+        PeerLimits { max_apdu: MaxApdu::Up480, segmentation: Segmentation::SegmentedBoth, max_segments: MaxSegments::Up4 }
+    }
+
+    fn segment_numbers(sent: &[(NetworkAddress, ApduHeader, Vec<u8>)]) -> Vec<u8> {
+        sent.iter().map(|(_, header, _)| match *header {
+            ApduHeader::ConfirmedReq { segmented: Some(ref info), .. } => info.sequence_number,
+            _ => panic!("not a segment"),
+        }).collect()
+    }
+
+    #[test]
+    fn long_request_is_segmented() {
+        let (mut client, _) = client();
+        client.set_peer_limits(&test_peer(), segmented_peer());
+        let invoke_id = client.send_request(&test_peer(), 15, long_write(), &readproperty::Ack::SCHEMA).unwrap();
+        // The first segment goes on its own
+        assert_eq!(vec!(0), segment_numbers(&client.transport().sent));
+        assert_eq!(480, 6 + client.transport().sent[0].2.len());
+        assert_eq!(Some(Duration::from_secs(2)), client.next_deadline());
+        let ack = |sequence_number, actual_window_size| ApduHeader::SegmentAck {
+            negative_ack: false, server: true, invoke_id, sequence_number, actual_window_size,
+        };
+        client.transport_mut().sent.clear();
+        assert_eq!(None, client.receive(&test_peer(), ack(0, 4), &[]));
+        assert_eq!(vec!(1, 2), segment_numbers(&client.transport().sent));
+        let data: Vec<u8> = client.transport().sent.iter().flat_map(|(_, _, segment)| segment.clone()).collect();
+        assert_eq!(encode(long_write())[474..].to_vec(), data);
+        assert_eq!(None, client.receive(&test_peer(), ack(2, 4), &[]));
+        assert_eq!(Some(Duration::from_secs(3)), client.next_deadline());
+        assert_eq!(Some(Ok(ConfirmedAck::Simple)),
+                   client.receive(&test_peer(), ApduHeader::SimpleAck { invoke_id, service: 15 }, &[]).map(|c| c.result));
+    }
+
+    #[test]
+    fn segments_are_sent_again() {
+        let (mut client, clock) = client();
+        client.set_peer_limits(&test_peer(), segmented_peer());
+        let invoke_id = client.send_request(&test_peer(), 15, long_write(), &readproperty::Ack::SCHEMA).unwrap();
+        client.receive(&test_peer(), ApduHeader::SegmentAck { negative_ack: false, server: true, invoke_id, sequence_number: 0, actual_window_size: 4 }, &[]);
+        client.transport_mut().sent.clear();
+        // The peer lost segment 1
+        assert_eq!(None, client.receive(&test_peer(), ApduHeader::SegmentAck { negative_ack: true, server: true, invoke_id, sequence_number: 0, actual_window_size: 4 }, &[]));
+        assert!(client.transport().sent.is_empty());
+        clock.advance(Duration::from_secs(2));
+        assert!(client.poll().is_empty());
+        assert_eq!(vec!(1, 2), segment_numbers(&client.transport().sent));
+        // No SegmentACK comes, then no answer to the whole request
+        client.number_of_apdu_retries = 1;
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(Some(Err(RequestError::Timeout)), client.poll().pop().map(|c| c.result));
+    }
+
+    #[test]
+    fn request_too_long_for_peer() {
+        let (mut client, _) = client();
+        assert_eq!(Err(RequestError::TooLong), client.send_request(&test_peer(), 15, vec!(ApplicationValue(PrimitiveValue::OctetString(vec![0; 1500]))), &readproperty::Ack::SCHEMA));
+        client.set_peer_limits(&test_peer(), PeerLimits { max_segments: MaxSegments::Up2, ..segmented_peer() });
+        assert_eq!(Err(RequestError::TooLong), client.send_request(&test_peer(), 15, long_write(), &readproperty::Ack::SCHEMA));
+        client.set_peer_limits(&test_peer(), PeerLimits { segmentation: Segmentation::SegmentedTransmit, ..segmented_peer() });
+        assert_eq!(Err(RequestError::TooLong), client.send_request(&test_peer(), 15, long_write(), &readproperty::Ack::SCHEMA));
+        assert!(client.transport().sent.is_empty());
+    }
+}
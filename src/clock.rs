@@ -0,0 +1,134 @@
+//! Time as seen by the parts of the stack which have timers, such as COV subscription lifetimes.
+//! Time is a monotonic duration since an arbitrary start, so a clock can be replaced by a manual
+//! one in tests.
+
+use ast::{Date, DayOfMonth, Month, Time, Year};
+use std::convert::TryFrom;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock {
+    /// The time since the clock's start
+    fn now(&self) -> Duration;
+
+    /// The date and time of day at a time on the clock, for clocks which know the date
+    fn date_time(&self, _at: Duration) -> Option<(Date, Time)> {
+        None
+    }
+}
+
+/// The system's monotonic clock, starting when it is created. Its dates and times are UTC.
+pub struct SystemClock {
+    start: Instant,
+    started: SystemTime,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+            started: SystemTime::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn date_time(&self, at: Duration) -> Option<(Date, Time)> {
+        (self.started + at).duration_since(UNIX_EPOCH).ok().map(utc_date_time)
+    }
+}
+
+/// The UTC date and time of day a duration after the start of 1970
+fn utc_date_time(since_epoch: Duration) -> (Date, Time) {
+    let seconds = since_epoch.as_secs();
+    let days = seconds / 86400;
+    // The proleptic Gregorian calendar in 400 year eras, with years starting in March
+    let day_of_era = (days + 719468) % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = (days + 719468) / 146097 * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    let date = Date {
+        year: u16::try_from(year).ok().and_then(Year::new),
+        month: Month::Number(month as u8),
+        day: DayOfMonth::Number((day_of_year - (153 * month_from_march + 2) / 5 + 1) as u8),
+        // 1970-01-01 was a Thursday
+        weekday: Some(((days + 3) % 7 + 1) as u8),
+    };
+    let time = Time {
+        hour: Some((seconds % 86400 / 3600) as u8),
+        minute: Some((seconds % 3600 / 60) as u8),
+        second: Some((seconds % 60) as u8),
+        hundredths: Some((since_epoch.subsec_millis() / 10) as u8),
+    };
+    (date, time)
+}
+
+/// A clock which only moves when it is told to. Clones share the same time, so a test can keep
+/// one to advance the clock it has handed over.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manual_clock_is_shared_by_clones() {
+        let clock = ManualClock::new();
+        let handed_over = clock.clone();
+        assert_eq!(Duration::from_secs(0), handed_over.now());
+        clock.advance(Duration::from_secs(5));
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(Duration::from_millis(5010), handed_over.now());
+    }
+
+    #[test]
+    fn utc_dates_and_times() {
+        let (date, time) = utc_date_time(Duration::from_millis(1_456_704_000_250));
+        assert_eq!(Date { year: Year::new(2016), month: Month::Number(2), day: DayOfMonth::Number(29), weekday: Some(1) }, date);
+        assert_eq!(Time { hour: Some(0), minute: Some(0), second: Some(0), hundredths: Some(25) }, time);
+        let (date, time) = utc_date_time(Duration::from_secs(86399));
+        assert_eq!(Date { year: Year::new(1970), month: Month::Number(1), day: DayOfMonth::Number(1), weekday: Some(4) }, date);
+        assert_eq!(Time { hour: Some(23), minute: Some(59), second: Some(59), hundredths: Some(0) }, time);
+        let (date, _) = utc_date_time(Duration::from_secs(1_609_459_199));
+        assert_eq!(Date { year: Year::new(2020), month: Month::Number(12), day: DayOfMonth::Number(31), weekday: Some(4) }, date);
+    }
+
+    #[test]
+    fn system_clock_knows_the_date() {
+        let clock = SystemClock::new();
+        assert!(clock.date_time(clock.now()).unwrap().0.year.unwrap().get() >= 2016);
+        assert_eq!(None, ManualClock::new().date_time(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,230 @@
+//! Change of value reporting - Clause 13.1. Devices subscribe to an object, or to one property of
+//! it, and are sent a notification with the current values when the subscription starts and
+//! whenever the values change. A REAL value only counts as changed once it has moved by the
+//! subscription's COV increment since the last notification.
+
+use ast::NetworkAddress;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+use object::ObjectId;
+use service::covnotification::Notification;
+use service::writepropertymultiple::PropertyValue;
+use std::time::Duration;
+use enumerations::PropertyIdentifier;
+
+/// The subscription table, which is kept in the `object::BacnetDB`
+#[derive(Default)]
+pub struct CovSubscriptions {
+    subscriptions: Vec<Entry>,
+}
+
+struct Entry {
+    subscription: Subscription,
+    /// The values in the last notification, `None` before the first one
+    notified: Option<Vec<PropertyValue>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Subscription {
+    pub subscriber: NetworkAddress,
+    pub subscriber_process_id: u32,
+    pub object_id: ObjectId,
+    /// The property and array index for SubscribeCOVProperty, SubscribeCOV watches the object's
+    /// COV properties
+    pub property: Option<(PropertyIdentifier, Option<u32>)>,
+    pub issue_confirmed_notifications: bool,
+    /// The change in a REAL value which counts as a change of value, when `None` the object's
+    /// COV_Increment is used if it has one, otherwise any change counts
+    pub cov_increment: Option<f32>,
+    /// When the subscription ends, `None` for an indefinite subscription
+    pub expires: Option<Duration>,
+}
+
+impl Subscription {
+    /// Subscriptions are identified by the subscriber and what it is watching, a subscription
+    /// with the same identity replaces an existing one
+    fn identified_by(&self, subscriber: &NetworkAddress, subscriber_process_id: u32, object_id: ObjectId, property: Option<(PropertyIdentifier, Option<u32>)>) -> bool {
+        self.subscriber == *subscriber &&
+            self.subscriber_process_id == subscriber_process_id &&
+            self.object_id == object_id &&
+            self.property == property
+    }
+}
+
+/// A notification to send to a subscriber
+#[derive(Debug, PartialEq)]
+pub struct CovNotification {
+    pub subscriber: NetworkAddress,
+    /// Whether to send a ConfirmedCOVNotification rather than an unconfirmed one
+    pub confirmed: bool,
+    pub notification: Notification,
+}
+
+/// The time a subscription with a lifetime in seconds ends, a lifetime of 0 is indefinite
+pub fn expiry(now: Duration, lifetime: u32) -> Option<Duration> {
+    match lifetime {
+        0 => None,
+        seconds => Some(now + Duration::from_secs(seconds as u64)),
+    }
+}
+
+impl CovSubscriptions {
+    /// Adds a subscription, or renews an existing one. Either way the subscriber is sent the
+    /// current values.
+    pub fn subscribe(&mut self, subscription: Subscription) {
+        self.cancel(&subscription.subscriber, subscription.subscriber_process_id, subscription.object_id, subscription.property);
+        self.subscriptions.push(Entry {
+            subscription,
+            notified: None,
+        });
+    }
+
+    /// Ends a subscription, it isn't an error if there is no such subscription
+    pub fn cancel(&mut self, subscriber: &NetworkAddress, subscriber_process_id: u32, object_id: ObjectId, property: Option<(PropertyIdentifier, Option<u32>)>) {
+        self.subscriptions.retain(|entry| !entry.subscription.identified_by(subscriber, subscriber_process_id, object_id, property));
+    }
+
+    pub fn subscriptions(&self) -> impl Iterator<Item = &Subscription> {
+        self.subscriptions.iter().map(|entry| &entry.subscription)
+    }
+
+    /// Drops the subscriptions which have ended, then returns a notification for each new
+    /// subscription and each subscription whose values have changed. `values` reads the current
+    /// values for a subscription along with the object's COV_Increment, it returns `None` when
+    /// they can't be read and the subscription is skipped.
+    pub fn notifications<F>(&mut self, now: Duration, device_id: ObjectId, values: F) -> Vec<CovNotification>
+        where F: Fn(&Subscription) -> Option<(Vec<PropertyValue>, Option<f32>)> {
+        self.subscriptions.retain(|entry| entry.subscription.expires.is_none_or(|expires| expires > now));
+        let mut notifications = vec!();
+        for entry in &mut self.subscriptions {
+            let (current, object_increment) = match values(&entry.subscription) {
+                Some(values) => values,
+                None => continue,
+            };
+            let increment = entry.subscription.cov_increment.or(object_increment);
+            let changed = match entry.notified {
+                Some(ref notified) => has_changed(notified, &current, increment),
+                None => true,
+            };
+            if !changed {
+                continue;
+            }
+            let subscription = &entry.subscription;
+            notifications.push(CovNotification {
+                subscriber: subscription.subscriber.clone(),
+                confirmed: subscription.issue_confirmed_notifications,
+                notification: Notification {
+                    subscriber_process_id: subscription.subscriber_process_id,
+                    initiating_device_id: device_id,
+                    monitored_object_id: subscription.object_id,
+                    time_remaining: subscription.expires
+                        .map_or(0, |expires| (expires - now).as_secs() as u32),
+                    values: current.clone(),
+                },
+            });
+            entry.notified = Some(current);
+        }
+        notifications
+    }
+}
+
+/// Whether any value differs from the one last notified, REAL values have to have moved by at
+/// least the increment (any change at all when the increment is 0)
+fn has_changed(notified: &[PropertyValue], current: &[PropertyValue], increment: Option<f32>) -> bool {
+    if notified.len() != current.len() {
+        return true
+    }
+    notified.iter().zip(current).any(|(old, new)| {
+        match (old.value.as_slice(), new.value.as_slice(), increment) {
+            ([ApplicationValue(PrimitiveValue::Real(old))], [ApplicationValue(PrimitiveValue::Real(new))], Some(increment)) =>
+                new != old && (new - old).abs() >= increment,
+            _ => old != new,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use service::test_peer;
+    use enumerations::ObjectType;
+
+    fn real(value: f32) -> Vec<PropertyValue> {
+        vec!(PropertyValue {
+            property: PropertyIdentifier::PresentValue,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::Real(value))),
+            priority: None,
+        })
+    }
+
+    fn subscription(expires: Option<Duration>, cov_increment: Option<f32>) -> Subscription {
+        Subscription {
+            subscriber: test_peer(),
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::AnalogInput, 10),
+            property: None,
+            issue_confirmed_notifications: false,
+            cov_increment,
+            expires,
+        }
+    }
+
+    fn notified_values(subscriptions: &mut CovSubscriptions, now: u64, value: f32, object_increment: Option<f32>) -> Vec<(u32, Vec<PropertyValue>)> {
+        subscriptions.notifications(Duration::from_secs(now), ObjectId(ObjectType::Device, 45), |_| Some((real(value), object_increment)))
+            .into_iter()
+            .map(|notification| (notification.notification.time_remaining, notification.notification.values))
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_measured_against_the_increment() {
+        assert!(!has_changed(&real(20.0), &real(20.5), Some(1.0)));
+        assert!(has_changed(&real(20.0), &real(21.0), Some(1.0)));
+        assert!(has_changed(&real(20.0), &real(19.0), Some(1.0)));
+        assert!(has_changed(&real(20.0), &real(20.5), None));
+        assert!(!has_changed(&real(20.0), &real(20.0), None));
+        assert!(!has_changed(&real(20.0), &real(20.0), Some(0.0)));
+    }
+
+    #[test]
+    fn new_subscriptions_are_notified() {
+        let mut subscriptions = CovSubscriptions::default();
+        subscriptions.subscribe(subscription(None, Some(1.0)));
+        assert_eq!(vec!((0, real(20.0))), notified_values(&mut subscriptions, 0, 20.0, None));
+        assert!(notified_values(&mut subscriptions, 1, 20.5, None).is_empty());
+        assert_eq!(vec!((0, real(21.0))), notified_values(&mut subscriptions, 2, 21.0, None));
+        // Renewing a subscription sends the values again
+        subscriptions.subscribe(subscription(None, Some(1.0)));
+        assert_eq!(1, subscriptions.subscriptions().count());
+        assert_eq!(vec!((0, real(21.0))), notified_values(&mut subscriptions, 3, 21.0, None));
+    }
+
+    #[test]
+    fn object_increment_is_used_by_default() {
+        let mut subscriptions = CovSubscriptions::default();
+        subscriptions.subscribe(subscription(None, None));
+        notified_values(&mut subscriptions, 0, 20.0, Some(2.0));
+        assert!(notified_values(&mut subscriptions, 1, 21.5, Some(2.0)).is_empty());
+        assert_eq!(vec!((0, real(22.0))), notified_values(&mut subscriptions, 2, 22.0, Some(2.0)));
+    }
+
+    #[test]
+    fn subscriptions_expire() {
+        let mut subscriptions = CovSubscriptions::default();
+        subscriptions.subscribe(subscription(expiry(Duration::from_secs(0), 60), None));
+        assert_eq!(vec!((50, real(20.0))), notified_values(&mut subscriptions, 10, 20.0, None));
+        assert!(notified_values(&mut subscriptions, 60, 25.0, None).is_empty());
+        assert_eq!(0, subscriptions.subscriptions().count());
+    }
+
+    #[test]
+    fn cancelled_subscriptions_are_removed() {
+        let mut subscriptions = CovSubscriptions::default();
+        subscriptions.subscribe(subscription(None, None));
+        subscriptions.cancel(&test_peer(), 17, ObjectId(ObjectType::AnalogInput, 10), None);
+        assert_eq!(1, subscriptions.subscriptions().count());
+        subscriptions.cancel(&test_peer(), 18, ObjectId(ObjectType::AnalogInput, 10), None);
+        assert_eq!(0, subscriptions.subscriptions().count());
+    }
+}
@@ -0,0 +1,295 @@
+//! The BACnet/IP datalink - Annex J. NPDUs are carried in UDP datagrams behind a BVLC header,
+//! broadcasts either go to the local subnet's broadcast address or, for a device registered as a
+//! foreign device, are distributed by a BBMD.
+
+use ast::{ApduHeader, Bvlc, NetworkAddress, Npdu};
+use ast::bvlc_result;
+use client::Transport;
+use parse::parse_bvlc;
+use serialise::{write_apdu_header, write_bvlc, write_npdu};
+use std::cell::Cell;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// The UDP port reserved for BACnet/IP, 0xBAC0
+pub const BACNET_IP_PORT: u16 = 47808;
+
+/// The largest BVLC datagram, a 1497 octet NPDU in a Forwarded-NPDU
+const MAX_DATAGRAM: usize = 1497 + 10;
+
+/// An NPDU received from the network
+#[derive(Debug, PartialEq)]
+pub struct ReceivedNpdu {
+    /// The B/IP address of the device which sent the NPDU, for a forwarded NPDU this is the
+    /// original sender rather than the BBMD which forwarded it
+    pub source: SocketAddr,
+    /// Whether the NPDU was broadcast rather than sent directly to this device
+    pub broadcast: bool,
+    pub npdu: Vec<u8>,
+}
+
+/// A BACnet/IP node which is not a BBMD
+pub struct BipTransport {
+    socket: UdpSocket,
+    broadcast_address: SocketAddr,
+    bbmd: Option<SocketAddr>,
+    last_result: Cell<Option<u16>>,
+}
+
+impl BipTransport {
+    /// Binds to `address`, broadcasts will be sent to `broadcast_address` which should be the
+    /// subnet's broadcast address with the BACnet/IP port
+    pub fn bind<A: ToSocketAddrs>(address: A, broadcast_address: SocketAddr) -> io::Result<BipTransport> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_broadcast(true)?;
+        Ok(BipTransport {
+            socket,
+            broadcast_address,
+            bbmd: None,
+            last_result: Cell::new(None),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sets how long `receive` blocks waiting for an NPDU, `None` blocks indefinitely
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Sends an NPDU directly to one device
+    pub fn send_unicast(&self, npdu: Vec<u8>, destination: SocketAddr) -> io::Result<()> {
+        self.send_bvlc(Bvlc::OriginalUnicastNpdu(npdu), destination)
+    }
+
+    /// Broadcasts an NPDU to the local subnet, or asks the BBMD to distribute it if this device
+    /// is registered as a foreign device
+    pub fn send_broadcast(&self, npdu: Vec<u8>) -> io::Result<()> {
+        match self.bbmd {
+            Some(bbmd) => self.send_bvlc(Bvlc::DistributeBroadcastToNetwork(npdu), bbmd),
+            None => self.send_bvlc(Bvlc::OriginalBroadcastNpdu(npdu), self.broadcast_address),
+        }
+    }
+
+    /// Registers this device with a BBMD as a foreign device (Annex J.5.2), so that it receives
+    /// broadcasts from the BBMD's network. The registration has to be renewed before the time to
+    /// live, in seconds, runs out. The BBMD's answer is available from `last_result` once it has
+    /// been received.
+    pub fn register_foreign_device(&mut self, bbmd: SocketAddr, time_to_live: u16) -> io::Result<()> {
+        self.bbmd = Some(bbmd);
+        self.last_result.set(None);
+        self.send_bvlc(Bvlc::RegisterForeignDevice(time_to_live), bbmd)
+    }
+
+    /// The code of the last BVLC Result received, see `ast::bvlc_result`
+    pub fn last_result(&self) -> Option<u16> {
+        self.last_result.get()
+    }
+
+    /// Blocks until an NPDU is received. BVLL requests which only a BBMD can handle are answered
+    /// with a NAK and invalid datagrams are dropped.
+    pub fn receive(&self) -> io::Result<ReceivedNpdu> {
+        let mut buf = [0u8; MAX_DATAGRAM];
+        loop {
+            let (length, source) = self.socket.recv_from(&mut buf)?;
+            let (npdu, broadcast, source) = match parse_bvlc(&buf[..length]) {
+                Ok(Bvlc::OriginalUnicastNpdu(npdu)) => (npdu, false, source),
+                Ok(Bvlc::OriginalBroadcastNpdu(npdu)) => (npdu, true, source),
+                Ok(Bvlc::ForwardedNpdu { originator, npdu }) => (npdu, true, SocketAddr::V4(originator)),
+                Ok(Bvlc::Result(code)) => {
+                    self.last_result.set(Some(code));
+                    continue;
+                },
+                Ok(request) => {
+                    if let Some(nak) = nak_for(&request) {
+                        self.send_bvlc(Bvlc::Result(nak), source)?;
+                    }
+                    continue;
+                },
+                Err(_) => continue,
+            };
+            return Ok(ReceivedNpdu { source, broadcast, npdu });
+        }
+    }
+
+    fn send_bvlc(&self, bvlc: Bvlc, destination: SocketAddr) -> io::Result<()> {
+        let mut buf = vec![];
+        write_bvlc(&mut buf, bvlc).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "NPDU too long for a BVLC"))?;
+        self.socket.send_to(&buf, destination).map(|_| ())
+    }
+}
+
+/// APDUs to devices on the local network are sent to their B/IP address, which is a 4 octet IP
+/// address and a 2 octet port. APDUs to other networks are broadcast for a router to pass on.
+impl Transport for BipTransport {
+    fn send(&mut self, destination: &NetworkAddress, header: ApduHeader, data: &[u8]) -> io::Result<()> {
+        let expecting_reply = matches!(header, ApduHeader::ConfirmedReq { .. });
+        let mut npdu = vec![];
+        if destination.network == 0 {
+            write_npdu(&mut npdu, Npdu::local(expecting_reply));
+        } else {
+            write_npdu(&mut npdu, Npdu { destination: Some(destination.clone()), ..Npdu::local(expecting_reply) });
+        }
+        write_apdu_header(&mut npdu, header);
+        npdu.extend_from_slice(data);
+        if destination.network != 0 {
+            return self.send_broadcast(npdu)
+        }
+        match destination.address[..] {
+            [a, b, c, d, port_high, port_low] => {
+                let address = SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), u16::from_be_bytes([port_high, port_low]));
+                self.send_unicast(npdu, SocketAddr::V4(address))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "not a B/IP address")),
+        }
+    }
+}
+
+/// The result a device which is not a BBMD sends for BBMD requests - Annex J.2
+fn nak_for(request: &Bvlc) -> Option<u16> {
+    match *request {
+        Bvlc::WriteBroadcastDistributionTable(_) => Some(bvlc_result::WRITE_BROADCAST_DISTRIBUTION_TABLE_NAK),
+        Bvlc::ReadBroadcastDistributionTable => Some(bvlc_result::READ_BROADCAST_DISTRIBUTION_TABLE_NAK),
+        Bvlc::RegisterForeignDevice(_) => Some(bvlc_result::REGISTER_FOREIGN_DEVICE_NAK),
+        Bvlc::ReadForeignDeviceTable => Some(bvlc_result::READ_FOREIGN_DEVICE_TABLE_NAK),
+        Bvlc::DeleteForeignDeviceTableEntry(_) => Some(bvlc_result::DELETE_FOREIGN_DEVICE_TABLE_ENTRY_NAK),
+        Bvlc::DistributeBroadcastToNetwork(_) => Some(bvlc_result::DISTRIBUTE_BROADCAST_TO_NETWORK_NAK),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BipTransport;
+    use super::ReceivedNpdu;
+    use ast::Bvlc;
+    use ast::bvlc_result;
+    use parse::parse_bvlc;
+    use serialise::write_bvlc;
+    use std::io;
+    use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+    use std::time::Duration;
+
+    /// A transport on loopback which broadcasts to `broadcast_address`
+    fn transport(broadcast_address: SocketAddr) -> BipTransport {
+        let transport = BipTransport::bind("127.0.0.1:0", broadcast_address).unwrap();
+        transport.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        transport
+    }
+
+    /// A plain socket on loopback to play the part of another device
+    fn peer() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        socket
+    }
+
+    fn send_bvlc(socket: &UdpSocket, bvlc: Bvlc, destination: SocketAddr) {
+        let mut buf = vec![];
+        write_bvlc(&mut buf, bvlc).unwrap();
+        socket.send_to(&buf, destination).unwrap();
+    }
+
+    fn receive_bvlc(socket: &UdpSocket) -> Bvlc {
+        let mut buf = [0u8; 1600];
+        let (length, _) = socket.recv_from(&mut buf).unwrap();
+        parse_bvlc(&buf[..length]).unwrap()
+    }
+
+    #[test]
+    fn unicast_between_transports() {
+        let b = transport("127.0.0.1:9".parse().unwrap());
+        let a = transport(b.local_addr().unwrap());
+        a.send_unicast(vec![0x01, 0x00, 0x10, 0x08], b.local_addr().unwrap()).unwrap();
+        assert_eq!(ReceivedNpdu {
+            source: a.local_addr().unwrap(),
+            broadcast: false,
+            npdu: vec![0x01, 0x00, 0x10, 0x08],
+        }, b.receive().unwrap());
+    }
+
+    #[test]
+    fn broadcast_between_transports() {
+        let b = transport("127.0.0.1:9".parse().unwrap());
+        let a = transport(b.local_addr().unwrap());
+        a.send_broadcast(vec![0x01, 0x00, 0x10, 0x08]).unwrap();
+        assert_eq!(ReceivedNpdu {
+            source: a.local_addr().unwrap(),
+            broadcast: true,
+            npdu: vec![0x01, 0x00, 0x10, 0x08],
+        }, b.receive().unwrap());
+    }
+
+    #[test]
+    fn forwarded_npdu_comes_from_originator() {
+        let a = transport("127.0.0.1:9".parse().unwrap());
+        let bbmd = peer();
+        let originator = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 47808);
+        send_bvlc(&bbmd, Bvlc::ForwardedNpdu { originator, npdu: vec![0x01, 0x00] }, a.local_addr().unwrap());
+        assert_eq!(ReceivedNpdu {
+            source: SocketAddr::V4(originator),
+            broadcast: true,
+            npdu: vec![0x01, 0x00],
+        }, a.receive().unwrap());
+    }
+
+    #[test]
+    fn bbmd_requests_are_refused() {
+        let a = transport("127.0.0.1:9".parse().unwrap());
+        let other = peer();
+        send_bvlc(&other, Bvlc::ReadBroadcastDistributionTable, a.local_addr().unwrap());
+        send_bvlc(&other, Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00]), a.local_addr().unwrap());
+        assert_eq!(vec![0x01u8, 0x00], a.receive().unwrap().npdu);
+        assert_eq!(Bvlc::Result(bvlc_result::READ_BROADCAST_DISTRIBUTION_TABLE_NAK), receive_bvlc(&other));
+    }
+
+    #[test]
+    fn invalid_datagrams_are_dropped() {
+        let a = transport("127.0.0.1:9".parse().unwrap());
+        let other = peer();
+        other.send_to(&[0x01u8, 0x02, 0x03], a.local_addr().unwrap()).unwrap();
+        send_bvlc(&other, Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00]), a.local_addr().unwrap());
+        assert_eq!(vec![0x01u8, 0x00], a.receive().unwrap().npdu);
+    }
+
+    #[test]
+    fn apdu_is_sent_to_bip_address() {
+        use ast::{ApduHeader, MacAddress, NetworkAddress};
+        use client::Transport;
+        let mut a = transport("127.0.0.1:9".parse().unwrap());
+        let other = peer();
+        let port = other.local_addr().unwrap().port().to_be_bytes();
+        let destination = NetworkAddress { network: 0, address: MacAddress::new(&[127, 0, 0, 1, port[0], port[1]]).unwrap() };
+        a.send(&destination, ApduHeader::SimpleAck { invoke_id: 3, service: 15 }, &[]).unwrap();
+        assert_eq!(Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00, 0x20, 0x03, 0x0F]), receive_bvlc(&other));
+        let invalid = NetworkAddress { network: 0, address: MacAddress::new(&[1, 2]).unwrap() };
+        assert_eq!(io::ErrorKind::InvalidInput,
+                   a.send(&invalid, ApduHeader::SimpleAck { invoke_id: 3, service: 15 }, &[]).unwrap_err().kind());
+    }
+
+    #[test]
+    fn npdu_too_long_for_a_bvlc_is_refused() {
+        let a = transport("127.0.0.1:9".parse().unwrap());
+        let other = peer();
+        assert_eq!(io::ErrorKind::InvalidInput, a.send_unicast(vec![0; 65532], other.local_addr().unwrap()).unwrap_err().kind());
+    }
+
+    #[test]
+    fn foreign_device_broadcasts_through_bbmd() {
+        let mut a = transport("127.0.0.1:9".parse().unwrap());
+        let bbmd = peer();
+        a.register_foreign_device(bbmd.local_addr().unwrap(), 60).unwrap();
+        assert_eq!(Bvlc::RegisterForeignDevice(60), receive_bvlc(&bbmd));
+
+        send_bvlc(&bbmd, Bvlc::Result(bvlc_result::SUCCESSFUL_COMPLETION), a.local_addr().unwrap());
+        send_bvlc(&bbmd, Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00]), a.local_addr().unwrap());
+        a.receive().unwrap();
+        assert_eq!(Some(bvlc_result::SUCCESSFUL_COMPLETION), a.last_result());
+
+        a.send_broadcast(vec![0x01, 0x00, 0x10, 0x08]).unwrap();
+        assert_eq!(Bvlc::DistributeBroadcastToNetwork(vec![0x01, 0x00, 0x10, 0x08]), receive_bvlc(&bbmd));
+    }
+}
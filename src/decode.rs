@@ -0,0 +1,1162 @@
+//! Decodes from a slice of the received data, which the `parse` functions are built on. The
+//! decoder tracks its position in the slice, so its errors give the offset of what
+//! couldn't be decoded, and octet strings, character strings and the octets of bit strings are
+//! borrowed from the data rather than copied. A value can be made owned with `into_owned` once it
+//! needs to outlive the data, such as to unmarshall it into a service message.
+//!
+//! The tags, primitive values and APDU and NPDU headers are decoded without allocating, so they
+//! are part of the encoding core. Decoding a production by its schema or a BVLC needs `std`.
+
+use ast;
+use ast::{Date, MacAddress, ObjectId, Time, Year, MAX_INSTANCE};
+use core::convert::TryFrom;
+use core::str;
+use enumerations::{AbortReason, ObjectType, RejectReason};
+#[cfg(feature = "std")]
+use parse::Schema;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+#[derive(Debug)]
+pub enum ParseError {
+    #[cfg(feature = "std")]
+    ReadError(io::Error),
+    InputEndedBeforeParsingCompleted, // "Input ended before parsing completed"
+    ValueSizeNotSupported, // such as an 8byte integer
+    InvalidValue(&'static str), // the content octets don't form a valid value of the type
+    NotImplemented(&'static str),
+    UnexpectedTag, // the tag isn't allowed where it is by the production's schema
+    MissingRequiredValue, // the production ended before a value it requires
+}
+
+impl PartialEq for ParseError {
+    fn eq(&self, other: &ParseError) -> bool {
+        match (self, other) {
+            #[cfg(feature = "std")]
+            (&ParseError::ReadError(_), &ParseError::ReadError(_)) => true,
+            (&ParseError::InputEndedBeforeParsingCompleted, &ParseError::InputEndedBeforeParsingCompleted) => true,
+            (&ParseError::ValueSizeNotSupported, &ParseError::ValueSizeNotSupported) => true,
+            (&ParseError::InvalidValue(string1), &ParseError::InvalidValue(string2)) => string1 == string2,
+            (&ParseError::NotImplemented(string1), &ParseError::NotImplemented(string2)) => string1 == string2,
+            (&ParseError::UnexpectedTag, &ParseError::UnexpectedTag) => true,
+            (&ParseError::MissingRequiredValue, &ParseError::MissingRequiredValue) => true,
+            _ => false
+        }
+    }
+}
+
+/// An error from decoding, with the offset in the data of the tag or header which couldn't be
+/// decoded, or the length of the data when it ended where a value was required
+#[derive(Debug, PartialEq)]
+pub struct DecodeError {
+    pub offset: usize,
+    pub error: ParseError,
+}
+
+impl From<DecodeError> for ParseError {
+    fn from(error: DecodeError) -> ParseError {
+        error.error
+    }
+}
+
+/// The primitive types, numbered by their application tags - Clause 20.2.1.4
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PrimitiveType {
+    Null = 0,
+    Boolean = 1,
+    Unsigned = 2,
+    Signed = 3,
+    Real = 4,
+    Double = 5,
+    OctetString = 6,
+    CharacterString = 7,
+    BitString = 8,
+    Enumerated = 9,
+    Date = 10,
+    Time = 11,
+    ObjectId = 12,
+}
+
+/// A tag, which is followed by the content octets of a primitive value (Clause 20.2.1). The tag
+/// value is usually the length of the content.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Tag {
+    /// The application tag number, which identifies the type, and the tag value
+    Application(u8, u32),
+    /// The context tag number and the tag value
+    Context(u8, u32),
+    /// The opening tag of a constructed value with the context tag number
+    Open(u8),
+    /// The closing tag of a constructed value with the context tag number
+    Close(u8),
+}
+
+/// A primitive value which borrows its content from the data it was decoded from
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value<'a> {
+    Null,
+    Boolean(bool),
+    Unsigned(u32),
+    Signed(i32),
+    Real(f32),
+    Double(f64),
+    OctetString(&'a [u8]),
+    CharacterString(CharacterString<'a>),
+    /// The octets of the bits and the number of unused bits in the last octet, which may not be
+    /// clear
+    BitString(&'a [u8], u8),
+    Enumerated(u32),
+    Date(Date),
+    Time(Time),
+    ObjectId(ObjectId),
+}
+
+impl<'a> Value<'a> {
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match *self {
+            Value::Null => PrimitiveType::Null,
+            Value::Boolean(_) => PrimitiveType::Boolean,
+            Value::Unsigned(_) => PrimitiveType::Unsigned,
+            Value::Signed(_) => PrimitiveType::Signed,
+            Value::Real(_) => PrimitiveType::Real,
+            Value::Double(_) => PrimitiveType::Double,
+            Value::OctetString(_) => PrimitiveType::OctetString,
+            Value::CharacterString(_) => PrimitiveType::CharacterString,
+            Value::BitString(..) => PrimitiveType::BitString,
+            Value::Enumerated(_) => PrimitiveType::Enumerated,
+            Value::Date(_) => PrimitiveType::Date,
+            Value::Time(_) => PrimitiveType::Time,
+            Value::ObjectId(_) => PrimitiveType::ObjectId,
+        }
+    }
+}
+
+/// The octets of a character string in their character set, which are checked to be valid in
+/// it. UTF-8, ISO 10646 (UCS-2) and ISO 8859-1 are supported (Clause 20.2.9).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CharacterString<'a> {
+    character_set: u8,
+    octets: &'a [u8],
+}
+
+impl<'a> CharacterString<'a> {
+    pub fn new(character_set: u8, octets: &'a [u8]) -> Result<CharacterString<'a>, ParseError> {
+        match character_set {
+            0 => {
+                if str::from_utf8(octets).is_err() {
+                    return Err(ParseError::InvalidValue("Invalid UTF-8"))
+                }
+            },
+            4 => {
+                if !octets.len().is_multiple_of(2) || char::decode_utf16(ucs2_units(octets)).any(|c| c.is_err()) {
+                    return Err(ParseError::InvalidValue("Invalid UCS-2"))
+                }
+            },
+            5 => (),
+            _ => return Err(ParseError::NotImplemented("Character set")),
+        }
+        Ok(CharacterString { character_set, octets })
+    }
+
+    /// A UTF-8 string, which has character set 0
+    pub fn utf8(string: &'a str) -> CharacterString<'a> {
+        CharacterString { character_set: 0, octets: string.as_bytes() }
+    }
+
+    pub fn character_set(&self) -> u8 {
+        self.character_set
+    }
+
+    pub fn octets(&self) -> &'a [u8] {
+        self.octets
+    }
+
+    /// The string if it's UTF-8, which is the only character set which needs no converting
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.character_set {
+            0 => str::from_utf8(self.octets).ok(),
+            _ => None,
+        }
+    }
+
+    /// The string, borrowed if it's UTF-8 and converted from the other character sets
+    #[cfg(feature = "std")]
+    pub fn to_str(self) -> Cow<'a, str> {
+        match self.character_set {
+            4 => Cow::Owned(char::decode_utf16(ucs2_units(self.octets)).map(|c| c.expect("checked when decoded")).collect()),
+            5 => Cow::Owned(self.octets.iter().map(|&octet| octet as char).collect()),
+            _ => Cow::Borrowed(str::from_utf8(self.octets).expect("checked when decoded")),
+        }
+    }
+}
+
+// The big-endian code units of UCS-2 octets
+fn ucs2_units(octets: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    octets.chunks(2).map(|pair| (pair[0] as u16) << 8 | pair[1] as u16)
+}
+
+/// Decodes the parts of a message in turn from a slice of it
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder { data, position: 0 }
+    }
+
+    /// The offset of the next octet to decode
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The data which hasn't been decoded yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    /// Decodes an NPDU header (Clause 6.2)
+    pub fn npdu(&mut self) -> Result<ast::Npdu, DecodeError> {
+        self.header(|decoder| {
+            if decoder.octet()? != 1 {
+                return Err(ParseError::InvalidValue("Unsupported protocol version"))
+            }
+            let control = decoder.octet()?;
+            let destination = if control & 0x20 != 0 {
+                Some(decoder.network_address()?)
+            } else {
+                None
+            };
+            let source = if control & 0x08 != 0 {
+                let source = decoder.network_address()?;
+                if source.network == 0xFFFF || source.address.is_empty() {
+                    return Err(ParseError::InvalidValue("Source can't be a broadcast address"))
+                }
+                Some(source)
+            } else {
+                None
+            };
+            let hop_count = if destination.is_some() {
+                decoder.octet()?
+            } else {
+                255
+            };
+            let network_message = if control & 0x80 != 0 {
+                let message_type = decoder.octet()?;
+                Some(ast::NetworkMessage {
+                    message_type,
+                    vendor_id: if message_type >= 0x80 { Some(decoder.unsigned(2)? as u16) } else { None },
+                })
+            } else {
+                None
+            };
+            Ok(ast::Npdu {
+                priority: control & 0x03,
+                expecting_reply: control & 0x04 != 0,
+                destination,
+                source,
+                hop_count,
+                network_message,
+            })
+        })
+    }
+
+    // A network number, address length and address
+    fn network_address(&mut self) -> Result<ast::NetworkAddress, ParseError> {
+        let network = self.unsigned(2)? as u16;
+        let length = self.octet()? as usize;
+        Ok(ast::NetworkAddress {
+            network,
+            address: MacAddress::new(self.octets(length)?).ok_or(ParseError::InvalidValue("MAC address too long"))?,
+        })
+    }
+
+    /// Decodes an APDU header (Clause 20.1)
+    pub fn apdu_header(&mut self) -> Result<ast::ApduHeader, DecodeError> {
+        use ast::{ApduHeader, MaxApdu, MaxSegments};
+
+        self.header(|decoder| {
+            let first_byte = decoder.octet()?;
+            let flags = first_byte & 0x0F;
+            match first_byte >> 4 {
+                0 => {
+                    let second_byte = decoder.octet()?;
+                    let invoke_id = decoder.octet()?;
+                    Ok(ApduHeader::ConfirmedReq {
+                        segmented: decoder.segment_info(flags)?,
+                        segmented_response_accepted: flags & 0b0010 != 0,
+                        max_segments: MaxSegments::from(second_byte >> 4),
+                        max_apdu: MaxApdu::from(second_byte & 0x0F),
+                        invoke_id,
+                        service: decoder.octet()?,
+                    })
+                },
+                1 =>
+                    Ok(ApduHeader::UnconfirmedReq {
+                        service: decoder.octet()?,
+                    }),
+                2 =>
+                    Ok(ApduHeader::SimpleAck {
+                        invoke_id: decoder.octet()?,
+                        service: decoder.octet()?,
+                    }),
+                3 => {
+                    let invoke_id = decoder.octet()?;
+                    Ok(ApduHeader::ComplexAck {
+                        segmented: decoder.segment_info(flags)?,
+                        invoke_id,
+                        service: decoder.octet()?,
+                    })
+                },
+                4 =>
+                    Ok(ApduHeader::SegmentAck {
+                        negative_ack: flags & 0b0010 != 0,
+                        server: flags & 0b0001 != 0,
+                        invoke_id: decoder.octet()?,
+                        sequence_number: decoder.octet()?,
+                        actual_window_size: decoder.octet()?,
+                    }),
+                5 =>
+                    Ok(ApduHeader::ErrorPdu {
+                        invoke_id: decoder.octet()?,
+                        error_choice: decoder.octet()?,
+                    }),
+                6 =>
+                    Ok(ApduHeader::RejectPdu {
+                        invoke_id: decoder.octet()?,
+                        reject_reason: RejectReason::from(decoder.octet()?),
+                    }),
+                7 =>
+                    Ok(ApduHeader::AbortPdu {
+                        server: flags & 0b0001 != 0,
+                        invoke_id: decoder.octet()?,
+                        abort_reason: AbortReason::from(decoder.octet()?),
+                    }),
+                _ =>
+                    Err(ParseError::InvalidValue("Reserved PDU type")),
+            }
+        })
+    }
+
+    // The sequence number and proposed window size if the segmented message flag is set
+    fn segment_info(&mut self, flags: u8) -> Result<Option<ast::SegmentInfo>, ParseError> {
+        if flags & 0b1000 == 0 {
+            return Ok(None)
+        }
+        Ok(Some(ast::SegmentInfo {
+            more_follows: flags & 0b0100 != 0,
+            sequence_number: self.octet()?,
+            proposed_window_size: self.octet()?,
+        }))
+    }
+
+    /// Decodes a header, an error is at the start of the header and leaves the decoder there
+    fn header<T, F>(&mut self, decode: F) -> Result<T, DecodeError>
+        where F: FnOnce(&mut Decoder<'a>) -> Result<T, ParseError> {
+        let offset = self.position;
+        decode(self).map_err(|error| {
+            self.position = offset;
+            DecodeError { offset, error }
+        })
+    }
+
+    /// The next tag without taking it, `None` at the end of the data
+    pub fn peek_tag(&self) -> Result<Option<Tag>, DecodeError> {
+        self.tag_at(self.position).map(|tag| tag.map(|(tag, _)| tag))
+    }
+
+    /// Takes the next tag, `None` at the end of the data. The content octets of a primitive value
+    /// follow an application or context tag.
+    pub fn take_tag(&mut self) -> Result<Option<Tag>, DecodeError> {
+        Ok(match self.tag_at(self.position)? {
+            Some((tag, end)) => {
+                self.position = end;
+                Some(tag)
+            },
+            None => None,
+        })
+    }
+
+    /// Decodes an application tagged value
+    ///
+    /// # Errors
+    ///
+    /// At the offset of the tag, if the next tag isn't an application tag, or if its value can't
+    /// be decoded
+    pub fn application_value(&mut self) -> Result<Value<'a>, DecodeError> {
+        let offset = self.position;
+        match self.take_tag()? {
+            Some(Tag::Application(tag_number, tag_value)) =>
+                self.application_content(tag_number, tag_value).map_err(|error| DecodeError { offset, error }),
+            tag => Err(DecodeError { offset, error: unexpected(tag) }),
+        }
+    }
+
+    /// Decodes a value of the type with the context tag number
+    ///
+    /// # Errors
+    ///
+    /// At the offset of the tag, if the next tag isn't a context tag with the number, or if its
+    /// value can't be decoded
+    pub fn context_value(&mut self, number: u8, primitive_type: PrimitiveType) -> Result<Value<'a>, DecodeError> {
+        let offset = self.position;
+        match self.take_tag()? {
+            Some(Tag::Context(tag_number, tag_value)) if tag_number == number =>
+                self.context_content(primitive_type, tag_value).map_err(|error| DecodeError { offset, error }),
+            tag => Err(DecodeError { offset, error: unexpected(tag) }),
+        }
+    }
+
+    /// Decodes the tag at `offset`, with the offset after it, `None` at the end of the data
+    ///
+    /// # Description of encoding
+    ///
+    /// BACnet encoding encodes meta data into a preceding octet.
+    /// BACnet has 2 classes of tags, context tags and application tags. The class is
+    /// identifed by the 5th bit of the tag octet, a `1` indicating a context tag.
+    /// The first 4 bits of a context tag are the context number - a context for the current production
+    /// should then specify the purpose and the primitive type of the value.
+    /// The first 4 bits of an application tag are the type number - identifiying the type of the
+    /// value.
+    /// The use of the last 3 bits of the tag depend on the type (identified either by the type field or
+    /// by the context identified by the context field). For a boolean the value can be encoded
+    /// directly into the tag, for a named tag, the last 3 bits identify the name, in general the last
+    /// 3 bits identify the length of the value in octets.
+    /// PD open / close tags are types of named context tags - their use must be identified by their
+    /// context number and they will be encoded by an 0xE (for open) and 0xF (for close). Their purpose
+    /// is to either allow for a context value with any type (the value would be specified as an
+    /// application tag inside), to specify a contructed type (where the production inside would have a
+    /// new context), or to group a sequence of values.
+    ///
+    /// ## Extending type and length fields
+    ///
+    /// The type field can be extended into another octet by using 0xF in the type field, then the next
+    /// octet becomes the type field and type / context values 0-254 (255 is reserved).
+    ///
+    /// The Length field can be extended by using 0b111 in the length field, the next octet becomes a
+    /// length field, if the next octet is 0xFE, the next 2 octets become a length field, if it is 0xFF
+    /// then the next 4 octets become a length field. This encoding allows lengths up to 2^32-1.
+    fn tag_at(&self, offset: usize) -> Result<Option<(Tag, usize)>, DecodeError> {
+        let data = self.data;
+        let truncated = || DecodeError { offset, error: ParseError::InputEndedBeforeParsingCompleted };
+        let first_byte = match data.get(offset) {
+            Some(&first_byte) => first_byte,
+            None => return Ok(None),
+        };
+        let mut end = offset + 1;
+        let mut tag_num = (first_byte & 0xF0) >> 4;
+        let class = (first_byte & 0x08) == 0x08;
+        let mut value = (first_byte & 0x07) as u32;
+
+        // Extended tag numbers
+        if tag_num == 0xF {
+            tag_num = *data.get(end).ok_or_else(truncated)?;
+            end += 1;
+        }
+        let tag = match value {
+            0x6 => Tag::Open(tag_num),
+            0x7 => Tag::Close(tag_num),
+            _ => {
+                // Extended values
+                if value == 0x5 {
+                    value = *data.get(end).ok_or_else(truncated)? as u32;
+                    end += 1;
+                    let size = match value {
+                        0xFE => 2,
+                        0xFF => 4,
+                        _ => 0,
+                    };
+                    if size != 0 {
+                        let octets = data.get(end..end + size).ok_or_else(truncated)?;
+                        value = unsigned(octets);
+                        end += size;
+                    }
+                }
+                if class {
+                    Tag::Context(tag_num, value)
+                } else {
+                    Tag::Application(tag_num, value)
+                }
+            },
+        };
+        Ok(Some((tag, end)))
+    }
+
+    /// Takes the next `length` octets
+    fn octets(&mut self, length: usize) -> Result<&'a [u8], ParseError> {
+        if self.data.len() - self.position < length {
+            return Err(ParseError::InputEndedBeforeParsingCompleted)
+        }
+        let octets = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(octets)
+    }
+
+    fn octet(&mut self) -> Result<u8, ParseError> {
+        Ok(self.octets(1)?[0])
+    }
+
+    fn four_octets(&mut self, length: usize) -> Result<[u8; 4], ParseError> {
+        if length != 4 {
+            return Err(ParseError::ValueSizeNotSupported)
+        }
+        let octets = self.octets(4)?;
+        Ok([octets[0], octets[1], octets[2], octets[3]])
+    }
+
+    fn unsigned(&mut self, length: usize) -> Result<u32, ParseError> {
+        if length > 4 {
+            return Err(ParseError::ValueSizeNotSupported)
+        }
+        Ok(unsigned(self.octets(length)?))
+    }
+
+    /// Decodes the content octets of an application tagged value (Clause 20.2.2 - 20.2.14)
+    fn application_content(&mut self, tag: u8, tag_value: u32) -> Result<Value<'a>, ParseError> {
+        let length = tag_value as usize;
+        match tag {
+            0 => {
+                if length != 0 {
+                    return Err(ParseError::InvalidValue("Null with content octets"))
+                }
+                Ok(Value::Null)
+            },
+            1 => match tag_value {
+                0 | 1 => Ok(Value::Boolean(tag_value == 1)),
+                _ => Err(ParseError::InvalidValue("Invalid boolean")),
+            },
+            2 => Ok(Value::Unsigned(self.unsigned(length)?)),
+            3 => {
+                if length == 0 || length > 4 {
+                    return Err(ParseError::ValueSizeNotSupported)
+                }
+                let unused_bits = 32 - 8 * length as u32;
+                Ok(Value::Signed(((self.unsigned(length)? << unused_bits) as i32) >> unused_bits))
+            },
+            4 => Ok(Value::Real(f32::from_bits(u32::from_be_bytes(self.four_octets(length)?)))),
+            5 => {
+                if length != 8 {
+                    return Err(ParseError::ValueSizeNotSupported)
+                }
+                let high = self.unsigned(4)? as u64;
+                let low = self.unsigned(4)? as u64;
+                Ok(Value::Double(f64::from_bits(high << 32 | low)))
+            },
+            6 => Ok(Value::OctetString(self.octets(length)?)),
+            7 => {
+                if length == 0 {
+                    return Err(ParseError::InvalidValue("Character string without a character set"))
+                }
+                let octets = self.octets(length)?;
+                Ok(Value::CharacterString(CharacterString::new(octets[0], &octets[1..])?))
+            },
+            8 => {
+                if length == 0 {
+                    return Err(ParseError::InvalidValue("Bit string without unused bits octet"))
+                }
+                let octets = self.octets(length)?;
+                Ok(Value::BitString(&octets[1..], unused_bits(octets[0], length - 1)?))
+            },
+            9 => Ok(Value::Enumerated(self.unsigned(length)?)),
+            10 => Ok(Value::Date(date(self.four_octets(length)?)?)),
+            11 => Ok(Value::Time(time(self.four_octets(length)?)?)),
+            12 => Ok(Value::ObjectId(object_id(u32::from_be_bytes(self.four_octets(length)?)))),
+            _ => Err(ParseError::NotImplemented("Reserved application tag")),
+        }
+    }
+
+    /// Context tagged booleans have a single content octet, other types are as application
+    /// tagged (Clause 20.2.3)
+    fn context_content(&mut self, primitive_type: PrimitiveType, tag_value: u32) -> Result<Value<'a>, ParseError> {
+        match primitive_type {
+            PrimitiveType::Boolean => {
+                if tag_value != 1 {
+                    return Err(ParseError::ValueSizeNotSupported)
+                }
+                match self.octet()? {
+                    octet @ (0 | 1) => Ok(Value::Boolean(octet == 1)),
+                    _ => Err(ParseError::InvalidValue("Invalid boolean")),
+                }
+            },
+            _ => self.application_content(primitive_type as u8, tag_value),
+        }
+    }
+}
+
+/// The error for a tag which isn't allowed where it is, a closing tag or the end of the data
+/// where a value is required means the value is missing
+fn unexpected(tag: Option<Tag>) -> ParseError {
+    match tag {
+        Some(Tag::Close(_)) | None => ParseError::MissingRequiredValue,
+        Some(_) => ParseError::UnexpectedTag,
+    }
+}
+
+// An unsigned integer from up to 4 big-endian octets
+fn unsigned(octets: &[u8]) -> u32 {
+    octets.iter().fold(0, |value, &octet| value << 8 | octet as u32)
+}
+
+/// Checks the unused bits octet of a bit string with `length` octets of bits
+fn unused_bits(unused_bits: u8, length: usize) -> Result<u8, ParseError> {
+    if unused_bits > 7 || (length == 0 && unused_bits != 0) {
+        return Err(ParseError::InvalidValue("Invalid bit string unused bits"))
+    }
+    Ok(unused_bits)
+}
+
+/// The object type is in the top 10 bits of an object identifier and the instance number in the
+/// rest (Clause 20.2.14)
+fn object_id(value: u32) -> ObjectId {
+    let object_type = ObjectType::try_from((value >> 22) as u16).expect("10 bits are at most 1023");
+    ObjectId(object_type, value & MAX_INSTANCE)
+}
+
+/// Dates are encoded as year - 1900, month, day and day of week, with 0xFF for unspecified
+/// fields (Clause 20.2.12)
+fn date(octets: [u8; 4]) -> Result<Date, ParseError> {
+    use ast::{Month, DayOfMonth};
+
+    let [year, month, day, weekday] = octets;
+    let month = match month {
+        month @ 1..=12 => Month::Number(month),
+        13 => Month::Odd,
+        14 => Month::Even,
+        0xFF => Month::Unspecified,
+        _ => return Err(ParseError::InvalidValue("Invalid month")),
+    };
+    let day = match day {
+        day @ 1..=31 => DayOfMonth::Number(day),
+        32 => DayOfMonth::Last,
+        33 => DayOfMonth::Odd,
+        34 => DayOfMonth::Even,
+        0xFF => DayOfMonth::Unspecified,
+        _ => return Err(ParseError::InvalidValue("Invalid day of month")),
+    };
+    let weekday = match weekday {
+        weekday @ 1..=7 => Some(weekday),
+        0xFF => None,
+        _ => return Err(ParseError::InvalidValue("Invalid day of week")),
+    };
+    Ok(Date {
+        year: if year == 0xFF { None } else { Year::new(Year::MIN + year as u16) },
+        month,
+        day,
+        weekday,
+    })
+}
+
+/// Times are encoded as hour, minute, second and hundredths, with 0xFF for unspecified fields
+/// (Clause 20.2.13)
+fn time(octets: [u8; 4]) -> Result<Time, ParseError> {
+    fn field(value: u8, max: u8) -> Result<Option<u8>, ParseError> {
+        match value {
+            0xFF => Ok(None),
+            value if value <= max => Ok(Some(value)),
+            _ => Err(ParseError::InvalidValue("Invalid time")),
+        }
+    }
+    let [hour, minute, second, hundredths] = octets;
+    Ok(Time {
+        hour: field(hour, 23)?,
+        minute: field(minute, 59)?,
+        second: field(second, 59)?,
+        hundredths: field(hundredths, 99)?,
+    })
+}
+
+#[cfg(feature = "std")]
+impl<'a> Value<'a> {
+    pub fn into_owned(self) -> ast::PrimitiveValue {
+        use ast::PrimitiveValue;
+
+        match self {
+            Value::Null => PrimitiveValue::Null,
+            Value::Boolean(value) => PrimitiveValue::Boolean(value),
+            Value::Unsigned(value) => PrimitiveValue::Unsigned(value),
+            Value::Signed(value) => PrimitiveValue::Signed(value),
+            Value::Real(value) => PrimitiveValue::Real(value),
+            Value::Double(value) => PrimitiveValue::Double(value),
+            Value::OctetString(octets) => PrimitiveValue::OctetString(octets.to_vec()),
+            Value::CharacterString(string) => PrimitiveValue::CharacterString(string.to_str().into_owned()),
+            Value::BitString(octets, unused_bits) =>
+                PrimitiveValue::BitString(ast::BitString::from_octets(octets.to_vec(), unused_bits)),
+            Value::Enumerated(value) => PrimitiveValue::Enumerated(value),
+            Value::Date(date) => PrimitiveValue::Date(date),
+            Value::Time(time) => PrimitiveValue::Time(time),
+            Value::ObjectId(object_id) => PrimitiveValue::ObjectId(object_id),
+        }
+    }
+}
+
+/// Borrows the content of an owned value, character strings are always UTF-8
+#[cfg(feature = "std")]
+impl<'a> From<&'a ast::PrimitiveValue> for Value<'a> {
+    fn from(value: &'a ast::PrimitiveValue) -> Value<'a> {
+        use ast::PrimitiveValue;
+
+        match *value {
+            PrimitiveValue::Null => Value::Null,
+            PrimitiveValue::Boolean(value) => Value::Boolean(value),
+            PrimitiveValue::Unsigned(value) => Value::Unsigned(value),
+            PrimitiveValue::Signed(value) => Value::Signed(value),
+            PrimitiveValue::Real(value) => Value::Real(value),
+            PrimitiveValue::Double(value) => Value::Double(value),
+            PrimitiveValue::OctetString(ref octets) => Value::OctetString(octets),
+            PrimitiveValue::CharacterString(ref string) => Value::CharacterString(CharacterString::utf8(string)),
+            PrimitiveValue::BitString(ref bits) => Value::BitString(bits.octets(), bits.unused_bits()),
+            PrimitiveValue::Enumerated(value) => Value::Enumerated(value),
+            PrimitiveValue::Date(date) => Value::Date(date),
+            PrimitiveValue::Time(time) => Value::Time(time),
+            PrimitiveValue::ObjectId(object_id) => Value::ObjectId(object_id),
+        }
+    }
+}
+
+/// A value of a sequence, as `ast::SequenceableValue` but borrowing from the data
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SequenceableValue<'a> {
+    ApplicationValue(Value<'a>),
+    ContextValue(u8, Value<'a>),
+    ContextValueSequence(u8, ValueSequence<'a>),
+}
+
+#[cfg(feature = "std")]
+pub type ValueSequence<'a> = Vec<SequenceableValue<'a>>;
+
+#[cfg(feature = "std")]
+impl<'a> SequenceableValue<'a> {
+    pub fn into_owned(self) -> ast::SequenceableValue {
+        match self {
+            SequenceableValue::ApplicationValue(value) => ast::SequenceableValue::ApplicationValue(value.into_owned()),
+            SequenceableValue::ContextValue(number, value) => ast::SequenceableValue::ContextValue(number, value.into_owned()),
+            SequenceableValue::ContextValueSequence(number, values) =>
+                ast::SequenceableValue::ContextValueSequence(number, values.into_iter().map(SequenceableValue::into_owned).collect()),
+        }
+    }
+}
+
+/// Decodes the whole of the data as the production described by `schema`
+#[cfg(feature = "std")]
+pub fn decode_value_sequence_to_end<'a>(data: &'a [u8], schema: &Schema) -> Result<ValueSequence<'a>, DecodeError> {
+    Decoder::new(data).value_sequence_to_end(schema)
+}
+
+/// Decodes the whole of the data as the production described by `schema`, and any values after
+/// it as `Schema::Any`
+#[cfg(feature = "std")]
+pub fn decode_value_sequence_with_extra_values<'a>(data: &'a [u8], schema: &Schema) -> Result<ValueSequence<'a>, DecodeError> {
+    Decoder::new(data).value_sequence_with_extra_values(schema)
+}
+
+/// The values with their content copied out of the data
+#[cfg(feature = "std")]
+pub fn into_owned(values: ValueSequence) -> ast::ValueSequence {
+    values.into_iter().map(SequenceableValue::into_owned).collect()
+}
+
+#[cfg(feature = "std")]
+impl<'a> Decoder<'a> {
+    /// Decodes a BACnet/IP BVLC message (Annex J.2), including the NPDU it carries
+    pub fn bvlc(&mut self) -> Result<ast::Bvlc, DecodeError> {
+        use ast::Bvlc;
+
+        self.header(|decoder| {
+            if decoder.octet()? != 0x81 {
+                return Err(ParseError::InvalidValue("Not a BACnet/IP BVLC"))
+            }
+            let function = decoder.octet()?;
+            let length = decoder.unsigned(2)? as usize;
+            if length < 4 {
+                return Err(ParseError::InvalidValue("BVLC length shorter than header"))
+            }
+            let mut content = Decoder::new(decoder.octets(length - 4)?);
+            Ok(match function {
+                0x00 => Bvlc::Result(content.unsigned(2)? as u16),
+                0x01 => Bvlc::WriteBroadcastDistributionTable(content.bdt()?),
+                0x02 => Bvlc::ReadBroadcastDistributionTable,
+                0x03 => Bvlc::ReadBroadcastDistributionTableAck(content.bdt()?),
+                0x04 => Bvlc::ForwardedNpdu {
+                    originator: content.socket_address()?,
+                    npdu: content.remaining().to_vec(),
+                },
+                0x05 => Bvlc::RegisterForeignDevice(content.unsigned(2)? as u16),
+                0x06 => Bvlc::ReadForeignDeviceTable,
+                0x07 => {
+                    if !content.remaining().len().is_multiple_of(10) {
+                        return Err(ParseError::InvalidValue("Partial foreign device table entry"))
+                    }
+                    let mut entries = vec![];
+                    while !content.is_empty() {
+                        entries.push(ast::FdtEntry {
+                            address: content.socket_address()?,
+                            time_to_live: content.unsigned(2)? as u16,
+                            time_remaining: content.unsigned(2)? as u16,
+                        });
+                    }
+                    Bvlc::ReadForeignDeviceTableAck(entries)
+                },
+                0x08 => Bvlc::DeleteForeignDeviceTableEntry(content.socket_address()?),
+                0x09 => Bvlc::DistributeBroadcastToNetwork(content.remaining().to_vec()),
+                0x0A => Bvlc::OriginalUnicastNpdu(content.remaining().to_vec()),
+                0x0B => Bvlc::OriginalBroadcastNpdu(content.remaining().to_vec()),
+                _ => return Err(ParseError::NotImplemented("BVLC function")),
+            })
+        })
+    }
+
+    // The entries of a broadcast distribution table, which is the rest of the data
+    fn bdt(&mut self) -> Result<Vec<ast::BdtEntry>, ParseError> {
+        if !self.remaining().len().is_multiple_of(10) {
+            return Err(ParseError::InvalidValue("Partial broadcast distribution table entry"))
+        }
+        let mut entries = vec![];
+        while !self.is_empty() {
+            entries.push(ast::BdtEntry {
+                address: self.socket_address()?,
+                broadcast_mask: Ipv4Addr::from(self.unsigned(4)?),
+            });
+        }
+        Ok(entries)
+    }
+
+    // A B/IP address, which is the 4 octet IP address followed by the 2 octet port
+    fn socket_address(&mut self) -> Result<SocketAddrV4, ParseError> {
+        let ip = Ipv4Addr::from(self.unsigned(4)?);
+        Ok(SocketAddrV4::new(ip, self.unsigned(2)? as u16))
+    }
+
+    /// Decodes the rest of the data as the production described by `schema`
+    ///
+    /// # Errors
+    ///
+    /// At the offset of the tag of the value which is unexpected or can't be decoded, or of a
+    /// value after the end of the production
+    pub fn value_sequence_to_end(&mut self, schema: &Schema) -> Result<ValueSequence<'a>, DecodeError> {
+        let mut values = vec!();
+        self.production(schema, &mut values)?;
+        self.end(values)
+    }
+
+    /// Decodes the rest of the data as the production described by `schema`, and any values after
+    /// it as `Schema::Any`, for the message to reject as extra values
+    ///
+    /// # Errors
+    ///
+    /// As `value_sequence_to_end`, except for values after the production
+    pub fn value_sequence_with_extra_values(&mut self, schema: &Schema) -> Result<ValueSequence<'a>, DecodeError> {
+        let mut values = vec!();
+        self.production(schema, &mut values)?;
+        self.production(&Schema::Any, &mut values)?;
+        self.end(values)
+    }
+
+    /// The values decoded, if they reach the end of the data
+    fn end(&self, values: ValueSequence<'a>) -> Result<ValueSequence<'a>, DecodeError> {
+        match self.peek_tag()? {
+            None => Ok(values),
+            Some(_) => Err(DecodeError { offset: self.position, error: ParseError::UnexpectedTag }),
+        }
+    }
+
+    /// Decodes the values of one production, adding them to `values`
+    fn production(&mut self, schema: &Schema, values: &mut ValueSequence<'a>) -> Result<(), DecodeError> {
+        match *schema {
+            Schema::Context(number, primitive_type) =>
+                values.push(SequenceableValue::ContextValue(number, self.context_value(number, primitive_type)?)),
+            Schema::Application => values.push(SequenceableValue::ApplicationValue(self.application_value()?)),
+            Schema::Constructed(number, schema) => {
+                let offset = self.position;
+                match self.take_tag()? {
+                    Some(Tag::Open(tag_number)) if tag_number == number => {
+                        let mut list = vec!();
+                        self.production(schema, &mut list)?;
+                        self.close(number)?;
+                        values.push(SequenceableValue::ContextValueSequence(number, list));
+                    },
+                    tag => return Err(DecodeError { offset, error: unexpected(tag) }),
+                }
+            },
+            Schema::Sequence(schemas) => {
+                for schema in schemas {
+                    self.production(schema, values)?;
+                }
+            },
+            Schema::Choice(schemas) => {
+                let tag = self.peek_tag()?;
+                match schemas.iter().find(|alternative| alternative.starts_with(tag.as_ref())) {
+                    Some(alternative) => self.production(alternative, values)?,
+                    None if schema.is_optional() => (),
+                    None => return Err(DecodeError { offset: self.position, error: unexpected(tag) }),
+                }
+            },
+            Schema::SequenceOf(schema) => {
+                while schema.starts_with(self.peek_tag()?.as_ref()) {
+                    self.production(schema, values)?;
+                }
+            },
+            Schema::Optional(schema) => {
+                if schema.starts_with(self.peek_tag()?.as_ref()) {
+                    self.production(schema, values)?;
+                }
+            },
+            Schema::Any => {
+                while Schema::Any.starts_with(self.peek_tag()?.as_ref()) {
+                    let offset = self.position;
+                    match self.take_tag()? {
+                        Some(Tag::Application(tag_number, tag_value)) => {
+                            let value = self.application_content(tag_number, tag_value).map_err(|error| DecodeError { offset, error })?;
+                            values.push(SequenceableValue::ApplicationValue(value));
+                        },
+                        Some(Tag::Context(tag_number, tag_value)) => {
+                            let octets = self.octets(tag_value as usize).map_err(|error| DecodeError { offset, error })?;
+                            values.push(SequenceableValue::ContextValue(tag_number, Value::OctetString(octets)));
+                        },
+                        Some(Tag::Open(tag_number)) => {
+                            let mut list = vec!();
+                            self.production(&Schema::Any, &mut list)?;
+                            self.close(tag_number)?;
+                            values.push(SequenceableValue::ContextValueSequence(tag_number, list));
+                        },
+                        Some(Tag::Close(_)) | None => unreachable!("any value doesn't start with a closing tag"),
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Takes the closing tag of a constructed value, a closing tag with another number doesn't
+    /// match the opening tag
+    fn close(&mut self, number: u8) -> Result<(), DecodeError> {
+        let offset = self.position;
+        match self.take_tag()? {
+            Some(Tag::Close(tag_number)) if tag_number == number => Ok(()),
+            None => Err(DecodeError { offset, error: ParseError::MissingRequiredValue }),
+            Some(_) => Err(DecodeError { offset, error: ParseError::UnexpectedTag }),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{decode_value_sequence_to_end, DecodeError, Decoder, SequenceableValue, Tag, Value};
+    use ast;
+    use parse::{ParseError, Schema, EVERY_TYPE};
+    use service::readpropertymultiple;
+    use service::ServiceMessage;
+
+    /// Decodes the data, with the values owned
+    fn decode(data: &[u8], schema: &Schema) -> Result<ast::ValueSequence, DecodeError> {
+        decode_value_sequence_to_end(data, schema).map(|values| values.into_iter().map(SequenceableValue::into_owned).collect())
+    }
+
+    #[test]
+    fn decode_every_type() {
+        use ast::{BitString, Date, DayOfMonth, Month, ObjectId, PrimitiveValue, Time, Year};
+        use ast::SequenceableValue::{ApplicationValue, ContextValue, ContextValueSequence};
+        use enumerations::ObjectType;
+
+        // Every application type, including extended lengths, and context tagged booleans
+        assert_eq!(Ok(vec!(
+            ApplicationValue(PrimitiveValue::Null),
+            ApplicationValue(PrimitiveValue::Boolean(true)),
+            ApplicationValue(PrimitiveValue::Unsigned(0x9988)),
+            ApplicationValue(PrimitiveValue::Signed(-1)),
+            ApplicationValue(PrimitiveValue::Real(65.0)),
+            ApplicationValue(PrimitiveValue::Double(65.0)),
+            ApplicationValue(PrimitiveValue::OctetString(vec![0xAB, 0xCD])),
+            ApplicationValue(PrimitiveValue::CharacterString("Hello".to_string())),
+            ApplicationValue(PrimitiveValue::BitString(BitString::from_octets(vec![0xF0], 4))),
+            ApplicationValue(PrimitiveValue::Enumerated(3)),
+            ApplicationValue(PrimitiveValue::Date(Date {
+                year: Year::new(2018), month: Month::Number(10), day: DayOfMonth::Number(17), weekday: Some(2),
+            })),
+            ApplicationValue(PrimitiveValue::Time(Time { hour: Some(12), minute: Some(30), second: None, hundredths: None })),
+            ApplicationValue(PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))),
+            ContextValue(1, PrimitiveValue::Boolean(true)),
+            ContextValue(7, PrimitiveValue::CharacterString("A".to_string())),
+        )), decode(&[0x00, 0x11, 0x22, 0x99, 0x88, 0x31, 0xFF, 0x44, 0x42, 0x82, 0x00, 0x00,
+                     0x55, 0x08, 0x40, 0x50, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0xAB, 0xCD,
+                     0x75, 0x06, 0x00, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x82, 0x04, 0xF0, 0x91, 0x03,
+                     0xA4, 0x76, 0x0A, 0x11, 0x02, 0xB4, 0x0C, 0x1E, 0xFF, 0xFF, 0xC4, 0x02, 0x00, 0x00, 0x2D,
+                     0x19, 0x01, 0x7A, 0x00, 0x41], &EVERY_TYPE));
+        // UCS-2 and ISO 8859-1 character strings
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::CharacterString("A\u{E9}".to_string())),
+                           ApplicationValue(PrimitiveValue::CharacterString("A\u{E9}".to_string())))),
+                   decode(&[0x75, 0x05, 0x04, 0x00, 0x41, 0x00, 0xE9, 0x73, 0x05, 0x41, 0xE9], &EVERY_TYPE));
+        // The example from Clause E.3.7
+        let ack = decode(&[0x0C, 0x00, 0x00, 0x00, 0x10, 0x1E, 0x29, 0x55, 0x4E, 0x44, 0x42, 0x90, 0x99, 0x9A, 0x4F,
+                           0x29, 0x67, 0x4E, 0x91, 0x00, 0x4F, 0x1F], &readpropertymultiple::Ack::SCHEMA).unwrap();
+        assert_eq!(vec!(
+            ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 16))),
+            ContextValueSequence(1, vec!(
+                ContextValue(2, PrimitiveValue::Enumerated(85)),
+                ContextValueSequence(4, vec!(ApplicationValue(PrimitiveValue::Real(72.3)))),
+                ContextValue(2, PrimitiveValue::Enumerated(103)),
+                ContextValueSequence(4, vec!(ApplicationValue(PrimitiveValue::Enumerated(0)))),
+            )),
+        ), ack);
+        assert!(readpropertymultiple::Ack::unmarshall(&ack).is_ok());
+    }
+
+    #[test]
+    fn borrows_from_the_data() {
+        let data = [0x62u8, 0xAB, 0xCD, 0x74, 0x00, 0x41, 0x42, 0x43, 0x82, 0x04, 0xF0];
+        let values = decode_value_sequence_to_end(&data, &EVERY_TYPE).unwrap();
+        match values[0] {
+            SequenceableValue::ApplicationValue(Value::OctetString(octets)) => assert_eq!(data[1..3].as_ptr(), octets.as_ptr()),
+            ref value => panic!("{:?}", value),
+        }
+        match values[1] {
+            SequenceableValue::ApplicationValue(Value::CharacterString(string)) => assert_eq!(Some("ABC"), string.as_str()),
+            ref value => panic!("{:?}", value),
+        }
+        assert_eq!(SequenceableValue::ApplicationValue(Value::BitString(&[0xF0], 4)), values[2]);
+    }
+
+    #[test]
+    fn errors_give_the_offset() {
+        // A truncated unsigned
+        assert_eq!(Err(DecodeError { offset: 2, error: ParseError::InputEndedBeforeParsingCompleted }),
+                   decode(&[0x00, 0x00, 0x22, 0x01], &EVERY_TYPE));
+        // A truncated extended length
+        assert_eq!(Err(DecodeError { offset: 1, error: ParseError::InputEndedBeforeParsingCompleted }),
+                   decode(&[0x00, 0x65, 0xFE, 0x00], &EVERY_TYPE));
+        // An invalid month
+        assert_eq!(Err(DecodeError { offset: 1, error: ParseError::InvalidValue("Invalid month") }),
+                   decode(&[0x00, 0xA4, 0x76, 0x10, 0x11, 0x02], &EVERY_TYPE));
+        // Invalid UCS-2, an unpaired surrogate
+        assert_eq!(Err(DecodeError { offset: 1, error: ParseError::InvalidValue("Invalid UCS-2") }),
+                   decode(&[0x00, 0x73, 0x04, 0xD8, 0x00], &EVERY_TYPE));
+        // A closing tag which doesn't match and a missing closing tag
+        assert_eq!(Err(DecodeError { offset: 3, error: ParseError::UnexpectedTag }),
+                   decode(&[0x3E, 0x21, 0x01, 0x5F], &EVERY_TYPE));
+        assert_eq!(Err(DecodeError { offset: 3, error: ParseError::MissingRequiredValue }),
+                   decode(&[0x3E, 0x21, 0x01], &EVERY_TYPE));
+        // A value after the end of the production
+        assert_eq!(Err(DecodeError { offset: 2, error: ParseError::UnexpectedTag }),
+                   decode(&[0x21, 0x01, 0x0F], &EVERY_TYPE));
+        // A null with content octets, which aren't read as the next tags, and booleans above 1
+        assert_eq!(Err(DecodeError { offset: 1, error: ParseError::InvalidValue("Null with content octets") }),
+                   decode(&[0x00, 0x02, 0x08, 0x08], &EVERY_TYPE));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Invalid boolean") }),
+                   decode(&[0x12], &EVERY_TYPE));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Invalid boolean") }),
+                   decode(&[0x19, 0x02], &EVERY_TYPE));
+    }
+
+    #[test]
+    fn decode_tags() {
+        let tags: &[(&[u8], Tag)] = &[
+            (&[0x2E], Tag::Open(2)),
+            (&[0x2F], Tag::Close(2)),
+            (&[0x24], Tag::Application(2, 4)),
+            // Extended tag number
+            (&[0xF9, 0x59], Tag::Context(0x59, 1)),
+            // Extended values of 8, 16 and 32 bits
+            (&[0x05, 200], Tag::Application(0, 200)),
+            (&[0x05, 0xFE, 0x59, 0x59], Tag::Application(0, 0x5959)),
+            (&[0x05, 0xFF, 0x59, 0x59, 0x59, 0x59], Tag::Application(0, 0x59595959)),
+        ];
+        for &(data, tag) in tags {
+            let mut decoder = Decoder::new(data);
+            assert_eq!(Ok(Some(tag)), decoder.take_tag());
+            assert!(decoder.is_empty());
+        }
+    }
+
+    #[test]
+    fn decode_headers() {
+        let data = [0x01u8, 0x04, 0x00, 0x05, 0x01, 0x0C, 0xC4, 0x02, 0x00, 0x00, 0x2D, 0x91, 0x55];
+        let mut decoder = Decoder::new(&data);
+        assert!(decoder.npdu().unwrap().expecting_reply);
+        assert_eq!(2, decoder.position());
+        assert!(decoder.apdu_header().is_ok());
+        assert_eq!(6, decoder.position());
+        assert_eq!(2, decoder.value_sequence_to_end(&EVERY_TYPE).unwrap().len());
+        assert!(decoder.is_empty());
+
+        // A truncated APDU header is reported at its start, where the decoder is left
+        let mut decoder = Decoder::new(&data[..4]);
+        assert!(decoder.npdu().is_ok());
+        assert_eq!(Err(DecodeError { offset: 2, error: ParseError::InputEndedBeforeParsingCompleted }), decoder.apdu_header());
+        assert_eq!(2, decoder.position());
+    }
+
+    /// Headers decode to their fields, with the length of the header
+    #[test]
+    fn decode_every_header() {
+        use ast::{ApduHeader, MacAddress, MaxApdu, MaxSegments, NetworkAddress, NetworkMessage, Npdu, SegmentInfo};
+        use enumerations::{AbortReason, RejectReason};
+
+        fn npdu(data: &[u8]) -> Result<(Npdu, usize), DecodeError> {
+            let mut decoder = Decoder::new(data);
+            decoder.npdu().map(|npdu| (npdu, decoder.position()))
+        }
+        assert_eq!(Ok((Npdu { destination: Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }), ..Npdu::local(false) }, 6)),
+                   npdu(&[0x01, 0x20, 0xFF, 0xFF, 0x00, 0xFF]));
+        assert_eq!(Ok((Npdu {
+            destination: Some(NetworkAddress { network: 5, address: MacAddress::new(&[0x0A]).unwrap() }),
+            source: Some(NetworkAddress { network: 2, address: MacAddress::new(&[0x01, 0x02]).unwrap() }),
+            hop_count: 15,
+            ..Npdu::local(false)
+        }, 12)), npdu(&[0x01, 0x28, 0x00, 0x05, 0x01, 0x0A, 0x00, 0x02, 0x02, 0x01, 0x02, 0x0F]));
+        assert_eq!(Ok((Npdu { network_message: Some(NetworkMessage { message_type: 0x81, vendor_id: Some(260) }), ..Npdu::local(false) }, 5)),
+                   npdu(&[0x01, 0x80, 0x81, 0x01, 0x04]));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Source can't be a broadcast address") }),
+                   npdu(&[0x01, 0x08, 0xFF, 0xFF, 0x00]));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Unsupported protocol version") }), npdu(&[0x02, 0x00]));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InputEndedBeforeParsingCompleted }), npdu(&[0x01, 0x20, 0x00, 0x05]));
+
+        fn apdu_header(data: &[u8]) -> Result<(ApduHeader, usize), DecodeError> {
+            let mut decoder = Decoder::new(data);
+            decoder.apdu_header().map(|header| (header, decoder.position()))
+        }
+        assert_eq!(Ok((ApduHeader::ConfirmedReq {
+            segmented: Some(SegmentInfo { more_follows: true, sequence_number: 2, proposed_window_size: 4 }),
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 1,
+            service: 12,
+        }, 6)), apdu_header(&[0x0E, 0x05, 0x01, 0x02, 0x04, 0x0C]));
+        assert_eq!(Ok((ApduHeader::UnconfirmedReq { service: 8 }, 2)), apdu_header(&[0x10, 0x08]));
+        assert_eq!(Ok((ApduHeader::ComplexAck {
+            segmented: Some(SegmentInfo { more_follows: false, sequence_number: 0, proposed_window_size: 4 }),
+            invoke_id: 1,
+            service: 14,
+        }, 5)), apdu_header(&[0x38, 0x01, 0x00, 0x04, 0x0E]));
+        assert_eq!(Ok((ApduHeader::SegmentAck { negative_ack: true, server: true, invoke_id: 1, sequence_number: 2, actual_window_size: 4 }, 4)),
+                   apdu_header(&[0x43, 0x01, 0x02, 0x04]));
+        assert_eq!(Ok((ApduHeader::RejectPdu { invoke_id: 1, reject_reason: RejectReason::InvalidTag }, 3)), apdu_header(&[0x60, 0x01, 0x04]));
+        assert_eq!(Ok((ApduHeader::AbortPdu { server: true, invoke_id: 1, abort_reason: AbortReason::SegmentationNotSupported }, 3)),
+                   apdu_header(&[0x71, 0x01, 0x04]));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Reserved PDU type") }), apdu_header(&[0x90, 0x01]));
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InputEndedBeforeParsingCompleted }), apdu_header(&[0x00, 0x05]));
+    }
+
+    #[test]
+    fn decode_bvlc() {
+        use ast::Bvlc;
+
+        let data = [0x81, 0x0A, 0x00, 0x06, 0x01, 0x00, 0xFF];
+        let mut decoder = Decoder::new(&data);
+        assert_eq!(Ok(Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00])), decoder.bvlc());
+        assert_eq!(6, decoder.position());
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("BVLC length shorter than header") }),
+                   Decoder::new(&[0x81, 0x0A, 0x00, 0x03]).bvlc());
+        assert_eq!(Err(DecodeError { offset: 0, error: ParseError::InvalidValue("Partial foreign device table entry") }),
+                   Decoder::new(&[0x81, 0x07, 0x00, 0x06, 0x0A, 0x00]).bvlc());
+    }
+}
@@ -0,0 +1,540 @@
+//! Encodes into a buffer supplied by the caller, as an alternative to the `Vec` writers in
+//! `serialise` for devices which can't allocate. Each part is encoded whole or not at all, so
+//! when the buffer is full the encoder is left as it was and the part can be encoded into the
+//! next buffer.
+
+use ast;
+use ast::{Date, NetworkAddress, ObjectId, Time, Year, MAX_INSTANCE, MAX_MAC_LEN};
+use core::convert::TryFrom;
+use decode::{Tag, Value};
+use enumerations::ObjectType;
+
+/// The most octets a tag takes, with an extended tag number and a four octet extended length
+pub const MAX_TAG_LEN: usize = 7;
+
+/// The most octets an APDU header takes, which is a segmented confirmed request
+pub const MAX_APDU_HEADER_LEN: usize = 6;
+
+/// The most octets an NPDU header takes, with a destination and source of the longest MAC
+/// address and a proprietary network message
+pub const MAX_NPDU_HEADER_LEN: usize = 2 + 2 * (3 + MAX_MAC_LEN) + 1 + 3;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EncodeError {
+    /// The part didn't fit in what is left of the buffer
+    BufferFull,
+    /// A field of the value is outside what its encoding can hold, such as an object instance
+    /// above `MAX_INSTANCE` or a 25th hour
+    OutOfRange,
+}
+
+/// Encodes the parts of a message in turn into a buffer
+pub struct Encoder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Encoder<'a> {
+        Encoder { buffer, len: 0 }
+    }
+
+    /// The number of octets encoded so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The octets encoded so far, from the start of the buffer
+    pub fn encoded(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Encodes an NPDU header (Clause 6.2), the APDU or network layer message content should
+    /// follow
+    pub fn npdu(&mut self, npdu: &ast::Npdu) -> Result<(), EncodeError> {
+        self.whole(|encoder| {
+            let network_message = npdu.network_message.is_some() as u8;
+            let dnet = npdu.destination.is_some() as u8;
+            let snet = npdu.source.is_some() as u8;
+            let expecting_reply = npdu.expecting_reply as u8;
+            encoder.octets(&[1, network_message << 7 ^ dnet << 5 ^ snet << 3 ^ expecting_reply << 2 ^ npdu.priority & 0x03])?;
+            if let Some(ref destination) = npdu.destination {
+                encoder.network_address(destination)?;
+            }
+            if let Some(ref source) = npdu.source {
+                encoder.network_address(source)?;
+            }
+            if npdu.destination.is_some() {
+                encoder.octets(&[npdu.hop_count])?;
+            }
+            if let Some(ast::NetworkMessage { message_type, vendor_id }) = npdu.network_message {
+                encoder.octets(&[message_type])?;
+                if message_type >= 0x80 {
+                    encoder.octets(&vendor_id.unwrap_or(0).to_be_bytes())?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn network_address(&mut self, address: &NetworkAddress) -> Result<(), EncodeError> {
+        self.octets(&address.network.to_be_bytes())?;
+        self.octets(&[address.address.len() as u8])?;
+        self.octets(&address.address)
+    }
+
+    /// Encodes an APDU header (Clause 20.1)
+    pub fn apdu_header(&mut self, header: &ast::ApduHeader) -> Result<(), EncodeError> {
+        use ast::ApduHeader;
+
+        let mut octets = [0; MAX_APDU_HEADER_LEN];
+        let len = match *header {
+            ApduHeader::ConfirmedReq { service, max_apdu, invoke_id, max_segments, ref segmented, segmented_response_accepted } => {
+                let seg = segmented.is_some() as u8;
+                let mor = segmented.as_ref().is_some_and(|info| info.more_follows) as u8;
+                let sa = segmented_response_accepted as u8;
+                octets[0] = seg << 3 ^ mor << 2 ^ sa << 1;    // PDU type (0) and flags
+                octets[1] = u8::from(max_segments) << 4 ^ (u8::from(max_apdu) & 0x0F);  // transport limitations
+                octets[2] = invoke_id;
+                let len = segment_info(segmented, &mut octets[3..]) + 3;
+                octets[len] = service;
+                len + 1
+            },
+            ApduHeader::UnconfirmedReq { service } => {
+                octets[..2].copy_from_slice(&[1 << 4, service]);
+                2
+            },
+            ApduHeader::SimpleAck { service, invoke_id } => {
+                octets[..3].copy_from_slice(&[2 << 4, invoke_id, service]);
+                3
+            },
+            ApduHeader::ComplexAck { service, invoke_id, ref segmented } => {
+                let seg = segmented.is_some() as u8;
+                let mor = segmented.as_ref().is_some_and(|info| info.more_follows) as u8;
+                octets[0] = 3 << 4 ^ seg << 3 ^ mor << 2;    // PDU type and flags
+                octets[1] = invoke_id;
+                let len = segment_info(segmented, &mut octets[2..]) + 2;
+                octets[len] = service;
+                len + 1
+            },
+            ApduHeader::SegmentAck { negative_ack, server, invoke_id, sequence_number, actual_window_size } => {
+                let nak = negative_ack as u8;
+                let srv = server as u8;
+                octets[..4].copy_from_slice(&[4 << 4 ^ nak << 1 ^ srv, invoke_id, sequence_number, actual_window_size]);
+                4
+            },
+            ApduHeader::ErrorPdu { invoke_id, error_choice } => {
+                octets[..3].copy_from_slice(&[5 << 4, invoke_id, error_choice]);
+                3
+            },
+            ApduHeader::RejectPdu { invoke_id, reject_reason } => {
+                octets[..3].copy_from_slice(&[6 << 4, invoke_id, reject_reason.into()]);
+                3
+            },
+            ApduHeader::AbortPdu { invoke_id, server, abort_reason } => {
+                octets[..3].copy_from_slice(&[7 << 4 ^ server as u8, invoke_id, abort_reason.into()]);
+                3
+            },
+        };
+        self.octets(&octets[..len])
+    }
+
+    /// Encodes a tag (Clause 20.2.1). The tag number is extended into another octet above 14,
+    /// and the tag value of an application or context tag above 4, into one octet up to 253 and
+    /// after 0xFE or 0xFF into two or four octets above that.
+    pub fn tag(&mut self, tag: Tag) -> Result<(), EncodeError> {
+        let (is_named, tag_number, is_context, lvt) = match tag {
+            Tag::Open(context) => (true, context, true, 0x6),
+            Tag::Close(context) => (true, context, true, 0x7),
+            Tag::Application(tag, value) => (false, tag, false, value),
+            Tag::Context(context, value) => (false, context, true, value),
+        };
+        let class_flag = if is_context { 0x8 } else { 0x0 };
+        let tag_portion = if tag_number <= 14 {     // Clause 20.2.1.2
+            tag_number
+        } else {
+            0b1111
+        };
+        let value_portion = if is_named || lvt <= 4 {           // Clause 20.2.1.3.1
+            lvt
+        } else {
+            0b101
+        };
+        let mut octets = [0; MAX_TAG_LEN];
+        octets[0] = (tag_portion << 4) ^ class_flag ^ value_portion as u8;
+        let mut len = 1;
+        if tag_portion == 0b1111 {
+            octets[len] = tag_number;
+            len += 1;
+        }
+        if value_portion == 0b101 {
+            let extended: &[u8] = if lvt <= 253 {
+                &[lvt as u8]
+            } else if lvt <= 65535 {
+                &[254, (lvt >> 8) as u8, lvt as u8]
+            } else {
+                &[255, (lvt >> 24) as u8, (lvt >> 16) as u8, (lvt >> 8) as u8, lvt as u8]
+            };
+            octets[len..len + extended.len()].copy_from_slice(extended);
+            len += extended.len();
+        }
+        self.octets(&octets[..len])
+    }
+
+    /// Encodes an application tagged value, booleans are in the tag value
+    pub fn application_value(&mut self, value: &Value) -> Result<(), EncodeError> {
+        check(value)?;
+        if let Value::Boolean(b) = *value {
+            return self.tag(Tag::Application(1, b as u32))
+        }
+        let content = Content::of(value);
+        self.whole(|encoder| {
+            encoder.tag(Tag::Application(value.primitive_type() as u8, content.len() as u32))?;
+            encoder.content(&content)
+        })
+    }
+
+    /// Encodes a value with the context tag number. Context tagged booleans need the tag value for
+    /// the length, so they have a content octet (Clause 20.2.3).
+    pub fn context_value(&mut self, number: u8, value: &Value) -> Result<(), EncodeError> {
+        check(value)?;
+        self.whole(|encoder| {
+            if let Value::Boolean(b) = *value {
+                encoder.tag(Tag::Context(number, 1))?;
+                return encoder.octets(&[b as u8])
+            }
+            let content = Content::of(value);
+            encoder.tag(Tag::Context(number, content.len() as u32))?;
+            encoder.content(&content)
+        })
+    }
+
+    /// Encodes the opening tag of a constructed value with the context tag number
+    pub fn open(&mut self, number: u8) -> Result<(), EncodeError> {
+        self.tag(Tag::Open(number))
+    }
+
+    /// Encodes the closing tag of a constructed value with the context tag number
+    pub fn close(&mut self, number: u8) -> Result<(), EncodeError> {
+        self.tag(Tag::Close(number))
+    }
+
+    fn content(&mut self, content: &Content) -> Result<(), EncodeError> {
+        self.octets(&content.octets[..content.len])?;
+        self.octets(content.borrowed)
+    }
+
+    /// Copies the octets to the end of what has been encoded
+    fn octets(&mut self, octets: &[u8]) -> Result<(), EncodeError> {
+        let end = self.len + octets.len();
+        if end > self.buffer.len() {
+            return Err(EncodeError::BufferFull)
+        }
+        self.buffer[self.len..end].copy_from_slice(octets);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Encodes a part in several steps, leaving the encoder as it was if one of them fails
+    fn whole<F>(&mut self, encode: F) -> Result<(), EncodeError>
+        where F: FnOnce(&mut Encoder<'a>) -> Result<(), EncodeError> {
+        let len = self.len;
+        let result = encode(self);
+        if result.is_err() {
+            self.len = len;
+        }
+        result
+    }
+}
+
+/// The most octets the value takes with its tag, to size a buffer to encode it into
+pub fn max_encoded_len(value: &Value) -> usize {
+    MAX_TAG_LEN + Content::of(value).len()
+}
+
+/// Checks the fields of the value fit their encoding, as `Content::of` would otherwise truncate them
+/// or encode values which don't decode
+fn check(value: &Value) -> Result<(), EncodeError> {
+    let in_range = match *value {
+        // Reserved and Proprietary object types can be made with values too large for 10 bits
+        Value::ObjectId(ObjectId(object_type, instance)) =>
+            ObjectType::try_from(u16::from(object_type)).is_ok() && instance <= MAX_INSTANCE,
+        Value::Date(date) => date_in_range(date),
+        Value::Time(time) => time_in_range(time),
+        _ => true,
+    };
+    if in_range { Ok(()) } else { Err(EncodeError::OutOfRange) }
+}
+
+/// Whether the numbered fields of a date are in range, as the year is by construction
+fn date_in_range(date: Date) -> bool {
+    use ast::{Month, DayOfMonth};
+
+    let month = match date.month {
+        Month::Number(month) => (1..=12).contains(&month),
+        _ => true,
+    };
+    let day = match date.day {
+        DayOfMonth::Number(day) => (1..=31).contains(&day),
+        _ => true,
+    };
+    month && day && date.weekday.is_none_or(|weekday| (1..=7).contains(&weekday))
+}
+
+/// Whether the specified fields of a time of day are in range
+fn time_in_range(time: Time) -> bool {
+    let field = |f: Option<u8>, max: u8| f.is_none_or(|f| f <= max);
+    field(time.hour, 23) && field(time.minute, 59) && field(time.second, 59) && field(time.hundredths, 99)
+}
+
+// The sequence number and proposed window size of a segmented message, with the number of octets
+fn segment_info(segmented: &Option<ast::SegmentInfo>, octets: &mut [u8]) -> usize {
+    match *segmented {
+        Some(ast::SegmentInfo { sequence_number, proposed_window_size, .. }) => {
+            octets[..2].copy_from_slice(&[sequence_number, proposed_window_size]);
+            2
+        },
+        None => 0,
+    }
+}
+
+/// The content octets of a value, up to eight encoded from it followed by any it borrows
+struct Content<'a> {
+    octets: [u8; 8],
+    len: usize,
+    borrowed: &'a [u8],
+}
+
+impl<'a> Content<'a> {
+    /// The content octets of the value as application tagged (Clause 20.2.2 - 20.2.14)
+    fn of(value: &Value<'a>) -> Content<'a> {
+        let mut content = Content { octets: [0; 8], len: 0, borrowed: &[] };
+        match *value {
+            Value::Null => (),
+            Value::Boolean(b) => content.push(&[b as u8]),
+            Value::Unsigned(u) => content.push(unsigned_octets(&u.to_be_bytes())),
+            Value::Signed(i) => content.push(signed_octets(&i.to_be_bytes())),
+            Value::Real(r) => content.push(&r.to_bits().to_be_bytes()),
+            Value::Double(d) => content.push(&d.to_bits().to_be_bytes()),
+            Value::OctetString(octets) => content.borrowed = octets,
+            Value::CharacterString(string) => {
+                content.push(&[string.character_set()]);
+                content.borrowed = string.octets();
+            },
+            Value::BitString(octets, unused_bits) => {
+                content.push(&[unused_bits]);
+                content.borrowed = octets;
+            },
+            Value::Enumerated(e) => content.push(unsigned_octets(&e.to_be_bytes())),
+            Value::Date(date) => content.push(&date_octets(date)),
+            Value::Time(time) => content.push(&time_octets(time)),
+            Value::ObjectId(ObjectId(object_type, instance)) => {
+                let id = (u16::from(object_type) as u32) << 22 | instance;
+                content.push(&id.to_be_bytes());
+            },
+        }
+        content
+    }
+
+    fn push(&mut self, octets: &[u8]) {
+        self.octets[self.len..self.len + octets.len()].copy_from_slice(octets);
+        self.len += octets.len();
+    }
+
+    fn len(&self) -> usize {
+        self.len + self.borrowed.len()
+    }
+}
+
+/// The shortest big-endian encoding of an unsigned value, at least one octet
+fn unsigned_octets(bytes: &[u8; 4]) -> &[u8] {
+    let start = bytes[..3].iter().take_while(|&&byte| byte == 0).count();
+    &bytes[start..]
+}
+
+/// The shortest big-endian two's complement encoding of a signed value, at least one octet
+fn signed_octets(bytes: &[u8; 4]) -> &[u8] {
+    let mut start = 0;
+    // an octet can be dropped if it only repeats the sign bit of the following octet
+    while start < 3 && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0) ||
+                        (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0)) {
+        start += 1;
+    }
+    &bytes[start..]
+}
+
+/// Dates are encoded as year - 1900, month, day and day of week, with 0xFF for unspecified fields
+fn date_octets(date: Date) -> [u8; 4] {
+    use ast::{Month, DayOfMonth};
+
+    let year = date.year.map_or(0xFF, |year| (year.get() - Year::MIN) as u8);
+    let month = match date.month {
+        Month::Number(month) => month,
+        Month::Odd => 13,
+        Month::Even => 14,
+        Month::Unspecified => 0xFF,
+    };
+    let day = match date.day {
+        DayOfMonth::Number(day) => day,
+        DayOfMonth::Last => 32,
+        DayOfMonth::Odd => 33,
+        DayOfMonth::Even => 34,
+        DayOfMonth::Unspecified => 0xFF,
+    };
+    [year, month, day, date.weekday.unwrap_or(0xFF)]
+}
+
+/// Times are encoded as hour, minute, second and hundredths, with 0xFF for unspecified fields
+fn time_octets(time: Time) -> [u8; 4] {
+    let field = |f: Option<u8>| f.unwrap_or(0xFF);
+    [field(time.hour), field(time.minute), field(time.second), field(time.hundredths)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EncodeError, Encoder, MAX_APDU_HEADER_LEN, MAX_NPDU_HEADER_LEN, MAX_TAG_LEN};
+    use ast::{ApduHeader, Date, DayOfMonth, MacAddress, MaxApdu, MaxSegments, Month, NetworkAddress, NetworkMessage, Npdu, ObjectId, SegmentInfo, Time, MAX_INSTANCE};
+    use decode::{CharacterString, Decoder, Tag, Value};
+    use enumerations::ObjectType;
+
+    #[test]
+    fn encode_values() {
+        let mut buffer = [0; 32];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.application_value(&Value::Unsigned(0x9988)).unwrap();
+        encoder.context_value(2, &Value::Boolean(false)).unwrap();
+        encoder.application_value(&Value::Boolean(true)).unwrap();
+        encoder.open(3).unwrap();
+        encoder.context_value(8, &Value::CharacterString(CharacterString::utf8("A"))).unwrap();
+        encoder.close(3).unwrap();
+        encoder.application_value(&Value::Signed(-72)).unwrap();
+        assert_eq!(&[0x22u8, 0x99, 0x88, 0x29, 0x00, 0x11, 0x3E, 0x8A, 0x00, 0x41, 0x3F, 0x31, 0xB8], encoder.encoded());
+    }
+
+    #[test]
+    fn values_decode_as_encoded() {
+        let values = [
+            Value::Null, Value::Boolean(true), Value::Unsigned(0), Value::Unsigned(u32::MAX), Value::Signed(-129),
+            Value::Real(-1.5), Value::Double(1.0e-300), Value::OctetString(&[0xAB; 300]),
+            Value::CharacterString(CharacterString::new(4, &[0x00, 0x41, 0x00, 0xE9]).unwrap()),
+            Value::BitString(&[0xB0], 4), Value::Enumerated(256),
+        ];
+        for value in &values {
+            let mut buffer = [0; 640];
+            let mut encoder = Encoder::new(&mut buffer);
+            encoder.application_value(value).unwrap();
+            encoder.context_value(5, value).unwrap();
+            let len = encoder.len();
+            let mut decoder = Decoder::new(&buffer[..len]);
+            assert_eq!(Ok(value.clone()), decoder.application_value());
+            assert_eq!(Ok(value.clone()), decoder.context_value(5, value.primitive_type()));
+            assert!(decoder.is_empty());
+        }
+    }
+
+    #[test]
+    fn encode_tags() {
+        let mut buffer = [0; MAX_TAG_LEN];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.tag(Tag::Context(0x59, 0x59595959)).unwrap();
+        assert_eq!(&[0xFDu8, 0x59, 0xFF, 0x59, 0x59, 0x59, 0x59], encoder.encoded());
+    }
+
+    #[test]
+    fn encode_headers() {
+        let npdu = Npdu {
+            priority: 2,
+            destination: Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }),
+            source: Some(NetworkAddress { network: 7, address: MacAddress::new(&[1, 2, 3, 4, 5, 6]).unwrap() }),
+            hop_count: 12,
+            network_message: Some(NetworkMessage { message_type: 0x81, vendor_id: Some(65535) }),
+            ..Npdu::local(false)
+        };
+        let header = ApduHeader::ConfirmedReq {
+            segmented: Some(SegmentInfo { more_follows: true, sequence_number: 255, proposed_window_size: 127 }),
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::MoreThan64,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 253,
+            service: 12,
+        };
+        let mut buffer = [0; MAX_NPDU_HEADER_LEN + MAX_APDU_HEADER_LEN];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.npdu(&npdu).unwrap();
+        encoder.apdu_header(&header).unwrap();
+        let len = encoder.len();
+        let mut decoder = Decoder::new(&buffer[..len]);
+        assert_eq!(Ok(npdu), decoder.npdu());
+        assert_eq!(Ok(header), decoder.apdu_header());
+        assert!(decoder.is_empty());
+
+        // The longest headers fit the constants
+        let address = NetworkAddress { network: 1, address: MacAddress::new(&[0xFF; 18]).unwrap() };
+        let npdu = Npdu { destination: Some(address.clone()), source: Some(address), ..npdu_with_message() };
+        let mut buffer = [0; MAX_NPDU_HEADER_LEN];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.npdu(&npdu).unwrap();
+        assert_eq!(MAX_NPDU_HEADER_LEN, encoder.len());
+    }
+
+    fn npdu_with_message() -> Npdu {
+        Npdu { network_message: Some(NetworkMessage { message_type: 0x80, vendor_id: Some(1) }), ..Npdu::local(false) }
+    }
+
+    #[test]
+    fn buffer_full() {
+        let mut buffer = [0; 4];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.application_value(&Value::Unsigned(1)).unwrap();
+        // Neither the value nor its tag is encoded if it doesn't all fit
+        assert_eq!(Err(EncodeError::BufferFull), encoder.application_value(&Value::Real(72.0)));
+        assert_eq!(Err(EncodeError::BufferFull), encoder.context_value(1, &Value::OctetString(&[1, 2])));
+        assert_eq!(Err(EncodeError::BufferFull), encoder.npdu(&npdu_with_message()));
+        assert_eq!(&[0x21u8, 0x01], encoder.encoded());
+        encoder.context_value(1, &Value::Boolean(true)).unwrap();
+        assert_eq!(&[0x21u8, 0x01, 0x19, 0x01], encoder.encoded());
+        assert_eq!(Err(EncodeError::BufferFull), encoder.close(1));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut buffer = [0; 16];
+        let mut encoder = Encoder::new(&mut buffer);
+        encoder.application_value(&Value::ObjectId(ObjectId(ObjectType::Proprietary(1023), MAX_INSTANCE))).unwrap();
+        encoder.application_value(&Value::Date(date())).unwrap();
+        encoder.application_value(&Value::Time(time())).unwrap();
+        assert_eq!(&[0xC4u8, 0xFF, 0xFF, 0xFF, 0xFF, 0xA4, 0xFF, 0x0C, 0x1F, 0x07, 0xB4, 0x17, 0x3B, 0x3B, 0x63], encoder.encoded());
+        // No field is truncated or encoded as a value which doesn't decode
+        let values = [
+            Value::ObjectId(ObjectId(ObjectType::Device, MAX_INSTANCE + 1)),
+            Value::ObjectId(ObjectId(ObjectType::Proprietary(1024), 0)),
+            Value::Date(Date { month: Month::Number(13), ..date() }),
+            Value::Date(Date { month: Month::Number(0), ..date() }),
+            Value::Date(Date { day: DayOfMonth::Number(32), ..date() }),
+            Value::Date(Date { weekday: Some(8), ..date() }),
+            Value::Time(Time { hour: Some(24), ..time() }),
+            Value::Time(Time { minute: Some(60), ..time() }),
+            Value::Time(Time { second: Some(60), ..time() }),
+            Value::Time(Time { hundredths: Some(100), ..time() }),
+        ];
+        for value in &values {
+            assert_eq!(Err(EncodeError::OutOfRange), encoder.application_value(value));
+            assert_eq!(Err(EncodeError::OutOfRange), encoder.context_value(1, value));
+        }
+        assert_eq!(15, encoder.len());
+    }
+
+    /// The last of every numbered field of a date
+    fn date() -> Date {
+        Date { year: None, month: Month::Number(12), day: DayOfMonth::Number(31), weekday: Some(7) }
+    }
+
+    /// The last of every field of a time
+    fn time() -> Time {
+        Time { hour: Some(23), minute: Some(59), second: Some(59), hundredths: Some(99) }
+    }
+}
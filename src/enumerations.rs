@@ -0,0 +1,1390 @@
+//! The enumerated types of Clause 21. Each converts to and from its value on the wire. Values
+//! the standard sets aside for vendors are kept as `Proprietary`, and values reserved for ASHRAE
+//! which this library doesn't know (from a later revision of the standard, say) are kept as
+//! `Reserved`, so no value is lost. They display as their lower case ASN.1 names,
+//! e.g. `analog-input`.
+
+#[cfg(feature = "std")]
+use ast::{Primitive, PrimitiveValue};
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
+use decode::PrimitiveType;
+
+/// Defines an enumeration along with its wire conversions, comparisons and display name. The
+/// proprietary ranges are optional, as is a `max` for a field narrower than `$repr`, which makes
+/// the conversion from `$repr` fallible. Each is an enumerated primitive value.
+macro_rules! enumeration {
+    ($(#[$attr:meta])* pub enum $name:ident: $repr:ty { $($variant:ident = $value:literal,)* }
+     proprietary $($range:expr),+; max $max:literal) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub enum $name {
+            $($variant,)*
+            /// A value reserved for ASHRAE which isn't known to this library
+            Reserved($repr),
+            /// A vendor's own value
+            Proprietary($repr),
+        }
+
+        /// Fails for a value above the largest the field can hold
+        impl TryFrom<$repr> for $name {
+            type Error = TooLarge;
+
+            fn try_from(value: $repr) -> Result<$name, TooLarge> {
+                match value {
+                    $($value => Ok($name::$variant),)*
+                    value if value > $max => Err(TooLarge),
+                    value if $(($range).contains(&value))||+ => Ok($name::Proprietary(value)),
+                    value => Ok($name::Reserved(value)),
+                }
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> $repr {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Reserved(value) | $name::Proprietary(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $($name::$variant => write_name(f, stringify!($variant)),)*
+                    $name::Reserved(value) | $name::Proprietary(value) => write!(f, "{}", value),
+                }
+            }
+        }
+
+        enumeration!(@comparison $name, $repr);
+        enumeration!(@primitive $name, $repr);
+    };
+    ($(#[$attr:meta])* pub enum $name:ident: $repr:ty { $($variant:ident = $value:literal,)* }
+     proprietary $($range:expr),+) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub enum $name {
+            $($variant,)*
+            /// A value reserved for ASHRAE which isn't known to this library
+            Reserved($repr),
+            /// A vendor's own value
+            Proprietary($repr),
+        }
+
+        impl From<$repr> for $name {
+            fn from(value: $repr) -> $name {
+                match value {
+                    $($value => $name::$variant,)*
+                    value if $(($range).contains(&value))||+ => $name::Proprietary(value),
+                    value => $name::Reserved(value),
+                }
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> $repr {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Reserved(value) | $name::Proprietary(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $($name::$variant => write_name(f, stringify!($variant)),)*
+                    $name::Reserved(value) | $name::Proprietary(value) => write!(f, "{}", value),
+                }
+            }
+        }
+
+        enumeration!(@comparison $name, $repr);
+        enumeration!(@primitive $name, $repr);
+    };
+    ($(#[$attr:meta])* pub enum $name:ident: $repr:ty { $($variant:ident = $value:literal,)* }) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        pub enum $name {
+            $($variant,)*
+            /// A value reserved for ASHRAE which isn't known to this library
+            Reserved($repr),
+        }
+
+        impl From<$repr> for $name {
+            fn from(value: $repr) -> $name {
+                match value {
+                    $($value => $name::$variant,)*
+                    value => $name::Reserved(value),
+                }
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> $repr {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Reserved(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $($name::$variant => write_name(f, stringify!($variant)),)*
+                    $name::Reserved(value) => write!(f, "{}", value),
+                }
+            }
+        }
+
+        enumeration!(@comparison $name, $repr);
+        enumeration!(@primitive $name, $repr);
+    };
+    (@primitive $name:ident, $repr:ty) => {
+        #[cfg(feature = "std")]
+        impl Primitive for $name {
+            const TYPE: PrimitiveType = PrimitiveType::Enumerated;
+
+            fn to_primitive(&self) -> PrimitiveValue {
+                PrimitiveValue::Enumerated(<$repr>::from(*self).into())
+            }
+
+            /// `None` for a value too large for the enumeration
+            fn from_primitive(value: &PrimitiveValue) -> Option<Self> {
+                match *value {
+                    PrimitiveValue::Enumerated(value) => <$repr>::try_from(value).ok().and_then(|value| $name::try_from(value).ok()),
+                    _ => None,
+                }
+            }
+        }
+    };
+    (@comparison $name:ident, $repr:ty) => {
+        /// Compared by wire value, so a `Reserved` value equals the variant with that value
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                <$repr>::from(*self) == <$repr>::from(*other)
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                <$repr>::from(*self).hash(state)
+            }
+        }
+
+        /// Ordered by wire value
+        impl Ord for $name {
+            fn cmp(&self, other: &$name) -> Ordering {
+                <$repr>::from(*self).cmp(&<$repr>::from(*other))
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+    };
+}
+
+/// A value too large for the field an enumeration is encoded in, such as an object type above
+/// 1023
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TooLarge;
+
+/// Writes a variant's name in the style of the ASN.1 - `AnalogInput` as `analog-input`
+fn write_name(f: &mut fmt::Formatter, variant: &str) -> fmt::Result {
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                f.write_str("-")?;
+            }
+            write!(f, "{}", c.to_ascii_lowercase())?;
+        } else {
+            write!(f, "{}", c)?;
+        }
+    }
+    Ok(())
+}
+
+enumeration! {
+    /// BACnetObjectType, the 10 bit type of an object identifier
+    pub enum ObjectType: u16 {
+        AnalogInput = 0,
+        AnalogOutput = 1,
+        AnalogValue = 2,
+        BinaryInput = 3,
+        BinaryOutput = 4,
+        BinaryValue = 5,
+        Calendar = 6,
+        Command = 7,
+        Device = 8,
+        EventEnrollment = 9,
+        File = 10,
+        Group = 11,
+        Loop = 12,
+        MultiStateInput = 13,
+        MultiStateOutput = 14,
+        NotificationClass = 15,
+        Program = 16,
+        Schedule = 17,
+        Averaging = 18,
+        MultiStateValue = 19,
+        TrendLog = 20,
+        LifeSafetyPoint = 21,
+        LifeSafetyZone = 22,
+        Accumulator = 23,
+        PulseConverter = 24,
+        EventLog = 25,
+        GlobalGroup = 26,
+        TrendLogMultiple = 27,
+        LoadControl = 28,
+        StructuredView = 29,
+        AccessDoor = 30,
+        Timer = 31,
+        AccessCredential = 32,
+        AccessPoint = 33,
+        AccessRights = 34,
+        AccessUser = 35,
+        AccessZone = 36,
+        CredentialDataInput = 37,
+        NetworkSecurity = 38,
+        BitstringValue = 39,
+        CharacterstringValue = 40,
+        DatePatternValue = 41,
+        DateValue = 42,
+        DatetimePatternValue = 43,
+        DatetimeValue = 44,
+        IntegerValue = 45,
+        LargeAnalogValue = 46,
+        OctetstringValue = 47,
+        PositiveIntegerValue = 48,
+        TimePatternValue = 49,
+        TimeValue = 50,
+        NotificationForwarder = 51,
+        AlertEnrollment = 52,
+        Channel = 53,
+        LightingOutput = 54,
+        BinaryLightingOutput = 55,
+        NetworkPort = 56,
+        ElevatorGroup = 57,
+        Escalator = 58,
+        Lift = 59,
+        Staging = 60,
+        AuditLog = 61,
+        AuditReporter = 62,
+        Color = 63,
+        ColorTemperature = 64,
+    }
+    proprietary 128..=1023; max 1023
+}
+
+enumeration! {
+    /// BACnetPropertyIdentifier. The properties which have been removed from the standard are left
+    /// out.
+    pub enum PropertyIdentifier: u32 {
+        AckedTransitions = 0,
+        AckRequired = 1,
+        Action = 2,
+        ActionText = 3,
+        ActiveText = 4,
+        ActiveVtSessions = 5,
+        AlarmValue = 6,
+        AlarmValues = 7,
+        All = 8,
+        AllWritesSuccessful = 9,
+        ApduSegmentTimeout = 10,
+        ApduTimeout = 11,
+        ApplicationSoftwareVersion = 12,
+        Archive = 13,
+        Bias = 14,
+        ChangeOfStateCount = 15,
+        ChangeOfStateTime = 16,
+        NotificationClass = 17,
+        ControlledVariableReference = 19,
+        ControlledVariableUnits = 20,
+        ControlledVariableValue = 21,
+        CovIncrement = 22,
+        DateList = 23,
+        DaylightSavingsStatus = 24,
+        Deadband = 25,
+        DerivativeConstant = 26,
+        DerivativeConstantUnits = 27,
+        Description = 28,
+        DescriptionOfHalt = 29,
+        DeviceAddressBinding = 30,
+        DeviceType = 31,
+        EffectivePeriod = 32,
+        ElapsedActiveTime = 33,
+        ErrorLimit = 34,
+        EventEnable = 35,
+        EventState = 36,
+        EventType = 37,
+        ExceptionSchedule = 38,
+        FaultValues = 39,
+        FeedbackValue = 40,
+        FileAccessMethod = 41,
+        FileSize = 42,
+        FileType = 43,
+        FirmwareRevision = 44,
+        HighLimit = 45,
+        InactiveText = 46,
+        InProcess = 47,
+        InstanceOf = 48,
+        IntegralConstant = 49,
+        IntegralConstantUnits = 50,
+        LimitEnable = 52,
+        ListOfGroupMembers = 53,
+        ListOfObjectPropertyReferences = 54,
+        LocalDate = 56,
+        LocalTime = 57,
+        Location = 58,
+        LowLimit = 59,
+        ManipulatedVariableReference = 60,
+        MaximumOutput = 61,
+        MaxApduLengthAccepted = 62,
+        MaxInfoFrames = 63,
+        MaxMaster = 64,
+        MaxPresValue = 65,
+        MinimumOffTime = 66,
+        MinimumOnTime = 67,
+        MinimumOutput = 68,
+        MinPresValue = 69,
+        ModelName = 70,
+        ModificationDate = 71,
+        NotifyType = 72,
+        NumberOfApduRetries = 73,
+        NumberOfStates = 74,
+        ObjectIdentifier = 75,
+        ObjectList = 76,
+        ObjectName = 77,
+        ObjectPropertyReference = 78,
+        ObjectType = 79,
+        Optional = 80,
+        OutOfService = 81,
+        OutputUnits = 82,
+        EventParameters = 83,
+        Polarity = 84,
+        PresentValue = 85,
+        Priority = 86,
+        PriorityArray = 87,
+        PriorityForWriting = 88,
+        ProcessIdentifier = 89,
+        ProgramChange = 90,
+        ProgramLocation = 91,
+        ProgramState = 92,
+        ProportionalConstant = 93,
+        ProportionalConstantUnits = 94,
+        ProtocolObjectTypesSupported = 96,
+        ProtocolServicesSupported = 97,
+        ProtocolVersion = 98,
+        ReadOnly = 99,
+        ReasonForHalt = 100,
+        RecipientList = 102,
+        Reliability = 103,
+        RelinquishDefault = 104,
+        Required = 105,
+        Resolution = 106,
+        SegmentationSupported = 107,
+        Setpoint = 108,
+        SetpointReference = 109,
+        StateText = 110,
+        StatusFlags = 111,
+        SystemStatus = 112,
+        TimeDelay = 113,
+        TimeOfActiveTimeReset = 114,
+        TimeOfStateCountReset = 115,
+        TimeSynchronizationRecipients = 116,
+        Units = 117,
+        UpdateInterval = 118,
+        UtcOffset = 119,
+        VendorIdentifier = 120,
+        VendorName = 121,
+        VtClassesSupported = 122,
+        WeeklySchedule = 123,
+        AttemptedSamples = 124,
+        AverageValue = 125,
+        BufferSize = 126,
+        ClientCovIncrement = 127,
+        CovResubscriptionInterval = 128,
+        EventTimeStamps = 130,
+        LogBuffer = 131,
+        LogDeviceObjectProperty = 132,
+        Enable = 133,
+        LogInterval = 134,
+        MaximumValue = 135,
+        MinimumValue = 136,
+        NotificationThreshold = 137,
+        ProtocolRevision = 139,
+        RecordsSinceNotification = 140,
+        RecordCount = 141,
+        StartTime = 142,
+        StopTime = 143,
+        StopWhenFull = 144,
+        TotalRecordCount = 145,
+        ValidSamples = 146,
+        WindowInterval = 147,
+        WindowSamples = 148,
+        MaximumValueTimestamp = 149,
+        MinimumValueTimestamp = 150,
+        VarianceValue = 151,
+        ActiveCovSubscriptions = 152,
+        BackupFailureTimeout = 153,
+        ConfigurationFiles = 154,
+        DatabaseRevision = 155,
+        DirectReading = 156,
+        LastRestoreTime = 157,
+        MaintenanceRequired = 158,
+        MemberOf = 159,
+        Mode = 160,
+        OperationExpected = 161,
+        Setting = 162,
+        Silenced = 163,
+        TrackingValue = 164,
+        ZoneMembers = 165,
+        LifeSafetyAlarmValues = 166,
+        MaxSegmentsAccepted = 167,
+        ProfileName = 168,
+        AutoSlaveDiscovery = 169,
+        ManualSlaveAddressBinding = 170,
+        SlaveAddressBinding = 171,
+        SlaveProxyEnable = 172,
+        LastNotifyRecord = 173,
+        ScheduleDefault = 174,
+        AcceptedModes = 175,
+        AdjustValue = 176,
+        Count = 177,
+        CountBeforeChange = 178,
+        CountChangeTime = 179,
+        CovPeriod = 180,
+        InputReference = 181,
+        LimitMonitoringInterval = 182,
+        LoggingObject = 183,
+        LoggingRecord = 184,
+        Prescale = 185,
+        PulseRate = 186,
+        Scale = 187,
+        ScaleFactor = 188,
+        UpdateTime = 189,
+        ValueBeforeChange = 190,
+        ValueSet = 191,
+        ValueChangeTime = 192,
+        AlignIntervals = 193,
+        IntervalOffset = 195,
+        LastRestartReason = 196,
+        LoggingType = 197,
+        RestartNotificationRecipients = 202,
+        TimeOfDeviceRestart = 203,
+        TimeSynchronizationInterval = 204,
+        Trigger = 205,
+        UtcTimeSynchronizationRecipients = 206,
+        NodeSubtype = 207,
+        NodeType = 208,
+        StructuredObjectList = 209,
+        SubordinateAnnotations = 210,
+        SubordinateList = 211,
+        ActualShedLevel = 212,
+        DutyWindow = 213,
+        ExpectedShedLevel = 214,
+        FullDutyBaseline = 215,
+        RequestedShedLevel = 218,
+        ShedDuration = 219,
+        ShedLevelDescriptions = 220,
+        ShedLevels = 221,
+        StateDescription = 222,
+        DoorAlarmState = 226,
+        DoorExtendedPulseTime = 227,
+        DoorMembers = 228,
+        DoorOpenTooLongTime = 229,
+        DoorPulseTime = 230,
+        DoorStatus = 231,
+        DoorUnlockDelayTime = 232,
+        LockStatus = 233,
+        MaskedAlarmValues = 234,
+        SecuredStatus = 235,
+        AbsenteeLimit = 244,
+        AccessAlarmEvents = 245,
+        AccessDoors = 246,
+        AccessEvent = 247,
+        AccessEventAuthenticationFactor = 248,
+        AccessEventCredential = 249,
+        AccessEventTime = 250,
+        AccessTransactionEvents = 251,
+        Accompaniment = 252,
+        AccompanimentTime = 253,
+        ActivationTime = 254,
+        ActiveAuthenticationPolicy = 255,
+        AssignedAccessRights = 256,
+        AuthenticationFactors = 257,
+        AuthenticationPolicyList = 258,
+        AuthenticationPolicyNames = 259,
+        AuthenticationStatus = 260,
+        AuthorizationMode = 261,
+        BelongsTo = 262,
+        CredentialDisable = 263,
+        CredentialStatus = 264,
+        Credentials = 265,
+        CredentialsInZone = 266,
+        DaysRemaining = 267,
+        EntryPoints = 268,
+        ExitPoints = 269,
+        ExpirationTime = 270,
+        ExtendedTimeEnable = 271,
+        FailedAttemptEvents = 272,
+        FailedAttempts = 273,
+        FailedAttemptsTime = 274,
+        LastAccessEvent = 275,
+        LastAccessPoint = 276,
+        LastCredentialAdded = 277,
+        LastCredentialAddedTime = 278,
+        LastCredentialRemoved = 279,
+        LastCredentialRemovedTime = 280,
+        LastUseTime = 281,
+        Lockout = 282,
+        LockoutRelinquishTime = 283,
+        MaxFailedAttempts = 285,
+        Members = 286,
+        MusterPoint = 287,
+        NegativeAccessRules = 288,
+        NumberOfAuthenticationPolicies = 289,
+        OccupancyCount = 290,
+        OccupancyCountAdjust = 291,
+        OccupancyCountEnable = 292,
+        OccupancyLowerLimit = 294,
+        OccupancyLowerLimitEnforced = 295,
+        OccupancyState = 296,
+        OccupancyUpperLimit = 297,
+        OccupancyUpperLimitEnforced = 298,
+        PassbackMode = 300,
+        PassbackTimeout = 301,
+        PositiveAccessRules = 302,
+        ReasonForDisable = 303,
+        SupportedFormats = 304,
+        SupportedFormatClasses = 305,
+        ThreatAuthority = 306,
+        ThreatLevel = 307,
+        TraceFlag = 308,
+        TransactionNotificationClass = 309,
+        UserExternalIdentifier = 310,
+        UserInformationReference = 311,
+        UserName = 317,
+        UserType = 318,
+        UsesRemaining = 319,
+        ZoneFrom = 320,
+        ZoneTo = 321,
+        AccessEventTag = 322,
+        GlobalIdentifier = 323,
+        VerificationTime = 326,
+        BaseDeviceSecurityPolicy = 327,
+        DistributionKeyRevision = 328,
+        DoNotHide = 329,
+        KeySets = 330,
+        LastKeyServer = 331,
+        NetworkAccessSecurityPolicies = 332,
+        PacketReorderTime = 333,
+        SecurityPduTimeout = 334,
+        SecurityTimeWindow = 335,
+        SupportedSecurityAlgorithms = 336,
+        UpdateKeySetTimeout = 337,
+        BackupAndRestoreState = 338,
+        BackupPreparationTime = 339,
+        RestoreCompletionTime = 340,
+        RestorePreparationTime = 341,
+        BitMask = 342,
+        BitText = 343,
+        IsUtc = 344,
+        GroupMembers = 345,
+        GroupMemberNames = 346,
+        MemberStatusFlags = 347,
+        RequestedUpdateInterval = 348,
+        CovuPeriod = 349,
+        CovuRecipients = 350,
+        EventMessageTexts = 351,
+        EventMessageTextsConfig = 352,
+        EventDetectionEnable = 353,
+        EventAlgorithmInhibit = 354,
+        EventAlgorithmInhibitRef = 355,
+        TimeDelayNormal = 356,
+        ReliabilityEvaluationInhibit = 357,
+        FaultParameters = 358,
+        FaultType = 359,
+        LocalForwardingOnly = 360,
+        ProcessIdentifierFilter = 361,
+        SubscribedRecipients = 362,
+        PortFilter = 363,
+        AuthorizationExemptions = 364,
+        AllowGroupDelayInhibit = 365,
+        ChannelNumber = 366,
+        ControlGroups = 367,
+        ExecutionDelay = 368,
+        LastPriority = 369,
+        WriteStatus = 370,
+        PropertyList = 371,
+        SerialNumber = 372,
+        BlinkWarnEnable = 373,
+        DefaultFadeTime = 374,
+        DefaultRampRate = 375,
+        DefaultStepIncrement = 376,
+        EgressTime = 377,
+        InProgress = 378,
+        InstantaneousPower = 379,
+        LightingCommand = 380,
+        LightingCommandDefaultPriority = 381,
+        MaxActualValue = 382,
+        MinActualValue = 383,
+        Power = 384,
+        Transition = 385,
+        EgressActive = 386,
+        InterfaceValue = 387,
+        FaultHighLimit = 388,
+        FaultLowLimit = 389,
+        LowDiffLimit = 390,
+        StrikeCount = 391,
+        TimeOfStrikeCountReset = 392,
+        DefaultTimeout = 393,
+        InitialTimeout = 394,
+        LastStateChange = 395,
+        StateChangeValues = 396,
+        TimerRunning = 397,
+        TimerState = 398,
+        ApduLength = 399,
+        IpAddress = 400,
+        IpDefaultGateway = 401,
+        IpDhcpEnable = 402,
+        IpDhcpLeaseTime = 403,
+        IpDhcpLeaseTimeRemaining = 404,
+        IpDhcpServer = 405,
+        IpDnsServer = 406,
+        BacnetIpGlobalAddress = 407,
+        BacnetIpMode = 408,
+        BacnetIpMulticastAddress = 409,
+        BacnetIpNatTraversal = 410,
+        IpSubnetMask = 411,
+        BacnetIpUdpPort = 412,
+        BbmdAcceptFdRegistrations = 413,
+        BbmdBroadcastDistributionTable = 414,
+        BbmdForeignDeviceTable = 415,
+        ChangesPending = 416,
+        Command = 417,
+        FdBbmdAddress = 418,
+        FdSubscriptionLifetime = 419,
+        LinkSpeed = 420,
+        LinkSpeeds = 421,
+        LinkSpeedAutonegotiate = 422,
+        MacAddress = 423,
+        NetworkInterfaceName = 424,
+        NetworkNumber = 425,
+        NetworkNumberQuality = 426,
+        NetworkType = 427,
+        RoutingTable = 428,
+        VirtualMacAddressTable = 429,
+        CommandTimeArray = 430,
+        CurrentCommandPriority = 431,
+        LastCommandTime = 432,
+        ValueSource = 433,
+        ValueSourceArray = 434,
+        BacnetIpv6Mode = 435,
+        Ipv6Address = 436,
+        Ipv6PrefixLength = 437,
+        BacnetIpv6UdpPort = 438,
+        Ipv6DefaultGateway = 439,
+        BacnetIpv6MulticastAddress = 440,
+        Ipv6DnsServer = 441,
+        Ipv6AutoAddressingEnable = 442,
+        Ipv6DhcpLeaseTime = 443,
+        Ipv6DhcpLeaseTimeRemaining = 444,
+        Ipv6DhcpServer = 445,
+        Ipv6ZoneIndex = 446,
+        AssignedLandingCalls = 447,
+        CarAssignedDirection = 448,
+        CarDoorCommand = 449,
+        CarDoorStatus = 450,
+        CarDoorText = 451,
+        CarDoorZone = 452,
+        CarDriveStatus = 453,
+        CarLoad = 454,
+        CarLoadUnits = 455,
+        CarMode = 456,
+        CarMovingDirection = 457,
+        CarPosition = 458,
+        ElevatorGroup = 459,
+        EnergyMeter = 460,
+        EnergyMeterRef = 461,
+        EscalatorMode = 462,
+        FaultSignals = 463,
+        FloorText = 464,
+        GroupId = 465,
+        GroupMode = 467,
+        HigherDeck = 468,
+        InstallationId = 469,
+        LandingCalls = 470,
+        LandingCallControl = 471,
+        LandingDoorStatus = 472,
+        LowerDeck = 473,
+        MachineRoomId = 474,
+        MakingCarCall = 475,
+        NextStoppingFloor = 476,
+        OperationDirection = 477,
+        PassengerAlarm = 478,
+        PowerMode = 479,
+        RegisteredCarCall = 480,
+        ActiveCovMultipleSubscriptions = 481,
+        ProtocolLevel = 482,
+        ReferencePort = 483,
+        DeployedProfileLocation = 484,
+        ProfileLocation = 485,
+        Tags = 486,
+        SubordinateNodeTypes = 487,
+        SubordinateTags = 488,
+        SubordinateRelationships = 489,
+        DefaultSubordinateRelationship = 490,
+        Represents = 491,
+        DefaultPresentValue = 492,
+        PresentStage = 493,
+        Stages = 494,
+        StageNames = 495,
+        TargetReferences = 496,
+        AuditSourceReporter = 497,
+        AuditLevel = 498,
+        AuditNotificationRecipient = 499,
+        AuditPriorityFilter = 500,
+        AuditableOperations = 501,
+        DeleteOnForward = 502,
+        MaximumSendDelay = 503,
+        MonitoredObjects = 504,
+        SendNow = 505,
+        FloorNumber = 506,
+        DeviceUuid = 507,
+    }
+    proprietary 512..=4194303
+}
+
+enumeration! {
+    /// BACnetEngineeringUnits
+    pub enum EngineeringUnits: u32 {
+        SquareMeters = 0,
+        SquareFeet = 1,
+        Milliamperes = 2,
+        Amperes = 3,
+        Ohms = 4,
+        Volts = 5,
+        Kilovolts = 6,
+        Megavolts = 7,
+        VoltAmperes = 8,
+        KilovoltAmperes = 9,
+        MegavoltAmperes = 10,
+        VoltAmperesReactive = 11,
+        KilovoltAmperesReactive = 12,
+        MegavoltAmperesReactive = 13,
+        DegreesPhase = 14,
+        PowerFactor = 15,
+        Joules = 16,
+        Kilojoules = 17,
+        WattHours = 18,
+        KilowattHours = 19,
+        Btus = 20,
+        Therms = 21,
+        TonHours = 22,
+        JoulesPerKilogramDryAir = 23,
+        BtusPerPoundDryAir = 24,
+        CyclesPerHour = 25,
+        CyclesPerMinute = 26,
+        Hertz = 27,
+        GramsOfWaterPerKilogramDryAir = 28,
+        PercentRelativeHumidity = 29,
+        Millimeters = 30,
+        Meters = 31,
+        Inches = 32,
+        Feet = 33,
+        WattsPerSquareFoot = 34,
+        WattsPerSquareMeter = 35,
+        Lumens = 36,
+        Luxes = 37,
+        FootCandles = 38,
+        Kilograms = 39,
+        PoundsMass = 40,
+        Tons = 41,
+        KilogramsPerSecond = 42,
+        KilogramsPerMinute = 43,
+        KilogramsPerHour = 44,
+        PoundsMassPerMinute = 45,
+        PoundsMassPerHour = 46,
+        Watts = 47,
+        Kilowatts = 48,
+        Megawatts = 49,
+        BtusPerHour = 50,
+        Horsepower = 51,
+        TonsRefrigeration = 52,
+        Pascals = 53,
+        Kilopascals = 54,
+        Bars = 55,
+        PoundsForcePerSquareInch = 56,
+        CentimetersOfWater = 57,
+        InchesOfWater = 58,
+        MillimetersOfMercury = 59,
+        CentimetersOfMercury = 60,
+        InchesOfMercury = 61,
+        DegreesCelsius = 62,
+        DegreesKelvin = 63,
+        DegreesFahrenheit = 64,
+        DegreeDaysCelsius = 65,
+        DegreeDaysFahrenheit = 66,
+        Years = 67,
+        Months = 68,
+        Weeks = 69,
+        Days = 70,
+        Hours = 71,
+        Minutes = 72,
+        Seconds = 73,
+        MetersPerSecond = 74,
+        KilometersPerHour = 75,
+        FeetPerSecond = 76,
+        FeetPerMinute = 77,
+        MilesPerHour = 78,
+        CubicFeet = 79,
+        CubicMeters = 80,
+        ImperialGallons = 81,
+        Liters = 82,
+        UsGallons = 83,
+        CubicFeetPerMinute = 84,
+        CubicMetersPerSecond = 85,
+        ImperialGallonsPerMinute = 86,
+        LitersPerSecond = 87,
+        LitersPerMinute = 88,
+        UsGallonsPerMinute = 89,
+        DegreesAngular = 90,
+        DegreesCelsiusPerHour = 91,
+        DegreesCelsiusPerMinute = 92,
+        DegreesFahrenheitPerHour = 93,
+        DegreesFahrenheitPerMinute = 94,
+        NoUnits = 95,
+        PartsPerMillion = 96,
+        PartsPerBillion = 97,
+        Percent = 98,
+        PercentPerSecond = 99,
+        PerMinute = 100,
+        PerSecond = 101,
+        PsiPerDegreeFahrenheit = 102,
+        Radians = 103,
+        RevolutionsPerMinute = 104,
+        Currency1 = 105,
+        Currency2 = 106,
+        Currency3 = 107,
+        Currency4 = 108,
+        Currency5 = 109,
+        Currency6 = 110,
+        Currency7 = 111,
+        Currency8 = 112,
+        Currency9 = 113,
+        Currency10 = 114,
+        SquareInches = 115,
+        SquareCentimeters = 116,
+        BtusPerPound = 117,
+        Centimeters = 118,
+        PoundsMassPerSecond = 119,
+        DeltaDegreesFahrenheit = 120,
+        DeltaDegreesKelvin = 121,
+        Kilohms = 122,
+        Megohms = 123,
+        Millivolts = 124,
+        KilojoulesPerKilogram = 125,
+        Megajoules = 126,
+        JoulesPerDegreeKelvin = 127,
+        JoulesPerKilogramDegreeKelvin = 128,
+        Kilohertz = 129,
+        Megahertz = 130,
+        PerHour = 131,
+        Milliwatts = 132,
+        Hectopascals = 133,
+        Millibars = 134,
+        CubicMetersPerHour = 135,
+        LitersPerHour = 136,
+        KilowattHoursPerSquareMeter = 137,
+        KilowattHoursPerSquareFoot = 138,
+        MegajoulesPerSquareMeter = 139,
+        MegajoulesPerSquareFoot = 140,
+        WattsPerSquareMeterDegreeKelvin = 141,
+        CubicFeetPerSecond = 142,
+        PercentObscurationPerFoot = 143,
+        PercentObscurationPerMeter = 144,
+        Milliohms = 145,
+        MegawattHours = 146,
+        KiloBtus = 147,
+        MegaBtus = 148,
+        KilojoulesPerKilogramDryAir = 149,
+        MegajoulesPerKilogramDryAir = 150,
+        KilojoulesPerDegreeKelvin = 151,
+        MegajoulesPerDegreeKelvin = 152,
+        Newton = 153,
+        GramsPerSecond = 154,
+        GramsPerMinute = 155,
+        TonsPerHour = 156,
+        KiloBtusPerHour = 157,
+        HundredthsSeconds = 158,
+        Milliseconds = 159,
+        NewtonMeters = 160,
+        MillimetersPerSecond = 161,
+        MillimetersPerMinute = 162,
+        MetersPerMinute = 163,
+        MetersPerHour = 164,
+        CubicMetersPerMinute = 165,
+        MetersPerSecondPerSecond = 166,
+        AmperesPerMeter = 167,
+        AmperesPerSquareMeter = 168,
+        AmpereSquareMeters = 169,
+        Farads = 170,
+        Henrys = 171,
+        OhmMeters = 172,
+        Siemens = 173,
+        SiemensPerMeter = 174,
+        Teslas = 175,
+        VoltsPerDegreeKelvin = 176,
+        VoltsPerMeter = 177,
+        Webers = 178,
+        Candelas = 179,
+        CandelasPerSquareMeter = 180,
+        DegreesKelvinPerHour = 181,
+        DegreesKelvinPerMinute = 182,
+        JouleSeconds = 183,
+        RadiansPerSecond = 184,
+        SquareMetersPerNewton = 185,
+        KilogramsPerCubicMeter = 186,
+        NewtonSeconds = 187,
+        NewtonsPerMeter = 188,
+        WattsPerMeterPerDegreeKelvin = 189,
+        MicroSiemens = 190,
+        CubicFeetPerHour = 191,
+        UsGallonsPerHour = 192,
+        Kilometers = 193,
+        Micrometers = 194,
+        Grams = 195,
+        Milligrams = 196,
+        Milliliters = 197,
+        MillilitersPerSecond = 198,
+        Decibels = 199,
+        DecibelsMillivolt = 200,
+        DecibelsVolt = 201,
+        Millisiemens = 202,
+        WattHoursReactive = 203,
+        KilowattHoursReactive = 204,
+        MegawattHoursReactive = 205,
+        MillimetersOfWater = 206,
+        PerMille = 207,
+        GramsPerGram = 208,
+        KilogramsPerKilogram = 209,
+        GramsPerKilogram = 210,
+        MilligramsPerGram = 211,
+        MilligramsPerKilogram = 212,
+        GramsPerMilliliter = 213,
+        GramsPerLiter = 214,
+        MilligramsPerLiter = 215,
+        MicrogramsPerLiter = 216,
+        GramsPerCubicMeter = 217,
+        MilligramsPerCubicMeter = 218,
+        MicrogramsPerCubicMeter = 219,
+        NanogramsPerCubicMeter = 220,
+        GramsPerCubicCentimeter = 221,
+        Becquerels = 222,
+        Kilobecquerels = 223,
+        Megabecquerels = 224,
+        Gray = 225,
+        Milligray = 226,
+        Microgray = 227,
+        Sieverts = 228,
+        Millisieverts = 229,
+        Microsieverts = 230,
+        MicrosievertsPerHour = 231,
+        DecibelsA = 232,
+        NephelometricTurbidityUnit = 233,
+        Ph = 234,
+        GramsPerSquareMeter = 235,
+        MinutesPerDegreeKelvin = 236,
+        OhmMeterSquaredPerMeter = 237,
+        AmpereSeconds = 238,
+        VoltAmpereHours = 239,
+        KilovoltAmpereHours = 240,
+        MegavoltAmpereHours = 241,
+        VoltAmpereHoursReactive = 242,
+        KilovoltAmpereHoursReactive = 243,
+        MegavoltAmpereHoursReactive = 244,
+        VoltSquareHours = 245,
+        AmpereSquareHours = 246,
+        JoulePerHours = 247,
+        CubicFeetPerDay = 248,
+        CubicMetersPerDay = 249,
+        WattHoursPerCubicMeter = 250,
+        JoulesPerCubicMeter = 251,
+        MolePercent = 252,
+        PascalSeconds = 253,
+        MillionStandardCubicFeetPerMinute = 254,
+    }
+    proprietary 256..=47807, 50000..=65535
+}
+
+enumeration! {
+    /// BACnetSegmentation
+    pub enum Segmentation: u32 {
+        SegmentedBoth = 0,
+        SegmentedTransmit = 1,
+        SegmentedReceive = 2,
+        NoSegmentation = 3,
+    }
+}
+
+enumeration! {
+    /// BACnetBinaryPV
+    pub enum BinaryPv: u32 {
+        Inactive = 0,
+        Active = 1,
+    }
+}
+
+enumeration! {
+    /// BACnetPolarity
+    pub enum Polarity: u32 {
+        Normal = 0,
+        Reverse = 1,
+    }
+}
+
+enumeration! {
+    /// BACnetDeviceStatus
+    pub enum DeviceStatus: u32 {
+        Operational = 0,
+        OperationalReadOnly = 1,
+        DownloadRequired = 2,
+        DownloadInProgress = 3,
+        NonOperational = 4,
+        BackupInProgress = 5,
+    }
+    proprietary 64..=65535
+}
+
+enumeration! {
+    /// BACnetEventState
+    pub enum EventState: u32 {
+        Normal = 0,
+        Fault = 1,
+        Offnormal = 2,
+        HighLimit = 3,
+        LowLimit = 4,
+        LifeSafetyAlarm = 5,
+    }
+    proprietary 64..=65535
+}
+
+enumeration! {
+    /// The error class of an Error PDU
+    pub enum ErrorClass: u32 {
+        Device = 0,
+        Object = 1,
+        Property = 2,
+        Resources = 3,
+        Security = 4,
+        Services = 5,
+        Vt = 6,
+        Communication = 7,
+    }
+    proprietary 64..=65535
+}
+
+enumeration! {
+    /// The error code of an Error PDU. The codes which have been removed from the standard are
+    /// left out.
+    pub enum ErrorCode: u32 {
+        Other = 0,
+        AuthenticationFailed = 1,
+        ConfigurationInProgress = 2,
+        DeviceBusy = 3,
+        DynamicCreationNotSupported = 4,
+        FileAccessDenied = 5,
+        IncompatibleSecurityLevels = 6,
+        InconsistentParameters = 7,
+        InconsistentSelectionCriterion = 8,
+        InvalidDataType = 9,
+        InvalidFileAccessMethod = 10,
+        InvalidFileStartPosition = 11,
+        InvalidOperatorName = 12,
+        InvalidParameterDataType = 13,
+        InvalidTimeStamp = 14,
+        KeyGenerationError = 15,
+        MissingRequiredParameter = 16,
+        NoObjectsOfSpecifiedType = 17,
+        NoSpaceForObject = 18,
+        NoSpaceToAddListElement = 19,
+        NoSpaceToWriteProperty = 20,
+        NoVtSessionsAvailable = 21,
+        PropertyIsNotAList = 22,
+        ObjectDeletionNotPermitted = 23,
+        ObjectIdentifierAlreadyExists = 24,
+        OperationalProblem = 25,
+        PasswordFailure = 26,
+        ReadAccessDenied = 27,
+        SecurityNotSupported = 28,
+        ServiceRequestDenied = 29,
+        Timeout = 30,
+        UnknownObject = 31,
+        UnknownProperty = 32,
+        UnknownVtClass = 34,
+        UnknownVtSession = 35,
+        UnsupportedObjectType = 36,
+        ValueOutOfRange = 37,
+        VtSessionAlreadyClosed = 38,
+        VtSessionTerminationFailure = 39,
+        WriteAccessDenied = 40,
+        CharacterSetNotSupported = 41,
+        InvalidArrayIndex = 42,
+        CovSubscriptionFailed = 43,
+        NotCovProperty = 44,
+        OptionalFunctionalityNotSupported = 45,
+        InvalidConfigurationData = 46,
+        DatatypeNotSupported = 47,
+        DuplicateName = 48,
+        DuplicateObjectId = 49,
+        PropertyIsNotAnArray = 50,
+        AbortBufferOverflow = 51,
+        AbortInvalidApduInThisState = 52,
+        AbortPreemptedByHigherPriorityTask = 53,
+        AbortSegmentationNotSupported = 54,
+        AbortProprietary = 55,
+        AbortOther = 56,
+        InvalidTag = 57,
+        NetworkDown = 58,
+        RejectBufferOverflow = 59,
+        RejectInconsistentParameters = 60,
+        RejectInvalidParameterDataType = 61,
+        RejectInvalidTag = 62,
+        RejectMissingRequiredParameter = 63,
+        RejectParameterOutOfRange = 64,
+        RejectTooManyArguments = 65,
+        RejectUndefinedEnumeration = 66,
+        RejectUnrecognizedService = 67,
+        RejectProprietary = 68,
+        RejectOther = 69,
+        UnknownDevice = 70,
+        UnknownRoute = 71,
+        ValueNotInitialized = 72,
+        InvalidEventState = 73,
+        NoAlarmConfigured = 74,
+        LogBufferFull = 75,
+        LoggedValuePurged = 76,
+        NoPropertySpecified = 77,
+        NotConfiguredForTriggeredLogging = 78,
+        UnknownSubscription = 79,
+        ParameterOutOfRange = 80,
+        ListElementNotFound = 81,
+        Busy = 82,
+        CommunicationDisabled = 83,
+        Success = 84,
+        AccessDenied = 85,
+        BadDestinationAddress = 86,
+        BadDestinationDeviceId = 87,
+        BadSignature = 88,
+        BadSourceAddress = 89,
+        BadTimestamp = 90,
+        CannotUseKey = 91,
+        CannotVerifyMessageId = 92,
+        CorrectKeyRevision = 93,
+        DestinationDeviceIdRequired = 94,
+        DuplicateMessage = 95,
+        EncryptionNotConfigured = 96,
+        EncryptionRequired = 97,
+        IncorrectKey = 98,
+        InvalidKeyData = 99,
+        KeyUpdateInProgress = 100,
+        MalformedMessage = 101,
+        NotKeyServer = 102,
+        SecurityNotConfigured = 103,
+        SourceSecurityRequired = 104,
+        TooManyKeys = 105,
+        UnknownAuthenticationType = 106,
+        UnknownKey = 107,
+        UnknownKeyRevision = 108,
+        UnknownSourceMessage = 109,
+        NotRouterToDnet = 110,
+        RouterBusy = 111,
+        UnknownNetworkMessage = 112,
+        MessageTooLong = 113,
+        SecurityError = 114,
+        AddressingError = 115,
+        WriteBdtFailed = 116,
+        ReadBdtFailed = 117,
+        RegisterForeignDeviceFailed = 118,
+        ReadFdtFailed = 119,
+        DeleteFdtEntryFailed = 120,
+        DistributeBroadcastFailed = 121,
+        UnknownFileSize = 122,
+        AbortApduTooLong = 123,
+        AbortApplicationExceededReplyTime = 124,
+        AbortOutOfResources = 125,
+        AbortTsmTimeout = 126,
+        AbortWindowSizeOutOfRange = 127,
+        FileFull = 128,
+        InconsistentConfiguration = 129,
+        InconsistentObjectType = 130,
+        InternalError = 131,
+        NotConfigured = 132,
+        OutOfMemory = 133,
+        ValueTooLong = 134,
+        AbortInsufficientSecurity = 135,
+        AbortSecurityError = 136,
+        DuplicateEntry = 137,
+        InvalidValueInThisState = 138,
+        InvalidOperationInThisState = 139,
+        ListItemNotNumbered = 140,
+        ListItemNotTimestamped = 141,
+    }
+    proprietary 256..=65535
+}
+
+enumeration! {
+    /// The reason in a Reject PDU
+    pub enum RejectReason: u8 {
+        Other = 0,
+        BufferOverflow = 1,
+        InconsistentParameters = 2,
+        InvalidParameterDataType = 3,
+        InvalidTag = 4,
+        MissingRequiredParameter = 5,
+        ParameterOutOfRange = 6,
+        TooManyArguments = 7,
+        UndefinedEnumeration = 8,
+        UnrecognizedService = 9,
+    }
+    proprietary 64..=255
+}
+
+enumeration! {
+    /// The reason in an Abort PDU
+    pub enum AbortReason: u8 {
+        Other = 0,
+        BufferOverflow = 1,
+        InvalidApduInThisState = 2,
+        PreemptedByHigherPriorityTask = 3,
+        SegmentationNotSupported = 4,
+        SecurityError = 5,
+        InsufficientSecurity = 6,
+        WindowSizeOutOfRange = 7,
+        ApplicationExceededReplyTime = 8,
+        OutOfResources = 9,
+        TsmTimeout = 10,
+        ApduTooLong = 11,
+    }
+    proprietary 64..=255
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wire_values() {
+        assert_eq!(Ok(ObjectType::Device), ObjectType::try_from(8));
+        assert_eq!(8u16, ObjectType::Device.into());
+        assert_eq!(PropertyIdentifier::PresentValue, PropertyIdentifier::from(85));
+        assert_eq!(85u32, PropertyIdentifier::PresentValue.into());
+        assert_eq!(EngineeringUnits::DegreesCelsius, EngineeringUnits::from(62));
+        assert_eq!(Segmentation::NoSegmentation, Segmentation::from(3));
+        assert_eq!(EventState::HighLimit, EventState::from(3));
+        assert_eq!(BinaryPv::Active, BinaryPv::from(1));
+        assert_eq!(DeviceStatus::NonOperational, DeviceStatus::from(4));
+        assert_eq!(ErrorCode::UnknownProperty, ErrorCode::from(32));
+        assert_eq!(4u8, AbortReason::SegmentationNotSupported.into());
+    }
+
+    #[test]
+    fn unknown_values_are_kept() {
+        // Matched rather than compared, as values compare equal whichever variant holds them
+        assert!(matches!(ObjectType::try_from(127), Ok(ObjectType::Reserved(127))));
+        assert!(matches!(ObjectType::try_from(128), Ok(ObjectType::Proprietary(128))));
+        assert!(matches!(ObjectType::try_from(1023), Ok(ObjectType::Proprietary(1023))));
+        assert_eq!(1023u16, ObjectType::Proprietary(1023).into());
+        assert!(matches!(PropertyIdentifier::from(18), PropertyIdentifier::Reserved(18)));
+        assert!(matches!(PropertyIdentifier::from(512), PropertyIdentifier::Proprietary(512)));
+        assert!(matches!(PropertyIdentifier::from(4194304), PropertyIdentifier::Reserved(4194304)));
+        assert!(matches!(EngineeringUnits::from(256), EngineeringUnits::Proprietary(256)));
+        assert!(matches!(EngineeringUnits::from(47808), EngineeringUnits::Reserved(47808)));
+        assert!(matches!(EngineeringUnits::from(50000), EngineeringUnits::Proprietary(50000)));
+        assert!(matches!(Segmentation::from(4), Segmentation::Reserved(4)));
+        assert!(matches!(EventState::from(64), EventState::Proprietary(64)));
+        assert!(matches!(Polarity::from(2), Polarity::Reserved(2)));
+        assert!(matches!(RejectReason::from(64), RejectReason::Proprietary(64)));
+        assert!(matches!(RejectReason::from(63), RejectReason::Reserved(63)));
+    }
+
+    #[test]
+    fn object_types_are_10_bits() {
+        assert_eq!(Err(TooLarge), ObjectType::try_from(1024));
+        assert_eq!(Err(TooLarge), ObjectType::try_from(u16::MAX));
+        assert_eq!(None, ObjectType::from_primitive(&PrimitiveValue::Enumerated(1024)));
+    }
+
+    #[test]
+    fn every_value_round_trips() {
+        for value in 0..=1023 {
+            assert_eq!(Ok(value), ObjectType::try_from(value).map(u16::from));
+        }
+        for value in 0..=1000 {
+            assert_eq!(value, u32::from(PropertyIdentifier::from(value)));
+            assert_eq!(value, u32::from(EngineeringUnits::from(value)));
+            assert_eq!(value, u32::from(ErrorCode::from(value)));
+        }
+        for value in 0..=255 {
+            assert_eq!(value, u8::from(AbortReason::from(value)));
+        }
+    }
+
+    #[test]
+    fn enumerated_values() {
+        assert_eq!(PrimitiveValue::Enumerated(8), ObjectType::Device.to_primitive());
+        assert_eq!(Some(ObjectType::Device), ObjectType::from_primitive(&PrimitiveValue::Enumerated(8)));
+        assert_eq!(None, ObjectType::from_primitive(&PrimitiveValue::Enumerated(65536)));
+        assert_eq!(None, ObjectType::from_primitive(&PrimitiveValue::Unsigned(8)));
+    }
+
+    #[test]
+    fn ordered_by_wire_value() {
+        assert!(ObjectType::AnalogInput < ObjectType::Device);
+        assert!(ObjectType::Reserved(100) < ObjectType::Proprietary(128));
+        assert!(ObjectType::Reserved(7) < ObjectType::Device);
+    }
+
+    #[test]
+    fn compared_and_hashed_by_wire_value() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::BTreeSet;
+
+        fn hash(object_type: ObjectType) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            object_type.hash(&mut hasher);
+            hasher.finish()
+        }
+        assert_eq!(ObjectType::Device, ObjectType::Proprietary(8));
+        assert_eq!(ObjectType::Reserved(8), ObjectType::Device);
+        assert_eq!(hash(ObjectType::Device), hash(ObjectType::Reserved(8)));
+        assert_ne!(ObjectType::Reserved(9), ObjectType::Device);
+        let types: BTreeSet<ObjectType> = [ObjectType::Device, ObjectType::Reserved(8), ObjectType::Proprietary(8)].iter().cloned().collect();
+        assert_eq!(1, types.len());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("analog-input", ObjectType::AnalogInput.to_string());
+        assert_eq!("multi-state-value", ObjectType::MultiStateValue.to_string());
+        assert_eq!("present-value", PropertyIdentifier::PresentValue.to_string());
+        assert_eq!("max-apdu-length-accepted", PropertyIdentifier::MaxApduLengthAccepted.to_string());
+        assert_eq!("bacnet-ipv6-mode", PropertyIdentifier::BacnetIpv6Mode.to_string());
+        assert_eq!("degrees-celsius", EngineeringUnits::DegreesCelsius.to_string());
+        assert_eq!("currency10", EngineeringUnits::Currency10.to_string());
+        assert_eq!("no-segmentation", Segmentation::NoSegmentation.to_string());
+        assert_eq!("512", PropertyIdentifier::Proprietary(512).to_string());
+    }
+}
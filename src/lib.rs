@@ -1,6 +1,36 @@
+//! With the `std` feature off, which is on by default, only the encoding core is built: the
+//! headers and primitive values of `ast`, the enumerations, and the `decode` and `encode` codecs,
+//! which work on slices and don't allocate.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate bacnet_derive;
+
 pub mod ast;
+pub mod enumerations;
+#[cfg(feature = "std")]
 pub mod parse;
+pub mod decode;
+pub mod encode;
+#[cfg(feature = "std")]
 pub mod serialise;
+#[cfg(feature = "std")]
 pub mod service;
+#[cfg(feature = "std")]
 pub mod object;
-
+#[cfg(feature = "std")]
+pub mod datalink;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod cov;
+#[cfg(feature = "std")]
+pub mod client;
+#[cfg(feature = "std")]
+pub mod segmentation;
+#[cfg(feature = "std")]
+pub mod server;
@@ -0,0 +1,459 @@
+//! The analog objects - Analog Input (Clause 12.2), Analog Output (Clause 12.3) and Analog Value
+//! (Clause 12.4). An input's present value is set by the application, outputs and values are
+//! commanded through their priority arrays.
+
+use super::{BacnetObject, ObjectId, PropertyError, check_writable, scalar, single_value, status_flags, write_common};
+use super::priority::{PriorityArray, read_priority_array};
+use enumerations::{EngineeringUnits, EventState, ObjectType, PropertyIdentifier};
+use service::writeproperty::MIN_PRIORITY;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use std::time::Duration;
+
+/// The properties the standard requires of every analog object
+const REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectIdentifier,
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::ObjectType,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::StatusFlags,
+    PropertyIdentifier::EventState,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Units,
+];
+
+/// The properties the standard requires of commandable analog objects
+const COMMANDABLE_REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectIdentifier,
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::ObjectType,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::StatusFlags,
+    PropertyIdentifier::EventState,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Units,
+    PropertyIdentifier::PriorityArray,
+    PropertyIdentifier::RelinquishDefault,
+];
+
+/// The properties of an analog input which can be written, Present_Value only while the input is
+/// out of service
+const WRITABLE_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::CovIncrement,
+];
+
+const COMMANDABLE_WRITABLE_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::CovIncrement,
+    PropertyIdentifier::RelinquishDefault,
+];
+
+const OPTIONAL_PROPERTIES: &[PropertyIdentifier] = &[PropertyIdentifier::CovIncrement];
+
+const COV_PROPERTIES: &[PropertyIdentifier] = &[PropertyIdentifier::PresentValue, PropertyIdentifier::StatusFlags];
+
+pub struct AnalogInput {
+    pub instance: u32,
+    pub object_name: String,
+    /// Set by the application, WriteProperty can only change it while the input is out of service
+    pub present_value: f32,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub units: EngineeringUnits,
+    /// How far the present value has to move before COV subscribers are notified
+    pub cov_increment: f32,
+}
+
+impl AnalogInput {
+    /// An input in service with a present value of 0, which notifies COV subscribers of any change
+    pub fn new(instance: u32, object_name: &str, units: EngineeringUnits) -> AnalogInput {
+        AnalogInput {
+            instance,
+            object_name: object_name.to_string(),
+            present_value: 0.0,
+            event_state: EventState::Normal,
+            out_of_service: false,
+            units,
+            cov_increment: 0.0,
+        }
+    }
+}
+
+impl BacnetObject for AnalogInput {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::AnalogInput, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::AnalogInput) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Real(self.present_value),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::Units => PrimitiveValue::Enumerated(self.units.into()),
+            PropertyIdentifier::CovIncrement => PrimitiveValue::Real(self.cov_increment),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, _priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, WRITABLE_PROPERTIES)?;
+        if property == PropertyIdentifier::PresentValue && !self.out_of_service {
+            return Err(PropertyError::WriteAccessDenied)
+        }
+        if array_index.is_some() {
+            return Err(PropertyError::PropertyIsNotAnArray)
+        }
+        match (property, single_value(value)?) {
+            (PropertyIdentifier::PresentValue, &PrimitiveValue::Real(present_value)) =>
+                self.present_value = present_value,
+            (property, value) =>
+                write_analog(property, value, &mut self.object_name, &mut self.out_of_service, &mut self.cov_increment)?,
+        }
+        Ok(())
+    }
+}
+
+pub struct AnalogOutput {
+    pub instance: u32,
+    pub object_name: String,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub units: EngineeringUnits,
+    /// How far the present value has to move before COV subscribers are notified
+    pub cov_increment: f32,
+    pub priority_array: PriorityArray<f32>,
+}
+
+impl AnalogOutput {
+    /// An output in service without any commands, which notifies COV subscribers of any change
+    pub fn new(instance: u32, object_name: &str, units: EngineeringUnits, relinquish_default: f32) -> AnalogOutput {
+        AnalogOutput {
+            instance,
+            object_name: object_name.to_string(),
+            event_state: EventState::Normal,
+            out_of_service: false,
+            units,
+            cov_increment: 0.0,
+            priority_array: PriorityArray::new(relinquish_default),
+        }
+    }
+
+    /// The command at the highest priority, or the relinquish default if there are none
+    pub fn present_value(&self) -> f32 {
+        self.priority_array.present_value()
+    }
+}
+
+impl BacnetObject for AnalogOutput {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::AnalogOutput, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        COMMANDABLE_REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::AnalogOutput) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Real(self.present_value()),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::Units => PrimitiveValue::Enumerated(self.units.into()),
+            PropertyIdentifier::CovIncrement => PrimitiveValue::Real(self.cov_increment),
+            PropertyIdentifier::RelinquishDefault => PrimitiveValue::Real(self.priority_array.relinquish_default()),
+            PropertyIdentifier::PriorityArray => return read_priority_array(&self.priority_array, array_index),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, COMMANDABLE_WRITABLE_PROPERTIES)?;
+        write_commandable(property, array_index, value, priority, &mut CommandableProperties {
+            object_name: &mut self.object_name,
+            out_of_service: &mut self.out_of_service,
+            cov_increment: &mut self.cov_increment,
+            priority_array: &mut self.priority_array,
+        })
+    }
+}
+
+pub struct AnalogValue {
+    pub instance: u32,
+    pub object_name: String,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub units: EngineeringUnits,
+    /// How far the present value has to move before COV subscribers are notified
+    pub cov_increment: f32,
+    pub priority_array: PriorityArray<f32>,
+}
+
+impl AnalogValue {
+    /// A value in service without any commands, which notifies COV subscribers of any change
+    pub fn new(instance: u32, object_name: &str, units: EngineeringUnits, relinquish_default: f32) -> AnalogValue {
+        AnalogValue {
+            instance,
+            object_name: object_name.to_string(),
+            event_state: EventState::Normal,
+            out_of_service: false,
+            units,
+            cov_increment: 0.0,
+            priority_array: PriorityArray::new(relinquish_default),
+        }
+    }
+
+    /// The command at the highest priority, or the relinquish default if there are none
+    pub fn present_value(&self) -> f32 {
+        self.priority_array.present_value()
+    }
+}
+
+impl BacnetObject for AnalogValue {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::AnalogValue, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        COMMANDABLE_REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::AnalogValue) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Real(self.present_value()),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::Units => PrimitiveValue::Enumerated(self.units.into()),
+            PropertyIdentifier::CovIncrement => PrimitiveValue::Real(self.cov_increment),
+            PropertyIdentifier::RelinquishDefault => PrimitiveValue::Real(self.priority_array.relinquish_default()),
+            PropertyIdentifier::PriorityArray => return read_priority_array(&self.priority_array, array_index),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, COMMANDABLE_WRITABLE_PROPERTIES)?;
+        write_commandable(property, array_index, value, priority, &mut CommandableProperties {
+            object_name: &mut self.object_name,
+            out_of_service: &mut self.out_of_service,
+            cov_increment: &mut self.cov_increment,
+            priority_array: &mut self.priority_array,
+        })
+    }
+}
+
+/// The writable properties of an analog output or value
+struct CommandableProperties<'a> {
+    object_name: &'a mut String,
+    out_of_service: &'a mut bool,
+    cov_increment: &'a mut f32,
+    priority_array: &'a mut PriorityArray<f32>,
+}
+
+/// Writes a property of an analog output or value. Present_Value is written at the priority, the
+/// lowest if none is given, and writing NULL relinquishes the command at that priority. Analog
+/// values aren't held for minimum times, so the time commands are written at doesn't matter.
+fn write_commandable(property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>, object: &mut CommandableProperties) -> Result<(), PropertyError> {
+    if array_index.is_some() {
+        return Err(PropertyError::PropertyIsNotAnArray)
+    }
+    match (property, single_value(value)?) {
+        (PropertyIdentifier::PresentValue, &PrimitiveValue::Null) => {
+            object.priority_array.command(priority.unwrap_or(MIN_PRIORITY), None, Duration::ZERO)?;
+        },
+        (PropertyIdentifier::PresentValue, &PrimitiveValue::Real(value)) => {
+            object.priority_array.command(priority.unwrap_or(MIN_PRIORITY), Some(value), Duration::ZERO)?;
+        },
+        (PropertyIdentifier::RelinquishDefault, &PrimitiveValue::Real(value)) => {
+            object.priority_array.set_relinquish_default(value, Duration::ZERO);
+        },
+        (property, value) =>
+            write_analog(property, value, object.object_name, object.out_of_service, object.cov_increment)?,
+    }
+    Ok(())
+}
+
+/// Writes one of the properties all analog objects can write
+fn write_analog(property: PropertyIdentifier, value: &PrimitiveValue, object_name: &mut String, out_of_service: &mut bool, cov_increment: &mut f32) -> Result<(), PropertyError> {
+    match (property, value) {
+        (PropertyIdentifier::CovIncrement, &PrimitiveValue::Real(increment)) => {
+            if increment.is_nan() || increment < 0.0 {
+                return Err(PropertyError::ValueOutOfRange)
+            }
+            *cov_increment = increment;
+        },
+        _ => write_common(property, value, object_name, out_of_service)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::{BacnetDB, test_db};
+
+    fn db() -> BacnetDB {
+        let mut db = test_db();
+        db.add(AnalogInput::new(1, "Zone temperature", EngineeringUnits::DegreesCelsius)).unwrap();
+        db.add(AnalogOutput::new(1, "Valve", EngineeringUnits::Percent, 0.0)).unwrap();
+        db.add(AnalogValue::new(1, "Setpoint", EngineeringUnits::DegreesCelsius, 21.0)).unwrap();
+        db
+    }
+
+    fn real(value: f32) -> ValueSequence {
+        vec!(ApplicationValue(PrimitiveValue::Real(value)))
+    }
+
+    fn null() -> ValueSequence {
+        vec!(ApplicationValue(PrimitiveValue::Null))
+    }
+
+    const INPUT: ObjectId = ObjectId(ObjectType::AnalogInput, 1);
+    const OUTPUT: ObjectId = ObjectId(ObjectType::AnalogOutput, 1);
+    const VALUE: ObjectId = ObjectId(ObjectType::AnalogValue, 1);
+
+    #[test]
+    fn read_input() {
+        let mut db = db();
+        db.get_mut::<AnalogInput>(&INPUT).unwrap().present_value = 20.5;
+        assert_eq!(Ok(PrimitiveValue::Real(20.5)), db.get_value(&INPUT, PropertyIdentifier::PresentValue));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(0)), db.get_value(&INPUT, PropertyIdentifier::ObjectType));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(62)), db.get_value(&INPUT, PropertyIdentifier::Units));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(0)), db.get_value(&INPUT, PropertyIdentifier::EventState));
+        assert_eq!(Ok(PrimitiveValue::Boolean(false)), db.get_value(&INPUT, PropertyIdentifier::OutOfService));
+        assert_eq!(Err(PropertyError::UnknownProperty), db.get_value(&INPUT, PropertyIdentifier::PriorityArray));
+        assert_eq!(Err(PropertyError::PropertyIsNotAnArray), db.read_property(&INPUT, PropertyIdentifier::PresentValue, Some(1)));
+    }
+
+    #[test]
+    fn status_flags_follow_the_object() {
+        let mut db = db();
+        assert_eq!(Ok(status_flags(EventState::Normal, false)), db.get_value(&INPUT, PropertyIdentifier::StatusFlags));
+        {
+            let input = db.get_mut::<AnalogInput>(&INPUT).unwrap();
+            input.event_state = EventState::Fault;
+            input.out_of_service = true;
+        }
+        match db.get_value(&INPUT, PropertyIdentifier::StatusFlags) {
+            Ok(PrimitiveValue::BitString(flags)) => {
+                assert_eq!(4, flags.len());
+                assert_eq!((true, true, false, true), (flags.get(0), flags.get(1), flags.get(2), flags.get(3)));
+            },
+            flags => panic!("Unexpected status flags {:?}", flags),
+        }
+    }
+
+    #[test]
+    fn input_is_only_written_out_of_service() {
+        let mut db = db();
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &real(30.0), None));
+        db.set_value(&INPUT, PropertyIdentifier::OutOfService, PrimitiveValue::Boolean(true)).unwrap();
+        db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &real(30.0), None).unwrap();
+        assert_eq!(30.0, db.get::<AnalogInput>(&INPUT).unwrap().present_value);
+        assert_eq!(Err(PropertyError::InvalidDataType), db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &null(), None));
+    }
+
+    #[test]
+    fn commands_are_prioritised() {
+        let mut db = db();
+        assert_eq!(Ok(PrimitiveValue::Real(0.0)), db.get_value(&OUTPUT, PropertyIdentifier::PresentValue));
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &real(40.0), None).unwrap();
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &real(80.0), Some(8)).unwrap();
+        assert_eq!(Ok(PrimitiveValue::Real(80.0)), db.get_value(&OUTPUT, PropertyIdentifier::PresentValue));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Real(40.0)))), db.read_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(16)));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Unsigned(16)))), db.read_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(0)));
+
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &null(), Some(8)).unwrap();
+        assert_eq!(Ok(PrimitiveValue::Real(40.0)), db.get_value(&OUTPUT, PropertyIdentifier::PresentValue));
+        assert_eq!(Ok(null()), db.read_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(8)));
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &null(), None).unwrap();
+        assert_eq!(Ok(PrimitiveValue::Real(0.0)), db.get_value(&OUTPUT, PropertyIdentifier::PresentValue));
+    }
+
+    #[test]
+    fn relinquish_default() {
+        let mut db = db();
+        assert_eq!(Ok(PrimitiveValue::Real(21.0)), db.get_value(&VALUE, PropertyIdentifier::PresentValue));
+        db.set_value(&VALUE, PropertyIdentifier::RelinquishDefault, PrimitiveValue::Real(22.5)).unwrap();
+        assert_eq!(22.5, db.get::<AnalogValue>(&VALUE).unwrap().present_value());
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut db = db();
+        assert_eq!(Err(PropertyError::ValueOutOfRange), db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &real(1.0), Some(17)));
+        assert_eq!(Err(PropertyError::InvalidDataType), db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &vec!(ApplicationValue(PrimitiveValue::Unsigned(1))), None));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.write_property(&VALUE, PropertyIdentifier::PriorityArray, Some(1), &real(1.0), None));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.write_property(&VALUE, PropertyIdentifier::Units, None, &vec!(ApplicationValue(PrimitiveValue::Enumerated(62))), None));
+        assert_eq!(Err(PropertyError::UnknownProperty), db.write_property(&VALUE, PropertyIdentifier::VendorIdentifier, None, &real(1.0), None));
+        assert_eq!(Err(PropertyError::ValueOutOfRange), db.set_value(&VALUE, PropertyIdentifier::CovIncrement, PrimitiveValue::Real(-1.0)));
+    }
+
+    #[test]
+    fn all_properties() {
+        let db = db();
+        assert_eq!(11, db.expand_properties(&OUTPUT, PropertyIdentifier::All).unwrap().len());
+        assert_eq!(9, db.expand_properties(&INPUT, PropertyIdentifier::All).unwrap().len());
+        assert_eq!(Ok(vec!(PropertyIdentifier::PresentValue, PropertyIdentifier::StatusFlags)), db.cov_properties(&VALUE));
+    }
+}
@@ -0,0 +1,794 @@
+//! The binary objects - Binary Input (Clause 12.6), Binary Output (Clause 12.7) and Binary Value
+//! (Clause 12.8). Each one counts its changes of state and the time it spends active, outputs also
+//! hold their present value for a minimum time after it changes.
+
+use super::{BacnetObject, ObjectId, PropertyError, check_writable, scalar, single_value, status_flags, write_common};
+use super::priority::{PriorityArray, read_priority_array};
+use clock::Clock;
+use enumerations::{BinaryPv, EventState, ObjectType, Polarity, PropertyIdentifier};
+use service::writeproperty::MIN_PRIORITY;
+use ast::{Date, DayOfMonth, Month, Time};
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+use std::rc::Rc;
+use std::time::Duration;
+
+const INPUT_REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectIdentifier,
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::ObjectType,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::StatusFlags,
+    PropertyIdentifier::EventState,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Polarity,
+];
+
+const OUTPUT_REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectIdentifier,
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::ObjectType,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::StatusFlags,
+    PropertyIdentifier::EventState,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Polarity,
+    PropertyIdentifier::PriorityArray,
+    PropertyIdentifier::RelinquishDefault,
+];
+
+const VALUE_REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectIdentifier,
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::ObjectType,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::StatusFlags,
+    PropertyIdentifier::EventState,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::PriorityArray,
+    PropertyIdentifier::RelinquishDefault,
+];
+
+const OPTIONAL_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::InactiveText,
+    PropertyIdentifier::ActiveText,
+    PropertyIdentifier::ChangeOfStateTime,
+    PropertyIdentifier::ChangeOfStateCount,
+    PropertyIdentifier::TimeOfStateCountReset,
+    PropertyIdentifier::ElapsedActiveTime,
+    PropertyIdentifier::TimeOfActiveTimeReset,
+];
+
+const OUTPUT_OPTIONAL_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::InactiveText,
+    PropertyIdentifier::ActiveText,
+    PropertyIdentifier::ChangeOfStateTime,
+    PropertyIdentifier::ChangeOfStateCount,
+    PropertyIdentifier::TimeOfStateCountReset,
+    PropertyIdentifier::ElapsedActiveTime,
+    PropertyIdentifier::TimeOfActiveTimeReset,
+    PropertyIdentifier::MinimumOffTime,
+    PropertyIdentifier::MinimumOnTime,
+];
+
+/// The properties of a binary input which can be written, Present_Value only while the input is
+/// out of service. Change_Of_State_Count and Elapsed_Active_Time can only be reset to 0.
+const INPUT_WRITABLE_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Polarity,
+    PropertyIdentifier::InactiveText,
+    PropertyIdentifier::ActiveText,
+    PropertyIdentifier::ChangeOfStateCount,
+    PropertyIdentifier::ElapsedActiveTime,
+];
+
+const OUTPUT_WRITABLE_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::Polarity,
+    PropertyIdentifier::InactiveText,
+    PropertyIdentifier::ActiveText,
+    PropertyIdentifier::ChangeOfStateCount,
+    PropertyIdentifier::ElapsedActiveTime,
+    PropertyIdentifier::RelinquishDefault,
+    PropertyIdentifier::MinimumOffTime,
+    PropertyIdentifier::MinimumOnTime,
+];
+
+const VALUE_WRITABLE_PROPERTIES: &[PropertyIdentifier] = &[
+    PropertyIdentifier::ObjectName,
+    PropertyIdentifier::PresentValue,
+    PropertyIdentifier::OutOfService,
+    PropertyIdentifier::InactiveText,
+    PropertyIdentifier::ActiveText,
+    PropertyIdentifier::ChangeOfStateCount,
+    PropertyIdentifier::ElapsedActiveTime,
+    PropertyIdentifier::RelinquishDefault,
+];
+
+const COV_PROPERTIES: &[PropertyIdentifier] = &[PropertyIdentifier::PresentValue, PropertyIdentifier::StatusFlags];
+
+pub struct BinaryInput {
+    pub instance: u32,
+    pub object_name: String,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub polarity: Polarity,
+    pub inactive_text: String,
+    pub active_text: String,
+    present_value: BinaryPv,
+    history: History,
+    clock: Option<Rc<dyn Clock>>,
+}
+
+impl BinaryInput {
+    /// An inactive input in service with normal polarity
+    pub fn new(instance: u32, object_name: &str) -> BinaryInput {
+        BinaryInput {
+            instance,
+            object_name: object_name.to_string(),
+            event_state: EventState::Normal,
+            out_of_service: false,
+            polarity: Polarity::Normal,
+            inactive_text: "Inactive".to_string(),
+            active_text: "Active".to_string(),
+            present_value: BinaryPv::Inactive,
+            history: History::new(BinaryPv::Inactive, Duration::ZERO),
+            clock: None,
+        }
+    }
+
+    pub fn present_value(&self) -> BinaryPv {
+        self.present_value
+    }
+
+    /// Sets the present value, counting it as a change of state if it is different
+    pub fn set_present_value(&mut self, present_value: BinaryPv) {
+        if present_value != self.present_value {
+            self.present_value = present_value;
+            self.history.changed(present_value, now(&self.clock));
+        }
+    }
+}
+
+impl BacnetObject for BinaryInput {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::BinaryInput, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        INPUT_REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        if let Some(value) = self.history.read(property, array_index, &self.clock) {
+            return value
+        }
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::BinaryInput) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Enumerated(self.present_value.into()),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::Polarity => PrimitiveValue::Enumerated(self.polarity.into()),
+            PropertyIdentifier::InactiveText => PrimitiveValue::CharacterString(self.inactive_text.clone()),
+            PropertyIdentifier::ActiveText => PrimitiveValue::CharacterString(self.active_text.clone()),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, _priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, INPUT_WRITABLE_PROPERTIES)?;
+        if property == PropertyIdentifier::PresentValue && !self.out_of_service {
+            return Err(PropertyError::WriteAccessDenied)
+        }
+        if array_index.is_some() {
+            return Err(PropertyError::PropertyIsNotAnArray)
+        }
+        let value = single_value(value)?;
+        match property {
+            PropertyIdentifier::PresentValue => self.set_present_value(binary_pv(value)?),
+            PropertyIdentifier::Polarity => self.polarity = polarity(value)?,
+            PropertyIdentifier::ChangeOfStateCount |
+            PropertyIdentifier::ElapsedActiveTime => self.history.reset(property, value, self.present_value, now(&self.clock))?,
+            _ => write_binary(property, value, &mut self.object_name, &mut self.out_of_service, &mut self.inactive_text, &mut self.active_text)?,
+        }
+        Ok(())
+    }
+
+    fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.history = History::new(self.present_value, clock.now());
+        self.clock = Some(clock);
+    }
+}
+
+pub struct BinaryOutput {
+    pub instance: u32,
+    pub object_name: String,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub polarity: Polarity,
+    pub inactive_text: String,
+    pub active_text: String,
+    commands: PriorityArray<BinaryPv>,
+    history: History,
+    clock: Option<Rc<dyn Clock>>,
+}
+
+impl BinaryOutput {
+    /// An output in service with normal polarity, no commands and no minimum on or off times
+    pub fn new(instance: u32, object_name: &str, relinquish_default: BinaryPv) -> BinaryOutput {
+        BinaryOutput {
+            instance,
+            object_name: object_name.to_string(),
+            event_state: EventState::Normal,
+            out_of_service: false,
+            polarity: Polarity::Normal,
+            inactive_text: "Inactive".to_string(),
+            active_text: "Active".to_string(),
+            commands: PriorityArray::new(relinquish_default),
+            history: History::new(relinquish_default, Duration::ZERO),
+            clock: None,
+        }
+    }
+
+    pub fn present_value(&self) -> BinaryPv {
+        self.settled().0.present_value()
+    }
+
+    /// The commands at each priority, priority 1 first, with a value being held for its minimum
+    /// on or off time at priority 6
+    pub fn priority_array(&self) -> [Option<BinaryPv>; MIN_PRIORITY as usize] {
+        self.settled().0.commands()
+    }
+
+    /// Writes a command, or relinquishes the command at the priority if `value` is `None`.
+    /// Priority 6 is kept for the minimum on and off times if the output has either.
+    pub fn command(&mut self, priority: u8, value: Option<BinaryPv>) -> Result<(), PropertyError> {
+        let changes = self.commands.command(priority, value, now(&self.clock))?;
+        self.history.record(&changes);
+        Ok(())
+    }
+
+    pub fn set_relinquish_default(&mut self, relinquish_default: BinaryPv) {
+        let changes = self.commands.set_relinquish_default(relinquish_default, now(&self.clock));
+        self.history.record(&changes);
+    }
+
+    /// In seconds, how long the present value is held after it becomes active
+    pub fn minimum_on_time(&self) -> u32 {
+        self.commands.minimum_on_time().as_secs() as u32
+    }
+
+    /// In seconds, how long the present value is held after it becomes inactive
+    pub fn minimum_off_time(&self) -> u32 {
+        self.commands.minimum_off_time().as_secs() as u32
+    }
+
+    pub fn set_minimum_on_time(&mut self, seconds: u32) {
+        self.commands.set_minimum_on_time(Duration::from_secs(seconds as u64));
+    }
+
+    pub fn set_minimum_off_time(&mut self, seconds: u32) {
+        self.commands.set_minimum_off_time(Duration::from_secs(seconds as u64));
+    }
+
+    /// The commands and history once any value whose minimum time has run out is released
+    fn settled(&self) -> (PriorityArray<BinaryPv>, History) {
+        let (mut commands, mut history) = (self.commands.clone(), self.history);
+        history.record(&commands.release(now(&self.clock)));
+        (commands, history)
+    }
+}
+
+impl BacnetObject for BinaryOutput {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::BinaryOutput, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        OUTPUT_REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OUTPUT_OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let (commands, history) = self.settled();
+        if let Some(value) = history.read(property, array_index, &self.clock) {
+            return value
+        }
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::BinaryOutput) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Enumerated(commands.present_value().into()),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::Polarity => PrimitiveValue::Enumerated(self.polarity.into()),
+            PropertyIdentifier::InactiveText => PrimitiveValue::CharacterString(self.inactive_text.clone()),
+            PropertyIdentifier::ActiveText => PrimitiveValue::CharacterString(self.active_text.clone()),
+            PropertyIdentifier::MinimumOffTime => PrimitiveValue::Unsigned(self.minimum_off_time()),
+            PropertyIdentifier::MinimumOnTime => PrimitiveValue::Unsigned(self.minimum_on_time()),
+            PropertyIdentifier::RelinquishDefault => PrimitiveValue::Enumerated(commands.relinquish_default().into()),
+            PropertyIdentifier::PriorityArray => return read_priority_array(&commands, array_index),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, OUTPUT_WRITABLE_PROPERTIES)?;
+        if array_index.is_some() {
+            return Err(PropertyError::PropertyIsNotAnArray)
+        }
+        let value = single_value(value)?;
+        match (property, value) {
+            (PropertyIdentifier::PresentValue, &PrimitiveValue::Null) =>
+                self.command(priority.unwrap_or(MIN_PRIORITY), None)?,
+            (PropertyIdentifier::PresentValue, value) =>
+                self.command(priority.unwrap_or(MIN_PRIORITY), Some(binary_pv(value)?))?,
+            (PropertyIdentifier::RelinquishDefault, value) => self.set_relinquish_default(binary_pv(value)?),
+            (PropertyIdentifier::Polarity, value) => self.polarity = polarity(value)?,
+            (PropertyIdentifier::MinimumOffTime, &PrimitiveValue::Unsigned(time)) => self.set_minimum_off_time(time),
+            (PropertyIdentifier::MinimumOnTime, &PrimitiveValue::Unsigned(time)) => self.set_minimum_on_time(time),
+            (PropertyIdentifier::MinimumOffTime, _) |
+            (PropertyIdentifier::MinimumOnTime, _) => return Err(PropertyError::InvalidDataType),
+            (PropertyIdentifier::ChangeOfStateCount, value) |
+            (PropertyIdentifier::ElapsedActiveTime, value) => {
+                let now = now(&self.clock);
+                self.history.record(&self.commands.release(now));
+                self.history.reset(property, value, self.commands.present_value(), now)?
+            },
+            (property, value) =>
+                write_binary(property, value, &mut self.object_name, &mut self.out_of_service, &mut self.inactive_text, &mut self.active_text)?,
+        }
+        Ok(())
+    }
+
+    fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.history = History::new(self.commands.present_value(), clock.now());
+        self.clock = Some(clock);
+    }
+}
+
+pub struct BinaryValue {
+    pub instance: u32,
+    pub object_name: String,
+    pub event_state: EventState,
+    pub out_of_service: bool,
+    pub inactive_text: String,
+    pub active_text: String,
+    commands: PriorityArray<BinaryPv>,
+    history: History,
+    clock: Option<Rc<dyn Clock>>,
+}
+
+impl BinaryValue {
+    /// A value in service without any commands
+    pub fn new(instance: u32, object_name: &str, relinquish_default: BinaryPv) -> BinaryValue {
+        BinaryValue {
+            instance,
+            object_name: object_name.to_string(),
+            event_state: EventState::Normal,
+            out_of_service: false,
+            inactive_text: "Inactive".to_string(),
+            active_text: "Active".to_string(),
+            commands: PriorityArray::new(relinquish_default),
+            history: History::new(relinquish_default, Duration::ZERO),
+            clock: None,
+        }
+    }
+
+    pub fn present_value(&self) -> BinaryPv {
+        self.commands.present_value()
+    }
+
+    /// The commands at each priority, priority 1 first
+    pub fn priority_array(&self) -> [Option<BinaryPv>; MIN_PRIORITY as usize] {
+        self.commands.commands()
+    }
+
+    /// Writes a command, or relinquishes the command at the priority if `value` is `None`.
+    /// Binary values don't have minimum on and off times, so they can be commanded at priority 6.
+    pub fn command(&mut self, priority: u8, value: Option<BinaryPv>) -> Result<(), PropertyError> {
+        let changes = self.commands.command(priority, value, now(&self.clock))?;
+        self.history.record(&changes);
+        Ok(())
+    }
+
+    pub fn set_relinquish_default(&mut self, relinquish_default: BinaryPv) {
+        let changes = self.commands.set_relinquish_default(relinquish_default, now(&self.clock));
+        self.history.record(&changes);
+    }
+}
+
+impl BacnetObject for BinaryValue {
+    fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::BinaryValue, self.instance)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        VALUE_REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        OPTIONAL_PROPERTIES
+    }
+
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        COV_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        if let Some(value) = self.history.read(property, array_index, &self.clock) {
+            return value
+        }
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::BinaryValue) as u32),
+            PropertyIdentifier::PresentValue => PrimitiveValue::Enumerated(self.commands.present_value().into()),
+            PropertyIdentifier::StatusFlags => status_flags(self.event_state, self.out_of_service),
+            PropertyIdentifier::EventState => PrimitiveValue::Enumerated(self.event_state.into()),
+            PropertyIdentifier::OutOfService => PrimitiveValue::Boolean(self.out_of_service),
+            PropertyIdentifier::InactiveText => PrimitiveValue::CharacterString(self.inactive_text.clone()),
+            PropertyIdentifier::ActiveText => PrimitiveValue::CharacterString(self.active_text.clone()),
+            PropertyIdentifier::RelinquishDefault => PrimitiveValue::Enumerated(self.commands.relinquish_default().into()),
+            PropertyIdentifier::PriorityArray => return read_priority_array(&self.commands, array_index),
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError> {
+        check_writable(self, property, VALUE_WRITABLE_PROPERTIES)?;
+        if array_index.is_some() {
+            return Err(PropertyError::PropertyIsNotAnArray)
+        }
+        let value = single_value(value)?;
+        match (property, value) {
+            (PropertyIdentifier::PresentValue, &PrimitiveValue::Null) =>
+                self.command(priority.unwrap_or(MIN_PRIORITY), None)?,
+            (PropertyIdentifier::PresentValue, value) =>
+                self.command(priority.unwrap_or(MIN_PRIORITY), Some(binary_pv(value)?))?,
+            (PropertyIdentifier::RelinquishDefault, value) => self.set_relinquish_default(binary_pv(value)?),
+            (PropertyIdentifier::ChangeOfStateCount, value) |
+            (PropertyIdentifier::ElapsedActiveTime, value) =>
+                self.history.reset(property, value, self.commands.present_value(), now(&self.clock))?,
+            (property, value) =>
+                write_binary(property, value, &mut self.object_name, &mut self.out_of_service, &mut self.inactive_text, &mut self.active_text)?,
+        }
+        Ok(())
+    }
+
+    fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.history = History::new(self.commands.present_value(), clock.now());
+        self.clock = Some(clock);
+    }
+}
+
+/// The changes of state and time spent active of a binary object, as times on the database's
+/// clock
+#[derive(Clone, Copy)]
+struct History {
+    change_of_state_count: u32,
+    change_of_state_time: Option<Duration>,
+    time_of_state_count_reset: Duration,
+    /// The time spent active, not counting the time since `active_since`
+    elapsed_active_time: Duration,
+    active_since: Option<Duration>,
+    time_of_active_time_reset: Duration,
+}
+
+impl History {
+    fn new(present_value: BinaryPv, now: Duration) -> History {
+        History {
+            change_of_state_count: 0,
+            change_of_state_time: None,
+            time_of_state_count_reset: now,
+            elapsed_active_time: Duration::ZERO,
+            active_since: if present_value == BinaryPv::Active { Some(now) } else { None },
+            time_of_active_time_reset: now,
+        }
+    }
+
+    fn record(&mut self, changes: &[(BinaryPv, Duration)]) {
+        for &(present_value, at) in changes {
+            self.changed(present_value, at);
+        }
+    }
+
+    fn changed(&mut self, present_value: BinaryPv, at: Duration) {
+        self.change_of_state_count = self.change_of_state_count.wrapping_add(1);
+        self.change_of_state_time = Some(at);
+        match (present_value, self.active_since) {
+            (BinaryPv::Active, None) => self.active_since = Some(at),
+            (BinaryPv::Inactive, Some(since)) => {
+                self.elapsed_active_time += at.saturating_sub(since);
+                self.active_since = None;
+            },
+            _ => (),
+        }
+    }
+
+    /// The value of a property kept by the history, `None` for other properties
+    fn read(&self, property: PropertyIdentifier, array_index: Option<u32>, clock: &Option<Rc<dyn Clock>>) -> Option<Result<ValueSequence, PropertyError>> {
+        let value = match property {
+            PropertyIdentifier::ChangeOfStateTime => date_time(clock, self.change_of_state_time),
+            PropertyIdentifier::TimeOfStateCountReset => date_time(clock, Some(self.time_of_state_count_reset)),
+            PropertyIdentifier::TimeOfActiveTimeReset => date_time(clock, Some(self.time_of_active_time_reset)),
+            PropertyIdentifier::ChangeOfStateCount =>
+                return Some(scalar(PrimitiveValue::Unsigned(self.change_of_state_count), array_index)),
+            PropertyIdentifier::ElapsedActiveTime => {
+                let active_time = self.active_since.map_or(Duration::ZERO, |since| now(clock).saturating_sub(since));
+                let elapsed = (self.elapsed_active_time + active_time).as_secs();
+                return Some(scalar(PrimitiveValue::Unsigned(elapsed.min(u32::MAX as u64) as u32), array_index))
+            },
+            _ => return None,
+        };
+        Some(match array_index {
+            Some(_) => Err(PropertyError::PropertyIsNotAnArray),
+            None => Ok(value),
+        })
+    }
+
+    /// Resets Change_Of_State_Count or Elapsed_Active_Time, they can only be written with 0
+    fn reset(&mut self, property: PropertyIdentifier, value: &PrimitiveValue, present_value: BinaryPv, now: Duration) -> Result<(), PropertyError> {
+        match *value {
+            PrimitiveValue::Unsigned(0) => (),
+            PrimitiveValue::Unsigned(_) => return Err(PropertyError::ValueOutOfRange),
+            _ => return Err(PropertyError::InvalidDataType),
+        }
+        if property == PropertyIdentifier::ChangeOfStateCount {
+            self.change_of_state_count = 0;
+            self.time_of_state_count_reset = now;
+        } else {
+            self.elapsed_active_time = Duration::ZERO;
+            if present_value == BinaryPv::Active {
+                self.active_since = Some(now);
+            }
+            self.time_of_active_time_reset = now;
+        }
+        Ok(())
+    }
+}
+
+/// The time on an object's clock, which stands still until the object is added to a database
+fn now(clock: &Option<Rc<dyn Clock>>) -> Duration {
+    clock.as_ref().map_or(Duration::ZERO, |clock| clock.now())
+}
+
+/// A BACnetDateTime, unspecified if there isn't a time or the clock doesn't know the date
+fn date_time(clock: &Option<Rc<dyn Clock>>, at: Option<Duration>) -> ValueSequence {
+    let (date, time) = at.and_then(|at| clock.as_ref().and_then(|clock| clock.date_time(at)))
+        .unwrap_or((
+            Date { year: None, month: Month::Unspecified, day: DayOfMonth::Unspecified, weekday: None },
+            Time { hour: None, minute: None, second: None, hundredths: None }));
+    vec!(ApplicationValue(PrimitiveValue::Date(date)), ApplicationValue(PrimitiveValue::Time(time)))
+}
+
+/// Writes one of the properties all binary objects can write
+fn write_binary(property: PropertyIdentifier, value: &PrimitiveValue, object_name: &mut String, out_of_service: &mut bool, inactive_text: &mut String, active_text: &mut String) -> Result<(), PropertyError> {
+    match (property, value) {
+        (PropertyIdentifier::InactiveText, PrimitiveValue::CharacterString(text)) => *inactive_text = text.clone(),
+        (PropertyIdentifier::ActiveText, PrimitiveValue::CharacterString(text)) => *active_text = text.clone(),
+        _ => write_common(property, value, object_name, out_of_service)?,
+    }
+    Ok(())
+}
+
+fn binary_pv(value: &PrimitiveValue) -> Result<BinaryPv, PropertyError> {
+    match *value {
+        PrimitiveValue::Enumerated(value) => match BinaryPv::from(value) {
+            BinaryPv::Reserved(_) => Err(PropertyError::ValueOutOfRange),
+            value => Ok(value),
+        },
+        _ => Err(PropertyError::InvalidDataType),
+    }
+}
+
+fn polarity(value: &PrimitiveValue) -> Result<Polarity, PropertyError> {
+    match *value {
+        PrimitiveValue::Enumerated(value) => match Polarity::from(value) {
+            Polarity::Reserved(_) => Err(PropertyError::ValueOutOfRange),
+            value => Ok(value),
+        },
+        _ => Err(PropertyError::InvalidDataType),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clock::ManualClock;
+    use object::{BacnetDB, test_device};
+
+    const INPUT: ObjectId = ObjectId(ObjectType::BinaryInput, 1);
+    const OUTPUT: ObjectId = ObjectId(ObjectType::BinaryOutput, 1);
+    const VALUE: ObjectId = ObjectId(ObjectType::BinaryValue, 1);
+
+    fn db(clock: &ManualClock) -> BacnetDB {
+        let mut db = BacnetDB::with_clock(test_device(), Box::new(clock.clone()));
+        db.add(BinaryInput::new(1, "Door contact")).unwrap();
+        let mut output = BinaryOutput::new(1, "Fan", BinaryPv::Inactive);
+        output.set_minimum_on_time(60);
+        output.set_minimum_off_time(30);
+        db.add(output).unwrap();
+        db.add(BinaryValue::new(1, "Occupied", BinaryPv::Active)).unwrap();
+        db
+    }
+
+    fn enumerated(value: u32) -> ValueSequence {
+        vec!(ApplicationValue(PrimitiveValue::Enumerated(value)))
+    }
+
+    fn command(db: &mut BacnetDB, value: BinaryPv, priority: u8) -> Result<(), PropertyError> {
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &enumerated(value.into()), Some(priority))
+    }
+
+    fn relinquish(db: &mut BacnetDB, priority: u8) {
+        db.write_property(&OUTPUT, PropertyIdentifier::PresentValue, None, &vec!(ApplicationValue(PrimitiveValue::Null)), Some(priority)).unwrap();
+    }
+
+    fn present_value(db: &BacnetDB, object: &ObjectId) -> PrimitiveValue {
+        db.get_value(object, PropertyIdentifier::PresentValue).unwrap()
+    }
+
+    const ACTIVE: PrimitiveValue = PrimitiveValue::Enumerated(1);
+    const INACTIVE: PrimitiveValue = PrimitiveValue::Enumerated(0);
+
+    #[test]
+    fn read_input() {
+        let db = db(&ManualClock::new());
+        assert_eq!(INACTIVE, present_value(&db, &INPUT));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(3)), db.get_value(&INPUT, PropertyIdentifier::ObjectType));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(0)), db.get_value(&INPUT, PropertyIdentifier::Polarity));
+        assert_eq!(Ok(PrimitiveValue::CharacterString("Active".to_string())), db.get_value(&INPUT, PropertyIdentifier::ActiveText));
+        assert_eq!(Err(PropertyError::UnknownProperty), db.get_value(&INPUT, PropertyIdentifier::MinimumOnTime));
+        assert_eq!(Err(PropertyError::UnknownProperty), db.get_value(&VALUE, PropertyIdentifier::Polarity));
+        assert_eq!(Err(PropertyError::PropertyIsNotAnArray), db.read_property(&INPUT, PropertyIdentifier::ChangeOfStateTime, Some(1)));
+    }
+
+    #[test]
+    fn input_is_only_written_out_of_service() {
+        let mut db = db(&ManualClock::new());
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &enumerated(1), None));
+        db.set_value(&INPUT, PropertyIdentifier::OutOfService, PrimitiveValue::Boolean(true)).unwrap();
+        db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &enumerated(1), None).unwrap();
+        assert_eq!(BinaryPv::Active, db.get::<BinaryInput>(&INPUT).unwrap().present_value());
+        assert_eq!(Err(PropertyError::ValueOutOfRange), db.write_property(&INPUT, PropertyIdentifier::PresentValue, None, &enumerated(2), None));
+        assert_eq!(Err(PropertyError::ValueOutOfRange), db.set_value(&INPUT, PropertyIdentifier::Polarity, PrimitiveValue::Enumerated(2)));
+    }
+
+    #[test]
+    fn changes_of_state_are_counted() {
+        let clock = ManualClock::new();
+        let mut db = db(&clock);
+        for &value in &[BinaryPv::Active, BinaryPv::Active, BinaryPv::Inactive, BinaryPv::Active] {
+            clock.advance(Duration::from_secs(10));
+            db.get_mut::<BinaryInput>(&INPUT).unwrap().set_present_value(value);
+        }
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(3)), db.get_value(&INPUT, PropertyIdentifier::ChangeOfStateCount));
+        // Active from 10s to 30s, and since 40s
+        assert_eq!(Ok(PrimitiveValue::Unsigned(25)), db.get_value(&INPUT, PropertyIdentifier::ElapsedActiveTime));
+
+        assert_eq!(Err(PropertyError::ValueOutOfRange), db.set_value(&INPUT, PropertyIdentifier::ChangeOfStateCount, PrimitiveValue::Unsigned(1)));
+        db.set_value(&INPUT, PropertyIdentifier::ChangeOfStateCount, PrimitiveValue::Unsigned(0)).unwrap();
+        db.set_value(&INPUT, PropertyIdentifier::ElapsedActiveTime, PrimitiveValue::Unsigned(0)).unwrap();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(0)), db.get_value(&INPUT, PropertyIdentifier::ChangeOfStateCount));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(5)), db.get_value(&INPUT, PropertyIdentifier::ElapsedActiveTime));
+    }
+
+    #[test]
+    fn times_are_unspecified_without_a_date() {
+        let db = db(&ManualClock::new());
+        let unspecified = vec!(
+            ApplicationValue(PrimitiveValue::Date(Date { year: None, month: Month::Unspecified, day: DayOfMonth::Unspecified, weekday: None })),
+            ApplicationValue(PrimitiveValue::Time(Time { hour: None, minute: None, second: None, hundredths: None })));
+        assert_eq!(Ok(unspecified), db.read_property(&INPUT, PropertyIdentifier::ChangeOfStateTime, None));
+    }
+
+    #[test]
+    fn commands_are_prioritised() {
+        let clock = ManualClock::new();
+        let mut db = db(&clock);
+        assert_eq!(ACTIVE, present_value(&db, &VALUE));
+        db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &enumerated(0), Some(10)).unwrap();
+        db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &enumerated(1), None).unwrap();
+        assert_eq!(INACTIVE, present_value(&db, &VALUE));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Enumerated(0)))), db.read_property(&VALUE, PropertyIdentifier::PriorityArray, Some(10)));
+        db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &vec!(ApplicationValue(PrimitiveValue::Null)), Some(10)).unwrap();
+        assert_eq!(ACTIVE, present_value(&db, &VALUE));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(2)), db.get_value(&VALUE, PropertyIdentifier::ChangeOfStateCount));
+    }
+
+    #[test]
+    fn minimum_on_time_holds_the_output() {
+        let clock = ManualClock::new();
+        let mut db = db(&clock);
+        command(&mut db, BinaryPv::Active, 8).unwrap();
+        assert_eq!(ACTIVE, present_value(&db, &OUTPUT));
+        assert_eq!(Ok(vec!(ApplicationValue(ACTIVE))), db.read_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(6)));
+
+        // Relinquishing doesn't turn the output off until it has been on for a minute
+        clock.advance(Duration::from_secs(20));
+        relinquish(&mut db, 8);
+        assert_eq!(ACTIVE, present_value(&db, &OUTPUT));
+        clock.advance(Duration::from_secs(40));
+        assert_eq!(INACTIVE, present_value(&db, &OUTPUT));
+        // It is then held off for the minimum off time
+        assert_eq!(Ok(vec!(ApplicationValue(INACTIVE))), db.read_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(6)));
+        clock.advance(Duration::from_secs(10));
+        command(&mut db, BinaryPv::Active, 8).unwrap();
+        assert_eq!(INACTIVE, present_value(&db, &OUTPUT));
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(ACTIVE, present_value(&db, &OUTPUT));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(3)), db.get_value(&OUTPUT, PropertyIdentifier::ChangeOfStateCount));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(60)), db.get_value(&OUTPUT, PropertyIdentifier::ElapsedActiveTime));
+    }
+
+    #[test]
+    fn higher_priorities_override_the_minimum_time() {
+        let clock = ManualClock::new();
+        let mut db = db(&clock);
+        command(&mut db, BinaryPv::Active, 8).unwrap();
+        command(&mut db, BinaryPv::Inactive, 1).unwrap();
+        assert_eq!(INACTIVE, present_value(&db, &OUTPUT));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), command(&mut db, BinaryPv::Active, 6));
+        assert_eq!(Err(PropertyError::ValueOutOfRange), command(&mut db, BinaryPv::Active, 17));
+    }
+
+    #[test]
+    fn values_are_commanded_at_priority_6() {
+        let mut db = db(&ManualClock::new());
+        db.write_property(&VALUE, PropertyIdentifier::PresentValue, None, &enumerated(0), Some(6)).unwrap();
+        assert_eq!(INACTIVE, present_value(&db, &VALUE));
+        assert_eq!(Ok(vec!(ApplicationValue(INACTIVE))), db.read_property(&VALUE, PropertyIdentifier::PriorityArray, Some(6)));
+    }
+
+    #[test]
+    fn read_only_properties() {
+        let mut db = db(&ManualClock::new());
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.write_property(&OUTPUT, PropertyIdentifier::PriorityArray, Some(1), &enumerated(1), None));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), db.set_value(&OUTPUT, PropertyIdentifier::ChangeOfStateTime, PrimitiveValue::Null));
+        assert_eq!(Err(PropertyError::UnknownProperty), db.set_value(&VALUE, PropertyIdentifier::MinimumOnTime, PrimitiveValue::Unsigned(1)));
+        db.set_value(&OUTPUT, PropertyIdentifier::MinimumOnTime, PrimitiveValue::Unsigned(120)).unwrap();
+        assert_eq!(120, db.get::<BinaryOutput>(&OUTPUT).unwrap().minimum_on_time());
+    }
+}
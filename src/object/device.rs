@@ -0,0 +1,223 @@
+//! The Device object - Clause 12.11. Every BACnet device has exactly one, it describes the device
+//! and lists the device's other objects.
+
+use super::{BacnetObject, ObjectId, PropertyError, array, scalar, single_value};
+use enumerations::{DeviceStatus, ObjectType, PropertyIdentifier, Segmentation};
+use ast::{BitString, NetworkAddress};
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+
+/// The number of bits in Protocol_Services_Supported, one for each standard service
+pub const SERVICES_SUPPORTED_LEN: usize = 47;
+
+/// The number of bits in Protocol_Object_Types_Supported, one for each standard object type
+pub const OBJECT_TYPES_SUPPORTED_LEN: usize = 65;
+
+pub struct DeviceObject {
+    pub instance: u32,
+    pub object_name: String,
+    pub system_status: DeviceStatus,
+    pub vendor_name: String,
+    pub vendor_identifier: u32,
+    pub model_name: String,
+    pub firmware_revision: String,
+    pub application_software_version: String,
+    pub max_apdu_length_supported: u32,
+    pub segmentation_supported: Segmentation,
+    /// The most segments accepted in a segmented request
+    pub max_segments_accepted: u32,
+    /// In milliseconds
+    pub apdu_segment_timeout: u32,
+    /// In milliseconds
+    pub apdu_timeout: u32,
+    pub number_of_apdu_retries: u32,
+    /// The devices this one communicates with, and their addresses
+    pub device_address_binding: Vec<(ObjectId, NetworkAddress)>,
+    /// Kept by the `BacnetDB`, the device followed by the other objects in the database
+    pub(super) object_list: Vec<ObjectId>,
+    /// Kept by the `BacnetDB`, incremented whenever an object is added, removed or renamed
+    pub(super) database_revision: u32,
+    /// Kept by the `BacnetDB`, the types of the objects in the database
+    pub(super) protocol_object_types_supported: BitString,
+    /// Set from the services registered with the `Dispatcher` which handles the device's requests
+    pub(super) protocol_services_supported: BitString,
+}
+
+impl DeviceObject {
+    /// The properties the standard requires of a device object
+    pub const REQUIRED_PROPERTIES: &[PropertyIdentifier] = &[
+        PropertyIdentifier::ObjectIdentifier,
+        PropertyIdentifier::ObjectName,
+        PropertyIdentifier::ObjectType,
+        PropertyIdentifier::SystemStatus,
+        PropertyIdentifier::VendorName,
+        PropertyIdentifier::VendorIdentifier,
+        PropertyIdentifier::ModelName,
+        PropertyIdentifier::FirmwareRevision,
+        PropertyIdentifier::ApplicationSoftwareVersion,
+        PropertyIdentifier::ProtocolVersion,
+        PropertyIdentifier::ProtocolRevision,
+        PropertyIdentifier::ProtocolServicesSupported,
+        PropertyIdentifier::ProtocolObjectTypesSupported,
+        PropertyIdentifier::ObjectList,
+        PropertyIdentifier::MaxApduLengthAccepted,
+        PropertyIdentifier::SegmentationSupported,
+        PropertyIdentifier::ApduTimeout,
+        PropertyIdentifier::NumberOfApduRetries,
+        PropertyIdentifier::DeviceAddressBinding,
+        PropertyIdentifier::DatabaseRevision,
+    ];
+
+    /// The optional properties this implementation supports, which are required of devices which
+    /// support segmentation
+    pub const OPTIONAL_PROPERTIES: &[PropertyIdentifier] = &[
+        PropertyIdentifier::MaxSegmentsAccepted,
+        PropertyIdentifier::ApduSegmentTimeout,
+    ];
+
+    pub const PROTOCOL_VERSION: u32 = 1;
+
+    /// The revision of the standard the object types and services are taken from
+    pub const PROTOCOL_REVISION: u32 = 22;
+
+    /// An operational device which accepts 1476 octet APDUs without segmentation, with the
+    /// default APDU timeout of 3 seconds, segment timeout of 2 seconds and 3 retries. Its
+    /// firmware revision is this crate's version.
+    pub fn new(instance: u32, object_name: &str, vendor_identifier: u32) -> DeviceObject {
+        let mut protocol_object_types_supported = BitString::new(OBJECT_TYPES_SUPPORTED_LEN);
+        protocol_object_types_supported.set(u16::from(ObjectType::Device) as usize, true);
+        DeviceObject {
+            instance,
+            object_name: object_name.to_string(),
+            system_status: DeviceStatus::Operational,
+            vendor_name: String::new(),
+            vendor_identifier,
+            model_name: String::new(),
+            firmware_revision: env!("CARGO_PKG_VERSION").to_string(),
+            application_software_version: String::new(),
+            max_apdu_length_supported: 1476,
+            segmentation_supported: Segmentation::NoSegmentation,
+            max_segments_accepted: 64,
+            apdu_segment_timeout: 2000,
+            apdu_timeout: 3000,
+            number_of_apdu_retries: 3,
+            device_address_binding: vec!(),
+            object_list: vec!(ObjectId(ObjectType::Device, instance)),
+            database_revision: 0,
+            protocol_object_types_supported,
+            protocol_services_supported: BitString::new(SERVICES_SUPPORTED_LEN),
+        }
+    }
+
+    pub fn object_id(&self) -> ObjectId {
+        ObjectId(ObjectType::Device, self.instance)
+    }
+
+    pub fn object_list(&self) -> &[ObjectId] {
+        &self.object_list
+    }
+
+    pub fn database_revision(&self) -> u32 {
+        self.database_revision
+    }
+
+    pub fn protocol_object_types_supported(&self) -> &BitString {
+        &self.protocol_object_types_supported
+    }
+
+    pub fn protocol_services_supported(&self) -> &BitString {
+        &self.protocol_services_supported
+    }
+}
+
+impl BacnetObject for DeviceObject {
+    fn object_id(&self) -> ObjectId {
+        DeviceObject::object_id(self)
+    }
+
+    fn object_name(&self) -> &str {
+        &self.object_name
+    }
+
+    fn required_properties(&self) -> &'static [PropertyIdentifier] {
+        DeviceObject::REQUIRED_PROPERTIES
+    }
+
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        DeviceObject::OPTIONAL_PROPERTIES
+    }
+
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let value = match property {
+            PropertyIdentifier::ObjectIdentifier => PrimitiveValue::ObjectId(self.object_id()),
+            PropertyIdentifier::ObjectType => PrimitiveValue::Enumerated(u16::from(ObjectType::Device) as u32),
+            PropertyIdentifier::SystemStatus => PrimitiveValue::Enumerated(self.system_status.into()),
+            PropertyIdentifier::VendorName => PrimitiveValue::CharacterString(self.vendor_name.clone()),
+            PropertyIdentifier::ModelName => PrimitiveValue::CharacterString(self.model_name.clone()),
+            PropertyIdentifier::FirmwareRevision => PrimitiveValue::CharacterString(self.firmware_revision.clone()),
+            PropertyIdentifier::ApplicationSoftwareVersion => PrimitiveValue::CharacterString(self.application_software_version.clone()),
+            PropertyIdentifier::ProtocolVersion => PrimitiveValue::Unsigned(DeviceObject::PROTOCOL_VERSION),
+            PropertyIdentifier::ProtocolRevision => PrimitiveValue::Unsigned(DeviceObject::PROTOCOL_REVISION),
+            PropertyIdentifier::ProtocolServicesSupported => PrimitiveValue::BitString(self.protocol_services_supported.clone()),
+            PropertyIdentifier::ProtocolObjectTypesSupported => PrimitiveValue::BitString(self.protocol_object_types_supported.clone()),
+            PropertyIdentifier::MaxApduLengthAccepted => PrimitiveValue::Unsigned(self.max_apdu_length_supported),
+            PropertyIdentifier::SegmentationSupported => PrimitiveValue::Enumerated(self.segmentation_supported.into()),
+            PropertyIdentifier::VendorIdentifier => PrimitiveValue::Unsigned(self.vendor_identifier),
+            PropertyIdentifier::ObjectName => PrimitiveValue::CharacterString(self.object_name.clone()),
+            PropertyIdentifier::MaxSegmentsAccepted => PrimitiveValue::Unsigned(self.max_segments_accepted),
+            PropertyIdentifier::ApduSegmentTimeout => PrimitiveValue::Unsigned(self.apdu_segment_timeout),
+            PropertyIdentifier::ApduTimeout => PrimitiveValue::Unsigned(self.apdu_timeout),
+            PropertyIdentifier::NumberOfApduRetries => PrimitiveValue::Unsigned(self.number_of_apdu_retries),
+            PropertyIdentifier::DatabaseRevision => PrimitiveValue::Unsigned(self.database_revision),
+            PropertyIdentifier::ObjectList =>
+                return array(self.object_list.iter().map(|object| PrimitiveValue::ObjectId(*object)).collect(), array_index),
+            PropertyIdentifier::DeviceAddressBinding => {
+                if array_index.is_some() {
+                    return Err(PropertyError::PropertyIsNotAnArray)
+                }
+                return Ok(self.device_address_binding.iter().flat_map(|(device, address)| vec!(
+                    ApplicationValue(PrimitiveValue::ObjectId(*device)),
+                    ApplicationValue(PrimitiveValue::Unsigned(address.network as u32)),
+                    ApplicationValue(PrimitiveValue::OctetString(address.address.to_vec())))).collect())
+            },
+            _ => return Err(PropertyError::UnknownProperty),
+        };
+        scalar(value, array_index)
+    }
+
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, _priority: Option<u8>) -> Result<(), PropertyError> {
+        match property {
+            PropertyIdentifier::ObjectName |
+            PropertyIdentifier::ApduSegmentTimeout |
+            PropertyIdentifier::ApduTimeout |
+            PropertyIdentifier::NumberOfApduRetries => (),
+            _ => {
+                self.read_property(property, None)?;
+                return Err(PropertyError::WriteAccessDenied)
+            },
+        }
+        if array_index.is_some() {
+            return Err(PropertyError::PropertyIsNotAnArray)
+        }
+        match (property, single_value(value)?) {
+            (PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString(name)) => {
+                if name.is_empty() {
+                    return Err(PropertyError::ValueOutOfRange)
+                }
+                self.object_name = name.clone();
+            },
+            (PropertyIdentifier::ApduSegmentTimeout, &PrimitiveValue::Unsigned(timeout)) |
+            (PropertyIdentifier::ApduTimeout, &PrimitiveValue::Unsigned(timeout)) if timeout == 0 =>
+                return Err(PropertyError::ValueOutOfRange),
+            (PropertyIdentifier::ApduSegmentTimeout, &PrimitiveValue::Unsigned(timeout)) =>
+                self.apdu_segment_timeout = timeout,
+            (PropertyIdentifier::ApduTimeout, &PrimitiveValue::Unsigned(timeout)) =>
+                self.apdu_timeout = timeout,
+            (PropertyIdentifier::NumberOfApduRetries, &PrimitiveValue::Unsigned(retries)) =>
+                self.number_of_apdu_retries = retries,
+            _ => return Err(PropertyError::InvalidDataType),
+        }
+        Ok(())
+    }
+}
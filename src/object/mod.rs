@@ -0,0 +1,682 @@
+//! A big part of BACnet is its object database. It holds the device object and any number of
+//! other objects, each of which implements `BacnetObject`.
+
+use ast::BitString;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+use clock::{Clock, SystemClock};
+use cov::{CovNotification, CovSubscriptions, Subscription};
+use service::writepropertymultiple::PropertyValue;
+use enumerations::{EventState, ObjectType, PropertyIdentifier};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+mod analog;
+mod binary;
+mod device;
+mod priority;
+
+pub use ast::ObjectId;
+pub use self::analog::{AnalogInput, AnalogOutput, AnalogValue};
+pub use self::binary::{BinaryInput, BinaryOutput, BinaryValue};
+pub use self::device::{DeviceObject, OBJECT_TYPES_SUPPORTED_LEN, SERVICES_SUPPORTED_LEN};
+pub use self::priority::{Command, MINIMUM_ON_OFF_PRIORITY, PriorityArray, read_priority_array};
+
+/// The reasons a property can't be accessed
+#[derive(PartialEq, Debug)]
+pub enum PropertyError {
+    UnknownObject,
+    UnknownProperty,
+    PropertyIsNotAnArray,
+    InvalidArrayIndex,
+    WriteAccessDenied,
+    InvalidDataType,
+    ValueOutOfRange,
+    /// Another object in the device already has the name being written
+    DuplicateName,
+}
+
+/// The reasons an object can't be added to the database
+#[derive(PartialEq, Debug)]
+pub enum DatabaseError {
+    DuplicateObjectId,
+    DuplicateObjectName,
+    /// The database already has its Device object, a device has only one
+    SecondDevice,
+}
+
+/// An object in the database. Objects give their properties' values as application tagged
+/// values, the database takes care of everything which involves more than one object.
+pub trait BacnetObject: Any {
+    fn object_id(&self) -> ObjectId;
+
+    fn object_name(&self) -> &str;
+
+    /// The properties the standard requires of the object type, apart from Property_List which the
+    /// database gives
+    fn required_properties(&self) -> &'static [PropertyIdentifier];
+
+    /// The optional properties this object supports
+    fn optional_properties(&self) -> &'static [PropertyIdentifier] {
+        &[]
+    }
+
+    /// The properties reported in COV notifications for a SubscribeCOV subscription, empty if the
+    /// object doesn't support COV reporting
+    fn cov_properties(&self) -> &'static [PropertyIdentifier] {
+        &[]
+    }
+
+    /// Reads the value of a property, or one element of it if it is an array
+    fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError>;
+
+    /// Writes the value of a property, or one element of it if it is an array. The priority is
+    /// only used by commandable properties.
+    fn write_property(&mut self, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError>;
+
+    /// Gives an object which keeps track of time the database's clock, when it is added
+    fn set_clock(&mut self, _clock: Rc<dyn Clock>) {}
+}
+
+/// The value of a property which isn't an array
+pub fn scalar(value: PrimitiveValue, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+    match array_index {
+        Some(_) => Err(PropertyError::PropertyIsNotAnArray),
+        None => Ok(vec!(ApplicationValue(value))),
+    }
+}
+
+/// The value of an array property, or one element of it. Element 0 is the array's length and the
+/// other elements are numbered from 1.
+pub fn array(elements: Vec<PrimitiveValue>, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+    match array_index {
+        None => Ok(elements.into_iter().map(ApplicationValue).collect()),
+        Some(0) => Ok(vec!(ApplicationValue(PrimitiveValue::Unsigned(elements.len() as u32)))),
+        Some(index) => elements.into_iter().nth(index as usize - 1)
+            .map(|element| vec!(ApplicationValue(element)))
+            .ok_or(PropertyError::InvalidArrayIndex),
+    }
+}
+
+/// The value written to a property which takes a single application tagged value
+pub fn single_value(value: &ValueSequence) -> Result<&PrimitiveValue, PropertyError> {
+    match value.as_slice() {
+        [ApplicationValue(value)] => Ok(value),
+        _ => Err(PropertyError::InvalidDataType),
+    }
+}
+
+/// Gives `WriteAccessDenied` for the properties of an object which can be read but not written
+pub fn check_writable(object: &dyn BacnetObject, property: PropertyIdentifier, writable: &[PropertyIdentifier]) -> Result<(), PropertyError> {
+    if writable.contains(&property) {
+        return Ok(())
+    }
+    object.read_property(property, None)?;
+    Err(PropertyError::WriteAccessDenied)
+}
+
+/// Writes the Object_Name or Out_Of_Service of an object. Names can't be empty, the database
+/// checks they are unique.
+pub fn write_common(property: PropertyIdentifier, value: &PrimitiveValue, object_name: &mut String, out_of_service: &mut bool) -> Result<(), PropertyError> {
+    match (property, value) {
+        (PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString(name)) => {
+            if name.is_empty() {
+                return Err(PropertyError::ValueOutOfRange)
+            }
+            *object_name = name.clone();
+        },
+        (PropertyIdentifier::OutOfService, &PrimitiveValue::Boolean(value)) => *out_of_service = value,
+        _ => return Err(PropertyError::InvalidDataType),
+    }
+    Ok(())
+}
+
+/// The Status_Flags of an object (in alarm, fault, overridden and out of service). An object is in
+/// alarm unless its event state is normal, and in fault when its event state is fault.
+pub fn status_flags(event_state: EventState, out_of_service: bool) -> PrimitiveValue {
+    PrimitiveValue::BitString(BitString::from_bits(&[
+        event_state != EventState::Normal,
+        event_state == EventState::Fault,
+        false,
+        out_of_service]))
+}
+
+pub struct BacnetDB {
+    device: DeviceObject,
+    objects: BTreeMap<ObjectId, Box<dyn BacnetObject>>,
+    cov: CovSubscriptions,
+    clock: Rc<dyn Clock>,
+}
+
+impl BacnetDB {
+    pub fn new(device: DeviceObject) -> BacnetDB {
+        BacnetDB::with_clock(device, Box::new(SystemClock::new()))
+    }
+
+    /// A database whose timers, such as COV subscription lifetimes, run on `clock`
+    pub fn with_clock(device: DeviceObject, clock: Box<dyn Clock>) -> BacnetDB {
+        let mut db = BacnetDB {
+            device,
+            objects: BTreeMap::new(),
+            cov: CovSubscriptions::default(),
+            clock: Rc::from(clock),
+        };
+        db.device.object_list = db.object_ids();
+        db
+    }
+
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    pub fn device(&self) -> &DeviceObject {
+        &self.device
+    }
+
+    /// Adds an object, its identifier and its name have to be unique in the device
+    pub fn add<T: BacnetObject>(&mut self, mut object: T) -> Result<(), DatabaseError> {
+        let object_id = object.object_id();
+        if object_id.0 == ObjectType::Device {
+            return Err(DatabaseError::SecondDevice)
+        }
+        if self.object(&object_id).is_some() {
+            return Err(DatabaseError::DuplicateObjectId)
+        }
+        if self.find_object_by_name(object.object_name()).is_some() {
+            return Err(DatabaseError::DuplicateObjectName)
+        }
+        object.set_clock(self.clock.clone());
+        self.objects.insert(object_id, Box::new(object));
+        self.database_changed();
+        Ok(())
+    }
+
+    /// Removes an object, the device object can't be removed
+    pub fn remove(&mut self, object: &ObjectId) -> Option<Box<dyn BacnetObject>> {
+        let removed = self.objects.remove(object);
+        if removed.is_some() {
+            self.database_changed();
+        }
+        removed
+    }
+
+    /// Any object in the database, including the device
+    pub fn object(&self, object: &ObjectId) -> Option<&dyn BacnetObject> {
+        if *object == self.device.object_id() {
+            Some(&self.device)
+        } else {
+            self.objects.get(object).map(|object| object.as_ref())
+        }
+    }
+
+    fn object_mut(&mut self, object: &ObjectId) -> Option<&mut dyn BacnetObject> {
+        if *object == self.device.object_id() {
+            Some(&mut self.device)
+        } else {
+            self.objects.get_mut(object).map(|object| object.as_mut())
+        }
+    }
+
+    /// An object of a known type, `None` if there is no such object or it is a different type
+    pub fn get<T: BacnetObject>(&self, object: &ObjectId) -> Option<&T> {
+        self.object(object).and_then(|object| (object as &dyn Any).downcast_ref())
+    }
+
+    /// An object of a known type, to change it directly. Its name mustn't be changed this way as
+    /// the name wouldn't be checked, `write_property` should be used instead.
+    pub fn get_mut<T: BacnetObject>(&mut self, object: &ObjectId) -> Option<&mut T> {
+        self.object_mut(object).and_then(|object| (object as &mut dyn Any).downcast_mut())
+    }
+
+    /// The identifiers of every object, starting with the device
+    pub fn object_ids(&self) -> Vec<ObjectId> {
+        let mut ids = vec!(self.device.object_id());
+        ids.extend(self.objects.keys().cloned());
+        ids
+    }
+
+    /// Updates the device's Object_List and Database_Revision after an object has been added,
+    /// removed or renamed
+    fn database_changed(&mut self) {
+        self.device.object_list = self.object_ids();
+        self.device.database_revision = self.device.database_revision.wrapping_add(1);
+        let mut object_types = BitString::new(OBJECT_TYPES_SUPPORTED_LEN);
+        for ObjectId(object_type, _) in self.object_ids() {
+            let bit = u16::from(object_type) as usize;
+            if bit < OBJECT_TYPES_SUPPORTED_LEN {
+                object_types.set(bit, true);
+            }
+        }
+        self.device.protocol_object_types_supported = object_types;
+    }
+
+    /// Sets the device's Protocol_Services_Supported, done when a `Dispatcher` is attached
+    pub(crate) fn set_protocol_services_supported(&mut self, services: BitString) {
+        self.device.protocol_services_supported = services;
+    }
+
+    /// Reads the value of an object's property, or one element of it if it is an array. The
+    /// database gives every object's Property_List.
+    pub fn read_property(&self, object: &ObjectId, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+        let object = self.object(object).ok_or(PropertyError::UnknownObject)?;
+        if property == PropertyIdentifier::PropertyList {
+            return array(property_list(object), array_index)
+        }
+        object.read_property(property, array_index)
+    }
+
+    /// The value of a property which is a single application tagged value
+    pub fn get_value(&self, object: &ObjectId, property: PropertyIdentifier) -> Result<PrimitiveValue, PropertyError> {
+        let value = self.read_property(object, property, None)?;
+        single_value(&value).cloned()
+    }
+
+    /// Writes the value of an object's property, or one element of it if it is an array. The
+    /// priority is only used by commandable properties.
+    pub fn write_property(&mut self, object: &ObjectId, property: PropertyIdentifier, array_index: Option<u32>, value: &ValueSequence, priority: Option<u8>) -> Result<(), PropertyError> {
+        if self.object(object).is_none() {
+            return Err(PropertyError::UnknownObject)
+        }
+        if property == PropertyIdentifier::PropertyList {
+            return Err(PropertyError::WriteAccessDenied)
+        }
+        let renamed = match (property, value.as_slice()) {
+            (PropertyIdentifier::ObjectName, [ApplicationValue(PrimitiveValue::CharacterString(name))]) => {
+                match self.find_object_by_name(name) {
+                    Some(named) if named != *object => return Err(PropertyError::DuplicateName),
+                    Some(_) => false,
+                    None => true,
+                }
+            },
+            _ => false,
+        };
+        self.object_mut(object).ok_or(PropertyError::UnknownObject)?
+            .write_property(property, array_index, value, priority)?;
+        if renamed {
+            self.database_changed();
+        }
+        Ok(())
+    }
+
+    /// Writes a property which takes a single application tagged value
+    pub fn set_value(&mut self, object: &ObjectId, property: PropertyIdentifier, value: PrimitiveValue) -> Result<(), PropertyError> {
+        self.write_property(object, property, None, &vec!(ApplicationValue(value)), None)
+    }
+
+    /// The Object_Name of an object, if the object exists
+    pub fn object_name(&self, object: &ObjectId) -> Option<&str> {
+        self.object(object).map(|object| object.object_name())
+    }
+
+    /// Finds the object with the given Object_Name
+    pub fn find_object_by_name(&self, name: &str) -> Option<ObjectId> {
+        if self.device.object_name == name {
+            return Some(self.device.object_id())
+        }
+        self.objects.values()
+            .find(|object| object.object_name() == name)
+            .map(|object| object.object_id())
+    }
+
+    /// The properties of an object which one of the special property identifiers ALL, REQUIRED and
+    /// OPTIONAL stands for
+    pub fn expand_properties(&self, object: &ObjectId, special: PropertyIdentifier) -> Result<Vec<PropertyIdentifier>, PropertyError> {
+        let object = self.object(object).ok_or(PropertyError::UnknownObject)?;
+        match special {
+            PropertyIdentifier::All =>
+                Ok(object.required_properties().iter().chain(object.optional_properties()).cloned().collect()),
+            PropertyIdentifier::Required => Ok(object.required_properties().to_vec()),
+            PropertyIdentifier::Optional => Ok(object.optional_properties().to_vec()),
+            _ => Err(PropertyError::UnknownProperty),
+        }
+    }
+
+    /// The properties reported in COV notifications for a SubscribeCOV subscription to an object,
+    /// empty if the object doesn't support COV reporting
+    pub fn cov_properties(&self, object: &ObjectId) -> Result<Vec<PropertyIdentifier>, PropertyError> {
+        Ok(self.object(object).ok_or(PropertyError::UnknownObject)?.cov_properties().to_vec())
+    }
+
+    /// Adds or renews a COV subscription to an object or to one of its properties
+    pub fn subscribe_cov(&mut self, subscription: Subscription) -> Result<(), PropertyError> {
+        match subscription.property {
+            Some((property, array_index)) => {
+                self.read_property(&subscription.object_id, property, array_index)?;
+            },
+            None => {
+                self.cov_properties(&subscription.object_id)?;
+            },
+        }
+        self.cov.subscribe(subscription);
+        Ok(())
+    }
+
+    pub fn cov_subscriptions(&mut self) -> &mut CovSubscriptions {
+        &mut self.cov
+    }
+
+    /// The COV notifications due now, for new subscriptions and for changed values
+    pub fn cov_notifications(&mut self) -> Vec<CovNotification> {
+        let mut cov = mem::take(&mut self.cov);
+        let notifications = cov.notifications(self.now(), self.device.object_id(), |subscription| self.cov_values(subscription));
+        self.cov = cov;
+        notifications
+    }
+
+    /// The current values for a subscription and the object's COV_Increment
+    fn cov_values(&self, subscription: &Subscription) -> Option<(Vec<PropertyValue>, Option<f32>)> {
+        let object = &subscription.object_id;
+        let properties = match subscription.property {
+            Some(reference) => vec!(reference),
+            None => self.cov_properties(object).ok()?.into_iter().map(|property| (property, None)).collect(),
+        };
+        let mut values = vec!();
+        for (property, array_index) in properties {
+            values.push(PropertyValue {
+                property,
+                array_index,
+                value: self.read_property(object, property, array_index).ok()?,
+                priority: None,
+            });
+        }
+        let increment = match self.read_property(object, PropertyIdentifier::CovIncrement, None).as_ref().map(|value| value.as_slice()) {
+            Ok([ApplicationValue(PrimitiveValue::Real(increment))]) => Some(*increment),
+            _ => None,
+        };
+        Some((values, increment))
+    }
+}
+
+/// An object's Property_List, its properties other than the ones every object has
+fn property_list(object: &dyn BacnetObject) -> Vec<PrimitiveValue> {
+    object.required_properties().iter().chain(object.optional_properties())
+        .filter(|property| !matches!(property,
+            PropertyIdentifier::ObjectIdentifier |
+            PropertyIdentifier::ObjectName |
+            PropertyIdentifier::ObjectType |
+            PropertyIdentifier::PropertyList))
+        .map(|&property| PrimitiveValue::Enumerated(property.into()))
+        .collect()
+}
+
+/// The device used in tests
+#[cfg(test)]
+pub fn test_device() -> DeviceObject {
+    DeviceObject::new(45, "Test device", 23)
+}
+
+/// A database containing only a device, for testing
+#[cfg(test)]
+pub fn test_db() -> BacnetDB {
+    BacnetDB::new(test_device())
+}
+
+#[cfg(test)]
+mod test_db {
+    use super::*;
+
+    /// An object with a name and a writable value
+    struct TestObject {
+        object_id: ObjectId,
+        name: String,
+        value: u32,
+    }
+
+    impl TestObject {
+        fn new(instance: u32, name: &str) -> TestObject {
+            TestObject { object_id: ObjectId(ObjectType::AnalogInput, instance), name: name.to_string(), value: 0 }
+        }
+    }
+
+    impl BacnetObject for TestObject {
+        fn object_id(&self) -> ObjectId { self.object_id }
+
+        fn object_name(&self) -> &str { &self.name }
+
+        fn required_properties(&self) -> &'static [PropertyIdentifier] {
+            &[PropertyIdentifier::ObjectName, PropertyIdentifier::PresentValue]
+        }
+
+        fn read_property(&self, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+            match property {
+                PropertyIdentifier::ObjectName => scalar(PrimitiveValue::CharacterString(self.name.clone()), array_index),
+                PropertyIdentifier::PresentValue => scalar(PrimitiveValue::Unsigned(self.value), array_index),
+                _ => Err(PropertyError::UnknownProperty),
+            }
+        }
+
+        fn write_property(&mut self, property: PropertyIdentifier, _: Option<u32>, value: &ValueSequence, _: Option<u8>) -> Result<(), PropertyError> {
+            match (property, single_value(value)?) {
+                (PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString(name)) => self.name = name.clone(),
+                (PropertyIdentifier::PresentValue, &PrimitiveValue::Unsigned(value)) => self.value = value,
+                _ => return Err(PropertyError::InvalidDataType),
+            }
+            Ok(())
+        }
+    }
+
+    fn db_with_objects() -> BacnetDB {
+        let mut db = test_db();
+        db.add(TestObject::new(2, "Two")).unwrap();
+        db.add(TestObject::new(1, "One")).unwrap();
+        db
+    }
+
+    #[test]
+    fn objects_are_listed() {
+        let db = db_with_objects();
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(&[device, ObjectId(ObjectType::AnalogInput, 1), ObjectId(ObjectType::AnalogInput, 2)], db.device().object_list());
+        assert_eq!(2, db.device().database_revision());
+        assert_eq!(Ok(vec!(
+            ApplicationValue(PrimitiveValue::ObjectId(device)),
+            ApplicationValue(PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 1))),
+            ApplicationValue(PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 2))))),
+                   db.read_property(&device, PropertyIdentifier::ObjectList, None));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(2)), db.get_value(&device, PropertyIdentifier::DatabaseRevision));
+    }
+
+    #[test]
+    fn object_list_elements() {
+        let db = db_with_objects();
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Unsigned(3)))),
+                   db.read_property(&device, PropertyIdentifier::ObjectList, Some(0)));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 2))))),
+                   db.read_property(&device, PropertyIdentifier::ObjectList, Some(3)));
+        assert_eq!(Err(PropertyError::InvalidArrayIndex),
+                   db.read_property(&device, PropertyIdentifier::ObjectList, Some(4)));
+    }
+
+    #[test]
+    fn ids_and_names_are_unique() {
+        let mut db = db_with_objects();
+        assert_eq!(Err(DatabaseError::DuplicateObjectId), db.add(TestObject::new(1, "Three")));
+        assert_eq!(Err(DatabaseError::DuplicateObjectName), db.add(TestObject::new(3, "Two")));
+        assert_eq!(Err(DatabaseError::DuplicateObjectName), db.add(TestObject::new(3, "Test device")));
+        assert_eq!(Err(DatabaseError::SecondDevice), db.add(DeviceObject::new(46, "Another device", 23)));
+        assert_eq!(Err(DatabaseError::SecondDevice), db.add(DeviceObject::new(45, "Same device", 23)));
+        assert_eq!(Err(PropertyError::DuplicateName),
+                   db.set_value(&ObjectId(ObjectType::AnalogInput, 1), PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString("Two".to_string())));
+        assert_eq!(2, db.device().database_revision());
+    }
+
+    #[test]
+    fn renaming_changes_the_revision() {
+        let mut db = db_with_objects();
+        assert_eq!(Ok(()), db.set_value(&ObjectId(ObjectType::AnalogInput, 1), PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString("Uno".to_string())));
+        assert_eq!(Some(ObjectId(ObjectType::AnalogInput, 1)), db.find_object_by_name("Uno"));
+        assert_eq!(3, db.device().database_revision());
+        // Writing the same name again isn't a change
+        assert_eq!(Ok(()), db.set_value(&ObjectId(ObjectType::AnalogInput, 1), PropertyIdentifier::ObjectName, PrimitiveValue::CharacterString("Uno".to_string())));
+        assert_eq!(3, db.device().database_revision());
+    }
+
+    #[test]
+    fn remove_objects() {
+        let mut db = db_with_objects();
+        assert!(db.remove(&ObjectId(ObjectType::AnalogInput, 1)).is_some());
+        assert!(db.remove(&ObjectId(ObjectType::AnalogInput, 1)).is_none());
+        assert!(db.remove(&ObjectId(ObjectType::Device, 45)).is_none());
+        assert_eq!(&[ObjectId(ObjectType::Device, 45), ObjectId(ObjectType::AnalogInput, 2)], db.device().object_list());
+        assert_eq!(3, db.device().database_revision());
+        assert_eq!(None, db.find_object_by_name("One"));
+    }
+
+    #[test]
+    fn typed_and_generic_access() {
+        let mut db = db_with_objects();
+        assert_eq!(Ok(()), db.set_value(&ObjectId(ObjectType::AnalogInput, 2), PropertyIdentifier::PresentValue, PrimitiveValue::Unsigned(7)));
+        assert_eq!(7, db.get::<TestObject>(&ObjectId(ObjectType::AnalogInput, 2)).unwrap().value);
+        db.get_mut::<TestObject>(&ObjectId(ObjectType::AnalogInput, 2)).unwrap().value = 8;
+        assert_eq!(Ok(PrimitiveValue::Unsigned(8)), db.get_value(&ObjectId(ObjectType::AnalogInput, 2), PropertyIdentifier::PresentValue));
+        assert!(db.get::<DeviceObject>(&ObjectId(ObjectType::AnalogInput, 2)).is_none());
+        assert_eq!(ObjectId(ObjectType::Device, 45), db.get::<DeviceObject>(&ObjectId(ObjectType::Device, 45)).unwrap().object_id());
+        assert!(db.get_mut::<DeviceObject>(&ObjectId(ObjectType::Device, 45)).is_some());
+        assert!(db.get::<TestObject>(&ObjectId(ObjectType::Device, 45)).is_none());
+        assert_eq!(Ok(vec!(PropertyIdentifier::ObjectName, PropertyIdentifier::PresentValue)),
+                   db.expand_properties(&ObjectId(ObjectType::AnalogInput, 2), PropertyIdentifier::All));
+    }
+
+    #[test]
+    fn read_device_properties() {
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::ObjectId(device)))),
+                   test_db().read_property(&device, PropertyIdentifier::ObjectIdentifier, None));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Unsigned(23)))),
+                   test_db().read_property(&device, PropertyIdentifier::VendorIdentifier, None));
+        assert_eq!(Ok(PrimitiveValue::Enumerated(0)), test_db().get_value(&device, PropertyIdentifier::SystemStatus));
+        assert_eq!(Ok(PrimitiveValue::Unsigned(1)), test_db().get_value(&device, PropertyIdentifier::ProtocolVersion));
+        assert_eq!(Ok(PrimitiveValue::CharacterString(env!("CARGO_PKG_VERSION").to_string())),
+                   test_db().get_value(&device, PropertyIdentifier::FirmwareRevision));
+    }
+
+    #[test]
+    fn read_device_address_binding() {
+        use ast::{MacAddress, NetworkAddress};
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Ok(vec!()), test_db().read_property(&device, PropertyIdentifier::DeviceAddressBinding, None));
+        let peer = ObjectId(ObjectType::Device, 100);
+        let mut device_object = test_device();
+        device_object.device_address_binding.push((peer, NetworkAddress { network: 5, address: MacAddress::new(&[12]).unwrap() }));
+        let db = BacnetDB::new(device_object);
+        assert_eq!(Ok(vec!(
+            ApplicationValue(PrimitiveValue::ObjectId(peer)),
+            ApplicationValue(PrimitiveValue::Unsigned(5)),
+            ApplicationValue(PrimitiveValue::OctetString(vec!(12))))),
+                   db.read_property(&device, PropertyIdentifier::DeviceAddressBinding, None));
+    }
+
+    #[test]
+    fn object_types_supported() {
+        let mut db = db_with_objects();
+        let object_types = db.device().protocol_object_types_supported().clone();
+        assert_eq!(OBJECT_TYPES_SUPPORTED_LEN, object_types.len());
+        let supported: Vec<usize> = (0..object_types.len()).filter(|&bit| object_types.get(bit)).collect();
+        assert_eq!(vec!(0, 8), supported);
+        db.remove(&ObjectId(ObjectType::AnalogInput, 1));
+        db.remove(&ObjectId(ObjectType::AnalogInput, 2));
+        assert!(!db.device().protocol_object_types_supported().get(0));
+        assert_eq!(Ok(PrimitiveValue::BitString(db.device().protocol_object_types_supported().clone())),
+                   db.get_value(&ObjectId(ObjectType::Device, 45), PropertyIdentifier::ProtocolObjectTypesSupported));
+    }
+
+    #[test]
+    fn property_list() {
+        let db = db_with_objects();
+        let object = ObjectId(ObjectType::AnalogInput, 1);
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Enumerated(PropertyIdentifier::PresentValue.into())))),
+                   db.read_property(&object, PropertyIdentifier::PropertyList, None));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Unsigned(1)))),
+                   db.read_property(&object, PropertyIdentifier::PropertyList, Some(0)));
+        let device = db.read_property(&ObjectId(ObjectType::Device, 45), PropertyIdentifier::PropertyList, None).unwrap();
+        assert_eq!(DeviceObject::REQUIRED_PROPERTIES.len() - 3 + DeviceObject::OPTIONAL_PROPERTIES.len(), device.len());
+        assert_eq!(Err(PropertyError::WriteAccessDenied),
+                   test_db().write_property(&ObjectId(ObjectType::Device, 45), PropertyIdentifier::PropertyList, None, &vec!(), None));
+    }
+
+    #[test]
+    fn read_errors() {
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Err(PropertyError::UnknownObject),
+                   test_db().read_property(&ObjectId(ObjectType::Device, 46), PropertyIdentifier::ObjectIdentifier, None));
+        assert_eq!(Err(PropertyError::UnknownProperty),
+                   test_db().read_property(&device, PropertyIdentifier::PresentValue, None));
+        assert_eq!(Err(PropertyError::PropertyIsNotAnArray),
+                   test_db().read_property(&device, PropertyIdentifier::ObjectIdentifier, Some(1)));
+    }
+
+    #[test]
+    fn display_object_ids() {
+        assert_eq!("analog-input,3 present-value",
+                   format!("{} {}", ObjectId(ObjectType::AnalogInput, 3), PropertyIdentifier::PresentValue));
+        assert_eq!("device,45", ObjectId(ObjectType::Device, 45).to_string());
+    }
+
+    #[test]
+    fn find_objects_by_name() {
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Some("Test device"), test_db().object_name(&device));
+        assert_eq!(None, test_db().object_name(&ObjectId(ObjectType::Device, 46)));
+        assert_eq!(Some(device), test_db().find_object_by_name("Test device"));
+        assert_eq!(None, test_db().find_object_by_name("Test"));
+    }
+
+    #[test]
+    fn expand_special_properties() {
+        let device = ObjectId(ObjectType::Device, 45);
+        let all = test_db().expand_properties(&device, PropertyIdentifier::All).unwrap();
+        assert!(all.contains(&PropertyIdentifier::ObjectName));
+        assert_eq!(Ok(DeviceObject::REQUIRED_PROPERTIES.to_vec()), test_db().expand_properties(&device, PropertyIdentifier::Required));
+        assert_eq!(Ok(DeviceObject::OPTIONAL_PROPERTIES.to_vec()), test_db().expand_properties(&device, PropertyIdentifier::Optional));
+        assert_eq!(DeviceObject::REQUIRED_PROPERTIES.len() + DeviceObject::OPTIONAL_PROPERTIES.len(), all.len());
+        assert_eq!(Err(PropertyError::UnknownObject),
+                   test_db().expand_properties(&ObjectId(ObjectType::Device, 46), PropertyIdentifier::All));
+        for property in DeviceObject::REQUIRED_PROPERTIES {
+            assert!(test_db().read_property(&device, *property, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn write_device_properties() {
+        let device = ObjectId(ObjectType::Device, 45);
+        let mut db = test_db();
+        let name = vec!(ApplicationValue(PrimitiveValue::CharacterString("Boiler house".to_string())));
+        assert_eq!(Ok(()), db.write_property(&device, PropertyIdentifier::ObjectName, None, &name, None));
+        assert_eq!(Ok(name), db.read_property(&device, PropertyIdentifier::ObjectName, None));
+        let timeout = vec!(ApplicationValue(PrimitiveValue::Unsigned(10000)));
+        assert_eq!(Ok(()), db.write_property(&device, PropertyIdentifier::ApduTimeout, None, &timeout, Some(8)));
+        assert_eq!(10000, db.device().apdu_timeout);
+        assert_eq!(Ok(()), db.write_property(&device, PropertyIdentifier::ApduSegmentTimeout, None, &timeout, None));
+        assert_eq!(10000, db.device().apdu_segment_timeout);
+        let zero = vec!(ApplicationValue(PrimitiveValue::Unsigned(0)));
+        assert_eq!(Err(PropertyError::ValueOutOfRange),
+                   db.write_property(&device, PropertyIdentifier::ApduSegmentTimeout, None, &zero, None));
+    }
+
+    #[test]
+    fn write_errors() {
+        let device = ObjectId(ObjectType::Device, 45);
+        let unsigned = vec!(ApplicationValue(PrimitiveValue::Unsigned(1)));
+        assert_eq!(Err(PropertyError::UnknownObject),
+                   test_db().write_property(&ObjectId(ObjectType::AnalogInput, 1), PropertyIdentifier::ApduTimeout, None, &unsigned, None));
+        assert_eq!(Err(PropertyError::WriteAccessDenied),
+                   test_db().write_property(&device, PropertyIdentifier::VendorIdentifier, None, &unsigned, None));
+        assert_eq!(Err(PropertyError::WriteAccessDenied),
+                   test_db().write_property(&device, PropertyIdentifier::ObjectList, Some(1), &unsigned, None));
+        assert_eq!(Err(PropertyError::UnknownProperty),
+                   test_db().write_property(&device, PropertyIdentifier::PresentValue, None, &unsigned, None));
+        assert_eq!(Err(PropertyError::InvalidDataType),
+                   test_db().write_property(&device, PropertyIdentifier::ObjectName, None, &unsigned, None));
+        assert_eq!(Err(PropertyError::InvalidDataType),
+                   test_db().write_property(&device, PropertyIdentifier::ApduTimeout, None, &vec!(), None));
+        assert_eq!(Err(PropertyError::ValueOutOfRange),
+                   test_db().write_property(&device, PropertyIdentifier::ApduTimeout, None, &vec!(ApplicationValue(PrimitiveValue::Unsigned(0))), None));
+        assert_eq!(Err(PropertyError::PropertyIsNotAnArray),
+                   test_db().write_property(&device, PropertyIdentifier::ApduTimeout, Some(1), &unsigned, None));
+    }
+}
@@ -0,0 +1,263 @@
+//! Command prioritisation (Clause 19.2). A commandable property is written at a priority from 1,
+//! the highest, to 16, the lowest, and takes the value of the highest priority command. Writing
+//! NULL relinquishes a command, and with no commands left the property takes its relinquish
+//! default.
+
+use super::{PropertyError, array};
+use ast::{PrimitiveValue, ValueSequence};
+use enumerations::BinaryPv;
+use service::writeproperty::MIN_PRIORITY;
+use std::time::Duration;
+
+/// The priority a value is held at for its minimum on or off time
+pub const MINIMUM_ON_OFF_PRIORITY: u8 = 6;
+
+/// A value which can be commanded
+pub trait Command: Copy + PartialEq {
+    /// The value as it's read from the priority array
+    fn to_primitive(self) -> PrimitiveValue;
+
+    /// Whether the value is on, for values which are held for minimum on and off times
+    fn is_on(&self) -> Option<bool> {
+        None
+    }
+}
+
+impl Command for f32 {
+    fn to_primitive(self) -> PrimitiveValue {
+        PrimitiveValue::Real(self)
+    }
+}
+
+impl Command for BinaryPv {
+    fn to_primitive(self) -> PrimitiveValue {
+        PrimitiveValue::Enumerated(self.into())
+    }
+
+    fn is_on(&self) -> Option<bool> {
+        Some(*self == BinaryPv::Active)
+    }
+}
+
+/// The commands of a commandable property and the present value they give. After an on or off
+/// value becomes the present value it is held at priority 6 for the minimum on or off time
+/// (Clause 19.2.3), so it can only be overridden by higher priority commands.
+///
+/// Times are given by the caller, and changes of present value are returned with the time they
+/// happened at - a held value is released at the end of its minimum time even if that's before
+/// the next call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriorityArray<T> {
+    commands: [Option<T>; MIN_PRIORITY as usize],
+    relinquish_default: T,
+    present_value: T,
+    minimum_on_time: Duration,
+    minimum_off_time: Duration,
+    /// The value being held for its minimum on or off time, and when it is released
+    held: Option<(T, Duration)>,
+}
+
+impl<T: Command> PriorityArray<T> {
+    /// A priority array without any commands or minimum on and off times
+    pub fn new(relinquish_default: T) -> PriorityArray<T> {
+        PriorityArray {
+            commands: [None; MIN_PRIORITY as usize],
+            relinquish_default,
+            present_value: relinquish_default,
+            minimum_on_time: Duration::ZERO,
+            minimum_off_time: Duration::ZERO,
+            held: None,
+        }
+    }
+
+    /// The present value, as of the last command or release
+    pub fn present_value(&self) -> T {
+        self.present_value
+    }
+
+    pub fn relinquish_default(&self) -> T {
+        self.relinquish_default
+    }
+
+    /// The command at each priority, priority 1 first, with a held value at priority 6
+    pub fn commands(&self) -> [Option<T>; MIN_PRIORITY as usize] {
+        let mut commands = self.commands;
+        if let Some((value, _)) = self.held {
+            commands[MINIMUM_ON_OFF_PRIORITY as usize - 1] = Some(value);
+        }
+        commands
+    }
+
+    pub fn minimum_on_time(&self) -> Duration {
+        self.minimum_on_time
+    }
+
+    pub fn minimum_off_time(&self) -> Duration {
+        self.minimum_off_time
+    }
+
+    /// How long an on value is held, taking effect from the next change of present value
+    pub fn set_minimum_on_time(&mut self, minimum_on_time: Duration) {
+        self.minimum_on_time = minimum_on_time;
+    }
+
+    /// How long an off value is held, taking effect from the next change of present value
+    pub fn set_minimum_off_time(&mut self, minimum_off_time: Duration) {
+        self.minimum_off_time = minimum_off_time;
+    }
+
+    /// Writes a command, or relinquishes the command at the priority if `value` is `None`, giving
+    /// the changes of present value. Priority 6 is kept for holding values while there is a
+    /// minimum on or off time.
+    pub fn command(&mut self, priority: u8, value: Option<T>, now: Duration) -> Result<Vec<(T, Duration)>, PropertyError> {
+        if !(1..=MIN_PRIORITY).contains(&priority) {
+            return Err(PropertyError::ValueOutOfRange)
+        }
+        if priority == MINIMUM_ON_OFF_PRIORITY && self.has_minimum_time() {
+            return Err(PropertyError::WriteAccessDenied)
+        }
+        let mut changes = self.release(now);
+        self.commands[priority as usize - 1] = value;
+        changes.extend(self.update(now));
+        Ok(changes)
+    }
+
+    /// Whether values are held for a minimum on or off time
+    fn has_minimum_time(&self) -> bool {
+        self.minimum_on_time > Duration::ZERO || self.minimum_off_time > Duration::ZERO
+    }
+
+    /// Changes the relinquish default, giving the changes of present value
+    pub fn set_relinquish_default(&mut self, relinquish_default: T, now: Duration) -> Vec<(T, Duration)> {
+        let mut changes = self.release(now);
+        self.relinquish_default = relinquish_default;
+        changes.extend(self.update(now));
+        changes
+    }
+
+    /// Releases held values whose minimum time has run out by `now`, giving the changes of
+    /// present value. Each change happens when the minimum time ran out and is held in turn.
+    pub fn release(&mut self, now: Duration) -> Vec<(T, Duration)> {
+        let mut changes = vec!();
+        while let Some((_, until)) = self.held {
+            if until > now {
+                break
+            }
+            self.held = None;
+            changes.extend(self.update(until));
+        }
+        changes
+    }
+
+    /// Moves the present value to the highest priority command, holding an on or off value for
+    /// its minimum time
+    fn update(&mut self, now: Duration) -> Option<(T, Duration)> {
+        let commanded = self.commands().iter().flatten().next().cloned().unwrap_or(self.relinquish_default);
+        if commanded == self.present_value {
+            return None
+        }
+        self.present_value = commanded;
+        let minimum_time = match commanded.is_on() {
+            Some(true) => self.minimum_on_time,
+            Some(false) => self.minimum_off_time,
+            None => Duration::ZERO,
+        };
+        if minimum_time > Duration::ZERO {
+            self.held = Some((commanded, now + minimum_time));
+        }
+        Some((commanded, now))
+    }
+}
+
+/// The Priority_Array property, or one element of it, NULL where there isn't a command
+pub fn read_priority_array<T: Command>(priority_array: &PriorityArray<T>, array_index: Option<u32>) -> Result<ValueSequence, PropertyError> {
+    array(priority_array.commands().iter().map(|command| command.map_or(PrimitiveValue::Null, Command::to_primitive)).collect(), array_index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    fn binary(minimum_on_time: u64, minimum_off_time: u64) -> PriorityArray<BinaryPv> {
+        let mut priority_array = PriorityArray::new(BinaryPv::Inactive);
+        priority_array.set_minimum_on_time(secs(minimum_on_time));
+        priority_array.set_minimum_off_time(secs(minimum_off_time));
+        priority_array
+    }
+
+    #[test]
+    fn highest_priority_wins() {
+        let mut priority_array = PriorityArray::new(20.0);
+        assert_eq!(Ok(vec!((18.0, secs(0)))), priority_array.command(16, Some(18.0), secs(0)));
+        assert_eq!(Ok(vec!((22.0, secs(1)))), priority_array.command(8, Some(22.0), secs(1)));
+        assert_eq!(Ok(vec!()), priority_array.command(10, Some(19.0), secs(2)));
+        assert_eq!(22.0, priority_array.present_value());
+        let mut expected = [None; 16];
+        expected[7] = Some(22.0);
+        expected[9] = Some(19.0);
+        expected[15] = Some(18.0);
+        assert_eq!(expected, priority_array.commands());
+    }
+
+    #[test]
+    fn relinquishing_falls_back() {
+        let mut priority_array = PriorityArray::new(20.0);
+        priority_array.command(16, Some(18.0), secs(0)).unwrap();
+        priority_array.command(1, Some(25.0), secs(0)).unwrap();
+        assert_eq!(Ok(vec!((18.0, secs(1)))), priority_array.command(1, None, secs(1)));
+        assert_eq!(Ok(vec!((20.0, secs(2)))), priority_array.command(16, None, secs(2)));
+        assert_eq!(vec!((21.0, secs(3))), priority_array.set_relinquish_default(21.0, secs(3)));
+        assert_eq!(21.0, priority_array.present_value());
+        assert_eq!([None; 16], priority_array.commands());
+    }
+
+    #[test]
+    fn priorities_are_checked() {
+        let mut priority_array = PriorityArray::new(20.0);
+        assert_eq!(Err(PropertyError::ValueOutOfRange), priority_array.command(0, Some(1.0), secs(0)));
+        assert_eq!(Err(PropertyError::ValueOutOfRange), priority_array.command(17, Some(1.0), secs(0)));
+        // Priority 6 is only kept while values are held for a minimum time
+        assert!(priority_array.command(6, Some(1.0), secs(0)).is_ok());
+        assert_eq!(Ok(vec!((BinaryPv::Active, secs(0)))), binary(0, 0).command(6, Some(BinaryPv::Active), secs(0)));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), binary(60, 0).command(6, Some(BinaryPv::Active), secs(0)));
+        assert_eq!(Err(PropertyError::WriteAccessDenied), binary(0, 30).command(6, Some(BinaryPv::Active), secs(0)));
+    }
+
+    #[test]
+    fn minimum_on_and_off_times() {
+        let mut priority_array = binary(60, 30);
+        assert_eq!(Ok(vec!((BinaryPv::Active, secs(0)))), priority_array.command(8, Some(BinaryPv::Active), secs(0)));
+        assert_eq!(Some(BinaryPv::Active), priority_array.commands()[5]);
+        assert_eq!(Ok(vec!()), priority_array.command(8, None, secs(20)));
+        assert_eq!(BinaryPv::Active, priority_array.present_value());
+        // Released when the minimum on time ran out, then held off
+        assert_eq!(Ok(vec!((BinaryPv::Inactive, secs(60)))), priority_array.command(16, Some(BinaryPv::Active), secs(70)));
+        assert_eq!(Some(BinaryPv::Inactive), priority_array.commands()[5]);
+        assert_eq!(BinaryPv::Inactive, priority_array.present_value());
+        assert_eq!(vec!((BinaryPv::Active, secs(90))), priority_array.release(secs(100)));
+        assert_eq!(BinaryPv::Active, priority_array.present_value());
+    }
+
+    #[test]
+    fn higher_priorities_override_held_values() {
+        let mut priority_array = binary(60, 0);
+        priority_array.command(8, Some(BinaryPv::Active), secs(0)).unwrap();
+        assert_eq!(Ok(vec!((BinaryPv::Inactive, secs(10)))), priority_array.command(5, Some(BinaryPv::Inactive), secs(10)));
+        assert_eq!(Ok(vec!((BinaryPv::Active, secs(20)))), priority_array.command(5, None, secs(20)));
+    }
+
+    #[test]
+    fn release_only_when_the_time_runs_out() {
+        let mut priority_array = binary(10, 0);
+        priority_array.command(16, Some(BinaryPv::Active), secs(0)).unwrap();
+        priority_array.command(16, None, secs(1)).unwrap();
+        assert!(priority_array.release(secs(9)).is_empty());
+        assert_eq!(vec!((BinaryPv::Inactive, secs(10))), priority_array.release(secs(10)));
+        // Off isn't held
+        assert_eq!([None; 16], priority_array.commands());
+    }
+}
@@ -1,71 +1,28 @@
 use ast;
-use ast::SequenceableValue;
-use std::io;
+use decode;
+use decode::{decode_value_sequence_to_end, decode_value_sequence_with_extra_values, Decoder, Tag};
 use std::io::Read;
 
+pub use decode::{ParseError, PrimitiveType};
 
-#[derive(Debug)]
-pub enum ParseError {
-    ReadError(io::Error),
-    InputEndedBeforeParsingCompleted, // "Input ended before parsing completed"
-    ValueSizeNotSupported, // such as an 8byte integer
-    NotImplemented(&'static str),
-}
-
-impl PartialEq for ParseError {
-    fn eq(&self, other: &ParseError) -> bool {
-        match (self, other) {
-            (&ParseError::ReadError(_), &ParseError::ReadError(_)) => true,
-            (&ParseError::InputEndedBeforeParsingCompleted, &ParseError::InputEndedBeforeParsingCompleted) => true,
-            (&ParseError::ValueSizeNotSupported, &ParseError::ValueSizeNotSupported) => true,
-            (&ParseError::NotImplemented(string1), &ParseError::NotImplemented(string2)) => string1 == string2,
-            _ => false
-        }
-    }
-}
-
-pub fn parse_apdu_header(reader: &mut Read) -> Result<ast::ApduHeader, ParseError> {
-    use ast::ApduHeader;
-
-    let first_byte = try!(read_one_byte(reader));
-    match ((first_byte & 0xF0u8) >> 4, first_byte & 0x0Fu8) {
-        (0, 0) => {
-            let second_byte = try!(read_one_byte(reader));
-            Ok(ApduHeader::ConfirmedReq { 
-                segmented: None,
-                segmented_response_accepted: false,
-                max_segments: (second_byte >> 4) & 0b111,
-                max_apdu: second_byte & 0x0F,
-                invoke_id: try!(read_one_byte(reader)),
-                service: try!(read_one_byte(reader)),
-            })
-        },
-        (0, _) => 
-            Err(ParseError::NotImplemented("Segmentation")),
-        (1, _) =>
-            Ok(ApduHeader::UnconfirmedReq {
-                service: try!(read_one_byte(reader)),
-            }),
-        (2, _) =>
-            Ok(ApduHeader::SimpleAck {
-                invoke_id: try!(read_one_byte(reader)),
-                service: try!(read_one_byte(reader)),
-            }),
-        _ => 
-            Err(ParseError::NotImplemented("")),
-    }
+/// Parses an APDU header (Clause 20.1), leaving `data` at the start of the service data
+pub fn parse_apdu_header(data: &mut &[u8]) -> Result<ast::ApduHeader, ParseError> {
+    let mut decoder = Decoder::new(data);
+    let header = decoder.apdu_header()?;
+    *data = decoder.remaining();
+    Ok(header)
 }
 
 #[cfg(test)]
 mod test_apdu_header_parse {
     use super::parse_apdu_header;
     use super::ParseError;
-    use ast::ApduHeader;
-    use std::io;
+    use ast::{ApduHeader, MaxApdu, MaxSegments};
+    use ast::SegmentInfo;
+    use enumerations::{AbortReason, RejectReason};
 
-    fn parse_array(data: &[u8]) -> Result<ApduHeader, ParseError> {
-        let mut reader: &mut io::Read = &mut io::Cursor::new(data);
-        parse_apdu_header(reader)
+    fn parse_array(mut data: &[u8]) -> Result<ApduHeader, ParseError> {
+        parse_apdu_header(&mut data)
     }
 
     #[test]
@@ -73,8 +30,8 @@ mod test_apdu_header_parse {
         assert_eq!(Ok(ApduHeader::ConfirmedReq {
             segmented: None,
             segmented_response_accepted: false,
-            max_segments: 0x0,
-            max_apdu: 5,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up1476,
             invoke_id: 1,
             service: 15,
         }), parse_array(&[0u8, 5, 1, 15]));
@@ -94,113 +51,471 @@ mod test_apdu_header_parse {
             service: 15,
         }), parse_array(&[0x20u8, 1, 15]));
     }
+
+    #[test]
+    fn parse_confirmed_segmented() {
+        assert_eq!(Ok(ApduHeader::ConfirmedReq {
+            segmented: Some(SegmentInfo {
+                more_follows: true,
+                sequence_number: 255,
+                proposed_window_size: 127,
+            }),
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::MoreThan64,
+            max_apdu: MaxApdu::Reserved(0xF),
+            invoke_id: 253,
+            service: 254,
+        }), parse_array(&[0x0Eu8, 0x7F, 253, 255, 127, 254]));
+    }
+
+    #[test]
+    fn parse_confirmed_segmented_response_accepted() {
+        assert_eq!(Ok(ApduHeader::ConfirmedReq {
+            segmented: None,
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::Up32,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 1,
+            service: 14,
+        }), parse_array(&[0x02u8, 0x55, 1, 14]));
+    }
+
+    #[test]
+    fn parse_complexack() {
+        assert_eq!(Ok(ApduHeader::ComplexAck {
+            segmented: None,
+            invoke_id: 1,
+            service: 15,
+        }), parse_array(&[0x30u8, 1, 15]));
+        assert_eq!(Ok(ApduHeader::ComplexAck {
+            segmented: Some(SegmentInfo {
+                more_follows: false,
+                sequence_number: 3,
+                proposed_window_size: 4,
+            }),
+            invoke_id: 253,
+            service: 254,
+        }), parse_array(&[0x38u8, 253, 3, 4, 254]));
+    }
+
+    #[test]
+    fn parse_segment_ack() {
+        assert_eq!(Ok(ApduHeader::SegmentAck {
+            negative_ack: true,
+            server: false,
+            invoke_id: 1,
+            sequence_number: 45,
+            actual_window_size: 57,
+        }), parse_array(&[0x42u8, 1, 45, 57]));
+    }
+
+    #[test]
+    fn parse_error_reject_abort() {
+        assert_eq!(Ok(ApduHeader::ErrorPdu { invoke_id: 1, error_choice: 127 }), parse_array(&[0x50u8, 1, 127]));
+        assert_eq!(Ok(ApduHeader::RejectPdu { invoke_id: 1, reject_reason: RejectReason::Proprietary(127) }), parse_array(&[0x60u8, 1, 127]));
+        assert_eq!(Ok(ApduHeader::AbortPdu { server: true, invoke_id: 1, abort_reason: AbortReason::Proprietary(127) }), parse_array(&[0x71u8, 1, 127]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(ParseError::InvalidValue("Reserved PDU type"), parse_array(&[0x80u8, 1, 1]).unwrap_err());
+        assert_eq!(ParseError::InputEndedBeforeParsingCompleted, parse_array(&[0x08u8, 0x05, 1, 0]).unwrap_err());
+    }
+
+    #[test]
+    fn parse_leaves_the_service_data() {
+        let mut data: &[u8] = &[0x10u8, 0x08, 0x09, 0x01];
+        assert_eq!(Ok(ApduHeader::UnconfirmedReq { service: 8 }), parse_apdu_header(&mut data));
+        assert_eq!(&[0x09u8, 0x01], data);
+    }
+}
+
+/// Parses an NPDU header (Clause 6.2), leaving `data` at the start of the APDU or the network
+/// layer message content
+pub fn parse_npdu(data: &mut &[u8]) -> Result<ast::Npdu, ParseError> {
+    let mut decoder = Decoder::new(data);
+    let npdu = decoder.npdu()?;
+    *data = decoder.remaining();
+    Ok(npdu)
+}
+
+/// Splits a received datagram into its NPDU header and the APDU or network layer message content
+pub fn split_npdu(data: &[u8]) -> Result<(ast::Npdu, &[u8]), ParseError> {
+    let mut remaining = data;
+    let npdu = parse_npdu(&mut remaining)?;
+    Ok((npdu, remaining))
 }
 
-pub type Context = fn(u8) -> u8;
+#[cfg(test)]
+mod test_npdu_parse {
+    use super::parse_npdu;
+    use super::split_npdu;
+    use super::ParseError;
+    use ast::Npdu;
+    use ast::{MacAddress, NetworkAddress};
+    use ast::NetworkMessage;
+
+    fn parse_array(mut data: &[u8]) -> Result<Npdu, ParseError> {
+        parse_npdu(&mut data)
+    }
+
+    #[test]
+    fn parse_local() {
+        assert_eq!(Ok(Npdu::local(false)), parse_array(&[0x01u8, 0x00]));
+        assert_eq!(Ok(Npdu::local(true)), parse_array(&[0x01u8, 0x04]));
+    }
+
+    #[test]
+    fn parse_priority() {
+        assert_eq!(Ok(Npdu { priority: 3, ..Npdu::local(false) }), parse_array(&[0x01u8, 0x03]));
+    }
+
+    #[test]
+    fn parse_global_broadcast() {
+        assert_eq!(Ok(Npdu {
+            destination: Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }),
+            ..Npdu::local(false)
+        }), parse_array(&[0x01u8, 0x20, 0xFF, 0xFF, 0x00, 0xFF]));
+    }
+
+    #[test]
+    fn parse_routed() {
+        assert_eq!(Ok(Npdu {
+            expecting_reply: true,
+            destination: Some(NetworkAddress { network: 5, address: MacAddress::new(&[0x0A]).unwrap() }),
+            source: Some(NetworkAddress { network: 2, address: MacAddress::new(&[0x01, 0x02]).unwrap() }),
+            hop_count: 254,
+            ..Npdu::local(false)
+        }), parse_array(&[0x01u8, 0x2C, 0x00, 0x05, 0x01, 0x0A, 0x00, 0x02, 0x02, 0x01, 0x02, 0xFE]));
+    }
+
+    #[test]
+    fn parse_from_remote_network() {
+        assert_eq!(Ok(Npdu {
+            source: Some(NetworkAddress { network: 2, address: MacAddress::new(&[0xC0, 0xA8, 0x00, 0x01, 0xBA, 0xC0]).unwrap() }),
+            ..Npdu::local(false)
+        }), parse_array(&[0x01u8, 0x08, 0x00, 0x02, 0x06, 0xC0, 0xA8, 0x00, 0x01, 0xBA, 0xC0]));
+    }
+
+    #[test]
+    fn parse_network_messages() {
+        assert_eq!(Ok(Npdu {
+            network_message: Some(NetworkMessage { message_type: 0x00, vendor_id: None }),
+            ..Npdu::local(false)
+        }), parse_array(&[0x01u8, 0x80, 0x00]));
+        assert_eq!(Ok(Npdu {
+            network_message: Some(NetworkMessage { message_type: 0x80, vendor_id: Some(260) }),
+            ..Npdu::local(false)
+        }), parse_array(&[0x01u8, 0x80, 0x80, 0x01, 0x04]));
+    }
 
-pub fn parse_value_sequence_to_end(reader: &mut Read, context: Context) -> Result<ast::ValueSequence, ParseError> {
-    let mut list = vec!();
-    while let Some(child) = try!(parse_sequenceable_value(reader, context)) {
-        list.push(child);
+    #[test]
+    fn parse_errors() {
+        assert_eq!(ParseError::InvalidValue("Unsupported protocol version"), parse_array(&[0x02u8, 0x00]).unwrap_err());
+        assert_eq!(ParseError::InvalidValue("Source can't be a broadcast address"), parse_array(&[0x01u8, 0x08, 0x00, 0x02, 0x00]).unwrap_err());
+        assert_eq!(ParseError::InputEndedBeforeParsingCompleted, parse_array(&[0x01u8, 0x20, 0x00, 0x05, 0x01]).unwrap_err());
+    }
+
+    #[test]
+    fn split_datagram() {
+        let (npdu, apdu) = split_npdu(&[0x01u8, 0x20, 0xFF, 0xFF, 0x00, 0xFF, 0x10, 0x08]).unwrap();
+        assert_eq!(Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }), npdu.destination);
+        assert_eq!(&[0x10u8, 0x08], apdu);
     }
-    Ok(list)
+}
+
+/// Parses a BACnet/IP BVLC message (Annex J.2), including the NPDU it carries
+pub fn parse_bvlc(data: &[u8]) -> Result<ast::Bvlc, ParseError> {
+    Ok(Decoder::new(data).bvlc()?)
 }
 
 #[cfg(test)]
-mod test_value_sequence_parse {
+mod test_bvlc_parse {
+    use super::parse_bvlc;
     use super::ParseError;
-    use ast;
-    use ast::PrimitiveValue;
-    use ast::SequenceableValue::ContextValue;
-    use ast::SequenceableValue::ApplicationValue;
-    use std::io;
-    use super::parse_value_sequence_to_end;
+    use ast::{Bvlc, BdtEntry, FdtEntry};
+    use std::net::{Ipv4Addr, SocketAddrV4};
 
-    fn context(context_tag: u8) -> u8 {
-        context_tag - 1 // this gives us access to all the types for testing in a simple way
+    fn parse_array(data: &[u8]) -> Result<Bvlc, ParseError> {
+        parse_bvlc(data)
     }
 
-    fn parse_array(data: &[u8]) -> Result<ast::ValueSequence, ParseError> {
-        let mut reader: &mut io::Read = &mut io::Cursor::new(data);
-        parse_value_sequence_to_end(reader, context)
+    #[test]
+    fn parse_result() {
+        assert_eq!(Ok(Bvlc::Result(0x0030)), parse_array(&[0x81u8, 0x00, 0x00, 0x06, 0x00, 0x30]));
     }
 
-    fn parsed_value_sequence_eq(data: &[u8], value: ast::ValueSequence) {
-        assert_eq!(Ok(value), parse_array(data));
+    #[test]
+    fn parse_npdus() {
+        assert_eq!(Ok(Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00, 0x10, 0x08])),
+                   parse_array(&[0x81u8, 0x0A, 0x00, 0x08, 0x01, 0x00, 0x10, 0x08]));
+        assert_eq!(Ok(Bvlc::OriginalBroadcastNpdu(vec![0x01, 0x00, 0x10, 0x08])),
+                   parse_array(&[0x81u8, 0x0B, 0x00, 0x08, 0x01, 0x00, 0x10, 0x08]));
+        assert_eq!(Ok(Bvlc::DistributeBroadcastToNetwork(vec![0x01, 0x00, 0x10, 0x08])),
+                   parse_array(&[0x81u8, 0x09, 0x00, 0x08, 0x01, 0x00, 0x10, 0x08]));
+        assert_eq!(Ok(Bvlc::ForwardedNpdu {
+            originator: SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 0xBAC0),
+            npdu: vec![0x01, 0x00, 0x10, 0x08],
+        }), parse_array(&[0x81u8, 0x04, 0x00, 0x0E, 0xC0, 0xA8, 0x00, 0x01, 0xBA, 0xC0, 0x01, 0x00, 0x10, 0x08]));
     }
 
     #[test]
-    fn parse_empty_value_sequence() {
-        parsed_value_sequence_eq(&[], vec!())
+    fn parse_bdt() {
+        let table = vec![BdtEntry {
+            address: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 47808),
+            broadcast_mask: Ipv4Addr::new(255, 255, 255, 255),
+        }];
+        assert_eq!(Ok(Bvlc::ReadBroadcastDistributionTable), parse_array(&[0x81u8, 0x02, 0x00, 0x04]));
+        assert_eq!(Ok(Bvlc::ReadBroadcastDistributionTableAck(table.clone())),
+                   parse_array(&[0x81u8, 0x03, 0x00, 0x0E, 0x0A, 0x00, 0x00, 0x01, 0xBA, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF]));
+        assert_eq!(Ok(Bvlc::WriteBroadcastDistributionTable(table)),
+                   parse_array(&[0x81u8, 0x01, 0x00, 0x0E, 0x0A, 0x00, 0x00, 0x01, 0xBA, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF]));
+        assert_eq!(ParseError::InvalidValue("Partial broadcast distribution table entry"),
+                   parse_array(&[0x81u8, 0x01, 0x00, 0x06, 0x0A, 0x00]).unwrap_err());
     }
 
     #[test]
-    fn parse_basic_value_sequence() {
-        parsed_value_sequence_eq(&[0x22u8, 0x99, 0x88, 0x28], vec!(ApplicationValue(PrimitiveValue::Unsigned(0x9988)), ContextValue(2, PrimitiveValue::Boolean(false))))
+    fn parse_foreign_devices() {
+        assert_eq!(Ok(Bvlc::RegisterForeignDevice(60)), parse_array(&[0x81u8, 0x05, 0x00, 0x06, 0x00, 0x3C]));
+        assert_eq!(Ok(Bvlc::ReadForeignDeviceTable), parse_array(&[0x81u8, 0x06, 0x00, 0x04]));
+        assert_eq!(Ok(Bvlc::ReadForeignDeviceTableAck(vec![FdtEntry {
+            address: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 47808),
+            time_to_live: 60,
+            time_remaining: 75,
+        }])), parse_array(&[0x81u8, 0x07, 0x00, 0x0E, 0x0A, 0x00, 0x00, 0x02, 0xBA, 0xC0, 0x00, 0x3C, 0x00, 0x4B]));
+        assert_eq!(Ok(Bvlc::DeleteForeignDeviceTableEntry(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 47808))),
+                   parse_array(&[0x81u8, 0x08, 0x00, 0x0A, 0x0A, 0x00, 0x00, 0x02, 0xBA, 0xC0]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(ParseError::InvalidValue("Not a BACnet/IP BVLC"), parse_array(&[0x82u8, 0x0A, 0x00, 0x04]).unwrap_err());
+        assert_eq!(ParseError::NotImplemented("BVLC function"), parse_array(&[0x81u8, 0x0C, 0x00, 0x04]).unwrap_err());
+        assert_eq!(ParseError::InputEndedBeforeParsingCompleted, parse_array(&[0x81u8, 0x0A, 0x00, 0x08, 0x01]).unwrap_err());
+        assert_eq!(ParseError::InputEndedBeforeParsingCompleted, parse_array(&[0x81u8, 0x04, 0x00, 0x06, 0x0A, 0x00]).unwrap_err());
     }
 }
 
-/// Should be called when the reader's next octet is the start of a tag. Returns none if the parsed
-/// value is a close tag, or if the reader is at the end to start with.
+/// Describes a production of the ASN.1 in Clause 21 as far as is needed to decode it. Context
+/// tags only have a meaning within the production they're in, so the parser walks the schema as
+/// it reads the values, descending into the schema of a constructed value at its opening tag.
+/// What comes next is always decided by the next tag, as the encoding rules make sure it can be.
+#[derive(Debug, PartialEq)]
+pub enum Schema {
+    /// A primitive value with the context tag number
+    Context(u8, PrimitiveType),
+    /// An application tagged value of any type
+    Application,
+    /// A production enclosed in opening and closing tags with the context tag number
+    Constructed(u8, &'static Schema),
+    /// Each of the productions in order
+    Sequence(&'static [Schema]),
+    /// One of the productions, the one whose tag comes next
+    Choice(&'static [Schema]),
+    /// Any number of the production
+    SequenceOf(&'static Schema),
+    /// The production if its tag comes next
+    Optional(&'static Schema),
+    /// Values of any type, as in ABSTRACT-SYNTAX.&Type, up to the closing tag or the end. They
+    /// are usually application tagged, the type of a context tagged primitive isn't known so its
+    /// content octets are kept as an octet string, which encodes back to the same octets.
+    Any,
+}
+
+impl Schema {
+    /// Whether the production can start with `tag`, which is `None` at the end of the input
+    pub(crate) fn starts_with(&self, tag: Option<&Tag>) -> bool {
+        match (self, tag) {
+            (&Schema::Context(number, _), Some(&Tag::Context(tag_number, _))) => number == tag_number,
+            (&Schema::Application, Some(&Tag::Application(..))) => true,
+            (&Schema::Constructed(number, _), Some(&Tag::Open(tag_number))) => number == tag_number,
+            (&Schema::Sequence(schemas), _) => {
+                for schema in schemas {
+                    if schema.starts_with(tag) {
+                        return true
+                    }
+                    if !schema.is_optional() {
+                        return false
+                    }
+                }
+                false
+            },
+            (&Schema::Choice(schemas), _) => schemas.iter().any(|schema| schema.starts_with(tag)),
+            (&Schema::SequenceOf(schema), _) | (&Schema::Optional(schema), _) => schema.starts_with(tag),
+            (&Schema::Any, Some(&Tag::Close(_))) | (&Schema::Any, None) => false,
+            (&Schema::Any, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the production can be left out entirely
+    pub(crate) fn is_optional(&self) -> bool {
+        match *self {
+            Schema::Optional(_) | Schema::SequenceOf(_) | Schema::Any => true,
+            Schema::Sequence(schemas) => schemas.iter().all(Schema::is_optional),
+            Schema::Choice(schemas) => schemas.iter().any(Schema::is_optional),
+            _ => false,
+        }
+    }
+}
+
+/// Parses the whole of the input as the production described by `schema`, decoding it with
+/// `decode::Decoder` once it has all been read
 ///
 /// # Errors
 ///
-/// - if this is a context tag and there is no entry in the context for this tag
+/// - if a tag is found which the schema doesn't allow where it is, or a required value is
+///   missing
 /// - if the application tag is unsupported / reserved
 /// - if the length is too long
 /// - if the value can't be parsed
-/// - if the reader reaches the end of input before the parsing is complete
-pub fn parse_sequenceable_value(reader: &mut Read, context: Context) -> Result<Option<SequenceableValue>, ParseError> {
-    match parse_tag(reader) {
-        Ok(Tag::Application(tag, tag_value)) => 
-            Ok(Some(SequenceableValue::ApplicationValue(try!(tag_to_value(reader, tag, tag_value))))),
-        Ok(Tag::Context(tag, tag_value)) => 
-            Ok(Some(SequenceableValue::ContextValue(tag, try!(tag_to_value(reader, context(tag), tag_value))))),
-        Ok(Tag::Close(_)) =>
-            Ok(None),
-        Ok(Tag::Open(tag)) => {
-            let mut list = vec!();
-            while let Some(child) = try!(parse_sequenceable_value(reader, context)) {
-                list.push(child);
-            }
-            Ok(Some(SequenceableValue::ContextValueSequence(tag, list)))
-        },
-        Err(ParseError::InputEndedBeforeParsingCompleted) =>
-            Ok(None),
-        Err(other) =>
-            Err(other),
-    }
+/// - if the reader reaches the end of input in the middle of a value
+pub fn parse_value_sequence_to_end(reader: &mut dyn Read, schema: &Schema) -> Result<ast::ValueSequence, ParseError> {
+    let data = read_to_end(reader)?;
+    Ok(decode::into_owned(decode_value_sequence_to_end(&data, schema)?))
+}
+
+/// Parses the production described by `schema`, and any values after it as `Schema::Any`. The
+/// values after the production are left for the message to reject as extra values, rather than
+/// failing here with a tag the schema doesn't allow.
+pub fn parse_value_sequence_with_extra_values(reader: &mut dyn Read, schema: &Schema) -> Result<ast::ValueSequence, ParseError> {
+    let data = read_to_end(reader)?;
+    Ok(decode::into_owned(decode_value_sequence_with_extra_values(&data, schema)?))
 }
 
-fn tag_to_value(reader: &mut Read, tag: u8, tag_value: u32) -> Result<ast::PrimitiveValue, ParseError> {
+/// Context tag n holds a value of the type with application tag n, which gives the tests a simple
+/// way to parse every type, and values in opening and closing tags 3 and 5 are of any type
+#[cfg(test)]
+pub const EVERY_TYPE: Schema = Schema::SequenceOf(&Schema::Choice(&[
+    Schema::Application,
+    Schema::Context(0, PrimitiveType::Null),
+    Schema::Context(1, PrimitiveType::Boolean),
+    Schema::Context(2, PrimitiveType::Unsigned),
+    Schema::Context(3, PrimitiveType::Signed),
+    Schema::Context(4, PrimitiveType::Real),
+    Schema::Context(5, PrimitiveType::Double),
+    Schema::Context(6, PrimitiveType::OctetString),
+    Schema::Context(7, PrimitiveType::CharacterString),
+    Schema::Context(8, PrimitiveType::BitString),
+    Schema::Context(9, PrimitiveType::Enumerated),
+    Schema::Context(10, PrimitiveType::Date),
+    Schema::Context(11, PrimitiveType::Time),
+    Schema::Context(12, PrimitiveType::ObjectId),
+    Schema::Constructed(3, &Schema::Any),
+    Schema::Constructed(5, &Schema::Any),
+]));
+
+#[cfg(test)]
+mod test_value_sequence_parse {
+    use super::{ParseError, PrimitiveType, Schema, EVERY_TYPE};
+    use ast;
     use ast::PrimitiveValue;
+    use ast::SequenceableValue::ContextValue;
+    use ast::SequenceableValue::ContextValueSequence;
+    use ast::SequenceableValue::ApplicationValue;
+    use std::io;
+    use super::{parse_value_sequence_to_end, parse_value_sequence_with_extra_values};
 
-    match tag {
-        0 => Ok(PrimitiveValue::Null),
-        1 => Ok(PrimitiveValue::Boolean(tag_value != 0)),
-        2 => Ok(PrimitiveValue::Unsigned(try!(read_unsigned(reader, tag_value as usize)))),
-        _ => Err(ParseError::NotImplemented("Some tag")),
+    fn parse_array(data: &[u8], schema: &Schema) -> Result<ast::ValueSequence, ParseError> {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, schema)
+    }
+
+    fn parsed_value_sequence_eq(data: &[u8], value: ast::ValueSequence) {
+        assert_eq!(Ok(value), parse_array(data, &EVERY_TYPE));
+    }
+
+    #[test]
+    fn parse_empty_value_sequence() {
+        parsed_value_sequence_eq(&[], vec!())
+    }
+
+    #[test]
+    fn parse_basic_value_sequence() {
+        parsed_value_sequence_eq(&[0x22u8, 0x99, 0x88, 0x19, 0x00], vec!(ApplicationValue(PrimitiveValue::Unsigned(0x9988)), ContextValue(1, PrimitiveValue::Boolean(false))))
+    }
+
+    /// A list of an enumerated with an optional unsigned, where the tag numbers are reused for
+    /// other types inside the constructed value
+    const LIST: Schema = Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::ObjectId),
+        Schema::Constructed(1, &Schema::SequenceOf(&Schema::Sequence(&[
+            Schema::Context(0, PrimitiveType::Enumerated),
+            Schema::Optional(&Schema::Context(1, PrimitiveType::Unsigned)),
+        ]))),
+        Schema::Optional(&Schema::Choice(&[
+            Schema::Context(2, PrimitiveType::Boolean),
+            Schema::Context(3, PrimitiveType::CharacterString),
+        ])),
+    ]);
+
+    #[test]
+    fn parse_nested_contexts() {
+        use object::ObjectId;
+        use enumerations::ObjectType;
+        let list = vec!(ContextValue(0, PrimitiveValue::Enumerated(85)), ContextValue(0, PrimitiveValue::Enumerated(87)), ContextValue(1, PrimitiveValue::Unsigned(3)));
+        assert_eq!(Ok(vec!(ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))), ContextValueSequence(1, list))),
+                   parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x09, 0x55, 0x09, 0x57, 0x19, 0x03, 0x1F], &LIST));
+        assert_eq!(Ok(vec!(ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))), ContextValueSequence(1, vec!()),
+                           ContextValue(3, PrimitiveValue::CharacterString("A".to_string())))),
+                   parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x1F, 0x3A, 0x00, 0x41], &LIST));
+    }
+
+    #[test]
+    fn parse_against_the_schema() {
+        // Missing the list
+        assert_eq!(Err(ParseError::MissingRequiredValue), parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D], &LIST));
+        // The list out of order
+        assert_eq!(Err(ParseError::UnexpectedTag), parse_array(&[0x1Eu8, 0x1F, 0x0C, 0x02, 0x00, 0x00, 0x2D], &LIST));
+        // A value after the end of the production
+        assert_eq!(Err(ParseError::UnexpectedTag), parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x1F, 0x29, 0x01, 0x21, 0x01], &LIST));
+        // The list closed with the wrong tag
+        assert_eq!(Err(ParseError::UnexpectedTag), parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x2F], &LIST));
+        assert_eq!(Err(ParseError::MissingRequiredValue), parse_array(&[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E], &LIST));
+    }
+
+    #[test]
+    fn parse_extra_values() {
+        use object::ObjectId;
+        use enumerations::ObjectType;
+        // The values after the production are of any type
+        let reader: &mut dyn io::Read = &mut &[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x1F, 0x49, 0x07, 0x21, 0x01][..];
+        assert_eq!(Ok(vec!(ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))), ContextValueSequence(1, vec!()),
+                           ContextValue(4, PrimitiveValue::OctetString(vec![0x07])), ApplicationValue(PrimitiveValue::Unsigned(1)))),
+                   parse_value_sequence_with_extra_values(reader, &LIST));
+        let reader: &mut dyn io::Read = &mut &[0x0Cu8, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x1F, 0x2F][..];
+        assert_eq!(Err(ParseError::UnexpectedTag), parse_value_sequence_with_extra_values(reader, &LIST));
+    }
+
+    #[test]
+    fn parse_any_values() {
+        // The types of context tagged values in an any value aren't known
+        assert_eq!(Ok(vec!(ContextValueSequence(3, vec!(
+            ApplicationValue(PrimitiveValue::Unsigned(1)),
+            ContextValue(0, PrimitiveValue::OctetString(vec![0x02, 0x00, 0x00, 0x2D])),
+            ContextValueSequence(1, vec!(ContextValue(2, PrimitiveValue::OctetString(vec![0x01]))))),
+        ))), parse_array(&[0x3Eu8, 0x21, 0x01, 0x0C, 0x02, 0x00, 0x00, 0x2D, 0x1E, 0x29, 0x01, 0x1F, 0x3F], &EVERY_TYPE));
+        assert_eq!(Ok(vec!(ApplicationValue(PrimitiveValue::Null), ContextValue(9, PrimitiveValue::OctetString(vec![])))),
+                   parse_array(&[0x00u8, 0x98], &Schema::Any));
+        assert_eq!(Err(ParseError::UnexpectedTag), parse_array(&[0x00u8, 0x9F], &Schema::Any));
     }
 }
 
 #[cfg(test)]
 mod parse_sequenceable_value_tests {
     use super::ParseError;
-    use super::parse_sequenceable_value;
+    use super::{parse_value_sequence_to_end, EVERY_TYPE};
     use ast;
     use ast::PrimitiveValue;
     use ast::SequenceableValue::ContextValue;
     use ast::SequenceableValue::ApplicationValue;
     use ast::SequenceableValue::ContextValueSequence;
     use std::io;
-
-    fn context(context_tag: u8) -> u8 {
-        context_tag - 1 // this gives us access to all the types for testing in a simple way
-    }
+    use enumerations::ObjectType;
 
     fn parse_array(data: &[u8]) -> Result<Option<ast::SequenceableValue>, ParseError> {
-        let mut reader: &mut io::Read = &mut io::Cursor::new(data);
-        parse_sequenceable_value(reader, context)
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, &EVERY_TYPE).map(|values| values.into_iter().next())
     }
 
     fn parsed_context_value_eq(data: &[u8], value: ast::SequenceableValue) {
@@ -224,24 +539,26 @@ mod parse_sequenceable_value_tests {
 
     #[test]
     fn parse_context_null() {
-        parsed_context_value_eq(&[0x18u8], ContextValue(1, PrimitiveValue::Null));
+        parsed_context_value_eq(&[0x08u8], ContextValue(0, PrimitiveValue::Null));
     }
 
     #[test]
     fn parse_context_boolean() {
-        parsed_context_value_eq(&[0x28u8], ContextValue(2, PrimitiveValue::Boolean(false)));
-        parsed_context_value_eq(&[0x29u8], ContextValue(2, PrimitiveValue::Boolean(true)));
+        parsed_context_value_eq(&[0x19u8, 0x00], ContextValue(1, PrimitiveValue::Boolean(false)));
+        parsed_context_value_eq(&[0x19u8, 0x01], ContextValue(1, PrimitiveValue::Boolean(true)));
+        // context booleans have a content octet
+        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x18u8]).unwrap_err());
     }
 
     #[test]
     fn parse_context_unsigned() {
         use ast::PrimitiveValue::Unsigned;
-        parsed_context_value_eq(&[0x39u8, 200], ContextValue(3, Unsigned(200)));
-        parsed_context_value_eq(&[0x3Au8, 0x99, 0x88], ContextValue(3, Unsigned(0x9988)));
-        parsed_context_value_eq(&[0x3Bu8, 0x99, 0x88, 0x77], ContextValue(3, Unsigned(0x998877)));
-        parsed_context_value_eq(&[0x3Cu8, 0x99, 0x88, 0x77, 0x66], ContextValue(3, Unsigned(0x99887766)));
+        parsed_context_value_eq(&[0x29u8, 200], ContextValue(2, Unsigned(200)));
+        parsed_context_value_eq(&[0x2Au8, 0x99, 0x88], ContextValue(2, Unsigned(0x9988)));
+        parsed_context_value_eq(&[0x2Bu8, 0x99, 0x88, 0x77], ContextValue(2, Unsigned(0x998877)));
+        parsed_context_value_eq(&[0x2Cu8, 0x99, 0x88, 0x77, 0x66], ContextValue(2, Unsigned(0x99887766)));
         // length > 4 not supported
-        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x3du8, 5]).unwrap_err());
+        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x2du8, 5]).unwrap_err());
     }
 
     fn parsed_application_value_eq(data: &[u8], value: PrimitiveValue) {
@@ -269,183 +586,117 @@ mod parse_sequenceable_value_tests {
         // length > 4 not supported
         assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x25u8, 5]).unwrap_err());
     }
-    // TODO tests for all the error types
-}
 
-#[derive(PartialEq, Debug)]
-enum Tag {
-    Application(u8, u32),
-    Context(u8, u32),
-    Open(u8),
-    Close(u8),
-}
-
-/// Call when the next octet is the start of a tag, advances the reader to after the tag and
-/// returns the Tag enum
-///
-/// # Errors
-/// - If the reader reaches the end before the parsing is complete
-///
-/// # Description of encoding
-///
-/// BACnet encoding encodes meta data into a preceding octet.
-/// BACnet has 2 classes of tags, context tags and application tags. The class is
-/// identifed by the 5th bit of the tag octet, a `1` indicating a context tag.
-/// The first 4 bits of a context tag are the context number - a context for the current production
-/// should then specify the purpose and the primitive type of the value.
-/// The first 4 bits of an application tag are the type number - identifiying the type of the
-/// value.
-/// The use of the last 3 bits of the tag depend on the type (identified either by the type field or
-/// by the context identified by the context field). For a boolean the value can be encoded
-/// directly into the tag, for a named tag, the last 3 bits identify the name, in general the last
-/// 3 bits identify the length of the value in octets.
-/// PD open / close tags are types of named context tags - their use must be identified by their
-/// context number and they will be encoded by an 0xE (for open) and 0xF (for close). Their purpose
-/// is to either allow for a context value with any type (the value would be specified as an
-/// application tag inside), to specify a contructed type (where the production inside would have a
-/// new context), or to group a sequence of values.
-///
-/// ## Extending type and length fields
-///
-/// The type field can be extended into another octet by using 0xF in the type field, then the next
-/// octet becomes the type field and type / context values 0-254 (255 is reserved).
-///
-/// The Length field can be extended by using 0b111 in the length field, the next octet becomes a
-/// length field, if the next octet is 0xFE, the next 2 octets become a length field, if it is 0xFF
-/// then the next 4 octets become a length field. This encoding allows lengths up to 2^32-1.
-fn parse_tag(reader: &mut Read) -> Result<Tag, ParseError> {
-    let first_byte = try!(read_one_byte(reader));
-    let mut tag_num = (first_byte & 0xF0) >> 4;
-    let class = (first_byte & 0x08) == 0x08;
-    let mut value = (first_byte & 0x07) as u32;
-
-    // Extended tag numbers
-    if tag_num == 0xF {
-        tag_num = try!(read_one_byte(reader));
-    }
-    // Open / Close tags
-    if value == 0x6 {
-        Ok(Tag::Open(tag_num))
-    } else if value == 0x7 {
-        Ok(Tag::Close(tag_num))
-    } // Extended values
-    else {
-        if value == 0x5 {
-            value = try!(read_one_byte(reader)) as u32;
-            if value == 0xFE {
-                value = try!(read_unsigned(reader, 2));
-            } else if value == 0xFF {
-                value = try!(read_unsigned(reader, 4));
-            }
-        }
-        Ok(
-            if class {
-                Tag::Context(tag_num, value)
-            } else {
-                Tag::Application(tag_num, value)
-            }
-        )
+    #[test]
+    fn parse_signed() {
+        use ast::PrimitiveValue::Signed;
+        parsed_application_value_eq(&[0x31u8, 0x48], Signed(72));
+        parsed_application_value_eq(&[0x31u8, 0xB8], Signed(-72));
+        parsed_application_value_eq(&[0x32u8, 0x80, 0x00], Signed(-32768));
+        parsed_application_value_eq(&[0x33u8, 0x00, 0x80, 0x00], Signed(32768));
+        parsed_application_value_eq(&[0x34u8, 0x80, 0x00, 0x00, 0x00], Signed(i32::MIN));
+        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x35u8, 5]).unwrap_err());
     }
-}
 
-#[cfg(test)]
-mod test_read_tag {
-    use super::parse_tag;
-    use super::Tag;
-    use std::io::Read;
-
-    fn assert_tag_read(data: &[u8], expected: Tag) {
-        let mut reader: &mut Read = &mut data.clone();
-        let new_tag = parse_tag(&mut reader).unwrap();
-        assert_eq!(expected, new_tag);
+    #[test]
+    fn parse_real() {
+        parsed_application_value_eq(&[0x44u8, 0x42, 0x90, 0x00, 0x00], PrimitiveValue::Real(72.0));
+        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0x43u8, 0x42, 0x90, 0x00]).unwrap_err());
     }
-   
+
     #[test]
-    fn open() {
-        assert_tag_read(&[0x2eu8], Tag::Open(2));
+    fn parse_double() {
+        parsed_application_value_eq(&[0x55u8, 0x08, 0x40, 0x52, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], PrimitiveValue::Double(72.0));
     }
 
     #[test]
-    fn close() {
-        assert_tag_read(&[0x2fu8], Tag::Close(2));
+    fn parse_character_string() {
+        use ast::PrimitiveValue::CharacterString;
+        parsed_application_value_eq(&[0x75u8, 0x19, 0x00, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20,
+                                      0x61, 0x20, 0x42, 0x41, 0x43, 0x6E, 0x65, 0x74, 0x20, 0x73, 0x74,
+                                      0x72, 0x69, 0x6E, 0x67, 0x21],
+                                    CharacterString("This is a BACnet string!".to_string()));
+        parsed_application_value_eq(&[0x71u8, 0x00], CharacterString("".to_string()));
+        parsed_application_value_eq(&[0x75u8, 0x05, 0x04, 0x00, 0x41, 0x00, 0xE9], CharacterString("A\u{e9}".to_string()));
+        parsed_application_value_eq(&[0x73u8, 0x05, 0x41, 0xE9], CharacterString("A\u{e9}".to_string()));
+        assert_eq!(ParseError::InvalidValue("Invalid UTF-8"), parse_array(&[0x72u8, 0x00, 0xFF]).unwrap_err());
+        assert_eq!(ParseError::NotImplemented("Character set"), parse_array(&[0x72u8, 0x01, 0x41]).unwrap_err());
+        assert_eq!(ParseError::InputEndedBeforeParsingCompleted, parse_array(&[0x74u8, 0x00, 0x41]).unwrap_err());
     }
 
     #[test]
-    fn application0() {
-        assert_tag_read(&[0x24u8], Tag::Application(2, 4));
+    fn parse_octet_string() {
+        parsed_application_value_eq(&[0x63u8, 0x12, 0x34, 0xFF], PrimitiveValue::OctetString(vec![0x12, 0x34, 0xFF]));
+        parsed_application_value_eq(&[0x60u8], PrimitiveValue::OctetString(vec![]));
     }
 
-    /// Extended tag number context tag.
     #[test]
-    fn context1() {
-        assert_tag_read(&[0xF9u8, 0x59], Tag::Context(0x59, 1));
+    fn parse_bit_string() {
+        use ast::BitString;
+        parsed_application_value_eq(&[0x82u8, 0x04, 0xB0], PrimitiveValue::BitString(BitString::from_bits(&[true, false, true, true])));
+        parsed_application_value_eq(&[0x81u8, 0x00], PrimitiveValue::BitString(BitString::new(0)));
+        assert_eq!(ParseError::InvalidValue("Invalid bit string unused bits"), parse_array(&[0x81u8, 0x01]).unwrap_err());
+        assert_eq!(ParseError::InvalidValue("Invalid bit string unused bits"), parse_array(&[0x82u8, 0x08, 0x00]).unwrap_err());
     }
-    
-    /// Extended value (8-bit) application tag.
+
     #[test]
-    fn application1() {
-        assert_tag_read(&[0x05u8, 200], Tag::Application(0, 200));
+    fn parse_date() {
+        use ast::{Date, Month, DayOfMonth, Year};
+        parsed_application_value_eq(&[0xA4u8, 0x5B, 0x01, 0x18, 0x04], PrimitiveValue::Date(Date {
+            year: Year::new(1991), month: Month::Number(1), day: DayOfMonth::Number(24), weekday: Some(4) }));
+        parsed_application_value_eq(&[0xA4u8, 0xFF, 0x0D, 0x20, 0xFF], PrimitiveValue::Date(Date {
+            year: None, month: Month::Odd, day: DayOfMonth::Last, weekday: None }));
+        parsed_application_value_eq(&[0xA4u8, 0x74, 0x0E, 0x21, 0x07], PrimitiveValue::Date(Date {
+            year: Year::new(2016), month: Month::Even, day: DayOfMonth::Odd, weekday: Some(7) }));
+        assert_eq!(ParseError::InvalidValue("Invalid month"), parse_array(&[0xA4u8, 0x74, 0x0F, 0x01, 0x01]).unwrap_err());
+        assert_eq!(ParseError::ValueSizeNotSupported, parse_array(&[0xA3u8, 0x74, 0x01, 0x01]).unwrap_err());
     }
-    
-    /// Extended value (16-bit) application tag.
+
     #[test]
-    fn application2() {
-        assert_tag_read(&[0x05u8, 0xFE, 0x59, 0x59], Tag::Application(0, 0x5959));
+    fn parse_time() {
+        use ast::Time;
+        parsed_application_value_eq(&[0xB4u8, 0x11, 0x23, 0x2D, 0x11], PrimitiveValue::Time(Time {
+            hour: Some(17), minute: Some(35), second: Some(45), hundredths: Some(17) }));
+        parsed_application_value_eq(&[0xB4u8, 0x11, 0xFF, 0xFF, 0xFF], PrimitiveValue::Time(Time {
+            hour: Some(17), minute: None, second: None, hundredths: None }));
+        assert_eq!(ParseError::InvalidValue("Invalid time"), parse_array(&[0xB4u8, 0x18, 0x00, 0x00, 0x00]).unwrap_err());
     }
-    
-    /// Extended value (32-bit) application tag.
+
     #[test]
-    fn application32 () {
-        assert_tag_read(&[0x05u8, 0xFF, 0x59, 0x59, 0x59, 0x59], Tag::Application(0, 0x59595959));
+    fn parse_enumerated() {
+        use ast::PrimitiveValue::Enumerated;
+        parsed_application_value_eq(&[0x91u8, 0x00], Enumerated(0));
+        parsed_application_value_eq(&[0x92u8, 0x01, 0x00], Enumerated(256));
     }
-}
 
-// Read an unsigned integer of the specified number of bytes
-fn read_unsigned(reader: &mut Read, size: usize) -> Result<u32, ParseError> {
-    if size > 4 {
-        return Err(ParseError::ValueSizeNotSupported)
+    #[test]
+    fn parse_object_id() {
+        use object::ObjectId;
+        parsed_application_value_eq(&[0xC4u8, 0x00, 0xC0, 0x00, 0x0F], PrimitiveValue::ObjectId(ObjectId(ObjectType::BinaryInput, 15)));
+        parsed_application_value_eq(&[0xC4u8, 0x02, 0x3F, 0xFF, 0xFF], PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 0x3FFFFF)));
     }
-    let mut value: u32 = 0;
-    for i in (0..size).rev() {
-        let next_byte = try!(read_one_byte(reader)) as u32;
-        value = value | (next_byte << (8 * i));
+
+    #[test]
+    fn parse_context_types() {
+        use object::ObjectId;
+        parsed_context_value_eq(&[0x39u8, 0xB8], ContextValue(3, PrimitiveValue::Signed(-72)));
+        parsed_context_value_eq(&[0x4Cu8, 0x42, 0x90, 0x00, 0x00], ContextValue(4, PrimitiveValue::Real(72.0)));
+        parsed_context_value_eq(&[0x7Au8, 0x00, 0x41], ContextValue(7, PrimitiveValue::CharacterString("A".to_string())));
+        parsed_context_value_eq(&[0x99u8, 0x03], ContextValue(9, PrimitiveValue::Enumerated(3)));
+        parsed_context_value_eq(&[0xCCu8, 0x02, 0x00, 0x00, 0x2D], ContextValue(12, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))));
     }
-    Ok(value)
-}
 
-fn read_one_byte(reader: &mut Read) -> Result<u8, ParseError> {
-    let mut buf = [0];
-    let ret;
-    loop {
-        match reader.read(&mut buf) {
-            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
-            Err(e) => {
-                ret = Err(ParseError::ReadError(e));
-                break;
-            },
-            Ok(0) => {
-                ret = Err(ParseError::InputEndedBeforeParsingCompleted);
-                break;
-            },
-            Ok(..) => {
-                ret = Ok(buf[0]);
-                break;
-            },
-        };
+    #[test]
+    fn parse_unsupported_application_tags() {
+        assert_eq!(ParseError::NotImplemented("Reserved application tag"), parse_array(&[0xD0u8]).unwrap_err());
     }
-    ret
+    // TODO tests for all the error types
 }
 
-#[test]
-fn test_read_unsigned_16() {
-    let mut data: &[u8] = &[0x99u8, 0x11];
-    let mut reader: &mut Read = &mut data;
-    assert_eq!(read_unsigned(reader, 2).unwrap(), 0x9911);
-}
 
-#[test]
-fn test_read_unsigned_32() {
-    let mut data: &[u8] = &[0x22u8, 0x33, 0x44, 0x55];
-    let mut reader: &mut Read = &mut data;
-    assert_eq!(read_unsigned(reader, 4).unwrap(), 0x22334455);
+// Read the rest of the input
+fn read_to_end(reader: &mut dyn Read) -> Result<Vec<u8>, ParseError> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data).map_err(ParseError::ReadError)?;
+    Ok(data)
 }
@@ -0,0 +1,405 @@
+//! Segmentation of APDUs which are too long for the peer - Clause 5.2 and 5.4. A Confirmed-Request
+//! or Complex-ACK is sent as numbered segments of its service data. The receiver acknowledges each
+//! window of segments with a SegmentACK, which also tells the sender how large the next window can
+//! be, and asks for a window to be sent again with a negative SegmentACK when a segment goes
+//! missing. Sequence numbers are a single octet, so they wrap around on long messages.
+//!
+//! The sender and receiver here only keep track of the segments, sending them and timing out is
+//! left to the transaction state machines which use them.
+
+use ast::ApduHeader;
+use ast::SegmentInfo;
+use enumerations::AbortReason;
+
+/// The largest window a sender can propose or a receiver can accept - Clause 20.1.2.8
+pub const MAX_WINDOW_SIZE: u8 = 127;
+
+/// How many segment timeouts the receiver of a segmented message waits for its next segment
+/// before giving up, Tseg_wait being four times Tseg - Clause 5.3
+pub const SEGMENT_WAIT_TIMEOUTS: u32 = 4;
+
+/// The length of an unsegmented Confirmed-Request header
+pub const UNSEGMENTED_CONFIRMED_REQUEST_HEADER_LENGTH: usize = 4;
+
+/// The length of a segmented Confirmed-Request header
+pub const SEGMENTED_CONFIRMED_REQUEST_HEADER_LENGTH: usize = 6;
+
+/// The length of an unsegmented Complex-ACK header
+pub const UNSEGMENTED_COMPLEX_ACK_HEADER_LENGTH: usize = 3;
+
+/// The length of a segmented Complex-ACK header
+pub const SEGMENTED_COMPLEX_ACK_HEADER_LENGTH: usize = 5;
+
+/// The number of segments needed to send `data_length` octets of service data in APDUs of
+/// `max_apdu_length` octets behind a header of `header_length` octets
+pub fn segment_count(data_length: usize, max_apdu_length: usize, header_length: usize) -> usize {
+    let segment_length = max_apdu_length.saturating_sub(header_length).max(1);
+    data_length.div_ceil(segment_length).max(1)
+}
+
+/// Sends service data as segments. The first segment is sent on its own, after that each
+/// SegmentACK moves the window on to the segments after the one acknowledged.
+#[derive(Debug)]
+pub struct SegmentSender {
+    header: ApduHeader,
+    segments: Vec<Vec<u8>>,
+    proposed_window_size: u8,
+    /// The index of the first segment in the window
+    initial: usize,
+    actual_window_size: usize,
+}
+
+impl SegmentSender {
+    /// Splits `data` into segments to be sent with `header`, which is a Confirmed-Request or a
+    /// Complex-ACK, so that each APDU is at most `max_apdu_length` octets
+    pub fn new(header: ApduHeader, data: &[u8], max_apdu_length: usize, proposed_window_size: u8) -> SegmentSender {
+        let header_length = match header {
+            ApduHeader::ConfirmedReq { .. } => SEGMENTED_CONFIRMED_REQUEST_HEADER_LENGTH,
+            _ => SEGMENTED_COMPLEX_ACK_HEADER_LENGTH,
+        };
+        let segment_length = max_apdu_length.saturating_sub(header_length).max(1);
+        let mut segments: Vec<Vec<u8>> = data.chunks(segment_length).map(|segment| segment.to_vec()).collect();
+        if segments.is_empty() {
+            segments.push(vec!());
+        }
+        SegmentSender {
+            header,
+            segments,
+            proposed_window_size: proposed_window_size.clamp(1, MAX_WINDOW_SIZE),
+            initial: 0,
+            actual_window_size: 1,
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// The segments in the current window with their headers, to be sent or sent again
+    pub fn window(&self) -> Vec<(ApduHeader, &[u8])> {
+        let end = (self.initial + self.actual_window_size).min(self.segments.len());
+        (self.initial..end).map(|index| {
+            let info = SegmentInfo {
+                more_follows: index + 1 < self.segments.len(),
+                sequence_number: index as u8,
+                proposed_window_size: self.proposed_window_size,
+            };
+            (with_segment_info(&self.header, info), &self.segments[index][..])
+        }).collect()
+    }
+
+    /// Handles a SegmentACK, negative or not. Acknowledgements outside the window are duplicates
+    /// which change nothing, otherwise the window moves on to the segment after the one
+    /// acknowledged, or the last segment has been acknowledged.
+    pub fn ack(&mut self, sequence_number: u8, actual_window_size: u8) -> Result<SegmentAcked, AbortReason> {
+        if actual_window_size == 0 || actual_window_size > MAX_WINDOW_SIZE {
+            return Err(AbortReason::WindowSizeOutOfRange)
+        }
+        let offset = sequence_number.wrapping_sub(self.initial as u8) as usize;
+        if offset >= self.actual_window_size || self.initial + offset >= self.segments.len() {
+            return Ok(SegmentAcked::Duplicate)
+        }
+        let acked = self.initial + offset;
+        if acked + 1 == self.segments.len() {
+            return Ok(SegmentAcked::Complete)
+        }
+        self.initial = acked + 1;
+        self.actual_window_size = actual_window_size as usize;
+        Ok(SegmentAcked::NextWindow)
+    }
+
+    /// Starts again from the first segment, for when the whole message has to be sent again
+    pub fn restart(&mut self) {
+        self.initial = 0;
+        self.actual_window_size = 1;
+    }
+}
+
+/// What a SegmentACK means to the sender
+#[derive(Debug, PartialEq)]
+pub enum SegmentAcked {
+    /// It was for segments already acknowledged, it only shows the receiver is still there
+    Duplicate,
+    /// The next window should be sent
+    NextWindow,
+    /// Every segment has been received
+    Complete,
+}
+
+/// Puts segments back together in order, acknowledging each window and asking for it to be sent
+/// again when a segment is missing
+#[derive(Debug)]
+pub struct SegmentReceiver {
+    invoke_id: u8,
+    server: bool,
+    data: Vec<u8>,
+    segments: usize,
+    max_segments: Option<usize>,
+    actual_window_size: u8,
+    initial_sequence_number: u8,
+    last_sequence_number: u8,
+    complete: bool,
+}
+
+impl SegmentReceiver {
+    /// Starts reassembling a message from its first segment, giving the SegmentACK to send back.
+    /// `server` is whether this side is the server, `window_size` is the largest window this side
+    /// accepts, and more than `max_segments` segments won't be accepted.
+    pub fn new(invoke_id: u8, server: bool, info: &SegmentInfo, data: &[u8], window_size: u8, max_segments: Option<usize>) -> Result<(SegmentReceiver, ApduHeader), AbortReason> {
+        if info.proposed_window_size == 0 || info.proposed_window_size > MAX_WINDOW_SIZE {
+            return Err(AbortReason::WindowSizeOutOfRange)
+        }
+        if info.sequence_number != 0 {
+            return Err(AbortReason::InvalidApduInThisState)
+        }
+        let receiver = SegmentReceiver {
+            invoke_id,
+            server,
+            data: data.to_vec(),
+            segments: 1,
+            max_segments,
+            actual_window_size: info.proposed_window_size.min(window_size.clamp(1, MAX_WINDOW_SIZE)),
+            initial_sequence_number: 0,
+            last_sequence_number: 0,
+            complete: !info.more_follows,
+        };
+        let ack = receiver.segment_ack(false);
+        Ok((receiver, ack))
+    }
+
+    /// Handles a segment after the first, giving the SegmentACK to send back if one is due - at
+    /// the end of each window, at the last segment, and negatively for a segment out of order. A
+    /// duplicate of a segment already received means the SegmentACK was lost, the last segment
+    /// received in order is acknowledged again - Clause 5.4.
+    pub fn receive(&mut self, info: &SegmentInfo, data: &[u8]) -> Result<Option<ApduHeader>, AbortReason> {
+        if self.complete {
+            return Err(AbortReason::InvalidApduInThisState)
+        }
+        if info.sequence_number != self.last_sequence_number.wrapping_add(1) {
+            let duplicate = self.last_sequence_number.wrapping_sub(info.sequence_number) < self.actual_window_size;
+            self.initial_sequence_number = self.last_sequence_number;
+            return Ok(Some(self.segment_ack(!duplicate)))
+        }
+        if self.max_segments.is_some_and(|max_segments| self.segments >= max_segments) {
+            return Err(AbortReason::BufferOverflow)
+        }
+        self.data.extend_from_slice(data);
+        self.segments += 1;
+        self.last_sequence_number = info.sequence_number;
+        if !info.more_follows {
+            self.complete = true;
+            return Ok(Some(self.segment_ack(false)))
+        }
+        if info.sequence_number.wrapping_sub(self.initial_sequence_number) == self.actual_window_size {
+            self.initial_sequence_number = info.sequence_number;
+            return Ok(Some(self.segment_ack(false)))
+        }
+        Ok(None)
+    }
+
+    /// Whether the last segment has been received
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// The service data received so far
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn segment_ack(&self, negative_ack: bool) -> ApduHeader {
+        ApduHeader::SegmentAck {
+            negative_ack,
+            server: self.server,
+            invoke_id: self.invoke_id,
+            sequence_number: self.last_sequence_number,
+            actual_window_size: self.actual_window_size,
+        }
+    }
+}
+
+/// A Confirmed-Request or Complex-ACK header for one segment
+fn with_segment_info(header: &ApduHeader, info: SegmentInfo) -> ApduHeader {
+    match header.clone() {
+        ApduHeader::ConfirmedReq { segmented_response_accepted, max_segments, max_apdu, invoke_id, service, .. } =>
+            ApduHeader::ConfirmedReq { segmented: Some(info), segmented_response_accepted, max_segments, max_apdu, invoke_id, service },
+        ApduHeader::ComplexAck { invoke_id, service, .. } =>
+            ApduHeader::ComplexAck { segmented: Some(info), invoke_id, service },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ast::{MaxApdu, MaxSegments};
+
+    fn ack_header() -> ApduHeader {
+        ApduHeader::ComplexAck { segmented: None, invoke_id: 9, service: 14 }
+    }
+
+    fn sequence_numbers(window: &[(ApduHeader, &[u8])]) -> Vec<u8> {
+        window.iter().map(|(header, _)| match *header {
+            ApduHeader::ComplexAck { segmented: Some(ref info), .. } => info.sequence_number,
+            ApduHeader::ConfirmedReq { segmented: Some(ref info), .. } => info.sequence_number,
+            _ => panic!("not a segment"),
+        }).collect()
+    }
+
+    fn info(sequence_number: u8, more_follows: bool) -> SegmentInfo {
+        SegmentInfo { more_follows, sequence_number, proposed_window_size: 4 }
+    }
+
+    #[test]
+    fn segment_counts() {
+        assert_eq!(1, segment_count(0, 50, 5));
+        assert_eq!(1, segment_count(45, 50, 5));
+        assert_eq!(2, segment_count(46, 50, 5));
+    }
+
+    #[test]
+    fn data_is_split_to_fit() {
+        let data: Vec<u8> = (0..100).collect();
+        let sender = SegmentSender::new(ack_header(), &data, 50, 4);
+        assert_eq!(3, sender.segment_count());
+        let window = sender.window();
+        assert_eq!(vec!((ApduHeader::ComplexAck {
+            segmented: Some(SegmentInfo { more_follows: true, sequence_number: 0, proposed_window_size: 4 }),
+            invoke_id: 9,
+            service: 14,
+        }, &data[..45])), window);
+        let request = ApduHeader::ConfirmedReq {
+            segmented: None, segmented_response_accepted: true, max_segments: MaxSegments::Up64, max_apdu: MaxApdu::Up50, invoke_id: 1, service: 15,
+        };
+        assert_eq!(44, SegmentSender::new(request, &data, 50, 4).window()[0].1.len());
+    }
+
+    #[test]
+    fn windows_follow_the_acks() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut sender = SegmentSender::new(ack_header(), &data, 6, 4);
+        assert_eq!(10, sender.segment_count());
+        assert_eq!(vec!(0), sequence_numbers(&sender.window()));
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(0, 3));
+        assert_eq!(vec!(1, 2, 3), sequence_numbers(&sender.window()));
+        // A negative ack asks for the window to be sent again from the segment after the one acked
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(1, 3));
+        assert_eq!(vec!(2, 3, 4), sequence_numbers(&sender.window()));
+        assert_eq!(Ok(SegmentAcked::Duplicate), sender.ack(1, 3));
+        assert_eq!(Ok(SegmentAcked::Duplicate), sender.ack(5, 3));
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(4, 127));
+        assert_eq!(vec!(5, 6, 7, 8, 9), sequence_numbers(&sender.window()));
+        assert!(!window_has_more(&sender.window()));
+        assert_eq!(Ok(SegmentAcked::Complete), sender.ack(9, 127));
+        assert_eq!(Err(AbortReason::WindowSizeOutOfRange), sender.ack(9, 0));
+        sender.restart();
+        assert_eq!(vec!(0), sequence_numbers(&sender.window()));
+    }
+
+    fn window_has_more(window: &[(ApduHeader, &[u8])]) -> bool {
+        match window.last() {
+            Some((ApduHeader::ComplexAck { segmented: Some(info), .. }, _)) => info.more_follows,
+            _ => panic!("not a segment"),
+        }
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let data = vec![0x55u8; 300];
+        let mut sender = SegmentSender::new(ack_header(), &data, 6, 127);
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(0, 127));
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(127, 127));
+        assert_eq!(Ok(SegmentAcked::NextWindow), sender.ack(254, 127));
+        assert_eq!(vec!(255, 0, 1), sequence_numbers(&sender.window()[..3]));
+        assert_eq!(Ok(SegmentAcked::Complete), sender.ack(43, 127));
+    }
+
+    #[test]
+    fn segments_are_reassembled() {
+        let (mut receiver, ack) = SegmentReceiver::new(9, false, &info(0, true), &[0, 1], 2, None).unwrap();
+        assert_eq!(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 0, actual_window_size: 2 }, ack);
+        assert_eq!(Ok(None), receiver.receive(&info(1, true), &[2, 3]));
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 2, actual_window_size: 2 })),
+                   receiver.receive(&info(2, true), &[4]));
+        assert!(!receiver.is_complete());
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 3, actual_window_size: 2 })),
+                   receiver.receive(&info(3, false), &[5]));
+        assert!(receiver.is_complete());
+        assert_eq!(vec!(0, 1, 2, 3, 4, 5), receiver.into_data());
+    }
+
+    #[test]
+    fn missing_segment_is_asked_for() {
+        let (mut receiver, _) = SegmentReceiver::new(9, true, &info(0, true), &[0], 4, None).unwrap();
+        assert_eq!(Ok(None), receiver.receive(&info(1, true), &[1]));
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: true, server: true, invoke_id: 9, sequence_number: 1, actual_window_size: 4 })),
+                   receiver.receive(&info(3, true), &[3]));
+        // The window starts again after the last segment received
+        assert_eq!(Ok(None), receiver.receive(&info(2, true), &[2]));
+        assert_eq!(Ok(None), receiver.receive(&info(3, true), &[3]));
+        assert_eq!(Ok(None), receiver.receive(&info(4, true), &[4]));
+        assert!(receiver.receive(&info(5, true), &[5]).unwrap().is_some());
+        assert_eq!(&[0, 1, 2, 3, 4, 5], receiver.data());
+    }
+
+    #[test]
+    fn duplicate_segment_is_acked_again() {
+        let (mut receiver, _) = SegmentReceiver::new(9, false, &info(0, true), &[0], 2, None).unwrap();
+        assert_eq!(Ok(None), receiver.receive(&info(1, true), &[1]));
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 2, actual_window_size: 2 })),
+                   receiver.receive(&info(2, true), &[2]));
+        // The ack was lost and the window is sent again
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 2, actual_window_size: 2 })),
+                   receiver.receive(&info(1, true), &[1]));
+        assert_eq!(Ok(Some(ApduHeader::SegmentAck { negative_ack: false, server: false, invoke_id: 9, sequence_number: 2, actual_window_size: 2 })),
+                   receiver.receive(&info(2, true), &[2]));
+        assert_eq!(Ok(None), receiver.receive(&info(3, true), &[3]));
+        assert_eq!(&[0, 1, 2, 3], receiver.data());
+    }
+
+    #[test]
+    fn sender_and_receiver_wrap_around_together() {
+        let data: Vec<u8> = (0..600).map(|n| n as u8).collect();
+        let mut sender = SegmentSender::new(ack_header(), &data, 7, 16);
+        assert_eq!(300, sender.segment_count());
+        let mut window = sender.window();
+        let (mut receiver, mut ack) = match window[0].0 {
+            ApduHeader::ComplexAck { segmented: Some(ref info), .. } => SegmentReceiver::new(9, false, info, window[0].1, 8, None).unwrap(),
+            _ => panic!("not a segment"),
+        };
+        loop {
+            let acked = match ack {
+                ApduHeader::SegmentAck { sequence_number, actual_window_size, .. } => sender.ack(sequence_number, actual_window_size).unwrap(),
+                _ => panic!("not an ack"),
+            };
+            if acked == SegmentAcked::Complete {
+                break
+            }
+            window = sender.window();
+            assert!(window.len() <= 8);
+            for (header, segment) in window {
+                if let ApduHeader::ComplexAck { segmented: Some(ref info), .. } = header {
+                    if let Some(next) = receiver.receive(info, segment).unwrap() {
+                        ack = next;
+                    }
+                }
+            }
+        }
+        assert!(receiver.is_complete());
+        assert_eq!(data, receiver.into_data());
+    }
+
+    #[test]
+    fn receiver_limits() {
+        assert_eq!(Err(AbortReason::WindowSizeOutOfRange),
+                   SegmentReceiver::new(9, false, &SegmentInfo { more_follows: true, sequence_number: 0, proposed_window_size: 0 }, &[], 4, None).map(|_| ()));
+        assert_eq!(Err(AbortReason::InvalidApduInThisState), SegmentReceiver::new(9, false, &info(1, true), &[], 4, None).map(|_| ()));
+        let (mut receiver, _) = SegmentReceiver::new(9, false, &info(0, true), &[0], 4, Some(2)).unwrap();
+        assert!(receiver.receive(&info(1, true), &[1]).is_ok());
+        assert_eq!(Err(AbortReason::BufferOverflow), receiver.receive(&info(2, false), &[2]));
+    }
+}
@@ -1,75 +1,23 @@
 use ast;
 use ast::SequenceableValue;
-
+use decode::{Tag, Value};
+use encode::{max_encoded_len, EncodeError, Encoder, MAX_APDU_HEADER_LEN, MAX_NPDU_HEADER_LEN, MAX_TAG_LEN};
+use std::convert::TryFrom;
+use std::net::SocketAddrV4;
 
 pub fn write_apdu_header(writer: &mut Vec<u8>, header: ast::ApduHeader) {
-    use ast::ApduHeader;
-
-    match header {
-        ApduHeader::ConfirmedReq { service, max_apdu, invoke_id, max_segments, segmented, segmented_response_accepted } => {
-            let seg = segmented.is_some() as u8;
-            let mor = segmented.as_ref().map_or(false, |info| info.more_follows) as u8;
-            let sa = segmented_response_accepted as u8;
-            writer.push(0 << 4 ^ seg << 3 ^ mor << 2 ^ sa << 1);    // PDU type and flags
-            writer.push(max_segments << 4 ^ max_apdu);  // transport limitations
-            writer.push(invoke_id);
-            if let Some(ast::SegmentInfo { sequence_number, proposed_window_size, .. }) = segmented {
-                writer.push(sequence_number);
-                writer.push(proposed_window_size);
-            }
-            writer.push(service);
-        },
-        ApduHeader::UnconfirmedReq { service } => {
-            writer.push(1 << 4);
-            writer.push(service);
-        },
-        ApduHeader::SimpleAck { service, invoke_id } => {
-            writer.push(2 << 4);    // PDU type
-            writer.push(invoke_id);
-            writer.push(service);
-        },
-        ApduHeader::ComplexAck { service, invoke_id, segmented } => {
-            let seg = segmented.is_some() as u8;
-            let mor = segmented.as_ref().map_or(false, |info| info.more_follows) as u8;
-            writer.push(3 << 4 ^ seg << 3 ^ mor << 2);    // PDU type and flags
-            writer.push(invoke_id);
-            if let Some(ast::SegmentInfo { sequence_number, proposed_window_size, .. }) = segmented {
-                writer.push(sequence_number);
-                writer.push(proposed_window_size);
-            }
-            writer.push(service);
-        },
-        ApduHeader::SegmentAck { negative_ack, server, invoke_id, sequence_number, actual_window_size } => {
-            let nak = negative_ack as u8;
-            let srv = server as u8;
-            writer.push(4 << 4 ^ nak << 1 ^ srv);
-            writer.push(invoke_id);
-            writer.push(sequence_number);
-            writer.push(actual_window_size);
-        },
-        ApduHeader::ErrorPdu { invoke_id, error_choice } => {
-            writer.push(5 << 4);
-            writer.push(invoke_id);
-            writer.push(error_choice);
-        },
-        ApduHeader::RejectPdu { invoke_id, reject_reason } => {
-            writer.push(6 << 4);
-            writer.push(invoke_id);
-            writer.push(reject_reason);
-        },
-        ApduHeader::AbortPdu { invoke_id, server, abort_reason } => {
-            writer.push(7 << 4 ^ server as u8);
-            writer.push(invoke_id);
-            writer.push(abort_reason);
-        },
-    }
+    let mut buffer = [0; MAX_APDU_HEADER_LEN];
+    let mut encoder = Encoder::new(&mut buffer);
+    encoder.apdu_header(&header).expect("every APDU header fits");
+    writer.extend_from_slice(encoder.encoded());
 }
 
 #[cfg(test)]
 mod test_apdu_header_write {
     use super::write_apdu_header;
-    use ast::ApduHeader;
+    use ast::{ApduHeader, MaxApdu, MaxSegments};
     use ast::SegmentInfo;
+    use enumerations::{AbortReason, RejectReason};
 
     fn assert_header_eq(header: ApduHeader, data: &[u8]) {
         let mut buf = vec![];
@@ -82,8 +30,8 @@ mod test_apdu_header_write {
         assert_header_eq(ApduHeader::ConfirmedReq {
             segmented: None,
             segmented_response_accepted: false,
-            max_segments: 0x0,
-            max_apdu: 5,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up1476,
             invoke_id: 1,
             service: 15,
         }, &[0u8, 5, 1, 15]);
@@ -98,8 +46,8 @@ mod test_apdu_header_write {
                 proposed_window_size: 127,
             }),
             segmented_response_accepted: true,
-            max_segments: 0x7,
-            max_apdu: 0xF,
+            max_segments: MaxSegments::MoreThan64,
+            max_apdu: MaxApdu::Reserved(0xF),
             invoke_id: 253,
             service: 254,
         }, &[0x0Eu8, 0x7F, 253, 255, 127, 254]);
@@ -165,7 +113,7 @@ mod test_apdu_header_write {
     fn write_reject_pdu() {
         assert_header_eq(ApduHeader::RejectPdu {
             invoke_id: 1,
-            reject_reason: 127,
+            reject_reason: RejectReason::Proprietary(127),
         }, &[0x60u8, 1, 127]);
     }
 
@@ -173,18 +121,304 @@ mod test_apdu_header_write {
     fn write_abort_pdu() {
         assert_header_eq(ApduHeader::AbortPdu {
             invoke_id: 1,
-            abort_reason: 127,
+            abort_reason: AbortReason::Proprietary(127),
             server: true,
         }, &[0x71u8, 1, 127]);
     }
+
+    /// Every header variant with every combination of flags
+    fn every_header() -> Vec<ApduHeader> {
+        let bools = [false, true];
+        let segment_infos = || vec![
+            None,
+            Some(SegmentInfo { more_follows: false, sequence_number: 0, proposed_window_size: 1 }),
+            Some(SegmentInfo { more_follows: true, sequence_number: 255, proposed_window_size: 127 }),
+        ];
+        let mut headers = vec![];
+        for segmented in segment_infos() {
+            for &segmented_response_accepted in &bools {
+                for &(max_segments, max_apdu) in &[(0, 0), (0x7, 0xF), (0x5, 0x5)] {
+                    let (max_segments, max_apdu) = (MaxSegments::from(max_segments), MaxApdu::from(max_apdu));
+                    headers.push(ApduHeader::ConfirmedReq { segmented: segmented.clone(), segmented_response_accepted, max_segments, max_apdu, invoke_id: 253, service: 12 });
+                }
+            }
+            headers.push(ApduHeader::ComplexAck { segmented, invoke_id: 0, service: 255 });
+        }
+        headers.push(ApduHeader::UnconfirmedReq { service: 8 });
+        headers.push(ApduHeader::SimpleAck { invoke_id: 255, service: 15 });
+        for &negative_ack in &bools {
+            for &server in &bools {
+                headers.push(ApduHeader::SegmentAck { negative_ack, server, invoke_id: 7, sequence_number: 200, actual_window_size: 16 });
+            }
+        }
+        headers.push(ApduHeader::ErrorPdu { invoke_id: 9, error_choice: 12 });
+        headers.push(ApduHeader::RejectPdu { invoke_id: 9, reject_reason: RejectReason::UnrecognizedService });
+        for &server in &bools {
+            headers.push(ApduHeader::AbortPdu { server, invoke_id: 9, abort_reason: AbortReason::SegmentationNotSupported });
+        }
+        headers
+    }
+
+    #[test]
+    fn round_trip_every_header() {
+        use parse::parse_apdu_header;
+
+        for header in every_header() {
+            let mut buf = vec![];
+            write_apdu_header(&mut buf, header.clone());
+            assert_eq!(header, parse_apdu_header(&mut &buf[..]).unwrap());
+        }
+    }
 }
  
-pub type Context = fn(u8) -> u8;
+/// Writes an NPDU header (Clause 6.2), the APDU or network layer message content should follow
+pub fn write_npdu(writer: &mut Vec<u8>, npdu: ast::Npdu) {
+    let mut buffer = [0; MAX_NPDU_HEADER_LEN];
+    let mut encoder = Encoder::new(&mut buffer);
+    encoder.npdu(&npdu).expect("every NPDU header fits");
+    writer.extend_from_slice(encoder.encoded());
+}
+
+#[cfg(test)]
+mod test_npdu_write {
+    use super::write_npdu;
+    use ast::{MacAddress, Npdu};
+    use ast::NetworkAddress;
+    use ast::NetworkMessage;
+    use parse::parse_npdu;
+
+    fn assert_npdu_eq(npdu: Npdu, data: &[u8]) {
+        let mut buf = vec![];
+        write_npdu(&mut buf, npdu);
+        assert_eq!(data.to_vec(), buf);
+    }
+
+    #[test]
+    fn write_local() {
+        assert_npdu_eq(Npdu::local(false), &[0x01u8, 0x00]);
+        assert_npdu_eq(Npdu::local(true), &[0x01u8, 0x04]);
+        assert_npdu_eq(Npdu { priority: 3, ..Npdu::local(false) }, &[0x01u8, 0x03]);
+    }
+
+    #[test]
+    fn write_global_broadcast() {
+        assert_npdu_eq(Npdu {
+            destination: Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }),
+            ..Npdu::local(false)
+        }, &[0x01u8, 0x20, 0xFF, 0xFF, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn write_routed() {
+        assert_npdu_eq(Npdu {
+            expecting_reply: true,
+            destination: Some(NetworkAddress { network: 5, address: MacAddress::new(&[0x0A]).unwrap() }),
+            source: Some(NetworkAddress { network: 2, address: MacAddress::new(&[0x01, 0x02]).unwrap() }),
+            hop_count: 254,
+            ..Npdu::local(false)
+        }, &[0x01u8, 0x2C, 0x00, 0x05, 0x01, 0x0A, 0x00, 0x02, 0x02, 0x01, 0x02, 0xFE]);
+    }
+
+    #[test]
+    fn write_network_messages() {
+        assert_npdu_eq(Npdu {
+            network_message: Some(NetworkMessage { message_type: 0x01, vendor_id: None }),
+            ..Npdu::local(false)
+        }, &[0x01u8, 0x80, 0x01]);
+        assert_npdu_eq(Npdu {
+            network_message: Some(NetworkMessage { message_type: 0x80, vendor_id: Some(260) }),
+            ..Npdu::local(false)
+        }, &[0x01u8, 0x80, 0x80, 0x01, 0x04]);
+    }
+
+    #[test]
+    fn reply_is_routed_back_to_source() {
+        let request = Npdu {
+            expecting_reply: true,
+            source: Some(NetworkAddress { network: 2, address: MacAddress::new(&[0x01, 0x02]).unwrap() }),
+            ..Npdu::local(true)
+        };
+        assert_npdu_eq(Npdu::reply_to(&request, false), &[0x01u8, 0x20, 0x00, 0x02, 0x02, 0x01, 0x02, 0xFF]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let npdus = vec!(
+            Npdu::local(true),
+            Npdu {
+                priority: 2,
+                destination: Some(NetworkAddress { network: 0xFFFF, address: MacAddress::default() }),
+                source: Some(NetworkAddress { network: 7, address: MacAddress::new(&[1, 2, 3, 4, 5, 6]).unwrap() }),
+                hop_count: 12,
+                network_message: Some(NetworkMessage { message_type: 0x81, vendor_id: Some(65535) }),
+                ..Npdu::local(false)
+            },
+        );
+        for npdu in npdus {
+            let mut buf = vec![];
+            write_npdu(&mut buf, npdu.clone());
+            assert_eq!(npdu, parse_npdu(&mut &buf[..]).unwrap());
+        }
+    }
+}
+ 
+/// A BVLC message too long for the two octet length in its header, which includes the four
+/// octets of the header itself
+#[derive(Debug, PartialEq)]
+pub struct BvlcTooLong;
+
+/// Writes a BACnet/IP BVLC message (Annex J.2), including the NPDU it carries. Nothing is
+/// written if the message is too long.
+pub fn write_bvlc(writer: &mut Vec<u8>, bvlc: ast::Bvlc) -> Result<(), BvlcTooLong> {
+    use ast::Bvlc;
+
+    let mut content = vec![];
+    let function = match bvlc {
+        Bvlc::Result(code) => {
+            content.extend(&code.to_be_bytes());
+            0x00
+        },
+        Bvlc::WriteBroadcastDistributionTable(table) => {
+            write_bdt(&mut content, table);
+            0x01
+        },
+        Bvlc::ReadBroadcastDistributionTable => 0x02,
+        Bvlc::ReadBroadcastDistributionTableAck(table) => {
+            write_bdt(&mut content, table);
+            0x03
+        },
+        Bvlc::ForwardedNpdu { originator, npdu } => {
+            write_socket_address(&mut content, originator);
+            content.extend(npdu);
+            0x04
+        },
+        Bvlc::RegisterForeignDevice(time_to_live) => {
+            content.extend(&time_to_live.to_be_bytes());
+            0x05
+        },
+        Bvlc::ReadForeignDeviceTable => 0x06,
+        Bvlc::ReadForeignDeviceTableAck(table) => {
+            for entry in table {
+                write_socket_address(&mut content, entry.address);
+                content.extend(&entry.time_to_live.to_be_bytes());
+                content.extend(&entry.time_remaining.to_be_bytes());
+            }
+            0x07
+        },
+        Bvlc::DeleteForeignDeviceTableEntry(address) => {
+            write_socket_address(&mut content, address);
+            0x08
+        },
+        Bvlc::DistributeBroadcastToNetwork(npdu) => {
+            content = npdu;
+            0x09
+        },
+        Bvlc::OriginalUnicastNpdu(npdu) => {
+            content = npdu;
+            0x0A
+        },
+        Bvlc::OriginalBroadcastNpdu(npdu) => {
+            content = npdu;
+            0x0B
+        },
+    };
+    let length = u16::try_from(content.len() + 4).map_err(|_| BvlcTooLong)?;
+    writer.push(0x81);  // BVLL for BACnet/IP
+    writer.push(function);
+    writer.extend(&length.to_be_bytes());
+    writer.extend(content);
+    Ok(())
+}
+
+fn write_bdt(writer: &mut Vec<u8>, table: Vec<ast::BdtEntry>) {
+    for entry in table {
+        write_socket_address(writer, entry.address);
+        writer.extend(&entry.broadcast_mask.octets());
+    }
+}
+
+fn write_socket_address(writer: &mut Vec<u8>, address: SocketAddrV4) {
+    writer.extend(&address.ip().octets());
+    writer.extend(&address.port().to_be_bytes());
+}
+
+#[cfg(test)]
+mod test_bvlc_write {
+    use super::{write_bvlc, BvlcTooLong};
+    use ast::{Bvlc, BdtEntry, FdtEntry};
+    use parse::parse_bvlc;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    fn assert_bvlc_eq(bvlc: Bvlc, data: &[u8]) {
+        let mut buf = vec![];
+        write_bvlc(&mut buf, bvlc).unwrap();
+        assert_eq!(data.to_vec(), buf);
+    }
+
+    #[test]
+    fn write_result() {
+        assert_bvlc_eq(Bvlc::Result(0x0030), &[0x81u8, 0x00, 0x00, 0x06, 0x00, 0x30]);
+    }
+
+    #[test]
+    fn write_npdus() {
+        assert_bvlc_eq(Bvlc::OriginalUnicastNpdu(vec![0x01, 0x00, 0x10, 0x08]), &[0x81u8, 0x0A, 0x00, 0x08, 0x01, 0x00, 0x10, 0x08]);
+        assert_bvlc_eq(Bvlc::OriginalBroadcastNpdu(vec![0x01, 0x00, 0x10, 0x08]), &[0x81u8, 0x0B, 0x00, 0x08, 0x01, 0x00, 0x10, 0x08]);
+        assert_bvlc_eq(Bvlc::ForwardedNpdu {
+            originator: SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 0xBAC0),
+            npdu: vec![0x01, 0x00, 0x10, 0x08],
+        }, &[0x81u8, 0x04, 0x00, 0x0E, 0xC0, 0xA8, 0x00, 0x01, 0xBA, 0xC0, 0x01, 0x00, 0x10, 0x08]);
+    }
 
-pub fn write_value_sequence(writer: &mut Vec<u8>, list: ast::ValueSequence) {
+    #[test]
+    fn too_long() {
+        let mut buf = vec![];
+        assert_eq!(Err(BvlcTooLong), write_bvlc(&mut buf, Bvlc::OriginalUnicastNpdu(vec![0; 65532])));
+        assert!(buf.is_empty());
+        write_bvlc(&mut buf, Bvlc::OriginalUnicastNpdu(vec![0; 65531])).unwrap();
+        assert_eq!(&[0x81u8, 0x0A, 0xFF, 0xFF], &buf[..4]);
+    }
+
+    #[test]
+    fn write_register_foreign_device() {
+        assert_bvlc_eq(Bvlc::RegisterForeignDevice(60), &[0x81u8, 0x05, 0x00, 0x06, 0x00, 0x3C]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let address = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 47808);
+        let bdt = vec![
+            BdtEntry { address, broadcast_mask: Ipv4Addr::new(255, 255, 255, 255) },
+            BdtEntry { address: SocketAddrV4::new(Ipv4Addr::new(10, 0, 1, 1), 47809), broadcast_mask: Ipv4Addr::new(255, 255, 255, 0) },
+        ];
+        let messages = vec![
+            Bvlc::Result(0),
+            Bvlc::WriteBroadcastDistributionTable(bdt.clone()),
+            Bvlc::ReadBroadcastDistributionTable,
+            Bvlc::ReadBroadcastDistributionTableAck(bdt),
+            Bvlc::ForwardedNpdu { originator: address, npdu: vec![1, 2, 3] },
+            Bvlc::RegisterForeignDevice(65535),
+            Bvlc::ReadForeignDeviceTable,
+            Bvlc::ReadForeignDeviceTableAck(vec![FdtEntry { address, time_to_live: 30, time_remaining: 60 }]),
+            Bvlc::DeleteForeignDeviceTableEntry(address),
+            Bvlc::DistributeBroadcastToNetwork(vec![1, 2, 3]),
+            Bvlc::OriginalUnicastNpdu(vec![]),
+            Bvlc::OriginalBroadcastNpdu((0..=255).collect()),
+        ];
+        for message in messages {
+            let mut buf = vec![];
+            write_bvlc(&mut buf, message.clone()).unwrap();
+            assert_eq!(message, parse_bvlc(&buf).unwrap());
+        }
+    }
+}
+
+/// Writes the values in turn, failing if one has a field its encoding can't hold
+pub fn write_value_sequence(writer: &mut Vec<u8>, list: ast::ValueSequence) -> Result<(), EncodeError> {
     for e in list {
-        write_sequenceable_value(writer, e);
+        write_sequenceable_value(writer, e)?;
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -197,7 +431,7 @@ mod test_value_sequence_write {
 
     fn written_value_sequence_eq(data: &[u8], value: ast::ValueSequence) {
         let mut buf = vec![];
-        write_value_sequence(&mut buf, value);
+        write_value_sequence(&mut buf, value).unwrap();
         assert_eq!(data.to_vec(), buf);
     }
 
@@ -208,54 +442,43 @@ mod test_value_sequence_write {
 
     #[test]
     fn write_basic_value_sequence() {
-        written_value_sequence_eq(&[0x22u8, 0x99, 0x88, 0x28], vec!(ApplicationValue(PrimitiveValue::Unsigned(0x9988)), ContextValue(2, PrimitiveValue::Boolean(false))))
+        written_value_sequence_eq(&[0x22u8, 0x99, 0x88, 0x29, 0x00], vec!(ApplicationValue(PrimitiveValue::Unsigned(0x9988)), ContextValue(2, PrimitiveValue::Boolean(false))))
     }
 }
 
 /// Call to write a sequenceable value to the writer, the tag for the value is written first
 /// followed by the encoded value
-pub fn write_sequenceable_value(writer: &mut Vec<u8>, value: SequenceableValue) {
+pub fn write_sequenceable_value(writer: &mut Vec<u8>, value: SequenceableValue) -> Result<(), EncodeError> {
     match value {
-        SequenceableValue::ContextValue(context, value) => {
-            let (lvt, content, _) = primitive_value_to_tag_value(value);
-            write_tag(writer, Tag::Context(context, lvt));
-            writer.extend(content);
+        SequenceableValue::ContextValue(context, ref value) => {
+            let value = Value::from(value);
+            encode_onto(writer, max_encoded_len(&value), |encoder| encoder.context_value(context, &value))
         },
-        SequenceableValue::ApplicationValue(value) => {
-            let (lvt, content, primitive_type) = primitive_value_to_tag_value(value);
-            write_tag(writer, Tag::Application(primitive_type, lvt));
-            writer.extend(content);
+        SequenceableValue::ApplicationValue(ref value) => {
+            let value = Value::from(value);
+            encode_onto(writer, max_encoded_len(&value), |encoder| encoder.application_value(&value))
         },
         SequenceableValue::ContextValueSequence(context, list) => {
             write_tag(writer, Tag::Open(context));
-            for e in list {
-                write_sequenceable_value(writer, e);
-            }
-           write_tag(writer, Tag::Close(context)); 
+            write_value_sequence(writer, list)?;
+            write_tag(writer, Tag::Close(context));
+            Ok(())
         },
-    };
+    }
 }
 
-/// Call to write a PrimitiveValue and return the lvt portion of the tag
-fn primitive_value_to_tag_value(value: ast::PrimitiveValue) -> (u32, Vec<u8>, u8) {
-    use ast::PrimitiveValue;
-
-    match value {
-        PrimitiveValue::Null => (0, vec![], 0),
-        PrimitiveValue::Boolean(b) => (b as u32, vec![], 1),
-        PrimitiveValue::Unsigned(u) => {
-            let mut uv: Vec<u8> = vec![u as u8];
-            let mut t = u >> 8;
-            println!("{} {}", u, t);
-            while t > 0 {
-                uv.push(t as u8);
-                t = t >> 8;
-            }
-            uv.reverse();
-            (uv.len() as u32, uv, 2)
-        },
-        _ => panic!("Not implemented"),
-    }
+/// Encodes onto the end of the writer, which is first grown by the most `encode` can encode. The
+/// writer is left as it was if the value has a field its encoding can't hold.
+fn encode_onto<F>(writer: &mut Vec<u8>, max_len: usize, encode: F) -> Result<(), EncodeError>
+    where F: FnOnce(&mut Encoder) -> Result<(), EncodeError> {
+    let start = writer.len();
+    writer.resize(start + max_len, 0);
+    let mut encoder = Encoder::new(&mut writer[start..]);
+    let result = encode(&mut encoder);
+    assert_ne!(Err(EncodeError::BufferFull), result, "the writer was grown by enough");
+    let len = encoder.len();
+    writer.truncate(start + len);
+    result
 }
 
 #[cfg(test)]
@@ -267,10 +490,11 @@ mod write_sequenceable_value_tests {
     use ast::SequenceableValue::ContextValue;
     use ast::SequenceableValue::ApplicationValue;
     use ast::SequenceableValue::ContextValueSequence;
+    use enumerations::ObjectType;
 
     fn write_array(sequenceable_value: SequenceableValue) -> Vec<u8> {
         let mut writer = Vec::new();
-        write_sequenceable_value(&mut writer, sequenceable_value);
+        write_sequenceable_value(&mut writer, sequenceable_value).unwrap();
         writer
     }
 
@@ -295,8 +519,8 @@ mod write_sequenceable_value_tests {
 
     #[test]
     fn write_context_boolean() {
-        written_context_value_eq(&[0x28u8], ContextValue(2, PrimitiveValue::Boolean(false)));
-        written_context_value_eq(&[0x29u8], ContextValue(2, PrimitiveValue::Boolean(true)));
+        written_context_value_eq(&[0x29u8, 0x00], ContextValue(2, PrimitiveValue::Boolean(false)));
+        written_context_value_eq(&[0x29u8, 0x01], ContextValue(2, PrimitiveValue::Boolean(true)));
     }
 
     #[test]
@@ -330,15 +554,172 @@ mod write_sequenceable_value_tests {
         written_application_value_eq(&[0x22u8, 0x99, 0x88], Unsigned(0x9988));
         written_application_value_eq(&[0x23u8, 0x99, 0x88, 0x77], Unsigned(0x998877));
         written_application_value_eq(&[0x24u8, 0x99, 0x88, 0x77, 0x66], Unsigned(0x99887766));
+        written_application_value_eq(&[0x21u8, 0x00], Unsigned(0));
+    }
+
+    #[test]
+    fn write_signed() {
+        use ast::PrimitiveValue::Signed;
+        written_application_value_eq(&[0x31u8, 0x48], Signed(72));
+        written_application_value_eq(&[0x31u8, 0xB8], Signed(-72));
+        written_application_value_eq(&[0x31u8, 0x00], Signed(0));
+        written_application_value_eq(&[0x31u8, 0xFF], Signed(-1));
+        written_application_value_eq(&[0x32u8, 0x00, 0x80], Signed(128));
+        written_application_value_eq(&[0x32u8, 0x80, 0x00], Signed(-32768));
+        written_application_value_eq(&[0x33u8, 0x00, 0x80, 0x00], Signed(32768));
+        written_application_value_eq(&[0x34u8, 0x80, 0x00, 0x00, 0x00], Signed(i32::MIN));
+        written_application_value_eq(&[0x34u8, 0x7F, 0xFF, 0xFF, 0xFF], Signed(i32::MAX));
+    }
+
+    #[test]
+    fn write_real() {
+        written_application_value_eq(&[0x44u8, 0x42, 0x90, 0x00, 0x00], PrimitiveValue::Real(72.0));
+    }
+
+    #[test]
+    fn write_double() {
+        written_application_value_eq(&[0x55u8, 0x08, 0x40, 0x52, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], PrimitiveValue::Double(72.0));
+    }
+
+    #[test]
+    fn write_character_string() {
+        use ast::PrimitiveValue::CharacterString;
+        written_application_value_eq(&[0x75u8, 0x19, 0x00, 0x54, 0x68, 0x69, 0x73, 0x20, 0x69, 0x73, 0x20,
+                                       0x61, 0x20, 0x42, 0x41, 0x43, 0x6E, 0x65, 0x74, 0x20, 0x73, 0x74,
+                                       0x72, 0x69, 0x6E, 0x67, 0x21],
+                                     CharacterString("This is a BACnet string!".to_string()));
+        written_application_value_eq(&[0x71u8, 0x00], CharacterString("".to_string()));
+    }
+
+    #[test]
+    fn write_octet_string() {
+        written_application_value_eq(&[0x63u8, 0x12, 0x34, 0xFF], PrimitiveValue::OctetString(vec![0x12, 0x34, 0xFF]));
+        written_application_value_eq(&[0x60u8], PrimitiveValue::OctetString(vec![]));
+    }
+
+    #[test]
+    fn write_bit_string() {
+        use ast::BitString;
+        written_application_value_eq(&[0x82u8, 0x04, 0xB0], PrimitiveValue::BitString(BitString::from_bits(&[true, false, true, true])));
+        written_application_value_eq(&[0x81u8, 0x00], PrimitiveValue::BitString(BitString::new(0)));
+    }
+
+    #[test]
+    fn write_date() {
+        use ast::{Date, Month, DayOfMonth, Year};
+        written_application_value_eq(&[0xA4u8, 0x5B, 0x01, 0x18, 0x04], PrimitiveValue::Date(Date {
+            year: Year::new(1991), month: Month::Number(1), day: DayOfMonth::Number(24), weekday: Some(4) }));
+        written_application_value_eq(&[0xA4u8, 0xFF, 0x0D, 0x20, 0xFF], PrimitiveValue::Date(Date {
+            year: None, month: Month::Odd, day: DayOfMonth::Last, weekday: None }));
+        // The last year which isn't the unspecified 0xFF
+        written_application_value_eq(&[0xA4u8, 0xFE, 0x01, 0x01, 0xFF], PrimitiveValue::Date(Date {
+            year: Year::new(2154), month: Month::Number(1), day: DayOfMonth::Number(1), weekday: None }));
+    }
+
+    #[test]
+    fn write_time() {
+        use ast::Time;
+        written_application_value_eq(&[0xB4u8, 0x11, 0x23, 0x2D, 0x11], PrimitiveValue::Time(Time {
+            hour: Some(17), minute: Some(35), second: Some(45), hundredths: Some(17) }));
+        written_application_value_eq(&[0xB4u8, 0xFF, 0xFF, 0xFF, 0xFF], PrimitiveValue::Time(Time {
+            hour: None, minute: None, second: None, hundredths: None }));
+    }
+
+    #[test]
+    fn write_enumerated() {
+        use ast::PrimitiveValue::Enumerated;
+        written_application_value_eq(&[0x91u8, 0x00], Enumerated(0));
+        written_application_value_eq(&[0x92u8, 0x01, 0x00], Enumerated(256));
+    }
+
+    #[test]
+    fn write_object_id() {
+        use object::ObjectId;
+        written_application_value_eq(&[0xC4u8, 0x00, 0xC0, 0x00, 0x0F], PrimitiveValue::ObjectId(ObjectId(ObjectType::BinaryInput, 15)));
+        written_application_value_eq(&[0xC4u8, 0x02, 0x3F, 0xFF, 0xFF], PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 0x3FFFFF)));
+    }
+
+    #[test]
+    fn write_context_types() {
+        use object::ObjectId;
+        written_context_value_eq(&[0x49u8, 0xB8], ContextValue(4, PrimitiveValue::Signed(-72)));
+        written_context_value_eq(&[0x5Cu8, 0x42, 0x90, 0x00, 0x00], ContextValue(5, PrimitiveValue::Real(72.0)));
+        written_context_value_eq(&[0x8Au8, 0x00, 0x41], ContextValue(8, PrimitiveValue::CharacterString("A".to_string())));
+        written_context_value_eq(&[0xA9u8, 0x03], ContextValue(10, PrimitiveValue::Enumerated(3)));
+        written_context_value_eq(&[0xDCu8, 0x02, 0x00, 0x00, 0x2D], ContextValue(13, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))));
+    }
+
+    #[test]
+    fn value_out_of_range_is_not_written() {
+        use encode::EncodeError;
+        use object::ObjectId;
+        let mut writer = vec![0x21, 0x01];
+        let value = PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 0x400000));
+        assert_eq!(Err(EncodeError::OutOfRange), write_sequenceable_value(&mut writer, ApplicationValue(value.clone())));
+        assert_eq!(Err(EncodeError::OutOfRange), write_sequenceable_value(&mut writer, ContextValue(1, value)));
+        assert_eq!(vec![0x21u8, 0x01], writer);
     }
 }
- 
-#[derive(PartialEq, Debug)]
-enum Tag {
-    Application(u8, u32),
-    Context(u8, u32),
-    Open(u8),
-    Close(u8),
+
+#[cfg(test)]
+mod test_round_trip {
+    use super::write_sequenceable_value;
+    use ast::{BitString, Date, Month, DayOfMonth, Time, Year};
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue;
+    use ast::SequenceableValue::ContextValue;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::ObjectId;
+    use parse::{parse_value_sequence_to_end, EVERY_TYPE};
+    use std::io;
+    use enumerations::ObjectType;
+
+    fn assert_round_trip(value: PrimitiveValue, context_tag: u8) {
+        for sequenceable_value in [ApplicationValue(value.clone()), ContextValue(context_tag, value)] {
+            let mut buf = vec![];
+            write_sequenceable_value(&mut buf, sequenceable_value.clone()).unwrap();
+            let reader: &mut dyn io::Read = &mut io::Cursor::new(buf);
+            let parsed: Vec<SequenceableValue> = parse_value_sequence_to_end(reader, &EVERY_TYPE).unwrap();
+            assert_eq!(vec!(sequenceable_value), parsed);
+        }
+    }
+
+    #[test]
+    fn round_trip_every_primitive() {
+        assert_round_trip(PrimitiveValue::Null, 0);
+        assert_round_trip(PrimitiveValue::Boolean(false), 1);
+        assert_round_trip(PrimitiveValue::Boolean(true), 1);
+        for &u in &[0, 1, 255, 256, 65535, 65536, 0xFFFFFF, 0x1000000, u32::MAX] {
+            assert_round_trip(PrimitiveValue::Unsigned(u), 2);
+            assert_round_trip(PrimitiveValue::Enumerated(u), 9);
+        }
+        for &i in &[0, 1, -1, 127, 128, -128, -129, 32767, -32768, 8388607, -8388608, i32::MAX, i32::MIN] {
+            assert_round_trip(PrimitiveValue::Signed(i), 3);
+        }
+        for &r in &[0.0, -1.5, 72.5, f32::MAX, f32::INFINITY] {
+            assert_round_trip(PrimitiveValue::Real(r), 4);
+        }
+        for &d in &[0.0, -1.5, 1.0e-300, f64::MIN_POSITIVE] {
+            assert_round_trip(PrimitiveValue::Double(d), 5);
+        }
+        assert_round_trip(PrimitiveValue::CharacterString("".to_string()), 7);
+        assert_round_trip(PrimitiveValue::CharacterString("Fran\u{e7}ais \u{2603}".to_string()), 7);
+        assert_round_trip(PrimitiveValue::CharacterString("x".repeat(300)), 7);
+        assert_round_trip(PrimitiveValue::OctetString(vec![]), 6);
+        assert_round_trip(PrimitiveValue::OctetString((0..=255).collect()), 6);
+        for len in 0..18 {
+            let bits: Vec<bool> = (0..len).map(|bit| bit % 3 == 0).collect();
+            assert_round_trip(PrimitiveValue::BitString(BitString::from_bits(&bits)), 8);
+        }
+        for &(year, month, day, weekday) in &[(Year::new(1900), Month::Number(12), DayOfMonth::Number(31), Some(1)),
+                                              (Year::new(2154), Month::Even, DayOfMonth::Even, Some(7)),
+                                              (None, Month::Unspecified, DayOfMonth::Unspecified, None)] {
+            assert_round_trip(PrimitiveValue::Date(Date { year, month, day, weekday }), 10);
+        }
+        assert_round_trip(PrimitiveValue::Time(Time { hour: Some(0), minute: Some(59), second: None, hundredths: Some(99) }), 11);
+        assert_round_trip(PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 0)), 12);
+        assert_round_trip(PrimitiveValue::ObjectId(ObjectId(ObjectType::Proprietary(1023), 0x3FFFFF)), 12);
+    }
 }
  
 /// Call to write a tag (Clause 20.2.1)
@@ -371,36 +752,7 @@ enum Tag {
 /// length field, if the next octet is 0xFE, the next 2 octets become a length field, if it is 0xFF
 /// then the next 4 octets become a length field. This encoding allows lengths up to 2^32-1.
 fn write_tag(writer: &mut Vec<u8>, tag: Tag) {
-    let (is_named, tag_number, is_context, lvt) = match tag {
-        Tag::Open(context) => (true, context, true, 0x6),
-        Tag::Close(context) => (true, context, true, 0x7),
-        Tag::Application(tag, value) => (false, tag, false, value),
-        Tag::Context(context, value) => (false, context, true, value),
-    };
-    let class_flag = if is_context { 0x8 } else { 0x0 };
-    let tag_portion = if tag_number <= 14 {     // Clause 20.2.1.2
-        tag_number
-    } else {
-        0b1111
-    };
-    let value_portion = if is_named || lvt <= 4 {           // Clause 20.2.1.3.1
-        lvt
-    } else {
-        0b101
-    };
-    writer.push((tag_portion << 4) ^ class_flag ^ value_portion as u8);
-    if tag_portion == 0b1111 {
-        writer.extend(&[tag_number]);
-    }
-    if value_portion == 0b101 {
-        if lvt <= 253 {
-            writer.push(lvt as u8);
-        } else if lvt <= 65535 {
-            writer.extend(vec![254, (lvt >> 8) as u8, lvt as u8]);
-        } else {
-            writer.extend(vec![255, (lvt >> 24) as u8, (lvt >> 16) as u8, (lvt >> 8) as u8, lvt as u8]);
-        }
-    }
+    encode_onto(writer, MAX_TAG_LEN, |encoder| encoder.tag(tag)).expect("every tag can be encoded");
 }
 
 #[cfg(test)]
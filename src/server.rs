@@ -0,0 +1,547 @@
+//! The responding side of confirmed services - Clause 5.4.5. Requests are handled by a
+//! `Dispatcher` and its answers sent back, segmented when they are too long for the APDUs the
+//! requester accepts. Segmented requests are put back together before they are handled.
+//!
+//! The device's Segmentation_Supported, Max_Segments_Accepted, APDU_Segment_Timeout and
+//! Number_Of_APDU_Retries are used as they are when each APDU is handled.
+
+use ast::ApduHeader;
+use ast::NetworkAddress;
+use ast::SegmentInfo;
+use ast::ValueSequence;
+use client::Transport;
+use clock::Clock;
+use enumerations::{AbortReason, Segmentation};
+use object::BacnetDB;
+use segmentation::{SEGMENT_WAIT_TIMEOUTS, SegmentAcked, SegmentReceiver, SegmentSender, UNSEGMENTED_COMPLEX_ACK_HEADER_LENGTH};
+use serialise::write_value_sequence;
+use service::Dispatcher;
+use std::time::Duration;
+
+/// Where a transaction has got to
+enum State {
+    /// Receiving the segments of a request, which has the header of its first segment
+    SegmentedRequest(ApduHeader, SegmentReceiver),
+    /// Sending the segments of a Complex-ACK
+    SegmentedResponse(SegmentSender),
+}
+
+/// A transaction which takes more than one APDU each way
+struct Transaction {
+    peer: NetworkAddress,
+    invoke_id: u8,
+    state: State,
+    retries: u32,
+    deadline: Duration,
+}
+
+/// Answers requests from other devices. Requests are passed in with `receive`, and `poll` should
+/// be called when `next_deadline` has passed to send segments again and to time them out.
+pub struct Server<T: Transport> {
+    dispatcher: Dispatcher,
+    transport: T,
+    clock: Box<dyn Clock>,
+    /// The window size proposed when sending segments, and the largest accepted when receiving
+    /// them
+    pub window_size: u8,
+    transactions: Vec<Transaction>,
+}
+
+impl<T: Transport> Server<T> {
+    /// A server for the services registered with the dispatcher, with windows of 16 segments. The
+    /// dispatcher is attached to the device's database.
+    pub fn new(dispatcher: Dispatcher, transport: T, clock: Box<dyn Clock>, db: &mut BacnetDB) -> Server<T> {
+        dispatcher.attach(db);
+        Server {
+            dispatcher,
+            transport,
+            clock,
+            window_size: 16,
+            transactions: vec!(),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Handles an APDU from `source`, sending any answer back to it
+    pub fn receive(&mut self, source: &NetworkAddress, header: ApduHeader, data: &[u8], db: &mut BacnetDB) {
+        match header {
+            ApduHeader::ConfirmedReq { segmented: Some(ref info), invoke_id, .. } =>
+                self.segment_received(source, invoke_id, &header, info, data, db),
+            // A request sent again while its answer is being sent is already being answered
+            ApduHeader::ConfirmedReq { invoke_id, .. } if self.position(source, invoke_id).is_some() => (),
+            ApduHeader::ConfirmedReq { .. } => self.handle(source, header, data, db),
+            ApduHeader::UnconfirmedReq { .. } => self.handle(source, header, data, db),
+            ApduHeader::SegmentAck { server: false, invoke_id, sequence_number, actual_window_size, .. } =>
+                self.segment_acked(source, invoke_id, sequence_number, actual_window_size, db),
+            ApduHeader::AbortPdu { server: false, invoke_id, .. } => {
+                if let Some(index) = self.position(source, invoke_id) {
+                    self.transactions.remove(index);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Sends windows of segments again whose SegmentACKs are overdue, and gives up on those
+    /// which have run out of retries and on requests whose segments have stopped coming
+    pub fn poll(&mut self, db: &BacnetDB) {
+        let now = self.clock.now();
+        let timeout = segment_timeout(db);
+        let retries = db.device().number_of_apdu_retries;
+        let transport = &mut self.transport;
+        self.transactions.retain_mut(|transaction| {
+            if transaction.deadline > now {
+                return true
+            }
+            match transaction.state {
+                State::SegmentedResponse(ref segments) if transaction.retries < retries => {
+                    transaction.retries += 1;
+                    transaction.deadline = now + timeout;
+                    for (header, segment) in segments.window() {
+                        // A segment which can't be sent is as good as lost, it times out like one
+                        let _ = transport.send(&transaction.peer, header, segment);
+                    }
+                    true
+                },
+                _ => false,
+            }
+        });
+    }
+
+    /// When the next segment or SegmentACK becomes overdue, if there are transactions in
+    /// progress
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.transactions.iter().map(|transaction| transaction.deadline).min()
+    }
+
+    /// Parses and handles a complete request, and sends the answer
+    fn handle(&mut self, source: &NetworkAddress, header: ApduHeader, data: &[u8], db: &mut BacnetDB) {
+        let body = match self.dispatcher.parse_request(&header, data) {
+            Ok(body) => body,
+            Err(error) => {
+                if let ApduHeader::ConfirmedReq { invoke_id, .. } = header {
+                    let _ = self.transport.send(source, ApduHeader::RejectPdu { invoke_id, reject_reason: error.reject_reason() }, &[]);
+                }
+                return
+            },
+        };
+        if let Some((response, body)) = self.dispatcher.handle_apdu(header.clone(), &body, source, db) {
+            self.respond(source, &header, response, body, db);
+        }
+    }
+
+    /// Sends the answer to a request, in segments if it is too long for one APDU
+    fn respond(&mut self, source: &NetworkAddress, request: &ApduHeader, response: ApduHeader, body: ValueSequence, db: &BacnetDB) {
+        let mut data = vec![];
+        if write_value_sequence(&mut data, body).is_err() {
+            // An answer which can't be encoded can only be reported to a confirmed request
+            if let ApduHeader::ConfirmedReq { invoke_id, .. } = *request {
+                self.abort(source, invoke_id, AbortReason::Other);
+            }
+            return
+        }
+        let (segmented_response_accepted, max_segments, max_apdu, invoke_id) = match *request {
+            ApduHeader::ConfirmedReq { segmented_response_accepted, max_segments, max_apdu, invoke_id, .. } =>
+                (segmented_response_accepted, max_segments, max_apdu, invoke_id),
+            _ => {
+                let _ = self.transport.send(source, response, &data);
+                return
+            },
+        };
+        let device = db.device();
+        let max_apdu_length = max_apdu.length().min(device.max_apdu_length_supported as usize);
+        let fits = UNSEGMENTED_COMPLEX_ACK_HEADER_LENGTH + data.len() <= max_apdu_length;
+        if fits || !matches!(response, ApduHeader::ComplexAck { .. }) {
+            let _ = self.transport.send(source, response, &data);
+            return
+        }
+        let transmits_segments = device.segmentation_supported == Segmentation::SegmentedBoth
+            || device.segmentation_supported == Segmentation::SegmentedTransmit;
+        if !segmented_response_accepted || !transmits_segments {
+            return self.abort(source, invoke_id, AbortReason::SegmentationNotSupported)
+        }
+        let segments = SegmentSender::new(response, &data, max_apdu_length, self.window_size);
+        if !max_segments.accepts(segments.segment_count()) {
+            return self.abort(source, invoke_id, AbortReason::BufferOverflow)
+        }
+        for (header, segment) in segments.window() {
+            let _ = self.transport.send(source, header, segment);
+        }
+        self.transactions.push(Transaction {
+            peer: source.clone(),
+            invoke_id,
+            state: State::SegmentedResponse(segments),
+            retries: 0,
+            deadline: self.clock.now() + segment_timeout(db),
+        });
+    }
+
+    /// Adds a segment of a request, handling the request once it is complete
+    fn segment_received(&mut self, source: &NetworkAddress, invoke_id: u8, header: &ApduHeader, info: &SegmentInfo, data: &[u8], db: &mut BacnetDB) {
+        let now = self.clock.now();
+        let received = match self.position(source, invoke_id) {
+            Some(index) => match self.transactions[index].state {
+                State::SegmentedRequest(_, ref mut receiver) => receiver.receive(info, data).map(|ack| (index, ack)),
+                State::SegmentedResponse(_) => Err(AbortReason::InvalidApduInThisState),
+            },
+            None => {
+                let device = db.device();
+                let receives_segments = device.segmentation_supported == Segmentation::SegmentedBoth
+                    || device.segmentation_supported == Segmentation::SegmentedReceive;
+                if !receives_segments {
+                    return self.abort(source, invoke_id, AbortReason::SegmentationNotSupported)
+                }
+                SegmentReceiver::new(invoke_id, true, info, data, self.window_size, Some(device.max_segments_accepted as usize))
+                    .map(|(receiver, ack)| {
+                        self.transactions.push(Transaction {
+                            peer: source.clone(),
+                            invoke_id,
+                            state: State::SegmentedRequest(header.clone(), receiver),
+                            retries: 0,
+                            deadline: now,
+                        });
+                        (self.transactions.len() - 1, Some(ack))
+                    })
+            },
+        };
+        let (index, ack) = match received {
+            Ok(received) => received,
+            Err(reason) => return self.abort(source, invoke_id, reason),
+        };
+        self.transactions[index].deadline = now + segment_timeout(db) * SEGMENT_WAIT_TIMEOUTS;
+        if let Some(ack) = ack {
+            let _ = self.transport.send(source, ack, &[]);
+        }
+        let complete = match self.transactions[index].state {
+            State::SegmentedRequest(_, ref receiver) => receiver.is_complete(),
+            _ => false,
+        };
+        if complete {
+            if let State::SegmentedRequest(header, receiver) = self.transactions.remove(index).state {
+                self.handle(source, unsegmented(header), &receiver.into_data(), db);
+            }
+        }
+    }
+
+    /// Moves on to the next window of a response's segments, the transaction is over once they
+    /// have all been received
+    fn segment_acked(&mut self, source: &NetworkAddress, invoke_id: u8, sequence_number: u8, actual_window_size: u8, db: &BacnetDB) {
+        let index = match self.position(source, invoke_id) {
+            Some(index) => index,
+            None => return,
+        };
+        let now = self.clock.now();
+        let transaction = &mut self.transactions[index];
+        let acked = match transaction.state {
+            State::SegmentedResponse(ref mut segments) => segments.ack(sequence_number, actual_window_size),
+            State::SegmentedRequest(..) => Err(AbortReason::InvalidApduInThisState),
+        };
+        match acked {
+            Ok(SegmentAcked::Duplicate) =>
+                transaction.deadline = now + segment_timeout(db),
+            Ok(SegmentAcked::NextWindow) => {
+                transaction.retries = 0;
+                transaction.deadline = now + segment_timeout(db);
+                if let State::SegmentedResponse(ref segments) = transaction.state {
+                    for (header, segment) in segments.window() {
+                        let _ = self.transport.send(source, header, segment);
+                    }
+                }
+            },
+            Ok(SegmentAcked::Complete) => {
+                self.transactions.remove(index);
+            },
+            Err(reason) => self.abort(source, invoke_id, reason),
+        }
+    }
+
+    /// Ends a transaction, telling the requester
+    fn abort(&mut self, source: &NetworkAddress, invoke_id: u8, abort_reason: AbortReason) {
+        if let Some(index) = self.position(source, invoke_id) {
+            self.transactions.remove(index);
+        }
+        let _ = self.transport.send(source, ApduHeader::AbortPdu { server: true, invoke_id, abort_reason }, &[]);
+    }
+
+    fn position(&self, peer: &NetworkAddress, invoke_id: u8) -> Option<usize> {
+        self.transactions.iter().position(|transaction| transaction.peer == *peer && transaction.invoke_id == invoke_id)
+    }
+}
+
+/// The device's APDU_Segment_Timeout
+fn segment_timeout(db: &BacnetDB) -> Duration {
+    Duration::from_millis(db.device().apdu_segment_timeout as u64)
+}
+
+/// The header of a segmented request as if it had been sent in one APDU
+fn unsegmented(header: ApduHeader) -> ApduHeader {
+    match header {
+        ApduHeader::ConfirmedReq { segmented_response_accepted, max_segments, max_apdu, invoke_id, service, .. } =>
+            ApduHeader::ConfirmedReq { segmented: None, segmented_response_accepted, max_segments, max_apdu, invoke_id, service },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ast::{MaxApdu, MaxSegments};
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue::ApplicationValue;
+    use clock::ManualClock;
+    use enumerations::{ObjectType, PropertyIdentifier, RejectReason};
+    use object::ObjectId;
+    use object::DeviceObject;
+    use object::BinaryInput;
+    use client::test_link::TestLink;
+    use service::{ServiceMessage, readproperty, test_peer, writeproperty};
+
+    fn server(db: &mut BacnetDB) -> (Server<TestLink>, ManualClock) {
+        let clock = ManualClock::new();
+        (Server::new(Dispatcher::with_default_services(), TestLink::default(), Box::new(clock.clone()), db), clock)
+    }
+
+    /// A database whose device has a name too long for 128 octet APDUs
+    fn segmenting_db(segmentation_supported: Segmentation) -> BacnetDB {
+        let mut device = DeviceObject::new(7, &long_name(), 999);
+        device.segmentation_supported = segmentation_supported;
+        BacnetDB::new(device)
+    }
+
+    fn long_name() -> String {
+        (0..300).map(|n| (b'a' + (n % 26) as u8) as char).collect()
+    }
+
+    fn encode(body: ValueSequence) -> Vec<u8> {
+        let mut data = vec![];
+        write_value_sequence(&mut data, body).unwrap();
+        data
+    }
+
+    fn read_name() -> Vec<u8> {
+        encode(readproperty::Message::new(ObjectId(ObjectType::Device, 7), PropertyIdentifier::ObjectName, None).marshall())
+    }
+
+    fn name_ack() -> Vec<u8> {
+        encode(readproperty::Ack {
+            object_id: ObjectId(ObjectType::Device, 7),
+            property: PropertyIdentifier::ObjectName,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::CharacterString(long_name()))),
+        }.marshall())
+    }
+
+    /// A request for APDUs of up to 128 octets
+    fn request(segmented_response_accepted: bool, max_segments: MaxSegments, service: u8) -> ApduHeader {
+        ApduHeader::ConfirmedReq { segmented: None, segmented_response_accepted, max_segments, max_apdu: MaxApdu::Up128, invoke_id: 3, service }
+    }
+
+    fn segment_ack(negative_ack: bool, sequence_number: u8, actual_window_size: u8) -> ApduHeader {
+        ApduHeader::SegmentAck { negative_ack, server: false, invoke_id: 3, sequence_number, actual_window_size }
+    }
+
+    fn sequence_numbers(sent: &[(NetworkAddress, ApduHeader, Vec<u8>)]) -> Vec<u8> {
+        sent.iter().map(|(_, header, _)| match *header {
+            ApduHeader::ComplexAck { segmented: Some(ref info), .. } => info.sequence_number,
+            ref other => panic!("not a segment {:?}", other),
+        }).collect()
+    }
+
+    fn abort(abort_reason: AbortReason) -> ApduHeader {
+        ApduHeader::AbortPdu { server: true, invoke_id: 3, abort_reason }
+    }
+
+    #[test]
+    fn short_answer_is_sent_whole() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &read_name(), &mut db);
+        let (ref peer, ref header, ref data) = server.transport().sent[0];
+        assert_eq!(*peer, test_peer());
+        assert_eq!(*header, ApduHeader::ComplexAck { segmented: None, invoke_id: 3, service: 12 });
+        assert!(data.ends_with(b"dev\x3F"));
+        assert_eq!(server.next_deadline(), None);
+        assert_eq!(&Dispatcher::with_default_services().services_supported(), db.device().protocol_services_supported());
+    }
+
+    #[test]
+    fn answer_which_cant_be_encoded_is_aborted() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        db.add(BinaryInput::new(0x400000, "out of range")).unwrap();
+        let (mut server, _) = server(&mut db);
+        let read_list = encode(readproperty::Message::new(ObjectId(ObjectType::Device, 7), PropertyIdentifier::ObjectList, None).marshall());
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &read_list, &mut db);
+        assert_eq!(vec!((test_peer(), abort(AbortReason::Other), vec![])), server.transport().sent);
+    }
+
+    #[test]
+    fn long_answer_is_segmented() {
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        let (mut server, _) = server(&mut db);
+        server.receive(&test_peer(), request(true, MaxSegments::Unspecified, 12), &read_name(), &mut db);
+        assert_eq!(sequence_numbers(&server.transport().sent), vec![0]);
+        server.receive(&test_peer(), segment_ack(false, 0, 2), &[], &mut db);
+        assert_eq!(sequence_numbers(&server.transport().sent[1..]), vec![1, 2]);
+        // Segment 2 went missing
+        server.receive(&test_peer(), segment_ack(true, 1, 2), &[], &mut db);
+        assert_eq!(sequence_numbers(&server.transport().sent[3..]), vec![2]);
+        server.receive(&test_peer(), segment_ack(false, 2, 2), &[], &mut db);
+        assert_eq!(server.next_deadline(), None);
+
+        let sent = &server.transport().sent;
+        let data: Vec<u8> = [&sent[0], &sent[1], &sent[3]].iter().flat_map(|&(_, _, data)| data.clone()).collect();
+        assert_eq!(data, name_ack());
+        assert!(sent.iter().all(|(_, _, data)| 5 + data.len() <= 128));
+    }
+
+    #[test]
+    fn segmented_answer_has_to_be_accepted() {
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        let (mut server, _) = server(&mut db);
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &read_name(), &mut db);
+        assert_eq!(server.transport().sent[0].1, abort(AbortReason::SegmentationNotSupported));
+
+        let mut db = segmenting_db(Segmentation::SegmentedReceive);
+        server.receive(&test_peer(), request(true, MaxSegments::Unspecified, 12), &read_name(), &mut db);
+        assert_eq!(server.transport().sent[1].1, abort(AbortReason::SegmentationNotSupported));
+    }
+
+    #[test]
+    fn too_many_segments() {
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        let (mut server, _) = server(&mut db);
+        server.receive(&test_peer(), request(true, MaxSegments::Up2, 12), &read_name(), &mut db);
+        assert_eq!(server.transport().sent[0].1, abort(AbortReason::BufferOverflow));
+        assert_eq!(server.next_deadline(), None);
+    }
+
+    #[test]
+    fn segments_are_sent_again() {
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        let (mut server, clock) = server(&mut db);
+        server.receive(&test_peer(), request(true, MaxSegments::Unspecified, 12), &read_name(), &mut db);
+        assert_eq!(server.next_deadline(), Some(Duration::from_secs(2)));
+        for retry in 1..4 {
+            clock.advance(Duration::from_secs(2));
+            server.poll(&db);
+            assert_eq!(server.transport().sent.len(), retry + 1);
+        }
+        clock.advance(Duration::from_secs(2));
+        server.poll(&db);
+        assert_eq!(server.transport().sent.len(), 4);
+        assert_eq!(server.next_deadline(), None);
+    }
+
+    #[test]
+    fn segmented_request_is_reassembled() {
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        let (mut server, clock) = server(&mut db);
+        let data = encode(writeproperty::Message {
+            object_id: ObjectId(ObjectType::Device, 7),
+            property: PropertyIdentifier::ObjectName,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::CharacterString("renamed".repeat(30)))),
+            priority: None,
+        }.marshall());
+        let segments: Vec<&[u8]> = data.chunks(100).collect();
+        for (sequence_number, segment) in segments.iter().enumerate() {
+            let header = ApduHeader::ConfirmedReq {
+                segmented: Some(SegmentInfo { more_follows: sequence_number + 1 < segments.len(), sequence_number: sequence_number as u8, proposed_window_size: 1 }),
+                segmented_response_accepted: true,
+                max_segments: MaxSegments::Unspecified,
+                max_apdu: MaxApdu::Up128,
+                invoke_id: 3,
+                service: 15,
+            };
+            assert_eq!(server.next_deadline(), if sequence_number == 0 { None } else { Some(clock.now() + Duration::from_secs(8)) });
+            server.receive(&test_peer(), header, segment, &mut db);
+        }
+        let sent: Vec<ApduHeader> = server.transport().sent.iter().map(|(_, header, _)| header.clone()).collect();
+        assert_eq!(sent, vec![
+            ApduHeader::SegmentAck { negative_ack: false, server: true, invoke_id: 3, sequence_number: 0, actual_window_size: 1 },
+            ApduHeader::SegmentAck { negative_ack: false, server: true, invoke_id: 3, sequence_number: 1, actual_window_size: 1 },
+            ApduHeader::SegmentAck { negative_ack: false, server: true, invoke_id: 3, sequence_number: 2, actual_window_size: 1 },
+            ApduHeader::SimpleAck { invoke_id: 3, service: 15 },
+        ]);
+        assert_eq!(db.device().object_name, "renamed".repeat(30));
+        assert_eq!(server.next_deadline(), None);
+    }
+
+    #[test]
+    fn segmented_request_needs_segmentation() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, clock) = server(&mut db);
+        let header = ApduHeader::ConfirmedReq {
+            segmented: Some(SegmentInfo { more_follows: true, sequence_number: 0, proposed_window_size: 1 }),
+            segmented_response_accepted: true,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up128,
+            invoke_id: 3,
+            service: 15,
+        };
+        server.receive(&test_peer(), header.clone(), &[0; 100], &mut db);
+        assert_eq!(server.transport().sent[0].1, abort(AbortReason::SegmentationNotSupported));
+
+        // The rest of a request which stops coming is forgotten
+        let mut db = segmenting_db(Segmentation::SegmentedBoth);
+        server.receive(&test_peer(), header, &[0; 100], &mut db);
+        clock.advance(Duration::from_secs(8));
+        server.poll(&db);
+        assert_eq!(server.next_deadline(), None);
+    }
+
+    #[test]
+    fn unknown_service_is_rejected() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 26), &[0x09, 0x01], &mut db);
+        assert_eq!(server.transport().sent[0].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::UnrecognizedService });
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &[0x0C, 0x02], &mut db);
+        assert_eq!(server.transport().sent[1].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::InvalidTag });
+    }
+
+    #[test]
+    fn missing_parameter_is_rejected() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        // A ReadProperty request without its property identifier
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &[0x0C, 0x02, 0x00, 0x00, 0x07], &mut db);
+        assert_eq!(server.transport().sent[0].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::MissingRequiredParameter });
+    }
+
+    #[test]
+    fn extra_parameter_is_rejected() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        let mut data = read_name();
+        data.extend_from_slice(&[0x21, 0x01]);
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &data, &mut db);
+        assert_eq!(server.transport().sent[0].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::TooManyArguments });
+    }
+
+    #[test]
+    fn wrongly_typed_parameter_is_rejected() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        // A ReadProperty request whose object identifier is a single octet
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 12), &[0x09, 0x07, 0x19, 0x4D], &mut db);
+        assert_eq!(server.transport().sent[0].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::InvalidParameterDataType });
+    }
+
+    #[test]
+    fn unsupported_values_of_a_known_service_are_rejected() {
+        let mut db = BacnetDB::new(DeviceObject::new(7, "dev", 999));
+        let (mut server, _) = server(&mut db);
+        // A WriteProperty request whose value is in a character set which isn't supported
+        let data = [0x0C, 0x02, 0x00, 0x00, 0x07, 0x19, 0x4D, 0x3E, 0x72, 0x01, 0x41, 0x3F];
+        server.receive(&test_peer(), request(false, MaxSegments::Unspecified, 15), &data, &mut db);
+        assert_eq!(server.transport().sent[0].1, ApduHeader::RejectPdu { invoke_id: 3, reject_reason: RejectReason::InvalidParameterDataType });
+    }
+}
@@ -0,0 +1,155 @@
+//! COV notifications (Clause 13.6 and 13.7) tell a subscriber the values of an object's properties
+//! after a change, or when its subscription starts. They are sent as a confirmed or an unconfirmed
+//! request as the subscriber asked, the two carry the same notification.
+//!
+//! The property values are a constructed value which reuses the context tag numbers of the
+//! notification's fields.
+
+use super::ServiceMessage;
+use super::{UnmarshallError, Values, required};
+use super::writepropertymultiple::{PROPERTY_VALUE, PropertyValue, marshall_property_values, unmarshall_property_values};
+
+use object::ObjectId;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ContextValue;
+use ast::SequenceableValue::ContextValueSequence;
+use parse::{PrimitiveType, Schema};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Notification {
+    pub subscriber_process_id: u32,
+    pub initiating_device_id: ObjectId,
+    pub monitored_object_id: ObjectId,
+    /// Seconds until the subscription ends, 0 for an indefinite subscription
+    pub time_remaining: u32,
+    pub values: Vec<PropertyValue>,
+}
+
+/// ConfirmedCOVNotification
+#[derive(Debug, PartialEq)]
+pub struct Confirmed(pub Notification);
+
+/// UnconfirmedCOVNotification
+#[derive(Debug, PartialEq)]
+pub struct Unconfirmed(pub Notification);
+
+impl Notification {
+    const SCHEMA: Schema = Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::Unsigned),
+        Schema::Context(1, PrimitiveType::ObjectId),
+        Schema::Context(2, PrimitiveType::ObjectId),
+        Schema::Context(3, PrimitiveType::Unsigned),
+        Schema::Constructed(4, &Schema::SequenceOf(&PROPERTY_VALUE)),
+    ]);
+
+    fn marshall(&self) -> ValueSequence {
+        vec!(
+            ContextValue(0, PrimitiveValue::Unsigned(self.subscriber_process_id)),
+            ContextValue(1, PrimitiveValue::ObjectId(self.initiating_device_id)),
+            ContextValue(2, PrimitiveValue::ObjectId(self.monitored_object_id)),
+            ContextValue(3, PrimitiveValue::Unsigned(self.time_remaining)),
+            ContextValueSequence(4, marshall_property_values(&self.values)))
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Notification, UnmarshallError> {
+        let mut values = Values::new(body);
+        let notification = Notification {
+            subscriber_process_id: required(values.context(0)?, 0)?,
+            initiating_device_id: required(values.context(1)?, 1)?,
+            monitored_object_id: required(values.context(2)?, 2)?,
+            time_remaining: required(values.context(3)?, 3)?,
+            values: unmarshall_property_values(&required(values.constructed(4), 4)?)?,
+        };
+        values.end()?;
+        Ok(notification)
+    }
+}
+
+impl ServiceMessage for Confirmed {
+    type Message = Self;
+
+    const SCHEMA: Schema = Notification::SCHEMA;
+
+    fn choice() -> u8 { 1 }
+
+    fn marshall(&self) -> ValueSequence {
+        self.0.marshall()
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        Notification::unmarshall(body).map(Confirmed)
+    }
+}
+
+impl ServiceMessage for Unconfirmed {
+    type Message = Self;
+
+    const SCHEMA: Schema = Notification::SCHEMA;
+
+    fn choice() -> u8 { 2 }
+
+    fn marshall(&self) -> ValueSequence {
+        self.0.marshall()
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        Notification::unmarshall(body).map(Unconfirmed)
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::*;
+    use service::Position;
+    use ast::BitString;
+    use ast::SequenceableValue::ApplicationValue;
+    use serialise::write_value_sequence;
+    use parse::parse_value_sequence_to_end;
+    use enumerations::{ObjectType, PropertyIdentifier};
+    use std::io;
+
+    /// The example from Clause E.2.1 - analog input 10 of device 4 has changed to 65.0
+    const NOTIFICATION: &[u8] = &[
+        0x09, 0x12, 0x1C, 0x02, 0x00, 0x00, 0x04, 0x2C, 0x00, 0x00, 0x00, 0x0A, 0x39, 0x00,
+        0x4E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x82, 0x00, 0x00, 0x2F, 0x09, 0x6F, 0x2E, 0x82, 0x04, 0x00, 0x2F, 0x4F];
+
+    fn notification() -> Notification {
+        Notification {
+            subscriber_process_id: 18,
+            initiating_device_id: ObjectId(ObjectType::Device, 4),
+            monitored_object_id: ObjectId(ObjectType::AnalogInput, 10),
+            time_remaining: 0,
+            values: vec!(
+                PropertyValue { property: PropertyIdentifier::PresentValue, array_index: None, value: vec!(ApplicationValue(PrimitiveValue::Real(65.0))), priority: None },
+                PropertyValue { property: PropertyIdentifier::StatusFlags, array_index: None, value: vec!(ApplicationValue(PrimitiveValue::BitString(BitString::new(4)))), priority: None }),
+        }
+    }
+
+    #[test]
+    fn test_serialise() {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, Confirmed(notification()).marshall()).unwrap();
+        assert_eq!(NOTIFICATION.to_vec(), buf);
+    }
+
+    #[test]
+    fn test_parse() {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(NOTIFICATION);
+        let body = parse_value_sequence_to_end(reader, &Unconfirmed::SCHEMA).unwrap();
+        assert_eq!(Ok(Unconfirmed(notification())), Unconfirmed::unmarshall(&body));
+    }
+
+    #[test]
+    fn test_marshall_cycle() {
+        assert_eq!(Ok(Confirmed(notification())), Confirmed::unmarshall(&Confirmed(notification()).marshall()));
+        assert_eq!(Ok(Unconfirmed(notification())), Unconfirmed::unmarshall(&Unconfirmed(notification()).marshall()));
+    }
+
+    #[test]
+    fn test_unmarshall_without_values() {
+        let mut body = notification().marshall();
+        body.pop();
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(4))), Unconfirmed::unmarshall(&body));
+    }
+}
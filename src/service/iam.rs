@@ -1,83 +1,45 @@
 //! Iam messages are unconfirmed requests and can be broadcast on the network to update the network
 //! to a device's current info or can be sent as a reply by the whois service.
 
-use ast::ValueSequence;
-use ast::PrimitiveValue::Unsigned;
-use ast::SequenceableValue::ApplicationValue;
-use ast::PrimitiveValue::ObjectId;
-use ast::PrimitiveValue::Enumerated;
-use object::object_type;
-use super::ServiceMessage;
-use super::UnmarshallError;
+use enumerations::{ObjectType, Segmentation};
 use object;
 use object::DeviceObject;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, ServiceMessage)]
+#[bacnet(choice = 0)]
 pub struct Message {
-    device_instance: u32,
+    device_id: object::ObjectId,
     max_apdu: u32,
-    segmentation_support: u8,
+    segmentation_support: Segmentation,
     vendor_id: u32,
 }
 
 impl Message {
 	pub fn about(device: &DeviceObject) -> Message { Message {
-		device_instance: device.instance,
+		device_id: object::ObjectId(ObjectType::Device, device.instance),
 		max_apdu: device.max_apdu_length_supported,
 		segmentation_support: device.segmentation_supported,
 		vendor_id: device.vendor_identifier,
 	} }
 }
 
-impl ServiceMessage for Message {
-    type Message = Self;
-
-    fn choice() -> u8 {
-        0
-    }
-
-    fn marshall(&self) -> ValueSequence {
-        vec!(
-            ApplicationValue(ObjectId(object::ObjectId(object_type::DEVICE, self.device_instance))),
-            ApplicationValue(Unsigned(self.max_apdu)),
-            ApplicationValue(Enumerated(self.segmentation_support as u32)),
-            ApplicationValue(Unsigned(self.vendor_id)),
-        )
-    }
-
-    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
-        match (&body[0], &body[1], &body[2], &body[3]) {
-            (&ApplicationValue(ObjectId(object::ObjectId(object_type::DEVICE, device_instance))), 
-             &ApplicationValue(Unsigned(max_apdu)),
-             &ApplicationValue(Enumerated(segmentation_support)),
-             &ApplicationValue(Unsigned(vendor_id))) =>
-                Ok(Message {
-                    device_instance: device_instance,
-                    max_apdu: max_apdu,
-                    segmentation_support: segmentation_support as u8,
-                    vendor_id: vendor_id,
-                }),
-            _ => Err(UnmarshallError::RequiredValueNotProvided),
-        }
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::Message;
     use super::super::ServiceMessage;
+    use super::super::{Position, UnmarshallError};
     use ast::PrimitiveValue::Unsigned;
     use ast::PrimitiveValue::Enumerated;
     use ast::PrimitiveValue::ObjectId;
-    use ast::SequenceableValue::ApplicationValue;
+    use ast::SequenceableValue::{ApplicationValue, ContextValue};
     use object;
-    use object::object_type;
+    use enumerations::{ObjectType, Segmentation};
 
     #[test]
     fn test_unmarshall_correct() {
-        assert_eq!(Ok(Message { device_instance: 10, max_apdu: 1476, segmentation_support: 3, vendor_id: 1 }),
+        assert_eq!(Ok(Message { device_id: object::ObjectId(ObjectType::Device, 10), max_apdu: 1476, segmentation_support: Segmentation::NoSegmentation, vendor_id: 1 }),
                    Message::unmarshall(&vec!(
-                          ApplicationValue(ObjectId(object::ObjectId(object_type::DEVICE, 10))), 
+                          ApplicationValue(ObjectId(object::ObjectId(ObjectType::Device, 10))), 
                           ApplicationValue(Unsigned(1476)),
                           ApplicationValue(Enumerated(3)),
                           ApplicationValue(Unsigned(1)))));
@@ -86,18 +48,50 @@ mod test {
     #[test]
     fn test_marshall_correct() {
         assert_eq!(vec!(
-                ApplicationValue(ObjectId(object::ObjectId(object_type::DEVICE, 10))),
+                ApplicationValue(ObjectId(object::ObjectId(ObjectType::Device, 10))),
                 ApplicationValue(Unsigned(1476)),
                 ApplicationValue(Enumerated(3)),
                 ApplicationValue(Unsigned(1))),
-                Message { device_instance: 10, max_apdu: 1476, segmentation_support: 3, vendor_id: 1 }.marshall());
+                Message { device_id: object::ObjectId(ObjectType::Device, 10), max_apdu: 1476, segmentation_support: Segmentation::NoSegmentation, vendor_id: 1 }.marshall());
+    }
+
+    #[test]
+    fn test_unmarshall_short() {
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Index(2))), Message::unmarshall(&vec!(
+                   ApplicationValue(ObjectId(object::ObjectId(ObjectType::Device, 10))),
+                   ApplicationValue(Unsigned(1476)))));
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Index(0))), Message::unmarshall(&vec!()));
+    }
+
+    #[test]
+    fn test_unmarshall_invalid() {
+        let message = |segmentation| vec!(
+            ApplicationValue(ObjectId(object::ObjectId(ObjectType::Device, 10))),
+            ApplicationValue(Unsigned(1476)),
+            segmentation,
+            ApplicationValue(Unsigned(1)));
+        assert_eq!(Err(UnmarshallError::WrongDataType(Position::Index(2))),
+                   Message::unmarshall(&message(ApplicationValue(Unsigned(3)))));
+        assert_eq!(Err(UnmarshallError::InvalidTag(Position::Index(2))),
+                   Message::unmarshall(&message(ContextValue(2, Enumerated(3)))));
+        let mut extra = message(ApplicationValue(Enumerated(3)));
+        extra.push(ApplicationValue(Unsigned(1)));
+        assert_eq!(Err(UnmarshallError::ExtraValues(4)), Message::unmarshall(&extra));
     }
 
     #[test]
     fn test_marshall_cycle() {
-        let message = Message { device_instance: 10, max_apdu: 1476, segmentation_support: 3, vendor_id: 1 };
+        let message = Message { device_id: object::ObjectId(ObjectType::Device, 10), max_apdu: 1476, segmentation_support: Segmentation::NoSegmentation, vendor_id: 1 };
         assert_eq!(message, Message::unmarshall(&message.marshall()).unwrap());
     }
+
+    #[test]
+    fn test_serialise() {
+        use serialise::write_value_sequence;
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, Message { device_id: object::ObjectId(ObjectType::Device, 10), max_apdu: 1476, segmentation_support: Segmentation::NoSegmentation, vendor_id: 1 }.marshall()).unwrap();
+        assert_eq!(vec![0xC4u8, 0x02, 0x00, 0x00, 0x0A, 0x22, 0x05, 0xC4, 0x91, 0x03, 0x21, 0x01], buf);
+    }
 }
 
 
@@ -0,0 +1,51 @@
+//! Ihave messages are unconfirmed requests which announce that a device contains an object. They
+//! are sent as a reply by the whohas service, or broadcast to tell the network about an object.
+
+use object;
+
+#[derive(Debug, PartialEq, ServiceMessage)]
+#[bacnet(choice = 1)]
+pub struct Message {
+    pub device_id: object::ObjectId,
+    pub object_id: object::ObjectId,
+    pub object_name: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Message;
+    use super::super::ServiceMessage;
+    use super::super::{Position, UnmarshallError};
+    use ast::PrimitiveValue::ObjectId;
+    use ast::SequenceableValue::ApplicationValue;
+    use object;
+    use enumerations::ObjectType;
+
+    fn message() -> Message {
+        Message {
+            device_id: object::ObjectId(ObjectType::Device, 8),
+            object_id: object::ObjectId(ObjectType::AnalogInput, 3),
+            object_name: "OATemp".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_marshall_cycle() {
+        assert_eq!(message(), Message::unmarshall(&message().marshall()).unwrap());
+    }
+
+    #[test]
+    fn test_unmarshall_short() {
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Index(1))),
+                   Message::unmarshall(&vec!(ApplicationValue(ObjectId(object::ObjectId(ObjectType::Device, 8))))));
+    }
+
+    #[test]
+    fn test_serialise() {
+        use serialise::write_value_sequence;
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, message().marshall()).unwrap();
+        assert_eq!(vec![0xC4u8, 0x02, 0x00, 0x00, 0x08, 0xC4, 0x00, 0x00, 0x00, 0x03,
+                        0x75, 0x07, 0x00, 0x4F, 0x41, 0x54, 0x65, 0x6D, 0x70], buf);
+    }
+}
@@ -1,43 +1,586 @@
 //! Implementations of bacnet services
 
+use ast::BitString;
 use ast::ValueSequence;
 use ast::ApduHeader;
-use object::BacnetDB;
+use ast::NetworkAddress;
+use ast::Primitive;
+use ast::PrimitiveValue;
+use ast::PrimitiveValue::Enumerated;
+use ast::SequenceableValue;
+use ast::SequenceableValue::{ApplicationValue, ContextValue, ContextValueSequence};
+use decode::{decode_value_sequence_with_extra_values, into_owned};
+use enumerations::{AbortReason, ErrorClass, ErrorCode, RejectReason};
+use object::{BacnetDB, SERVICES_SUPPORTED_LEN};
+use object::PropertyError;
+use parse::{ParseError, Schema};
+use std::collections::HashMap;
 pub mod whois;
 pub mod iam;
+pub mod whohas;
+pub mod ihave;
+pub mod readproperty;
+pub mod writeproperty;
+pub mod readpropertymultiple;
+pub mod writepropertymultiple;
+pub mod subscribecov;
+pub mod subscribecovproperty;
+pub mod covnotification;
 
-pub fn handle_apdu(header: ApduHeader, body: &ValueSequence, db: &BacnetDB) -> Option<ValueSequence> {
-    match header {
-        ApduHeader::UnconfirmedReq { service: choice } =>
-            unconfirmed_service(choice)(body, db),
-        _ => panic!("Unsupported request type"),
+/// Routes service requests to the handlers registered for their service choice, and turns the
+/// result into the response APDU. The schema of each service's request is kept with its handler
+/// to parse the requests.
+#[derive(Default)]
+pub struct Dispatcher {
+    confirmed: HashMap<u8, (ConfirmedHandler, Schema)>,
+    unconfirmed: HashMap<u8, (UnconfirmedHandler, Schema)>,
+}
+
+impl Dispatcher {
+    /// A dispatcher with no services registered, every confirmed request will be rejected
+    pub fn new() -> Dispatcher {
+        Dispatcher::default()
+    }
+
+    /// A dispatcher with all the services implemented in this crate registered
+    pub fn with_default_services() -> Dispatcher {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.register_unconfirmed::<whois::Message>(whois::handler);
+        dispatcher.register_unconfirmed::<whohas::Message>(whohas::handler);
+        dispatcher.register_confirmed::<readproperty::Message>(readproperty::handler);
+        dispatcher.register_confirmed::<writeproperty::Message>(writeproperty::handler);
+        dispatcher.register_confirmed::<readpropertymultiple::Message>(readpropertymultiple::handler);
+        dispatcher.register_confirmed::<writepropertymultiple::Message>(writepropertymultiple::handler);
+        dispatcher.register_confirmed::<subscribecov::Message>(subscribecov::handler);
+        dispatcher.register_confirmed::<subscribecovproperty::Message>(subscribecovproperty::handler);
+        dispatcher
+    }
+
+    /// Registers the handler for the confirmed service whose request is `S`, replacing any
+    /// previous handler
+    pub fn register_confirmed<S: ServiceMessage>(&mut self, handler: ConfirmedHandler) {
+        self.confirmed.insert(S::choice(), (handler, S::SCHEMA));
+    }
+
+    /// Registers the handler for the unconfirmed service whose request is `S`, replacing any
+    /// previous handler
+    pub fn register_unconfirmed<S: ServiceMessage>(&mut self, handler: UnconfirmedHandler) {
+        self.unconfirmed.insert(S::choice(), (handler, S::SCHEMA));
+    }
+
+    /// Parses the service data of a request with the schema of the service registered for it.
+    /// Values after the request's are kept, for the service to reject as too many arguments.
+    pub fn parse_request(&self, header: &ApduHeader, data: &[u8]) -> Result<ValueSequence, RequestParseError> {
+        let schema = match *header {
+            ApduHeader::ConfirmedReq { service, .. } => self.confirmed.get(&service).map(|(_, schema)| schema),
+            ApduHeader::UnconfirmedReq { service } => self.unconfirmed.get(&service).map(|(_, schema)| schema),
+            _ => return Err(ParseError::InvalidValue("not a request").into()),
+        };
+        match schema {
+            Some(schema) => Ok(into_owned(decode_value_sequence_with_extra_values(data, schema).map_err(ParseError::from)?)),
+            None => Err(RequestParseError::UnrecognizedService),
+        }
+    }
+
+    /// The registered services as a BACnetServicesSupported bit string, for the device's
+    /// Protocol_Services_Supported
+    pub fn services_supported(&self) -> BitString {
+        let mut services = BitString::new(SERVICES_SUPPORTED_LEN);
+        let bits = self.confirmed.keys().filter_map(|&choice| confirmed_service_bit(choice))
+            .chain(self.unconfirmed.keys().filter_map(|&choice| unconfirmed_service_bit(choice)));
+        for bit in bits {
+            services.set(bit, true);
+        }
+        services
+    }
+
+    /// Sets the device's Protocol_Services_Supported to the services registered here, once they
+    /// have all been registered and before the device's requests are handled
+    pub fn attach(&self, db: &mut BacnetDB) {
+        db.set_protocol_services_supported(self.services_supported());
+    }
+
+    /// Handles a request from `source`, returning the APDU to send back if there is one.
+    /// Confirmed requests always have a response, a failure being reported in a Reject, Error or
+    /// Abort PDU. Invalid or unknown unconfirmed requests are ignored. PDUs other than requests
+    /// aren't handled here and get no response.
+    pub fn handle_apdu(&self, header: ApduHeader, body: &ValueSequence, source: &NetworkAddress, db: &mut BacnetDB) -> Option<(ApduHeader, ValueSequence)> {
+        match header {
+            ApduHeader::ConfirmedReq { segmented: Some(_), invoke_id, .. } =>
+                Some(abort(invoke_id, AbortReason::SegmentationNotSupported)),
+            ApduHeader::ConfirmedReq { invoke_id, service, .. } =>
+                Some(self.confirmed_service(invoke_id, service, body, source, db)),
+            ApduHeader::UnconfirmedReq { service } =>
+                self.unconfirmed.get(&service)
+                    .and_then(|(handler, _)| handler(body, db))
+                    .map(|(service, body)| (ApduHeader::UnconfirmedReq { service }, body)),
+            _ => None,
+        }
+    }
+
+    fn confirmed_service(&self, invoke_id: u8, service: u8, body: &ValueSequence, source: &NetworkAddress, db: &mut BacnetDB) -> (ApduHeader, ValueSequence) {
+        let result = match self.confirmed.get(&service) {
+            Some((handler, _)) => handler(body, source, db),
+            None => Err(ServiceError::Reject(RejectReason::UnrecognizedService)),
+        };
+        match result {
+            Ok(ConfirmedAck::Simple) =>
+                (ApduHeader::SimpleAck { invoke_id, service }, vec!()),
+            Ok(ConfirmedAck::Complex(body)) =>
+                (ApduHeader::ComplexAck { segmented: None, invoke_id, service }, body),
+            Err(ServiceError::Error(body)) =>
+                (ApduHeader::ErrorPdu { invoke_id, error_choice: service }, body),
+            Err(ServiceError::Reject(reject_reason)) =>
+                (ApduHeader::RejectPdu { invoke_id, reject_reason }, vec!()),
+            Err(ServiceError::Abort(reason)) =>
+                abort(invoke_id, reason),
+        }
+    }
+}
+
+/// The bit for a confirmed service in BACnetServicesSupported, whose order isn't the order of the
+/// service choices
+fn confirmed_service_bit(choice: u8) -> Option<usize> {
+    match choice {
+        // Acknowledge-Alarm to Request-Key
+        0..=25 => Some(choice as usize),
+        26 => Some(35),     // Read-Range
+        27 => Some(37),     // Life-Safety-Operation
+        28 => Some(38),     // Subscribe-COV-Property
+        29 => Some(39),     // Get-Event-Information
+        30 => Some(41),     // Subscribe-COV-Property-Multiple
+        31 => Some(42),     // Confirmed-COV-Notification-Multiple
+        32 => Some(44),     // Confirmed-Audit-Notification
+        33 => Some(45),     // Audit-Log-Query
+        _ => None,
     }
 }
 
-fn unconfirmed_service(choice: u8) -> UnconfirmedHandler {
+/// The bit for an unconfirmed service in BACnetServicesSupported
+fn unconfirmed_service_bit(choice: u8) -> Option<usize> {
     match choice {
-        8 => whois::handler,
-        _ => panic!("Not suporting this service"),
+        // I-Am to Who-Is
+        0..=8 => Some(choice as usize + 26),
+        9 => Some(36),      // UTC-Time-Synchronization
+        10 => Some(40),     // Write-Group
+        11 => Some(43),     // Unconfirmed-COV-Notification-Multiple
+        12 => Some(46),     // Unconfirmed-Audit-Notification
+        _ => None,
     }
 }
 
+/// The service data of an Error PDU, which is the error class and code, or for
+/// WritePropertyMultiple the error followed by the write which failed
+pub const ERROR: Schema = Schema::Choice(&[
+    Schema::Sequence(&[Schema::Application, Schema::Application]),
+    writepropertymultiple::Error::SCHEMA,
+]);
+
+fn abort(invoke_id: u8, abort_reason: AbortReason) -> (ApduHeader, ValueSequence) {
+    (ApduHeader::AbortPdu { server: true, invoke_id, abort_reason }, vec!())
+}
+
 pub trait ServiceMessage {
     type Message;
+    /// Describes the message's values, so that its context tags can be parsed
+    const SCHEMA: Schema;
     fn choice() -> u8;
     fn unmarshall(body: &ValueSequence) -> Result<Self::Message, UnmarshallError>;
     fn marshall(&self) -> ValueSequence;
 }
 
+/// Where in a message's body a value was expected, or found
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Position {
+    /// The value with this context tag
+    Context(u8),
+    /// The value at this index of the body, for application tagged values
+    Index(usize),
+}
+
+/// Why a message's body couldn't be unmarshalled, each is answered with its own reject reason -
+/// see `UnmarshallError::reject_reason`
 #[derive(Debug, PartialEq)]
 pub enum UnmarshallError {
-    RequiredValueNotProvided,
+    /// A required value isn't there
+    MissingRequiredValue(Position),
+    /// The value is there but isn't of the message's datatype
+    WrongDataType(Position),
+    /// The value is of the right datatype but outside the range the message allows
+    OutOfRange(Position),
+    /// There is a value with a tag the message doesn't have in that place
+    InvalidTag(Position),
+    /// The message's values are followed by more, the first extra one is at this index of the
+    /// body
+    ExtraValues(usize),
 }
 
-/// An unconfirmed service must accept a service message, it also has access to the 
-/// bacnet object database and has the option to send an unconfirmed message in response
-type UnconfirmedHandler = fn(&ValueSequence, &BacnetDB) -> Option<ValueSequence>;
+impl UnmarshallError {
+    /// The reason to reject a request whose body has this error
+    pub fn reject_reason(&self) -> RejectReason {
+        match *self {
+            UnmarshallError::MissingRequiredValue(_) => RejectReason::MissingRequiredParameter,
+            UnmarshallError::WrongDataType(_) => RejectReason::InvalidParameterDataType,
+            UnmarshallError::OutOfRange(_) => RejectReason::ParameterOutOfRange,
+            UnmarshallError::InvalidTag(_) => RejectReason::InvalidTag,
+            UnmarshallError::ExtraValues(_) => RejectReason::TooManyArguments,
+        }
+    }
+}
+
+/// Why a request's service data couldn't be parsed - see `RequestParseError::reject_reason`
+#[derive(Debug, PartialEq)]
+pub enum RequestParseError {
+    /// No service is registered for the request's service choice
+    UnrecognizedService,
+    /// The service data doesn't parse with the service's schema
+    Parse(ParseError),
+}
+
+impl From<ParseError> for RequestParseError {
+    fn from(error: ParseError) -> RequestParseError {
+        RequestParseError::Parse(error)
+    }
+}
+
+impl RequestParseError {
+    /// The reason to reject a confirmed request with this error
+    pub fn reject_reason(&self) -> RejectReason {
+        match *self {
+            RequestParseError::UnrecognizedService => RejectReason::UnrecognizedService,
+            RequestParseError::Parse(ParseError::MissingRequiredValue) => RejectReason::MissingRequiredParameter,
+            RequestParseError::Parse(ParseError::ValueSizeNotSupported) |
+            RequestParseError::Parse(ParseError::InvalidValue(_)) |
+            RequestParseError::Parse(ParseError::NotImplemented(_)) => RejectReason::InvalidParameterDataType,
+            RequestParseError::Parse(_) => RejectReason::InvalidTag,
+        }
+    }
+}
+
+/// The error for a value at `position` which isn't a `T`
+fn unconvertible<T: Primitive>(value: &PrimitiveValue, position: Position) -> UnmarshallError {
+    if value.primitive_type() == T::TYPE {
+        UnmarshallError::OutOfRange(position)
+    } else {
+        UnmarshallError::WrongDataType(position)
+    }
+}
+
+/// A required value with the context tag `number`
+pub(crate) fn required<T>(value: Option<T>, number: u8) -> Result<T, UnmarshallError> {
+    value.ok_or(UnmarshallError::MissingRequiredValue(Position::Context(number)))
+}
+
+/// Converts the primitive value at `position` of a message's body
+pub(crate) fn convert<T: Primitive>(value: &PrimitiveValue, position: Position) -> Result<T, UnmarshallError> {
+    T::from_primitive(value).ok_or_else(|| unconvertible::<T>(value, position))
+}
+
+/// The values of a message's body, which the fields of a derived `ServiceMessage` are taken from
+/// in order
+pub(crate) struct Values<'a> {
+    body: &'a [SequenceableValue],
+    index: usize,
+}
+
+impl<'a> Values<'a> {
+    pub(crate) fn new(body: &'a [SequenceableValue]) -> Values<'a> {
+        Values { body, index: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a SequenceableValue> {
+        self.body.get(self.index)
+    }
+
+    /// Takes the next value if it is application tagged, `None` if it isn't
+    pub(crate) fn application<T: Primitive>(&mut self) -> Result<Option<T>, UnmarshallError> {
+        match self.peek() {
+            Some(ApplicationValue(value)) => {
+                self.index += 1;
+                convert(value, Position::Index(self.index - 1)).map(Some)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Takes the next value if it has the context tag `number`, `None` if it doesn't
+    pub(crate) fn context<T: Primitive>(&mut self, number: u8) -> Result<Option<T>, UnmarshallError> {
+        match self.peek() {
+            Some(ContextValue(tag, value)) if *tag == number => {
+                self.index += 1;
+                convert(value, Position::Context(number)).map(Some)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Takes the next values if they are in opening and closing tags with the number `number`
+    pub(crate) fn constructed(&mut self, number: u8) -> Option<ValueSequence> {
+        match self.peek() {
+            Some(ContextValueSequence(tag, sequence)) if *tag == number => {
+                self.index += 1;
+                Some(sequence.clone())
+            },
+            _ => None,
+        }
+    }
+
+    /// The error for a required application tagged value which isn't next
+    pub(crate) fn missing(&self) -> UnmarshallError {
+        match self.peek() {
+            Some(ApplicationValue(_)) | None => UnmarshallError::MissingRequiredValue(Position::Index(self.index)),
+            Some(_) => UnmarshallError::InvalidTag(Position::Index(self.index)),
+        }
+    }
+
+    /// Whether every value has been taken
+    pub(crate) fn is_done(&self) -> bool {
+        self.peek().is_none()
+    }
 
-// trait UnconfirmedService {
-// 	fn service_choice() -> u8;
-// 	fn handle(&ValueSequence, &BacnetDB) -> Option<()>;
-// }
+    /// Checks that every value has been taken
+    pub(crate) fn end(&self) -> Result<(), UnmarshallError> {
+        match self.peek() {
+            Some(_) => Err(UnmarshallError::ExtraValues(self.index)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The successful result of a confirmed service
+#[derive(Debug, PartialEq)]
+pub enum ConfirmedAck {
+    /// Answered with a Simple-ACK PDU
+    Simple,
+    /// Answered with a Complex-ACK PDU carrying the service's result
+    Complex(ValueSequence),
+}
+
+/// The ways a confirmed service can fail, each is answered with the matching PDU
+#[derive(Debug, PartialEq)]
+pub enum ServiceError {
+    /// The request was invalid and has had no effect
+    Reject(RejectReason),
+    /// The service failed, most services report this with an error class and code - see
+    /// `ServiceError::error`
+    Error(ValueSequence),
+    /// The transaction has been ended
+    Abort(AbortReason),
+}
+
+impl ServiceError {
+    /// The usual error, which is an error class and an error code (Clause 18)
+    pub fn error(class: ErrorClass, code: ErrorCode) -> ServiceError {
+        ServiceError::Error(vec!(ApplicationValue(Enumerated(class.into())), ApplicationValue(Enumerated(code.into()))))
+    }
+}
+
+impl From<UnmarshallError> for ServiceError {
+    fn from(error: UnmarshallError) -> ServiceError {
+        ServiceError::Reject(error.reject_reason())
+    }
+}
+
+impl From<PropertyError> for ServiceError {
+    fn from(error: PropertyError) -> ServiceError {
+        let (class, code) = property_error(&error);
+        ServiceError::error(class, code)
+    }
+}
+
+/// The error class and code reporting a failed property access
+pub fn property_error(error: &PropertyError) -> (ErrorClass, ErrorCode) {
+    match *error {
+        PropertyError::UnknownObject => (ErrorClass::Object, ErrorCode::UnknownObject),
+        PropertyError::UnknownProperty => (ErrorClass::Property, ErrorCode::UnknownProperty),
+        PropertyError::PropertyIsNotAnArray => (ErrorClass::Property, ErrorCode::PropertyIsNotAnArray),
+        PropertyError::InvalidArrayIndex => (ErrorClass::Property, ErrorCode::InvalidArrayIndex),
+        PropertyError::WriteAccessDenied => (ErrorClass::Property, ErrorCode::WriteAccessDenied),
+        PropertyError::InvalidDataType => (ErrorClass::Property, ErrorCode::InvalidDataType),
+        PropertyError::ValueOutOfRange => (ErrorClass::Property, ErrorCode::ValueOutOfRange),
+        PropertyError::DuplicateName => (ErrorClass::Property, ErrorCode::DuplicateName),
+    }
+}
+
+/// A confirmed service must accept a service message and always answers, either with an
+/// acknowledgement or with the reason it failed. It is told which device sent the request and may
+/// change the bacnet object database.
+pub type ConfirmedHandler = fn(&ValueSequence, &NetworkAddress, &mut BacnetDB) -> Result<ConfirmedAck, ServiceError>;
+
+/// The address requests come from in tests, a B/IP device on the local network
+#[cfg(test)]
+pub fn test_peer() -> NetworkAddress {
+    NetworkAddress {
+        network: 0,
+        address: ::ast::MacAddress::new(&[192, 168, 1, 10, 0xBA, 0xC0]).unwrap(),
+    }
+}
+
+/// An unconfirmed service must accept a service message, it also has access to the
+/// bacnet object database and has the option to send an unconfirmed message in response, which
+/// is returned with its service choice
+pub type UnconfirmedHandler = fn(&ValueSequence, &BacnetDB) -> Option<(u8, ValueSequence)>;
+
+#[cfg(test)]
+mod dispatcher_test {
+    use super::*;
+    use ast::{ApduHeader, MaxApdu, MaxSegments};
+    use ast::PrimitiveValue::Unsigned;
+    use ast::SequenceableValue::ContextValue;
+    use object::BacnetDB;
+    use object::test_db;
+
+    fn confirmed(service: u8) -> ApduHeader {
+        ApduHeader::ConfirmedReq {
+            segmented: None,
+            segmented_response_accepted: false,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 7,
+            service,
+        }
+    }
+
+    fn echo(body: &ValueSequence, _: &NetworkAddress, _: &mut BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+        match body.len() {
+            0 => Ok(ConfirmedAck::Simple),
+            1 => Ok(ConfirmedAck::Complex(body.clone())),
+            2 => Err(ServiceError::error(ErrorClass::Property, ErrorCode::UnknownProperty)),
+            3 => Err(UnmarshallError::MissingRequiredValue(Position::Context(0)).into()),
+            _ => Err(ServiceError::Abort(AbortReason::OutOfResources)),
+        }
+    }
+
+    /// The request of a service which is only in the tests
+    struct Echo;
+
+    impl ServiceMessage for Echo {
+        type Message = ValueSequence;
+        const SCHEMA: Schema = Schema::Any;
+        fn choice() -> u8 { 100 }
+        fn unmarshall(body: &ValueSequence) -> Result<ValueSequence, UnmarshallError> { Ok(body.clone()) }
+        fn marshall(&self) -> ValueSequence { vec!() }
+    }
+
+    fn dispatch(header: ApduHeader, body: ValueSequence) -> Option<(ApduHeader, ValueSequence)> {
+        let mut dispatcher = Dispatcher::with_default_services();
+        dispatcher.register_confirmed::<Echo>(echo);
+        dispatcher.handle_apdu(header, &body, &test_peer(), &mut test_db())
+    }
+
+    #[test]
+    fn unrecognized_confirmed_service_is_rejected() {
+        assert_eq!(Some((ApduHeader::RejectPdu { invoke_id: 7, reject_reason: RejectReason::UnrecognizedService }, vec!())),
+                   dispatch(confirmed(99), vec!()));
+    }
+
+    #[test]
+    fn unrecognized_unconfirmed_service_is_ignored() {
+        assert_eq!(None, dispatch(ApduHeader::UnconfirmedReq { service: 99 }, vec!()));
+    }
+
+    #[test]
+    fn segmented_request_is_aborted() {
+        use ast::SegmentInfo;
+        let header = ApduHeader::ConfirmedReq {
+            segmented: Some(SegmentInfo { more_follows: true, sequence_number: 0, proposed_window_size: 1 }),
+            segmented_response_accepted: false,
+            max_segments: MaxSegments::Unspecified,
+            max_apdu: MaxApdu::Up1476,
+            invoke_id: 7,
+            service: 100,
+        };
+        assert_eq!(Some((ApduHeader::AbortPdu { server: true, invoke_id: 7, abort_reason: AbortReason::SegmentationNotSupported }, vec!())),
+                   dispatch(header, vec!()));
+    }
+
+    #[test]
+    fn confirmed_results() {
+        let value = ContextValue(0, Unsigned(1));
+        assert_eq!(Some((ApduHeader::SimpleAck { invoke_id: 7, service: 100 }, vec!())),
+                   dispatch(confirmed(100), vec!()));
+        assert_eq!(Some((ApduHeader::ComplexAck { segmented: None, invoke_id: 7, service: 100 }, vec!(value.clone()))),
+                   dispatch(confirmed(100), vec!(value.clone())));
+        assert_eq!(Some((ApduHeader::ErrorPdu { invoke_id: 7, error_choice: 100 }, vec!(ApplicationValue(Enumerated(2)), ApplicationValue(Enumerated(32))))),
+                   dispatch(confirmed(100), vec!(value.clone(), value.clone())));
+        assert_eq!(Some((ApduHeader::RejectPdu { invoke_id: 7, reject_reason: RejectReason::MissingRequiredParameter }, vec!())),
+                   dispatch(confirmed(100), vec!(value.clone(), value.clone(), value.clone())));
+        assert_eq!(Some((ApduHeader::AbortPdu { server: true, invoke_id: 7, abort_reason: AbortReason::OutOfResources }, vec!())),
+                   dispatch(confirmed(100), vec!(value.clone(), value.clone(), value.clone(), value)));
+    }
+
+    #[test]
+    fn unmarshall_errors_are_rejected() {
+        use object::ObjectId;
+        use enumerations::ObjectType;
+        let reject = |reject_reason| Some((ApduHeader::RejectPdu { invoke_id: 7, reject_reason }, vec!()));
+        let object_id = ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45)));
+        assert_eq!(reject(RejectReason::MissingRequiredParameter), dispatch(confirmed(12), vec!(object_id.clone())));
+        assert_eq!(reject(RejectReason::InvalidParameterDataType),
+                   dispatch(confirmed(12), vec!(object_id.clone(), ContextValue(1, Unsigned(77)))));
+        assert_eq!(reject(RejectReason::TooManyArguments),
+                   dispatch(confirmed(12), vec!(object_id.clone(), ContextValue(1, Enumerated(77)), ContextValue(5, Unsigned(1)))));
+        assert_eq!(ServiceError::Reject(RejectReason::InvalidTag), UnmarshallError::InvalidTag(Position::Index(0)).into());
+        assert_eq!(ServiceError::Reject(RejectReason::ParameterOutOfRange), UnmarshallError::OutOfRange(Position::Context(0)).into());
+    }
+
+    #[test]
+    fn requests_are_parsed_with_the_registered_schema() {
+        let mut dispatcher = Dispatcher::new();
+        // Who-Is with a range, whose context tags are only known from its schema
+        let whois = [0x09u8, 0x00, 0x19, 0x64];
+        assert_eq!(Err(RequestParseError::UnrecognizedService),
+                   dispatcher.parse_request(&ApduHeader::UnconfirmedReq { service: 8 }, &whois));
+        dispatcher.register_unconfirmed::<whois::Message>(whois::handler);
+        assert_eq!(Ok(vec!(ContextValue(0, Unsigned(0)), ContextValue(1, Unsigned(100)))),
+                   dispatcher.parse_request(&ApduHeader::UnconfirmedReq { service: 8 }, &whois));
+        assert_eq!(Err(RequestParseError::UnrecognizedService),
+                   dispatcher.parse_request(&confirmed(8), &whois));
+    }
+
+    #[test]
+    fn services_supported() {
+        let services = Dispatcher::with_default_services().services_supported();
+        assert_eq!(47, services.len());
+        let supported: Vec<usize> = (0..services.len()).filter(|&bit| services.get(bit)).collect();
+        assert_eq!(vec!(5, 12, 14, 15, 16, 33, 34, 38), supported);
+        assert!(Dispatcher::new().services_supported().octets().iter().all(|&octet| octet == 0));
+    }
+
+    #[test]
+    fn device_has_the_services_supported() {
+        let mut db = test_db();
+        let dispatcher = Dispatcher::with_default_services();
+        dispatcher.attach(&mut db);
+        assert_eq!(&dispatcher.services_supported(), db.device().protocol_services_supported());
+    }
+
+    #[test]
+    fn whois_is_answered_with_iam() {
+        use service::iam;
+        let (header, body) = dispatch(ApduHeader::UnconfirmedReq { service: 8 }, vec!(ContextValue(0, Unsigned(0)), ContextValue(1, Unsigned(100)))).unwrap();
+        assert_eq!(ApduHeader::UnconfirmedReq { service: 0 }, header);
+        assert_eq!(Ok(iam::Message::about(test_db().device())), iam::Message::unmarshall(&body));
+    }
+
+    #[test]
+    fn whohas_is_answered_with_ihave() {
+        use service::{ihave, whohas};
+        let request = whohas::Message::new(whohas::ObjectSelector::Name("Test device".to_string()));
+        let (header, body) = dispatch(ApduHeader::UnconfirmedReq { service: 7 }, request.marshall()).unwrap();
+        assert_eq!(ApduHeader::UnconfirmedReq { service: 1 }, header);
+        assert_eq!(Ok(test_db().device().object_id()), ihave::Message::unmarshall(&body).map(|ihave| ihave.object_id));
+    }
+
+    #[test]
+    fn invalid_whois_is_ignored() {
+        assert_eq!(None, dispatch(ApduHeader::UnconfirmedReq { service: 8 }, vec!(ContextValue(0, Unsigned(0)))));
+        assert_eq!(None, dispatch(ApduHeader::UnconfirmedReq { service: 8 }, vec!(ContextValue(0, Enumerated(0)), ContextValue(1, Unsigned(100)))));
+    }
+
+    #[test]
+    fn responses_are_ignored() {
+        assert_eq!(None, dispatch(ApduHeader::SimpleAck { invoke_id: 7, service: 100 }, vec!()));
+        assert_eq!(None, dispatch(ApduHeader::AbortPdu { server: false, invoke_id: 7, abort_reason: AbortReason::Other }, vec!()));
+    }
+}
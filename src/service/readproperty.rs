@@ -0,0 +1,176 @@
+//! The ReadProperty service (Clause 15.5) is a confirmed request for the value of one property of
+//! an object, or of one element if the property is an array. The value is returned in a
+//! ComplexACK.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+
+use object;
+use object::ObjectId;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use enumerations::PropertyIdentifier;
+
+/// The request
+#[derive(Debug, PartialEq, ServiceMessage)]
+#[bacnet(choice = 12)]
+pub struct Message {
+    #[bacnet(context = 0)]
+    pub object_id: ObjectId,
+    #[bacnet(context = 1)]
+    pub property: PropertyIdentifier,
+    #[bacnet(context = 2, optional)]
+    pub array_index: Option<u32>,
+}
+
+impl Message {
+    pub fn new(object_id: ObjectId, property: PropertyIdentifier, array_index: Option<u32>) -> Message {
+        Message {
+            object_id,
+            property,
+            array_index,
+        }
+    }
+}
+
+/// The response, carried in a ComplexACK
+#[derive(Debug, PartialEq, ServiceMessage)]
+#[bacnet(choice = 12)]
+pub struct Ack {
+    #[bacnet(context = 0)]
+    pub object_id: ObjectId,
+    #[bacnet(context = 1)]
+    pub property: PropertyIdentifier,
+    #[bacnet(context = 2, optional)]
+    pub array_index: Option<u32>,
+    /// Application tagged, in opening and closing tags
+    #[bacnet(context = 3, constructed)]
+    pub value: ValueSequence,
+}
+
+pub fn handler(body: &ValueSequence, _: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    let value = db.read_property(&request.object_id, request.property, request.array_index)?;
+    Ok(ConfirmedAck::Complex(Ack {
+        object_id: request.object_id,
+        property: request.property,
+        array_index: request.array_index,
+        value,
+    }.marshall()))
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::handler;
+    use super::Ack;
+    use super::Message;
+    use service::{ConfirmedAck, ServiceError, ServiceMessage, test_peer};
+    use enumerations::{ErrorClass, ErrorCode, ObjectType, PropertyIdentifier, RejectReason};
+    use ast::PrimitiveValue::Unsigned;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::{ObjectId, test_db};
+
+    fn read(object_id: ObjectId, property: PropertyIdentifier, array_index: Option<u32>) -> Result<ConfirmedAck, ServiceError> {
+        handler(&Message::new(object_id, property, array_index).marshall(), &test_peer(), &mut test_db())
+    }
+
+    #[test]
+    fn read_vendor_identifier() {
+        let device = ObjectId(ObjectType::Device, 45);
+        assert_eq!(Ok(ConfirmedAck::Complex(Ack {
+            object_id: device,
+            property: PropertyIdentifier::VendorIdentifier,
+            array_index: None,
+            value: vec!(ApplicationValue(Unsigned(23))),
+        }.marshall())), read(device, PropertyIdentifier::VendorIdentifier, None));
+    }
+
+    #[test]
+    fn read_unknown_object() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Object, ErrorCode::UnknownObject)),
+                   read(ObjectId(ObjectType::Device, 46), PropertyIdentifier::VendorIdentifier, None));
+        assert_eq!(Err(ServiceError::error(ErrorClass::Object, ErrorCode::UnknownObject)),
+                   read(ObjectId(ObjectType::AnalogInput, 45), PropertyIdentifier::VendorIdentifier, None));
+    }
+
+    #[test]
+    fn read_unknown_property() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::UnknownProperty)),
+                   read(ObjectId(ObjectType::Device, 45), PropertyIdentifier::PresentValue, None));
+    }
+
+    #[test]
+    fn read_index_of_non_array() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::PropertyIsNotAnArray)),
+                   read(ObjectId(ObjectType::Device, 45), PropertyIdentifier::VendorIdentifier, Some(1)));
+    }
+
+    #[test]
+    fn missing_property_is_rejected() {
+        use ast::SequenceableValue::ContextValue;
+        use ast::PrimitiveValue;
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&vec!(ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45)))), &test_peer(), &mut test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::{Ack, Message};
+    use super::super::ServiceMessage;
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::ObjectId;
+    use parse::{Schema, parse_value_sequence_to_end};
+    use serialise::write_value_sequence;
+    use std::io;
+    use enumerations::{ObjectType, PropertyIdentifier};
+
+    /// The example from Clause E.3.5 - reading the present value of analog input 5
+    const REQUEST: &[u8] = &[0x0C, 0x00, 0x00, 0x00, 0x05, 0x19, 0x55];
+    const ACK: &[u8] = &[0x0C, 0x00, 0x00, 0x00, 0x05, 0x19, 0x55, 0x3E, 0x44, 0x42, 0x90, 0x00, 0x00, 0x3F];
+
+    fn request() -> Message {
+        Message::new(ObjectId(ObjectType::AnalogInput, 5), PropertyIdentifier::PresentValue, None)
+    }
+
+    fn ack() -> Ack {
+        Ack {
+            object_id: ObjectId(ObjectType::AnalogInput, 5),
+            property: PropertyIdentifier::PresentValue,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::Real(72.0))),
+        }
+    }
+
+    fn write(body: ::ast::ValueSequence) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, body).unwrap();
+        buf
+    }
+
+    fn parse(data: &[u8], schema: &Schema) -> ::ast::ValueSequence {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, schema).unwrap()
+    }
+
+    #[test]
+    fn test_serialise_request() {
+        assert_eq!(REQUEST.to_vec(), write(request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&parse(REQUEST, &Message::SCHEMA)));
+    }
+
+    #[test]
+    fn test_serialise_ack() {
+        assert_eq!(ACK.to_vec(), write(ack().marshall()));
+        assert_eq!(Ok(ack()), Ack::unmarshall(&parse(ACK, &Ack::SCHEMA)));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_index() {
+        let message = Message::new(ObjectId(ObjectType::Device, 45), PropertyIdentifier::ObjectList, Some(3));
+        assert_eq!(message, Message::unmarshall(&message.marshall()).unwrap());
+        assert_eq!(message, Message::unmarshall(&parse(&write(message.marshall()), &Message::SCHEMA)).unwrap());
+    }
+}
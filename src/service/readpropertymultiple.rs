@@ -0,0 +1,417 @@
+//! The ReadPropertyMultiple service (Clause 15.7) reads any number of properties of any number of
+//! objects in one request. Each property is read independently, a property which can't be read
+//! has an error in its place in the ack rather than failing the whole request. The special
+//! property identifiers ALL, REQUIRED and OPTIONAL read a group of an object's properties.
+//!
+//! The property lists are constructed values which reuse the context tag numbers of the enclosing
+//! object's fields, so each level of nesting has its own schema.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+use super::{UnmarshallError, Values, required};
+use super::property_error;
+
+use object;
+use object::ObjectId;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+use ast::SequenceableValue::ContextValue;
+use ast::SequenceableValue::ContextValueSequence;
+use enumerations::{ErrorClass, ErrorCode, PropertyIdentifier};
+use parse::{PrimitiveType, Schema};
+
+/// The request
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub specifications: Vec<ReadAccessSpecification>,
+}
+
+/// The properties to read from one object
+#[derive(Debug, PartialEq)]
+pub struct ReadAccessSpecification {
+    pub object_id: ObjectId,
+    pub properties: Vec<PropertyReference>,
+}
+
+/// A property, or one element of an array property
+#[derive(Debug, PartialEq)]
+pub struct PropertyReference {
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+}
+
+/// The response, carried in a ComplexACK
+#[derive(Debug, PartialEq)]
+pub struct Ack {
+    pub results: Vec<ReadAccessResult>,
+}
+
+/// The properties read from one object, special property identifiers having been replaced by
+/// the properties they stand for
+#[derive(Debug, PartialEq)]
+pub struct ReadAccessResult {
+    pub object_id: ObjectId,
+    pub results: Vec<PropertyResult>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PropertyResult {
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+    pub result: ReadResult,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReadResult {
+    Value(ValueSequence),
+    /// The property couldn't be read, with the error class and code saying why
+    Error(ErrorClass, ErrorCode),
+}
+
+pub fn handler(body: &ValueSequence, _: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    Ok(ConfirmedAck::Complex(Ack {
+        results: request.specifications.iter().map(|specification| read_access(specification, db)).collect(),
+    }.marshall()))
+}
+
+fn read_access(specification: &ReadAccessSpecification, db: &object::BacnetDB) -> ReadAccessResult {
+    let object_id = specification.object_id;
+    let mut results = vec!();
+    for reference in &specification.properties {
+        match reference.property {
+            PropertyIdentifier::All | PropertyIdentifier::Required | PropertyIdentifier::Optional =>
+                match db.expand_properties(&object_id, reference.property) {
+                    Ok(properties) => results.extend(properties.into_iter().map(|property| read(db, object_id, property, None))),
+                    Err(error) => results.push(PropertyResult {
+                        property: reference.property,
+                        array_index: reference.array_index,
+                        result: error_result(&error),
+                    }),
+                },
+            property => results.push(read(db, object_id, property, reference.array_index)),
+        }
+    }
+    ReadAccessResult {
+        object_id,
+        results,
+    }
+}
+
+fn read(db: &object::BacnetDB, object_id: ObjectId, property: PropertyIdentifier, array_index: Option<u32>) -> PropertyResult {
+    PropertyResult {
+        property,
+        array_index,
+        result: match db.read_property(&object_id, property, array_index) {
+            Ok(value) => ReadResult::Value(value),
+            Err(error) => error_result(&error),
+        },
+    }
+}
+
+fn error_result(error: &object::PropertyError) -> ReadResult {
+    let (class, code) = property_error(error);
+    ReadResult::Error(class, code)
+}
+
+/// A property identifier and optional array index, which is also used by other services
+pub(crate) const PROPERTY_REFERENCE: Schema = Schema::Sequence(&[
+    Schema::Context(0, PrimitiveType::Enumerated),
+    Schema::Optional(&Schema::Context(1, PrimitiveType::Unsigned)),
+]);
+
+impl ServiceMessage for Message {
+    type Message = Self;
+
+    /// Any number of objects, each with a list of property references
+    const SCHEMA: Schema = Schema::SequenceOf(&Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::ObjectId),
+        Schema::Constructed(1, &Schema::SequenceOf(&PROPERTY_REFERENCE)),
+    ]));
+
+    fn choice() -> u8 { 14 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = vec!();
+        for specification in &self.specifications {
+            let mut references = vec!();
+            for reference in &specification.properties {
+                references.push(ContextValue(0, PrimitiveValue::Enumerated(reference.property.into())));
+                if let Some(index) = reference.array_index {
+                    references.push(ContextValue(1, PrimitiveValue::Unsigned(index)));
+                }
+            }
+            body.push(ContextValue(0, PrimitiveValue::ObjectId(specification.object_id)));
+            body.push(ContextValueSequence(1, references));
+        }
+        body
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let specifications = unmarshall_objects(body, |list| {
+            let mut values = Values::new(list);
+            let mut properties = vec!();
+            while !values.is_done() {
+                properties.push(PropertyReference {
+                    property: required(values.context(0)?, 0)?,
+                    array_index: values.context(1)?,
+                });
+            }
+            Ok(properties)
+        })?;
+        Ok(Message {
+            specifications: specifications.into_iter()
+                .map(|(object_id, properties)| ReadAccessSpecification { object_id, properties })
+                .collect(),
+        })
+    }
+}
+
+impl ServiceMessage for Ack {
+    type Message = Self;
+
+    /// Each property result has either its value or an error class and code
+    const SCHEMA: Schema = Schema::SequenceOf(&Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::ObjectId),
+        Schema::Constructed(1, &Schema::SequenceOf(&Schema::Sequence(&[
+            Schema::Context(2, PrimitiveType::Enumerated),
+            Schema::Optional(&Schema::Context(3, PrimitiveType::Unsigned)),
+            Schema::Choice(&[
+                Schema::Constructed(4, &Schema::Any),
+                Schema::Constructed(5, &Schema::Sequence(&[Schema::Application, Schema::Application])),
+            ]),
+        ]))),
+    ]));
+
+    fn choice() -> u8 { 14 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = vec!();
+        for access in &self.results {
+            let mut results = vec!();
+            for result in &access.results {
+                results.push(ContextValue(2, PrimitiveValue::Enumerated(result.property.into())));
+                if let Some(index) = result.array_index {
+                    results.push(ContextValue(3, PrimitiveValue::Unsigned(index)));
+                }
+                results.push(match result.result {
+                    ReadResult::Value(ref value) => ContextValueSequence(4, value.clone()),
+                    ReadResult::Error(class, code) => ContextValueSequence(5, vec!(
+                        ApplicationValue(PrimitiveValue::Enumerated(class.into())),
+                        ApplicationValue(PrimitiveValue::Enumerated(code.into())))),
+                });
+            }
+            body.push(ContextValue(0, PrimitiveValue::ObjectId(access.object_id)));
+            body.push(ContextValueSequence(1, results));
+        }
+        body
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let results = unmarshall_objects(body, |list| {
+            let mut values = Values::new(list);
+            let mut results = vec!();
+            while !values.is_done() {
+                let property = required(values.context(2)?, 2)?;
+                let array_index = values.context(3)?;
+                let result = match values.constructed(4) {
+                    Some(value) => ReadResult::Value(value),
+                    None => {
+                        let (class, code) = unmarshall_error(&required(values.constructed(5), 4)?)?;
+                        ReadResult::Error(class, code)
+                    },
+                };
+                results.push(PropertyResult { property, array_index, result });
+            }
+            Ok(results)
+        })?;
+        Ok(Ack {
+            results: results.into_iter()
+                .map(|(object_id, results)| ReadAccessResult { object_id, results })
+                .collect(),
+        })
+    }
+}
+
+/// Both the request and the ack are a list of object identifiers, each followed by a list of
+/// properties which is unmarshalled by `properties`. At least one object is required.
+pub(crate) fn unmarshall_objects<T, F>(body: &ValueSequence, properties: F) -> Result<Vec<(ObjectId, T)>, UnmarshallError>
+    where F: Fn(&ValueSequence) -> Result<T, UnmarshallError> {
+    let mut values = Values::new(body);
+    let mut objects = vec!();
+    loop {
+        let object_id = required(values.context(0)?, 0)?;
+        let list = required(values.constructed(1), 1)?;
+        objects.push((object_id, properties(&list)?));
+        if values.is_done() {
+            return Ok(objects)
+        }
+    }
+}
+
+/// An error class and code, which are application tagged
+pub(crate) fn unmarshall_error(body: &ValueSequence) -> Result<(ErrorClass, ErrorCode), UnmarshallError> {
+    let mut values = Values::new(body);
+    let class = values.application()?.ok_or_else(|| values.missing())?;
+    let code = values.application()?.ok_or_else(|| values.missing())?;
+    values.end()?;
+    Ok((class, code))
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::*;
+    use service::test_peer;
+    use ast::PrimitiveValue::Unsigned;
+    use object::{DeviceObject, test_db};
+    use enumerations::{ObjectType, RejectReason};
+
+    const DEVICE: ObjectId = ObjectId(ObjectType::Device, 45);
+
+    fn read(specifications: Vec<ReadAccessSpecification>) -> Ack {
+        match handler(&Message { specifications }.marshall(), &test_peer(), &mut test_db()) {
+            Ok(ConfirmedAck::Complex(body)) => Ack::unmarshall(&body).unwrap(),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    fn reference(property: PropertyIdentifier, array_index: Option<u32>) -> PropertyReference {
+        PropertyReference { property, array_index }
+    }
+
+    #[test]
+    fn read_several_properties_with_errors() {
+        let ack = read(vec!(
+            ReadAccessSpecification {
+                object_id: DEVICE,
+                properties: vec!(
+                    reference(PropertyIdentifier::VendorIdentifier, None),
+                    reference(PropertyIdentifier::PresentValue, None),
+                    reference(PropertyIdentifier::ApduTimeout, Some(1))),
+            },
+            ReadAccessSpecification {
+                object_id: ObjectId(ObjectType::Device, 46),
+                properties: vec!(reference(PropertyIdentifier::VendorIdentifier, None)),
+            }));
+        assert_eq!(Ack { results: vec!(
+            ReadAccessResult {
+                object_id: DEVICE,
+                results: vec!(
+                    PropertyResult { property: PropertyIdentifier::VendorIdentifier, array_index: None, result: ReadResult::Value(vec!(ApplicationValue(Unsigned(23)))) },
+                    PropertyResult { property: PropertyIdentifier::PresentValue, array_index: None, result: ReadResult::Error(ErrorClass::Property, ErrorCode::UnknownProperty) },
+                    PropertyResult { property: PropertyIdentifier::ApduTimeout, array_index: Some(1), result: ReadResult::Error(ErrorClass::Property, ErrorCode::PropertyIsNotAnArray) }),
+            },
+            ReadAccessResult {
+                object_id: ObjectId(ObjectType::Device, 46),
+                results: vec!(
+                    PropertyResult { property: PropertyIdentifier::VendorIdentifier, array_index: None, result: ReadResult::Error(ErrorClass::Object, ErrorCode::UnknownObject) }),
+            })}, ack);
+    }
+
+    #[test]
+    fn read_all_properties() {
+        let ack = read(vec!(ReadAccessSpecification { object_id: DEVICE, properties: vec!(reference(PropertyIdentifier::All, None)) }));
+        let properties: Vec<PropertyIdentifier> = ack.results[0].results.iter().map(|result| result.property).collect();
+        assert_eq!([DeviceObject::REQUIRED_PROPERTIES, DeviceObject::OPTIONAL_PROPERTIES].concat(), properties);
+        assert!(ack.results[0].results.iter().all(|result| matches!(result.result, ReadResult::Value(_))));
+    }
+
+    #[test]
+    fn read_optional_properties() {
+        let ack = read(vec!(ReadAccessSpecification { object_id: DEVICE, properties: vec!(reference(PropertyIdentifier::Optional, None)) }));
+        let properties: Vec<PropertyIdentifier> = ack.results[0].results.iter().map(|result| result.property).collect();
+        assert_eq!(DeviceObject::OPTIONAL_PROPERTIES.to_vec(), properties);
+        assert!(ack.results[0].results.iter().all(|result| matches!(result.result, ReadResult::Value(_))));
+    }
+
+    #[test]
+    fn read_all_of_unknown_object() {
+        let ack = read(vec!(ReadAccessSpecification { object_id: ObjectId(ObjectType::AnalogInput, 1), properties: vec!(reference(PropertyIdentifier::Required, None)) }));
+        assert_eq!(vec!(PropertyResult {
+            property: PropertyIdentifier::Required,
+            array_index: None,
+            result: ReadResult::Error(ErrorClass::Object, ErrorCode::UnknownObject),
+        }), ack.results[0].results);
+    }
+
+    #[test]
+    fn empty_request_is_rejected() {
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&vec!(), &test_peer(), &mut test_db()));
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&vec!(ContextValue(0, PrimitiveValue::ObjectId(DEVICE))), &test_peer(), &mut test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::*;
+    use serialise::write_value_sequence;
+    use parse::parse_value_sequence_to_end;
+    use enumerations::ObjectType;
+    use std::io;
+
+    /// The example from Clause E.3.7 - reading the present value and reliability of analog input
+    /// 16
+    const REQUEST: &[u8] = &[0x0C, 0x00, 0x00, 0x00, 0x10, 0x1E, 0x09, 0x55, 0x09, 0x67, 0x1F];
+    const ACK: &[u8] = &[0x0C, 0x00, 0x00, 0x00, 0x10, 0x1E, 0x29, 0x55, 0x4E, 0x44, 0x42, 0x90, 0x99, 0x9A, 0x4F,
+                         0x29, 0x67, 0x4E, 0x91, 0x00, 0x4F, 0x1F];
+
+    fn request() -> Message {
+        Message { specifications: vec!(ReadAccessSpecification {
+            object_id: ObjectId(ObjectType::AnalogInput, 16),
+            properties: vec!(
+                PropertyReference { property: PropertyIdentifier::PresentValue, array_index: None },
+                PropertyReference { property: PropertyIdentifier::Reliability, array_index: None }),
+        })}
+    }
+
+    fn ack() -> Ack {
+        Ack { results: vec!(ReadAccessResult {
+            object_id: ObjectId(ObjectType::AnalogInput, 16),
+            results: vec!(
+                PropertyResult { property: PropertyIdentifier::PresentValue, array_index: None, result: ReadResult::Value(vec!(ApplicationValue(PrimitiveValue::Real(72.3)))) },
+                PropertyResult { property: PropertyIdentifier::Reliability, array_index: None, result: ReadResult::Value(vec!(ApplicationValue(PrimitiveValue::Enumerated(0)))) }),
+        })}
+    }
+
+    fn write(body: ValueSequence) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, body).unwrap();
+        buf
+    }
+
+    fn parse(data: &[u8], schema: &Schema) -> ValueSequence {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, schema).unwrap()
+    }
+
+    #[test]
+    fn test_serialise_request() {
+        assert_eq!(REQUEST.to_vec(), write(request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&parse(REQUEST, &Message::SCHEMA)));
+    }
+
+    #[test]
+    fn test_serialise_ack() {
+        assert_eq!(ACK.to_vec(), write(ack().marshall()));
+        assert_eq!(Ok(ack()), Ack::unmarshall(&ack().marshall()));
+        assert_eq!(Ok(ack()), Ack::unmarshall(&parse(ACK, &Ack::SCHEMA)));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_errors_and_indexes() {
+        let ack = || Ack { results: vec!(ReadAccessResult {
+            object_id: ObjectId(ObjectType::Device, 45),
+            results: vec!(
+                PropertyResult { property: PropertyIdentifier::ObjectList, array_index: Some(0), result: ReadResult::Value(vec!(ApplicationValue(PrimitiveValue::Unsigned(2)))) },
+                PropertyResult { property: PropertyIdentifier::PresentValue, array_index: None, result: ReadResult::Error(ErrorClass::Property, ErrorCode::UnknownProperty) }),
+        })};
+        assert_eq!(Ok(ack()), Ack::unmarshall(&ack().marshall()));
+        assert_eq!(Ok(ack()), Ack::unmarshall(&parse(&write(ack().marshall()), &Ack::SCHEMA)));
+    }
+}
@@ -0,0 +1,152 @@
+//! The SubscribeCOV service (Clause 13.14) subscribes to COV notifications about an object, or
+//! cancels a subscription when neither the notification type nor the lifetime are given. The
+//! notifications are made by `object::BacnetDB::cov_notifications`.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+
+use cov;
+use object;
+use object::ObjectId;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use enumerations::{ErrorClass, ErrorCode, RejectReason};
+
+/// The request
+#[derive(Debug, PartialEq, ServiceMessage)]
+#[bacnet(choice = 5)]
+pub struct Message {
+    #[bacnet(context = 0)]
+    pub subscriber_process_id: u32,
+    #[bacnet(context = 1)]
+    pub object_id: ObjectId,
+    #[bacnet(context = 2, optional)]
+    pub issue_confirmed_notifications: Option<bool>,
+    /// In seconds, 0 for an indefinite subscription
+    #[bacnet(context = 3, optional)]
+    pub lifetime: Option<u32>,
+}
+
+impl Message {
+    /// A request without a notification type or a lifetime cancels the subscription
+    pub fn is_cancellation(&self) -> bool {
+        self.issue_confirmed_notifications.is_none() && self.lifetime.is_none()
+    }
+}
+
+pub fn handler(body: &ValueSequence, source: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    // The notification type and the lifetime are both given or both left out - Clause 13.14.1.1
+    if request.issue_confirmed_notifications.is_some() != request.lifetime.is_some() {
+        return Err(ServiceError::Reject(RejectReason::MissingRequiredParameter))
+    }
+    if db.cov_properties(&request.object_id)?.is_empty() {
+        return Err(ServiceError::error(ErrorClass::Object, ErrorCode::OptionalFunctionalityNotSupported))
+    }
+    if request.is_cancellation() {
+        db.cov_subscriptions().cancel(source, request.subscriber_process_id, request.object_id, None);
+    } else {
+        let expires = cov::expiry(db.now(), request.lifetime.unwrap_or(0));
+        db.subscribe_cov(cov::Subscription {
+            subscriber: source.clone(),
+            subscriber_process_id: request.subscriber_process_id,
+            object_id: request.object_id,
+            property: None,
+            issue_confirmed_notifications: request.issue_confirmed_notifications.unwrap_or(false),
+            cov_increment: None,
+            expires,
+        })?;
+    }
+    Ok(ConfirmedAck::Simple)
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::*;
+    use service::test_peer;
+    use object::test_db;
+    use enumerations::ObjectType;
+
+    #[test]
+    fn objects_without_cov_support() {
+        let request = Message {
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::Device, 45),
+            issue_confirmed_notifications: Some(false),
+            lifetime: Some(60),
+        };
+        assert_eq!(Err(ServiceError::error(ErrorClass::Object, ErrorCode::OptionalFunctionalityNotSupported)),
+                   handler(&request.marshall(), &test_peer(), &mut test_db()));
+    }
+
+    #[test]
+    fn lifetime_without_notification_type() {
+        let request = Message {
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::Device, 45),
+            issue_confirmed_notifications: None,
+            lifetime: Some(60),
+        };
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&request.marshall(), &test_peer(), &mut test_db()));
+        let request = Message { issue_confirmed_notifications: Some(true), lifetime: None, ..request };
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&request.marshall(), &test_peer(), &mut test_db()));
+    }
+
+    #[test]
+    fn unknown_object() {
+        let request = Message {
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::AnalogInput, 10),
+            issue_confirmed_notifications: None,
+            lifetime: None,
+        };
+        assert_eq!(Err(ServiceError::error(ErrorClass::Object, ErrorCode::UnknownObject)),
+                   handler(&request.marshall(), &test_peer(), &mut test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::*;
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue::ContextValue;
+    use parse::parse_value_sequence_to_end;
+    use serialise::write_value_sequence;
+    use std::io;
+    use enumerations::ObjectType;
+
+    /// The example from Clause E.2.3 - an indefinite subscription to analog input 10 with
+    /// confirmed notifications
+    const REQUEST: &[u8] = &[0x09, 0x12, 0x1C, 0x00, 0x00, 0x00, 0x0A, 0x29, 0x01, 0x39, 0x00];
+
+    fn request() -> Message {
+        Message {
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::AnalogInput, 10),
+            issue_confirmed_notifications: Some(true),
+            lifetime: Some(0),
+        }
+    }
+
+    #[test]
+    fn test_serialise_request() {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, request().marshall()).unwrap();
+        assert_eq!(REQUEST.to_vec(), buf);
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(REQUEST);
+        assert_eq!(Ok(request()), Message::unmarshall(&parse_value_sequence_to_end(reader, &Message::SCHEMA).unwrap()));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let cancellation = Message { issue_confirmed_notifications: None, lifetime: None, ..request() };
+        assert!(cancellation.is_cancellation());
+        assert!(!request().is_cancellation());
+        assert_eq!(Ok(cancellation), Message::unmarshall(&vec!(
+            ContextValue(0, PrimitiveValue::Unsigned(18)),
+            ContextValue(1, PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 10))))));
+    }
+}
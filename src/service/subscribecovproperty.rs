@@ -0,0 +1,259 @@
+//! The SubscribeCOVProperty service (Clause 13.15) subscribes to COV notifications about one
+//! property of an object, optionally with its own COV increment. Like SubscribeCOV a request
+//! without a notification type or a lifetime cancels the subscription.
+//!
+//! The property reference is a constructed value which reuses the context tag numbers of the
+//! request's fields.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+use super::{UnmarshallError, Values, required};
+use super::readpropertymultiple::PROPERTY_REFERENCE;
+use super::subscribecov;
+
+use cov;
+use object;
+use object::ObjectId;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ContextValue;
+use ast::SequenceableValue::ContextValueSequence;
+use enumerations::PropertyIdentifier;
+use parse::{PrimitiveType, Schema};
+
+/// The request
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub subscriber_process_id: u32,
+    pub object_id: ObjectId,
+    pub issue_confirmed_notifications: Option<bool>,
+    /// In seconds, 0 for an indefinite subscription
+    pub lifetime: Option<u32>,
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+    pub cov_increment: Option<f32>,
+}
+
+impl Message {
+    /// A request without a notification type or a lifetime cancels the subscription
+    pub fn is_cancellation(&self) -> bool {
+        self.issue_confirmed_notifications.is_none() && self.lifetime.is_none()
+    }
+}
+
+pub fn handler(body: &ValueSequence, source: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    let property = Some((request.property, request.array_index));
+    if request.is_cancellation() {
+        db.cov_subscriptions().cancel(source, request.subscriber_process_id, request.object_id, property);
+    } else {
+        let expires = cov::expiry(db.now(), request.lifetime.unwrap_or(0));
+        db.subscribe_cov(cov::Subscription {
+            subscriber: source.clone(),
+            subscriber_process_id: request.subscriber_process_id,
+            object_id: request.object_id,
+            property,
+            issue_confirmed_notifications: request.issue_confirmed_notifications.unwrap_or(false),
+            cov_increment: request.cov_increment,
+            expires,
+        })?;
+    }
+    Ok(ConfirmedAck::Simple)
+}
+
+impl ServiceMessage for Message {
+    type Message = Self;
+
+    /// The fields of SubscribeCOV, then the property reference and COV increment
+    const SCHEMA: Schema = Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::Unsigned),
+        Schema::Context(1, PrimitiveType::ObjectId),
+        Schema::Optional(&Schema::Context(2, PrimitiveType::Boolean)),
+        Schema::Optional(&Schema::Context(3, PrimitiveType::Unsigned)),
+        Schema::Constructed(4, &PROPERTY_REFERENCE),
+        Schema::Optional(&Schema::Context(5, PrimitiveType::Real)),
+    ]);
+
+    fn choice() -> u8 { 28 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = subscribecov::Message {
+            subscriber_process_id: self.subscriber_process_id,
+            object_id: self.object_id,
+            issue_confirmed_notifications: self.issue_confirmed_notifications,
+            lifetime: self.lifetime,
+        }.marshall();
+        let mut reference = vec!(ContextValue(0, PrimitiveValue::Enumerated(self.property.into())));
+        if let Some(index) = self.array_index {
+            reference.push(ContextValue(1, PrimitiveValue::Unsigned(index)));
+        }
+        body.push(ContextValueSequence(4, reference));
+        if let Some(increment) = self.cov_increment {
+            body.push(ContextValue(5, PrimitiveValue::Real(increment)));
+        }
+        body
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let mut values = Values::new(body);
+        let subscriber_process_id = required(values.context(0)?, 0)?;
+        let object_id = required(values.context(1)?, 1)?;
+        let issue_confirmed_notifications = values.context(2)?;
+        let lifetime = values.context(3)?;
+        let reference = required(values.constructed(4), 4)?;
+        let cov_increment = values.context(5)?;
+        values.end()?;
+        let mut values = Values::new(&reference);
+        let property = required(values.context(0)?, 0)?;
+        let array_index = values.context(1)?;
+        values.end()?;
+        Ok(Message {
+            subscriber_process_id,
+            object_id,
+            issue_confirmed_notifications,
+            lifetime,
+            property,
+            array_index,
+            cov_increment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::*;
+    use clock::ManualClock;
+    use service::test_peer;
+    use service::covnotification::Notification;
+    use service::writepropertymultiple::PropertyValue;
+    use ast::PrimitiveValue::CharacterString;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::{BacnetDB, test_device};
+    use std::time::Duration;
+    use enumerations::{ErrorClass, ErrorCode, ObjectType};
+
+    const DEVICE: ObjectId = ObjectId(ObjectType::Device, 45);
+
+    fn subscribe(db: &mut BacnetDB, property: PropertyIdentifier, lifetime: Option<u32>) -> Result<ConfirmedAck, ServiceError> {
+        let request = Message {
+            subscriber_process_id: 18,
+            object_id: DEVICE,
+            issue_confirmed_notifications: lifetime.map(|_| true),
+            lifetime,
+            property,
+            array_index: None,
+            cov_increment: None,
+        };
+        handler(&request.marshall(), &test_peer(), db)
+    }
+
+    fn name_notification(name: &str, time_remaining: u32) -> cov::CovNotification {
+        cov::CovNotification {
+            subscriber: test_peer(),
+            confirmed: true,
+            notification: Notification {
+                subscriber_process_id: 18,
+                initiating_device_id: DEVICE,
+                monitored_object_id: DEVICE,
+                time_remaining,
+                values: vec!(PropertyValue {
+                    property: PropertyIdentifier::ObjectName,
+                    array_index: None,
+                    value: vec!(ApplicationValue(CharacterString(name.to_string()))),
+                    priority: None,
+                }),
+            },
+        }
+    }
+
+    #[test]
+    fn notified_of_changes_until_expiry() {
+        let clock = ManualClock::new();
+        let mut db = BacnetDB::with_clock(test_device(), Box::new(clock.clone()));
+        assert_eq!(Ok(ConfirmedAck::Simple), subscribe(&mut db, PropertyIdentifier::ObjectName, Some(60)));
+        assert_eq!(vec!(name_notification("Test device", 60)), db.cov_notifications());
+        assert!(db.cov_notifications().is_empty());
+
+        clock.advance(Duration::from_secs(20));
+        let name = vec!(ApplicationValue(CharacterString("Boiler house".to_string())));
+        db.write_property(&DEVICE, PropertyIdentifier::ObjectName, None, &name, None).unwrap();
+        assert_eq!(vec!(name_notification("Boiler house", 40)), db.cov_notifications());
+
+        clock.advance(Duration::from_secs(40));
+        let name = vec!(ApplicationValue(CharacterString("Plant room".to_string())));
+        db.write_property(&DEVICE, PropertyIdentifier::ObjectName, None, &name, None).unwrap();
+        assert!(db.cov_notifications().is_empty());
+        assert_eq!(0, db.cov_subscriptions().subscriptions().count());
+    }
+
+    #[test]
+    fn cancellation() {
+        let mut db = BacnetDB::with_clock(test_device(), Box::new(ManualClock::new()));
+        subscribe(&mut db, PropertyIdentifier::ObjectName, Some(0)).unwrap();
+        assert_eq!(1, db.cov_subscriptions().subscriptions().count());
+        assert_eq!(Ok(ConfirmedAck::Simple), subscribe(&mut db, PropertyIdentifier::ObjectName, None));
+        assert_eq!(0, db.cov_subscriptions().subscriptions().count());
+    }
+
+    #[test]
+    fn unknown_property() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::UnknownProperty)),
+                   subscribe(&mut BacnetDB::new(test_device()), PropertyIdentifier::PresentValue, Some(0)));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::*;
+    use service::Position;
+    use serialise::write_value_sequence;
+    use parse::parse_value_sequence_to_end;
+    use enumerations::ObjectType;
+    use std::io;
+
+    fn parse(message: &Message) -> ValueSequence {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, message.marshall()).unwrap();
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(buf);
+        parse_value_sequence_to_end(reader, &Message::SCHEMA).unwrap()
+    }
+
+    #[test]
+    fn test_marshall_cycle() {
+        let message = || Message {
+            subscriber_process_id: 18,
+            object_id: ObjectId(ObjectType::AnalogInput, 10),
+            issue_confirmed_notifications: Some(true),
+            lifetime: Some(60),
+            property: PropertyIdentifier::PresentValue,
+            array_index: None,
+            cov_increment: Some(0.5),
+        };
+        assert_eq!(Ok(message()), Message::unmarshall(&message().marshall()));
+        assert_eq!(Ok(message()), Message::unmarshall(&parse(&message())));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_index() {
+        let message = || Message {
+            subscriber_process_id: 1,
+            object_id: ObjectId(ObjectType::Device, 45),
+            issue_confirmed_notifications: None,
+            lifetime: None,
+            property: PropertyIdentifier::ObjectList,
+            array_index: Some(2),
+            cov_increment: None,
+        };
+        assert_eq!(Ok(message()), Message::unmarshall(&message().marshall()));
+        assert_eq!(Ok(message()), Message::unmarshall(&parse(&message())));
+    }
+
+    #[test]
+    fn test_unmarshall_without_property() {
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(4))), Message::unmarshall(&vec!(
+            ContextValue(0, PrimitiveValue::Unsigned(18)),
+            ContextValue(1, PrimitiveValue::ObjectId(ObjectId(ObjectType::AnalogInput, 10))))));
+    }
+}
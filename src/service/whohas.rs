@@ -0,0 +1,206 @@
+//! The Whohas service is activated by unconfirmed whohas messages for which this device resides in
+//! the specified device ID range. It looks for an object by its identifier or by its name and
+//! sends out an ihave message if this device contains it
+
+use super::ServiceMessage;
+use super::{Position, UnmarshallError, Values};
+use super::whois::{MAX_INSTANCE, unmarshall_range};
+
+use object;
+use object::ObjectId;
+use service;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ContextValue;
+use parse::{PrimitiveType, Schema};
+
+/// How the object being looked for is identified
+#[derive(Debug, PartialEq)]
+pub enum ObjectSelector {
+    Id(ObjectId),
+    Name(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub device_instance_low: u32,
+    pub device_instance_high: u32,
+    pub object: ObjectSelector,
+}
+
+impl Message {
+    /// Looks for the object in every device
+    pub fn new(object: ObjectSelector) -> Message {
+        Message {
+            device_instance_low: 0,
+            device_instance_high: MAX_INSTANCE,
+            object,
+        }
+    }
+}
+
+/// Answers with an I-Have if this device is in range and has the object, an invalid Who-Has is
+/// ignored
+pub fn handler(body: &ValueSequence, db: &object::BacnetDB) -> Option<(u8, ValueSequence)> {
+    Message::unmarshall(body).ok()
+        .and_then(|whohas| m_handler(whohas, db))
+        .map(|ihave| (service::ihave::Message::choice(), ihave.marshall()))
+}
+
+fn m_handler(whohas: Message, db: &object::BacnetDB) -> Option<service::ihave::Message> {
+    let device = db.device();
+    if whohas.device_instance_low > device.instance || whohas.device_instance_high < device.instance {
+        return None
+    }
+    let object_id = match whohas.object {
+        ObjectSelector::Id(object_id) => object_id,
+        ObjectSelector::Name(ref name) => db.find_object_by_name(name)?,
+    };
+    db.object_name(&object_id).map(|name| service::ihave::Message {
+        device_id: device.object_id(),
+        object_id,
+        object_name: name.to_string(),
+    })
+}
+
+impl ServiceMessage for Message {
+    type Message = Self;
+
+    /// The device instance range is optional, the object is looked for by its identifier or by its
+    /// name
+    const SCHEMA: Schema = Schema::Sequence(&[
+        Schema::Optional(&Schema::Sequence(&[
+            Schema::Context(0, PrimitiveType::Unsigned),
+            Schema::Context(1, PrimitiveType::Unsigned),
+        ])),
+        Schema::Choice(&[
+            Schema::Context(2, PrimitiveType::ObjectId),
+            Schema::Context(3, PrimitiveType::CharacterString),
+        ]),
+    ]);
+
+    fn choice() -> u8 { 7 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = vec!();
+        if self.device_instance_low != 0 || self.device_instance_high != MAX_INSTANCE {
+            body.push(ContextValue(0, PrimitiveValue::Unsigned(self.device_instance_low)));
+            body.push(ContextValue(1, PrimitiveValue::Unsigned(self.device_instance_high)));
+        }
+        body.push(match self.object {
+            ObjectSelector::Id(object_id) => ContextValue(2, PrimitiveValue::ObjectId(object_id)),
+            ObjectSelector::Name(ref name) => ContextValue(3, PrimitiveValue::CharacterString(name.clone())),
+        });
+        body
+    }
+
+    /// The range is optional, a Who-Has without one is for every device
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let mut values = Values::new(body);
+        let (device_instance_low, device_instance_high) = unmarshall_range(&mut values)?;
+        // The object is identified one way or the other, a name after an identifier is an extra
+        // value
+        let object = match values.context(2)? {
+            Some(object_id) => ObjectSelector::Id(object_id),
+            None => match values.context(3)? {
+                Some(name) => ObjectSelector::Name(name),
+                None => return Err(UnmarshallError::MissingRequiredValue(Position::Context(2))),
+            },
+        };
+        values.end()?;
+        Ok(Message {
+            device_instance_low,
+            device_instance_high,
+            object,
+        })
+    }
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::{m_handler, Message, ObjectSelector};
+    use service::ihave;
+    use object::{ObjectId, test_db};
+    use enumerations::ObjectType;
+
+    fn ihave_device() -> Option<ihave::Message> {
+        Some(ihave::Message {
+            device_id: ObjectId(ObjectType::Device, 45),
+            object_id: ObjectId(ObjectType::Device, 45),
+            object_name: "Test device".to_string(),
+        })
+    }
+
+    #[test]
+    fn find_by_id() {
+        assert_eq!(ihave_device(), m_handler(Message::new(ObjectSelector::Id(ObjectId(ObjectType::Device, 45))), &test_db()));
+        assert_eq!(None, m_handler(Message::new(ObjectSelector::Id(ObjectId(ObjectType::AnalogInput, 1))), &test_db()));
+    }
+
+    #[test]
+    fn find_by_name() {
+        assert_eq!(ihave_device(), m_handler(Message::new(ObjectSelector::Name("Test device".to_string())), &test_db()));
+        assert_eq!(None, m_handler(Message::new(ObjectSelector::Name("OATemp".to_string())), &test_db()));
+    }
+
+    #[test]
+    fn range_checks() {
+        let whohas = |low, high| Message {
+            device_instance_low: low,
+            device_instance_high: high,
+            object: ObjectSelector::Name("Test device".to_string()),
+        };
+        assert_eq!(None, m_handler(whohas(46, 100), &test_db()));
+        assert_eq!(None, m_handler(whohas(0, 44), &test_db()));
+        assert_eq!(ihave_device(), m_handler(whohas(45, 45), &test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::{Message, ObjectSelector};
+    use super::super::ServiceMessage;
+    use super::super::{Position, UnmarshallError};
+    use ast::PrimitiveValue::{CharacterString, ObjectId, Unsigned};
+    use ast::SequenceableValue::ContextValue;
+    use object;
+    use parse::parse_value_sequence_to_end;
+    use serialise::write_value_sequence;
+    use std::io;
+    use enumerations::ObjectType;
+
+    /// Looking for the object named "OATemp" in every device
+    const REQUEST: &[u8] = &[0x3D, 0x07, 0x00, 0x4F, 0x41, 0x54, 0x65, 0x6D, 0x70];
+
+    #[test]
+    fn test_serialise() {
+        let message = Message::new(ObjectSelector::Name("OATemp".to_string()));
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, message.marshall()).unwrap();
+        assert_eq!(REQUEST.to_vec(), buf);
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(REQUEST);
+        assert_eq!(Ok(message), Message::unmarshall(&parse_value_sequence_to_end(reader, &Message::SCHEMA).unwrap()));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_range() {
+        let message = Message {
+            device_instance_low: 3,
+            device_instance_high: 300,
+            object: ObjectSelector::Id(object::ObjectId(ObjectType::AnalogInput, 3)),
+        };
+        assert_eq!(message, Message::unmarshall(&message.marshall()).unwrap());
+    }
+
+    #[test]
+    fn test_unmarshall_errors() {
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(2))), Message::unmarshall(&vec!()));
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(1))),
+                   Message::unmarshall(&vec!(ContextValue(0, Unsigned(3)), ContextValue(3, CharacterString("OATemp".to_string())))));
+        assert_eq!(Err(UnmarshallError::ExtraValues(1)), Message::unmarshall(&vec!(
+                   ContextValue(2, ObjectId(object::ObjectId(ObjectType::AnalogInput, 3))),
+                   ContextValue(3, CharacterString("OATemp".to_string())))));
+        assert_eq!(Err(UnmarshallError::WrongDataType(Position::Context(3))),
+                   Message::unmarshall(&vec!(ContextValue(3, Unsigned(3)))));
+    }
+}
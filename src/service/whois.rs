@@ -2,14 +2,16 @@
 //! the specified device ID range. The whois service should send out iam messages when activated
 
 use super::ServiceMessage;
-use super::UnmarshallError;
+use super::{Position, UnmarshallError, Values};
 
 use object;
 use service;
 use ast::ValueSequence;
 use ast::PrimitiveValue::Unsigned;
 use ast::SequenceableValue::ContextValue;
-use ast::get_context_value;
+use parse::{PrimitiveType, Schema};
+
+pub use ast::MAX_INSTANCE;
 
 #[derive(Debug, PartialEq)]
 pub struct Message {
@@ -20,14 +22,17 @@ pub struct Message {
 impl Message {
     pub fn new(device_instance_low: u32, device_instance_high: u32) -> Message {
         Message {
-            device_instance_low: device_instance_low,
-            device_instance_high: device_instance_high,
+            device_instance_low,
+            device_instance_high,
         }
     }
 }
 
-pub fn handler(body: &ValueSequence, db: &object::BacnetDB) -> Option<ValueSequence> {
-    m_handler(Message::unmarshall(body).unwrap(), db).map(|message| message.marshall())
+/// Answers with an I-Am if this device is in range, an invalid Who-Is is ignored
+pub fn handler(body: &ValueSequence, db: &object::BacnetDB) -> Option<(u8, ValueSequence)> {
+    Message::unmarshall(body).ok()
+        .and_then(|whois| m_handler(whois, db))
+        .map(|iam| (service::iam::Message::choice(), iam.marshall()))
 }
 
 fn m_handler(whois: Message, db: &object::BacnetDB) -> Option<service::iam::Message> {
@@ -44,18 +49,7 @@ mod handler_test {
 	use super::m_handler;
 	use super::Message;
 	use service::iam;
-	use object::BacnetDB;
-	use object::DeviceObject;
-	
-	const DEVICE: DeviceObject = DeviceObject {
-		instance: 45,
-		max_apdu_length_supported: 1000,
-		vendor_identifier: 23,
-		segmentation_supported: 3,	
-	};
-	fn test_db() -> BacnetDB {
-        BacnetDB::new(DEVICE)
-    }
+	use object::test_db;
 
 	fn whois_range(low: u32, high: u32) -> Option<iam::Message> {
 		m_handler(Message {
@@ -68,14 +62,20 @@ mod handler_test {
 	fn range_checks() {
 		assert_eq!(None, whois_range(46, 100));
 		assert_eq!(None, whois_range(0, 43));
-		assert_eq!(Some(iam::Message::about(&DEVICE)), whois_range(45, 100));
-		assert_eq!(Some(iam::Message::about(&DEVICE)), whois_range(1, 45));
+		assert_eq!(Some(iam::Message::about(test_db().device())), whois_range(45, 100));
+		assert_eq!(Some(iam::Message::about(test_db().device())), whois_range(1, 45));
 	}
 }
 
 impl ServiceMessage for Message {
     type Message = Self;
 
+    /// Both ends of the device instance range, or neither
+    const SCHEMA: Schema = Schema::Optional(&Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::Unsigned),
+        Schema::Context(1, PrimitiveType::Unsigned),
+    ]));
+
     fn choice() -> u8 { 8 }
 
     fn marshall(&self) -> ValueSequence {
@@ -84,14 +84,36 @@ impl ServiceMessage for Message {
             ContextValue(1, Unsigned(self.device_instance_high)))
     }
 
+    /// The range is optional, a Who-Is without one is for every device
     fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
-        match (get_context_value(body, 0), get_context_value(body, 1)) {
-            (Some(&Unsigned(low)), Some(&Unsigned(high))) => Ok(Message {
-                device_instance_low: low,
-                device_instance_high: high,
-            }),
-            _ => Err(UnmarshallError::RequiredValueNotProvided),
-        }
+        let mut values = Values::new(body);
+        let (device_instance_low, device_instance_high) = unmarshall_range(&mut values)?;
+        values.end()?;
+        Ok(Message {
+            device_instance_low,
+            device_instance_high,
+        })
+    }
+}
+
+/// Takes the optional device instance range in context tags 0 and 1, which is every device when it
+/// is left out
+pub(crate) fn unmarshall_range(values: &mut Values) -> Result<(u32, u32), UnmarshallError> {
+    let low = instance(values.context(0)?, 0)?;
+    let high = instance(values.context(1)?, 1)?;
+    match (low, high) {
+        (Some(low), Some(high)) => Ok((low, high)),
+        (None, None) => Ok((0, MAX_INSTANCE)),
+        (None, Some(_)) => Err(UnmarshallError::MissingRequiredValue(Position::Context(0))),
+        (Some(_), None) => Err(UnmarshallError::MissingRequiredValue(Position::Context(1))),
+    }
+}
+
+/// Checks that an end of the range is an instance number
+fn instance(value: Option<u32>, context: u8) -> Result<Option<u32>, UnmarshallError> {
+    match value {
+        Some(instance) if instance > MAX_INSTANCE => Err(UnmarshallError::OutOfRange(Position::Context(context))),
+        value => Ok(value),
     }
 }
 
@@ -99,7 +121,8 @@ impl ServiceMessage for Message {
 mod message {
     use super::Message;
     use super::super::ServiceMessage;
-    use ast::PrimitiveValue::Unsigned;
+    use super::super::{Position, UnmarshallError};
+    use ast::PrimitiveValue::{Boolean, Unsigned};
     use ast::SequenceableValue::ContextValue;
 
     #[test]
@@ -118,6 +141,30 @@ mod message {
                 Message { device_instance_low: 1, device_instance_high: 50000 }.marshall());
     }
 
+    #[test]
+    fn test_unmarshall_without_range() {
+        assert_eq!(Ok(Message { device_instance_low: 0, device_instance_high: 0x3FFFFF }),
+                   Message::unmarshall(&vec!()));
+    }
+
+    #[test]
+    fn test_unmarshall_half_range() {
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(0))),
+                   Message::unmarshall(&vec!(ContextValue(1, Unsigned(50000)))));
+        assert_eq!(Err(UnmarshallError::MissingRequiredValue(Position::Context(1))),
+                   Message::unmarshall(&vec!(ContextValue(0, Unsigned(1)))));
+    }
+
+    #[test]
+    fn test_unmarshall_invalid_range() {
+        assert_eq!(Err(UnmarshallError::OutOfRange(Position::Context(1))),
+                   Message::unmarshall(&vec!(ContextValue(0, Unsigned(1)), ContextValue(1, Unsigned(0x400000)))));
+        assert_eq!(Err(UnmarshallError::WrongDataType(Position::Context(0))),
+                   Message::unmarshall(&vec!(ContextValue(0, Boolean(true)), ContextValue(1, Unsigned(1)))));
+        assert_eq!(Err(UnmarshallError::ExtraValues(2)),
+                   Message::unmarshall(&vec!(ContextValue(0, Unsigned(1)), ContextValue(1, Unsigned(1)), ContextValue(2, Unsigned(1)))));
+    }
+
     #[test]
     fn test_marshall_cycle() {
         let message = Message { device_instance_low: 1, device_instance_high: 50000 };
@@ -0,0 +1,235 @@
+//! The WriteProperty service (Clause 15.9) is a confirmed request to change the value of one
+//! property of an object, or of one element if the property is an array. Commandable properties
+//! are written at a priority, 1 being the highest and 16 the lowest. Success is acknowledged with a
+//! SimpleACK.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+use super::{UnmarshallError, Values, required};
+
+use object;
+use object::ObjectId;
+use object::PropertyError;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ContextValue;
+use ast::SequenceableValue::ContextValueSequence;
+use enumerations::PropertyIdentifier;
+use parse::{PrimitiveType, Schema};
+
+/// The lowest priority a command can be written at
+pub const MIN_PRIORITY: u8 = 16;
+
+/// The request
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub object_id: ObjectId,
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+    pub value: ValueSequence,
+    pub priority: Option<u8>,
+}
+
+pub fn handler(body: &ValueSequence, _: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    if let Some(priority) = request.priority {
+        if !(1..=MIN_PRIORITY).contains(&priority) {
+            return Err(PropertyError::ValueOutOfRange.into())
+        }
+    }
+    db.write_property(&request.object_id, request.property, request.array_index, &request.value, request.priority)?;
+    Ok(ConfirmedAck::Simple)
+}
+
+impl ServiceMessage for Message {
+    type Message = Self;
+
+    /// The value is application tagged, in opening and closing tags
+    const SCHEMA: Schema = Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::ObjectId),
+        Schema::Context(1, PrimitiveType::Enumerated),
+        Schema::Optional(&Schema::Context(2, PrimitiveType::Unsigned)),
+        Schema::Constructed(3, &Schema::Any),
+        Schema::Optional(&Schema::Context(4, PrimitiveType::Unsigned)),
+    ]);
+
+    fn choice() -> u8 { 15 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = vec!(
+            ContextValue(0, PrimitiveValue::ObjectId(self.object_id)),
+            ContextValue(1, PrimitiveValue::Enumerated(self.property.into())));
+        if let Some(index) = self.array_index {
+            body.push(ContextValue(2, PrimitiveValue::Unsigned(index)));
+        }
+        body.push(ContextValueSequence(3, self.value.clone()));
+        if let Some(priority) = self.priority {
+            body.push(ContextValue(4, PrimitiveValue::Unsigned(priority as u32)));
+        }
+        body
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let mut values = Values::new(body);
+        let object_id = required(values.context(0)?, 0)?;
+        let property = required(values.context(1)?, 1)?;
+        let array_index = values.context(2)?;
+        let value = required(values.constructed(3), 3)?;
+        // Anything too large for a priority is out of range, which the handler reports
+        let priority = values.context::<u32>(4)?.map(|priority| priority.min(u8::MAX as u32) as u8);
+        values.end()?;
+        Ok(Message {
+            object_id,
+            property,
+            array_index,
+            value,
+            priority,
+        })
+    }
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::handler;
+    use super::Message;
+    use service::{ConfirmedAck, ServiceError, ServiceMessage, test_peer};
+    use enumerations::{ErrorClass, ErrorCode, ObjectType, PropertyIdentifier, RejectReason};
+    use ast::PrimitiveValue;
+    use ast::PrimitiveValue::Unsigned;
+    use ast::SequenceableValue::ApplicationValue;
+    use ast::ValueSequence;
+    use object::{BacnetDB, ObjectId, test_db};
+
+    fn write(db: &mut BacnetDB, property: PropertyIdentifier, value: PrimitiveValue, priority: Option<u8>) -> Result<ConfirmedAck, ServiceError> {
+        handler(&Message {
+            object_id: ObjectId(ObjectType::Device, 45),
+            property,
+            array_index: None,
+            value: vec!(ApplicationValue(value)),
+            priority,
+        }.marshall(), &test_peer(), db)
+    }
+
+    #[test]
+    fn write_apdu_timeout() {
+        let mut db = test_db();
+        assert_eq!(Ok(ConfirmedAck::Simple), write(&mut db, PropertyIdentifier::ApduTimeout, Unsigned(6000), None));
+        assert_eq!(6000, db.device().apdu_timeout);
+    }
+
+    #[test]
+    fn priority_is_ignored_for_non_commandable_properties() {
+        let mut db = test_db();
+        assert_eq!(Ok(ConfirmedAck::Simple), write(&mut db, PropertyIdentifier::NumberOfApduRetries, Unsigned(5), Some(16)));
+        assert_eq!(5, db.device().number_of_apdu_retries);
+    }
+
+    #[test]
+    fn write_read_only_property() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::WriteAccessDenied)),
+                   write(&mut test_db(), PropertyIdentifier::VendorIdentifier, Unsigned(5), None));
+    }
+
+    #[test]
+    fn write_out_of_range() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::ValueOutOfRange)),
+                   write(&mut test_db(), PropertyIdentifier::ApduTimeout, Unsigned(0), None));
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::ValueOutOfRange)),
+                   write(&mut test_db(), PropertyIdentifier::ApduTimeout, Unsigned(6000), Some(17)));
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::ValueOutOfRange)),
+                   write(&mut test_db(), PropertyIdentifier::ApduTimeout, Unsigned(6000), Some(0)));
+    }
+
+    #[test]
+    fn write_wrong_type() {
+        assert_eq!(Err(ServiceError::error(ErrorClass::Property, ErrorCode::InvalidDataType)),
+                   write(&mut test_db(), PropertyIdentifier::ApduTimeout, PrimitiveValue::Real(6.0), None));
+    }
+
+    #[test]
+    fn command_analog_value() {
+        use enumerations::EngineeringUnits;
+        use object::AnalogValue;
+        let mut db = test_db();
+        let setpoint = ObjectId(ObjectType::AnalogValue, 1);
+        db.add(AnalogValue::new(1, "Setpoint", EngineeringUnits::DegreesCelsius, 21.0)).unwrap();
+        let request = Message {
+            object_id: setpoint,
+            property: PropertyIdentifier::PresentValue,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::Real(19.5))),
+            priority: Some(8),
+        };
+        assert_eq!(Ok(ConfirmedAck::Simple), handler(&request.marshall(), &test_peer(), &mut db));
+        assert_eq!(Some(19.5), db.get::<AnalogValue>(&setpoint).unwrap().priority_array.commands()[7]);
+        assert_eq!(19.5, db.get::<AnalogValue>(&setpoint).unwrap().present_value());
+    }
+
+    #[test]
+    fn missing_value_is_rejected() {
+        use ast::SequenceableValue::ContextValue;
+        let body: ValueSequence = vec!(
+            ContextValue(0, PrimitiveValue::ObjectId(ObjectId(ObjectType::Device, 45))),
+            ContextValue(1, PrimitiveValue::Enumerated(PropertyIdentifier::ApduTimeout.into())));
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&body, &test_peer(), &mut test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::Message;
+    use super::super::ServiceMessage;
+    use ast::PrimitiveValue;
+    use ast::SequenceableValue::ApplicationValue;
+    use object::ObjectId;
+    use parse::parse_value_sequence_to_end;
+    use serialise::write_value_sequence;
+    use std::io;
+    use enumerations::{ObjectType, PropertyIdentifier};
+
+    /// The example from Clause E.3.9 - writing 180.0 to the present value of analog value 1
+    const REQUEST: &[u8] = &[0x0C, 0x00, 0x80, 0x00, 0x01, 0x19, 0x55, 0x3E, 0x44, 0x43, 0x34, 0x00, 0x00, 0x3F];
+
+    fn request() -> Message {
+        Message {
+            object_id: ObjectId(ObjectType::AnalogValue, 1),
+            property: PropertyIdentifier::PresentValue,
+            array_index: None,
+            value: vec!(ApplicationValue(PrimitiveValue::Real(180.0))),
+            priority: None,
+        }
+    }
+
+    fn write(body: ::ast::ValueSequence) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, body).unwrap();
+        buf
+    }
+
+    fn parse(data: &[u8]) -> ::ast::ValueSequence {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, &Message::SCHEMA).unwrap()
+    }
+
+    #[test]
+    fn test_serialise_request() {
+        assert_eq!(REQUEST.to_vec(), write(request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&parse(REQUEST)));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_index_and_priority() {
+        let message = Message {
+            object_id: ObjectId(ObjectType::BinaryOutput, 3),
+            property: PropertyIdentifier::PriorityArray,
+            array_index: Some(8),
+            value: vec!(ApplicationValue(PrimitiveValue::Null)),
+            priority: Some(8),
+        };
+        assert_eq!(message, Message::unmarshall(&message.marshall()).unwrap());
+        assert_eq!(message, Message::unmarshall(&parse(&write(message.marshall()))).unwrap());
+    }
+}
@@ -0,0 +1,347 @@
+//! The WritePropertyMultiple service (Clause 15.10) writes any number of properties of any number
+//! of objects in one request. The writes are made in order and stop at the first one which fails,
+//! the writes before it are kept. The error names the write which failed. Success is acknowledged
+//! with a SimpleACK.
+//!
+//! Like ReadPropertyMultiple the property lists are constructed values which reuse the context tag
+//! numbers of the enclosing object's fields.
+
+use super::ConfirmedAck;
+use super::ServiceError;
+use super::ServiceMessage;
+use super::{UnmarshallError, Values, required};
+use super::property_error;
+use super::readpropertymultiple::{unmarshall_error, unmarshall_objects};
+use super::writeproperty::MIN_PRIORITY;
+
+use object;
+use object::ObjectId;
+use object::PropertyError;
+use ast::NetworkAddress;
+use ast::ValueSequence;
+use ast::PrimitiveValue;
+use ast::SequenceableValue::ApplicationValue;
+use ast::SequenceableValue::ContextValue;
+use ast::SequenceableValue::ContextValueSequence;
+use enumerations::{ErrorClass, ErrorCode, PropertyIdentifier};
+use parse::{PrimitiveType, Schema};
+
+/// The request
+#[derive(Debug, PartialEq)]
+pub struct Message {
+    pub specifications: Vec<WriteAccessSpecification>,
+}
+
+/// The properties to write to one object
+#[derive(Debug, PartialEq)]
+pub struct WriteAccessSpecification {
+    pub object_id: ObjectId,
+    pub properties: Vec<PropertyValue>,
+}
+
+/// A value to write to a property, or to one element of an array property
+#[derive(Debug, PartialEq, Clone)]
+pub struct PropertyValue {
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+    pub value: ValueSequence,
+    pub priority: Option<u8>,
+}
+
+/// The error reporting a failed request, carried in an Error PDU
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub error_class: ErrorClass,
+    pub error_code: ErrorCode,
+    pub object_id: ObjectId,
+    pub property: PropertyIdentifier,
+    pub array_index: Option<u32>,
+}
+
+pub fn handler(body: &ValueSequence, _: &NetworkAddress, db: &mut object::BacnetDB) -> Result<ConfirmedAck, ServiceError> {
+    let request = Message::unmarshall(body)?;
+    for specification in &request.specifications {
+        for write in &specification.properties {
+            if let Err(error) = write_property(db, specification.object_id, write) {
+                let (error_class, error_code) = property_error(&error);
+                return Err(ServiceError::Error(Error {
+                    error_class,
+                    error_code,
+                    object_id: specification.object_id,
+                    property: write.property,
+                    array_index: write.array_index,
+                }.marshall()))
+            }
+        }
+    }
+    Ok(ConfirmedAck::Simple)
+}
+
+fn write_property(db: &mut object::BacnetDB, object_id: ObjectId, write: &PropertyValue) -> Result<(), PropertyError> {
+    if let Some(priority) = write.priority {
+        if !(1..=MIN_PRIORITY).contains(&priority) {
+            return Err(PropertyError::ValueOutOfRange)
+        }
+    }
+    db.write_property(&object_id, write.property, write.array_index, &write.value, write.priority)
+}
+
+impl ServiceMessage for Message {
+    type Message = Self;
+
+    /// Any number of objects, each with a list of property values
+    const SCHEMA: Schema = Schema::SequenceOf(&Schema::Sequence(&[
+        Schema::Context(0, PrimitiveType::ObjectId),
+        Schema::Constructed(1, &Schema::SequenceOf(&PROPERTY_VALUE)),
+    ]));
+
+    fn choice() -> u8 { 16 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut body = vec!();
+        for specification in &self.specifications {
+            body.push(ContextValue(0, PrimitiveValue::ObjectId(specification.object_id)));
+            body.push(ContextValueSequence(1, marshall_property_values(&specification.properties)));
+        }
+        body
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let specifications = unmarshall_objects(body, unmarshall_property_values)?;
+        Ok(Message {
+            specifications: specifications.into_iter()
+                .map(|(object_id, properties)| WriteAccessSpecification { object_id, properties })
+                .collect(),
+        })
+    }
+}
+
+/// One property value of the request or of a COV notification - the value is application tagged,
+/// in opening and closing tags
+pub(crate) const PROPERTY_VALUE: Schema = Schema::Sequence(&[
+    Schema::Context(0, PrimitiveType::Enumerated),
+    Schema::Optional(&Schema::Context(1, PrimitiveType::Unsigned)),
+    Schema::Constructed(2, &Schema::Any),
+    Schema::Optional(&Schema::Context(3, PrimitiveType::Unsigned)),
+]);
+
+/// A list of property values, as used in the request and in COV notifications
+pub fn marshall_property_values(values: &[PropertyValue]) -> ValueSequence {
+    let mut body = vec!();
+    for value in values {
+        body.push(ContextValue(0, PrimitiveValue::Enumerated(value.property.into())));
+        if let Some(index) = value.array_index {
+            body.push(ContextValue(1, PrimitiveValue::Unsigned(index)));
+        }
+        body.push(ContextValueSequence(2, value.value.clone()));
+        if let Some(priority) = value.priority {
+            body.push(ContextValue(3, PrimitiveValue::Unsigned(priority as u32)));
+        }
+    }
+    body
+}
+
+pub fn unmarshall_property_values(body: &ValueSequence) -> Result<Vec<PropertyValue>, UnmarshallError> {
+    let mut values = Values::new(body);
+    let mut properties = vec!();
+    while !values.is_done() {
+        let property = required(values.context(0)?, 0)?;
+        let array_index = values.context(1)?;
+        let value = required(values.constructed(2), 2)?;
+        // Anything too large for a priority is out of range, which the handler reports
+        let priority = values.context::<u32>(3)?.map(|priority| priority.min(u8::MAX as u32) as u8);
+        properties.push(PropertyValue { property, array_index, value, priority });
+    }
+    Ok(properties)
+}
+
+impl ServiceMessage for Error {
+    type Message = Self;
+
+    /// The error class and code, then the object, property and array index of the failed write
+    const SCHEMA: Schema = Schema::Sequence(&[
+        Schema::Constructed(0, &Schema::Sequence(&[Schema::Application, Schema::Application])),
+        Schema::Constructed(1, &Schema::Sequence(&[
+            Schema::Context(0, PrimitiveType::ObjectId),
+            Schema::Context(1, PrimitiveType::Enumerated),
+            Schema::Optional(&Schema::Context(2, PrimitiveType::Unsigned)),
+        ])),
+    ]);
+
+    fn choice() -> u8 { 16 }
+
+    fn marshall(&self) -> ValueSequence {
+        let mut reference = vec!(
+            ContextValue(0, PrimitiveValue::ObjectId(self.object_id)),
+            ContextValue(1, PrimitiveValue::Enumerated(self.property.into())));
+        if let Some(index) = self.array_index {
+            reference.push(ContextValue(2, PrimitiveValue::Unsigned(index)));
+        }
+        vec!(
+            ContextValueSequence(0, vec!(
+                ApplicationValue(PrimitiveValue::Enumerated(self.error_class.into())),
+                ApplicationValue(PrimitiveValue::Enumerated(self.error_code.into())))),
+            ContextValueSequence(1, reference))
+    }
+
+    fn unmarshall(body: &ValueSequence) -> Result<Self, UnmarshallError> {
+        let mut values = Values::new(body);
+        let (error_class, error_code) = unmarshall_error(&required(values.constructed(0), 0)?)?;
+        let reference = required(values.constructed(1), 1)?;
+        values.end()?;
+        let mut values = Values::new(&reference);
+        let object_id = required(values.context(0)?, 0)?;
+        let property = required(values.context(1)?, 1)?;
+        let array_index = values.context(2)?;
+        values.end()?;
+        Ok(Error { error_class, error_code, object_id, property, array_index })
+    }
+}
+
+#[cfg(test)]
+mod handler_test {
+    use super::*;
+    use service::test_peer;
+    use ast::PrimitiveValue::Unsigned;
+    use object::{BacnetDB, test_db};
+    use enumerations::{ObjectType, RejectReason};
+
+    const DEVICE: ObjectId = ObjectId(ObjectType::Device, 45);
+
+    fn write(db: &mut BacnetDB, properties: Vec<PropertyValue>) -> Result<ConfirmedAck, ServiceError> {
+        handler(&Message { specifications: vec!(WriteAccessSpecification { object_id: DEVICE, properties }) }.marshall(), &test_peer(), db)
+    }
+
+    fn unsigned(property: PropertyIdentifier, value: u32, priority: Option<u8>) -> PropertyValue {
+        PropertyValue { property, array_index: None, value: vec!(ApplicationValue(Unsigned(value))), priority }
+    }
+
+    #[test]
+    fn write_several_properties() {
+        let mut db = test_db();
+        assert_eq!(Ok(ConfirmedAck::Simple), write(&mut db, vec!(
+            unsigned(PropertyIdentifier::ApduTimeout, 6000, None),
+            unsigned(PropertyIdentifier::NumberOfApduRetries, 5, Some(10)))));
+        assert_eq!(6000, db.device().apdu_timeout);
+        assert_eq!(5, db.device().number_of_apdu_retries);
+    }
+
+    #[test]
+    fn first_failed_write_is_reported() {
+        let mut db = test_db();
+        assert_eq!(Err(ServiceError::Error(Error {
+            error_class: ErrorClass::Property,
+            error_code: ErrorCode::WriteAccessDenied,
+            object_id: DEVICE,
+            property: PropertyIdentifier::VendorIdentifier,
+            array_index: None,
+        }.marshall())), write(&mut db, vec!(
+            unsigned(PropertyIdentifier::ApduTimeout, 6000, None),
+            unsigned(PropertyIdentifier::VendorIdentifier, 5, None),
+            unsigned(PropertyIdentifier::NumberOfApduRetries, 5, None))));
+        // The writes before the failure stay, the ones after it aren't made
+        assert_eq!(6000, db.device().apdu_timeout);
+        assert_eq!(3, db.device().number_of_apdu_retries);
+    }
+
+    #[test]
+    fn unknown_object_and_bad_priority() {
+        let request = Message { specifications: vec!(WriteAccessSpecification {
+            object_id: ObjectId(ObjectType::Device, 46),
+            properties: vec!(unsigned(PropertyIdentifier::ApduTimeout, 6000, None)),
+        })};
+        assert_eq!(Err(ServiceError::Error(Error {
+            error_class: ErrorClass::Object,
+            error_code: ErrorCode::UnknownObject,
+            object_id: ObjectId(ObjectType::Device, 46),
+            property: PropertyIdentifier::ApduTimeout,
+            array_index: None,
+        }.marshall())), handler(&request.marshall(), &test_peer(), &mut test_db()));
+        assert_eq!(Err(ServiceError::Error(Error {
+            error_class: ErrorClass::Property,
+            error_code: ErrorCode::ValueOutOfRange,
+            object_id: DEVICE,
+            property: PropertyIdentifier::ApduTimeout,
+            array_index: None,
+        }.marshall())), write(&mut test_db(), vec!(unsigned(PropertyIdentifier::ApduTimeout, 6000, Some(17)))));
+    }
+
+    #[test]
+    fn missing_value_is_rejected() {
+        let body = vec!(
+            ContextValue(0, PrimitiveValue::ObjectId(DEVICE)),
+            ContextValueSequence(1, vec!(ContextValue(0, PrimitiveValue::Enumerated(PropertyIdentifier::ApduTimeout.into())))));
+        assert_eq!(Err(ServiceError::Reject(RejectReason::MissingRequiredParameter)),
+                   handler(&body, &test_peer(), &mut test_db()));
+    }
+}
+
+#[cfg(test)]
+mod message {
+    use super::*;
+    use serialise::write_value_sequence;
+    use parse::parse_value_sequence_to_end;
+    use enumerations::ObjectType;
+    use std::io;
+
+    /// The example from Clause E.3.10 - writing to the present values of analog values 5, 6 and 7
+    const REQUEST: &[u8] = &[
+        0x0C, 0x00, 0x80, 0x00, 0x05, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x86, 0x00, 0x00, 0x2F, 0x1F,
+        0x0C, 0x00, 0x80, 0x00, 0x06, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x86, 0x00, 0x00, 0x2F, 0x1F,
+        0x0C, 0x00, 0x80, 0x00, 0x07, 0x1E, 0x09, 0x55, 0x2E, 0x44, 0x42, 0x90, 0x00, 0x00, 0x2F, 0x1F];
+
+    fn request() -> Message {
+        let write = |instance, value| WriteAccessSpecification {
+            object_id: ObjectId(ObjectType::AnalogValue, instance),
+            properties: vec!(PropertyValue {
+                property: PropertyIdentifier::PresentValue,
+                array_index: None,
+                value: vec!(ApplicationValue(PrimitiveValue::Real(value))),
+                priority: None,
+            }),
+        };
+        Message { specifications: vec!(write(5, 67.0), write(6, 67.0), write(7, 72.0)) }
+    }
+
+    fn write(body: ValueSequence) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value_sequence(&mut buf, body).unwrap();
+        buf
+    }
+
+    fn parse(data: &[u8], schema: &Schema) -> ValueSequence {
+        let reader: &mut dyn io::Read = &mut io::Cursor::new(data);
+        parse_value_sequence_to_end(reader, schema).unwrap()
+    }
+
+    #[test]
+    fn test_serialise_request() {
+        assert_eq!(REQUEST.to_vec(), write(request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&request().marshall()));
+        assert_eq!(Ok(request()), Message::unmarshall(&parse(REQUEST, &Message::SCHEMA)));
+    }
+
+    #[test]
+    fn test_serialise_error() {
+        let error = || Error { error_class: ErrorClass::Property, error_code: ErrorCode::WriteAccessDenied, object_id: ObjectId(ObjectType::AnalogValue, 6), property: PropertyIdentifier::PresentValue, array_index: None };
+        assert_eq!(vec!(0x0Eu8, 0x91, 0x02, 0x91, 0x28, 0x0F, 0x1E, 0x0C, 0x00, 0x80, 0x00, 0x06, 0x19, 0x55, 0x1F),
+                   write(error().marshall()));
+        assert_eq!(Ok(error()), Error::unmarshall(&error().marshall()));
+        assert_eq!(Ok(error()), Error::unmarshall(&parse(&write(error().marshall()), &Error::SCHEMA)));
+    }
+
+    #[test]
+    fn test_marshall_cycle_with_index_and_priority() {
+        let message = || Message { specifications: vec!(WriteAccessSpecification {
+            object_id: ObjectId(ObjectType::BinaryOutput, 3),
+            properties: vec!(PropertyValue {
+                property: PropertyIdentifier::PriorityArray,
+                array_index: Some(8),
+                value: vec!(ApplicationValue(PrimitiveValue::Null)),
+                priority: Some(8),
+            }),
+        })};
+        assert_eq!(Ok(message()), Message::unmarshall(&message().marshall()));
+        assert_eq!(Ok(message()), Message::unmarshall(&parse(&write(message().marshall()), &Message::SCHEMA)));
+    }
+}